@@ -0,0 +1,113 @@
+//! Transforms and page boxes, as both the content stream interpreter of the
+//! web server and the scan finder of the pdf service read them.
+
+use crate::BBox;
+use lopdf::{Document, Object, ObjectId};
+
+/// Form XObjects nested deeper than this are not drawn.
+pub const MAX_FORM_DEPTH: usize = 8;
+
+/// An affine transform `[a b c d e f]` as PDF writes it.
+pub type Matrix = [f32; 6];
+
+pub const IDENTITY: Matrix = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+/// `a × b`, i.e. `a` applied first.
+pub fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    [
+        a[0] * b[0] + a[1] * b[2],
+        a[0] * b[1] + a[1] * b[3],
+        a[2] * b[0] + a[3] * b[2],
+        a[2] * b[1] + a[3] * b[3],
+        a[4] * b[0] + a[5] * b[2] + b[4],
+        a[4] * b[1] + a[5] * b[3] + b[5],
+    ]
+}
+
+pub fn translate(tx: f32, ty: f32) -> Matrix {
+    [1.0, 0.0, 0.0, 1.0, tx, ty]
+}
+
+/// The first six operands as a matrix; missing or non-numeric ones are 0.
+pub fn matrix(operands: &[Object]) -> Matrix {
+    let mut m = IDENTITY;
+    for (i, value) in m.iter_mut().enumerate() {
+        *value = operands
+            .get(i)
+            .and_then(|o| o.as_float().ok())
+            .unwrap_or(0.0);
+    }
+    m
+}
+
+/// The page's `/MediaBox`, inherited from its parents when the page has
+/// none; US Letter when no node gives one.
+pub fn media_box(doc: &Document, page_id: ObjectId) -> BBox {
+    let mut page = doc.get_dictionary(page_id).ok();
+    while let Some(dict) = page {
+        if let Ok(media_box) = dict.get(b"MediaBox").and_then(|b| doc.dereference(b)) {
+            if let Ok(values) = media_box.1.as_array() {
+                let m = matrix(values);
+                return BBox {
+                    x0: m[0],
+                    y0: m[1],
+                    x1: m[2],
+                    y1: m[3],
+                };
+            }
+        }
+        page = dict
+            .get(b"Parent")
+            .and_then(Object::as_reference)
+            .and_then(|id| doc.get_dictionary(id))
+            .ok();
+    }
+    // US Letter
+    BBox {
+        x0: 0.0,
+        y0: 0.0,
+        x1: 612.0,
+        y1: 792.0,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use lopdf::dictionary;
+
+    #[test]
+    fn test_multiply() {
+        let scaled = [2.0, 0.0, 0.0, 2.0, 0.0, 0.0];
+        // scale first, then move
+        assert_eq!(
+            multiply(&scaled, &translate(10.0, 5.0)),
+            [2.0, 0.0, 0.0, 2.0, 10.0, 5.0]
+        );
+        assert_eq!(
+            multiply(&translate(10.0, 5.0), &scaled),
+            [2.0, 0.0, 0.0, 2.0, 20.0, 10.0]
+        );
+        assert_eq!(multiply(&IDENTITY, &scaled), scaled);
+    }
+
+    #[test]
+    fn test_media_box() {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.add_object(dictionary! {
+            "Type" => "Pages",
+            "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+        });
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+        });
+        let own_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "MediaBox" => vec![0.into(), 0.into(), 300.into(), 400.into()],
+        });
+        assert_eq!(media_box(&doc, page_id).width(), 595.0);
+        assert_eq!(media_box(&doc, own_id).height(), 400.0);
+        assert_eq!(media_box(&doc, (99, 0)).height(), 792.0);
+    }
+}
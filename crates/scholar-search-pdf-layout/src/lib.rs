@@ -0,0 +1,170 @@
+//! The page layout of a PDF: blocks of lines of spans, placed on the page.
+//! The web server fills it from the text a page draws, the pdf service
+//! from OCR of scanned pages, and both hand it on in this one shape.
+//!
+//! Coordinates are PDF user space: points, with the origin at the bottom
+//! left of the page, so `y` grows upwards.
+
+use serde_derive::{Deserialize, Serialize};
+
+pub mod geometry;
+
+/// A gap wider than this, in font sizes, separates two words.
+pub const WORD_GAP: f32 = 0.15;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct BBox {
+    pub x0: f32,
+    pub y0: f32,
+    pub x1: f32,
+    pub y1: f32,
+}
+
+impl BBox {
+    pub fn union(&self, other: &BBox) -> BBox {
+        BBox {
+            x0: self.x0.min(other.x0),
+            y0: self.y0.min(other.y0),
+            x1: self.x1.max(other.x1),
+            y1: self.y1.max(other.y1),
+        }
+    }
+
+    pub fn intersection(&self, other: &BBox) -> BBox {
+        BBox {
+            x0: self.x0.max(other.x0),
+            y0: self.y0.max(other.y0),
+            x1: self.x1.min(other.x1),
+            y1: self.y1.min(other.y1),
+        }
+    }
+
+    pub fn width(&self) -> f32 {
+        self.x1 - self.x0
+    }
+
+    pub fn height(&self) -> f32 {
+        self.y1 - self.y0
+    }
+
+    /// Zero for an empty box, such as the intersection of disjoint boxes.
+    pub fn area(&self) -> f32 {
+        self.width().max(0.0) * self.height().max(0.0)
+    }
+
+    pub fn overlaps_x(&self, other: &BBox) -> bool {
+        self.x0 < other.x1 && other.x0 < self.x1
+    }
+}
+
+/// A run of glyphs in one font with no gap between them: a word, or the
+/// part of a word set in one font.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Span {
+    pub text: String,
+    pub font: String,
+    pub size: f32,
+    /// left end of the baseline
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+}
+
+impl Span {
+    pub fn right(&self) -> f32 {
+        self.x + self.width
+    }
+
+    /// Roughly the glyph box, as fonts put the descender at about a fifth
+    /// of the size.
+    pub fn bbox(&self) -> BBox {
+        BBox {
+            x0: self.x,
+            y0: self.y - 0.2 * self.size,
+            x1: self.right(),
+            y1: self.y + 0.8 * self.size,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Line {
+    pub spans: Vec<Span>,
+    pub bbox: BBox,
+}
+
+impl Line {
+    /// Panics on an empty `spans`: a line has at least one span, which the
+    /// other methods rely on.
+    pub fn new(spans: Vec<Span>) -> Self {
+        assert!(!spans.is_empty(), "a line needs at least one span");
+        let bbox = spans
+            .iter()
+            .skip(1)
+            .fold(spans[0].bbox(), |bbox, span| bbox.union(&span.bbox()));
+        Self { spans, bbox }
+    }
+
+    pub fn baseline(&self) -> f32 {
+        self.spans[0].y
+    }
+
+    pub fn size(&self) -> f32 {
+        self.spans.iter().map(|s| s.size).fold(0.0, f32::max)
+    }
+
+    /// The spans joined, with a space wherever they are a word gap apart.
+    pub fn text(&self) -> String {
+        let mut text = self.spans[0].text.to_owned();
+        for pair in self.spans.windows(2) {
+            if pair[1].x - pair[0].right() > WORD_GAP * pair[0].size.max(pair[1].size) {
+                text.push(' ');
+            }
+            text.push_str(&pair[1].text);
+        }
+        text
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Block {
+    pub lines: Vec<Line>,
+    pub bbox: BBox,
+    /// the column the block sits in, `None` when it spans the columns
+    pub column: Option<usize>,
+}
+
+impl Block {
+    pub fn new(line: Line, column: Option<usize>) -> Self {
+        Self {
+            bbox: line.bbox,
+            lines: vec![line],
+            column,
+        }
+    }
+
+    pub fn push(&mut self, line: Line) {
+        self.bbox = self.bbox.union(&line.bbox);
+        self.lines.push(line);
+    }
+
+    /// The lines of the block, one per text line.
+    pub fn text(&self) -> String {
+        self.lines
+            .iter()
+            .map(Line::text)
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PageLayout {
+    /// 1-based page number
+    pub number: u32,
+    pub width: f32,
+    pub height: f32,
+    pub columns: usize,
+    /// the blocks in reading order
+    pub blocks: Vec<Block>,
+}
@@ -1,3 +1,100 @@
+mod ocr;
+mod scan;
+use crate::ocr::{Ocr, OcrConfig};
+use crate::scan::scanned_pages;
+use std::path::PathBuf;
+use structopt::StructOpt;
+use tracing::info;
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "scholar-search-pdf-service")]
+enum Command {
+    /// List the pages of a PDF that are scans, i.e. draw one full-page image
+    /// and no text
+    Scan {
+        #[structopt(parse(from_os_str))]
+        pdf: PathBuf,
+    },
+    /// OCR the scanned pages of a PDF and write their layout as JSON, the
+    /// page layout native extraction gives for the other pages
+    Ocr {
+        #[structopt(parse(from_os_str))]
+        pdf: PathBuf,
+        /// Where to write the JSON, stdout by default
+        #[structopt(long, parse(from_os_str))]
+        out: Option<PathBuf>,
+        /// Directory with the `.traineddata` files, defaults to
+        /// SCHOLAR_SEARCH_TESSDATA or `tessdata`
+        #[structopt(long, parse(from_os_str))]
+        tessdata: Option<PathBuf>,
+        /// Languages to recognise, e.g. `eng+deu`, defaults to
+        /// SCHOLAR_SEARCH_OCR_LANGUAGES or `eng`
+        #[structopt(long)]
+        lang: Option<String>,
+    },
+}
+
 fn main() {
-    println!("Hello, world!");
+    tracing_subscriber::fmt()
+        .with_target(false)
+        .with_writer(std::io::stderr)
+        .with_max_level(tracing::Level::DEBUG)
+        .init();
+    match Command::from_args() {
+        Command::Scan { pdf } => scan(pdf),
+        Command::Ocr {
+            pdf,
+            out,
+            tessdata,
+            lang,
+        } => run_ocr(pdf, out, tessdata, lang),
+    }
+}
+
+fn or_exit<T>(result: anyhow::Result<T>) -> T {
+    result.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    })
+}
+
+fn scan(pdf: PathBuf) {
+    let doc = or_exit(lopdf::Document::load(&pdf).map_err(Into::into));
+    for page in or_exit(scanned_pages(&doc)) {
+        println!(
+            "page {}: {}x{} px image",
+            page.number, page.image.width, page.image.height
+        );
+    }
+}
+
+fn run_ocr(pdf: PathBuf, out: Option<PathBuf>, tessdata: Option<PathBuf>, lang: Option<String>) {
+    let mut config = OcrConfig::from_env();
+    if let Some(tessdata) = tessdata {
+        config.tessdata = tessdata;
+    }
+    if let Some(lang) = lang {
+        config = OcrConfig::new(config.tessdata, &lang);
+    }
+    let doc = or_exit(lopdf::Document::load(&pdf).map_err(Into::into));
+    let pages = or_exit(scanned_pages(&doc));
+    info!(
+        "ocr: {} of {} pages are scans",
+        pages.len(),
+        doc.get_pages().len()
+    );
+    let mut ocr = or_exit(Ocr::new(&config));
+    let layouts: Vec<_> = pages
+        .iter()
+        .map(|page| {
+            let layout = or_exit(ocr.page_layout(page));
+            info!("ocr: page {}: {} blocks", page.number, layout.blocks.len());
+            layout
+        })
+        .collect();
+    let json = or_exit(serde_json::to_string(&layouts).map_err(Into::into));
+    match out {
+        Some(out) => or_exit(std::fs::write(&out, json).map_err(Into::into)),
+        None => println!("{}", json),
+    }
 }
@@ -0,0 +1,273 @@
+//! Tesseract OCR of scanned pages, turned into the page layout native
+//! extraction gives: a block per recognised paragraph, a line per text line
+//! and a span per word, placed where the word sits on the page.
+
+use crate::scan::ScannedPage;
+use anyhow::{anyhow, bail, Error};
+use scholar_search_pdf_layout::{Block, Line, PageLayout, Span};
+use std::ffi::CString;
+use std::path::{Path, PathBuf};
+use tesseract_plumbing::{leptonica_plumbing::Pix, TessBaseApi};
+
+/// Fonts put the descender at about a fifth of the size, see `Span::bbox`.
+const DESCENT: f32 = 0.2;
+/// Spans of recognised words name this as their font.
+const OCR_FONT: &str = "ocr";
+/// `level` of a word in Tesseract's TSV output.
+const WORD_LEVEL: u32 = 5;
+
+/// Where the trained language data is and which languages to recognise.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OcrConfig {
+    /// the directory holding `<lang>.traineddata`
+    pub tessdata: PathBuf,
+    /// e.g. `["eng", "deu"]`
+    pub languages: Vec<String>,
+}
+
+impl OcrConfig {
+    /// `SCHOLAR_SEARCH_TESSDATA` (default `tessdata`) and
+    /// `SCHOLAR_SEARCH_OCR_LANGUAGES` (default `eng`, `+` separated as
+    /// Tesseract takes them).
+    pub fn from_env() -> Self {
+        let tessdata =
+            std::env::var("SCHOLAR_SEARCH_TESSDATA").unwrap_or_else(|_| "tessdata".to_string());
+        let languages =
+            std::env::var("SCHOLAR_SEARCH_OCR_LANGUAGES").unwrap_or_else(|_| "eng".to_string());
+        Self::new(tessdata, &languages)
+    }
+
+    pub fn new(tessdata: impl Into<PathBuf>, languages: &str) -> Self {
+        Self {
+            tessdata: tessdata.into(),
+            languages: languages
+                .split('+')
+                .map(str::trim)
+                .filter(|l| !l.is_empty())
+                .map(str::to_string)
+                .collect(),
+        }
+    }
+
+    /// Fails with the missing file rather than Tesseract's bare init error.
+    pub fn check(&self) -> Result<(), Error> {
+        if self.languages.is_empty() {
+            bail!("no OCR language given");
+        }
+        for language in &self.languages {
+            let data = self.tessdata.join(format!("{}.traineddata", language));
+            if !data.is_file() {
+                bail!("{}: no such language data", data.display());
+            }
+        }
+        Ok(())
+    }
+}
+
+fn c_string(s: &str) -> Result<CString, Error> {
+    CString::new(s).map_err(|_| anyhow!("{:?}: contains a NUL byte", s))
+}
+
+fn c_path(path: &Path) -> Result<CString, Error> {
+    let path = path
+        .to_str()
+        .ok_or_else(|| anyhow!("{}: not UTF-8", path.display()))?;
+    c_string(path)
+}
+
+/// One Tesseract instance, loaded once and reused for every page.
+pub struct Ocr {
+    api: TessBaseApi,
+}
+
+impl Ocr {
+    pub fn new(config: &OcrConfig) -> Result<Self, Error> {
+        config.check()?;
+        let mut api = TessBaseApi::create();
+        api.init_2(
+            Some(&c_path(&config.tessdata)?),
+            Some(&c_string(&config.languages.join("+"))?),
+        )?;
+        Ok(Self { api })
+    }
+
+    /// The layout of a scanned page from the words Tesseract finds in it.
+    pub fn page_layout(&mut self, page: &ScannedPage) -> Result<PageLayout, Error> {
+        let pix = Pix::read_mem(&page.image.data)
+            .map_err(|e| anyhow!("page {}: unreadable image: {}", page.number, e))?;
+        self.api.set_image_2(&pix);
+        // scans carry no resolution Tesseract trusts, the page size gives it
+        let inches = page.image_box.width() / 72.0;
+        if inches > 0.0 {
+            self.api
+                .set_source_resolution((page.image.width as f32 / inches).round() as i32);
+        }
+        self.api.recognize()?;
+        let tsv = self.api.get_tsv_text(0)?;
+        let tsv = tsv.as_ref().to_string_lossy();
+        Ok(layout(page, &words(&tsv)))
+    }
+}
+
+/// A word Tesseract recognised, in pixels from the top left of the image.
+#[derive(Debug, Clone, PartialEq)]
+struct Word {
+    block: u32,
+    paragraph: u32,
+    line: u32,
+    left: f32,
+    top: f32,
+    width: f32,
+    height: f32,
+    text: String,
+}
+
+/// The words of Tesseract's TSV output, in its reading order. Words it
+/// isn't confident are words at all (confidence -1) are left out.
+fn words(tsv: &str) -> Vec<Word> {
+    tsv.lines()
+        .filter_map(|row| {
+            let fields: Vec<&str> = row.splitn(12, '\t').collect();
+            if fields.len() < 12 || fields[0].parse::<u32>().ok()? != WORD_LEVEL {
+                return None;
+            }
+            let number = |i: usize| fields[i].parse::<f32>().ok();
+            let text = fields[11].trim();
+            if text.is_empty() || number(10)? < 0.0 {
+                return None;
+            }
+            Some(Word {
+                block: fields[2].parse().ok()?,
+                paragraph: fields[3].parse().ok()?,
+                line: fields[4].parse().ok()?,
+                left: number(6)?,
+                top: number(7)?,
+                width: number(8)?,
+                height: number(9)?,
+                text: text.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Places the words on the page: pixels map linearly into the box the
+/// image is drawn in, with `y` flipped. A line's words share its baseline
+/// and size, taken from the extent of the whole line, as Tesseract boxes
+/// each word to its own ascenders and descenders.
+fn layout(page: &ScannedPage, words: &[Word]) -> PageLayout {
+    let image = &page.image;
+    let scale_x = page.image_box.width() / image.width.max(1) as f32;
+    let scale_y = page.image_box.height() / image.height.max(1) as f32;
+    let mut blocks: Vec<Block> = vec![];
+    let mut key = None;
+    for line in
+        words.chunk_by(|a, b| (a.block, a.paragraph, a.line) == (b.block, b.paragraph, b.line))
+    {
+        let bottom = line
+            .iter()
+            .map(|w| w.top + w.height)
+            .fold(f32::MIN, f32::max);
+        let top = line.iter().map(|w| w.top).fold(f32::MAX, f32::min);
+        let size = (bottom - top) * scale_y;
+        let baseline = page.image_box.y1 - bottom * scale_y + DESCENT * size;
+        let spans = line
+            .iter()
+            .map(|w| Span {
+                text: w.text.to_owned(),
+                font: OCR_FONT.to_string(),
+                size,
+                x: page.image_box.x0 + w.left * scale_x,
+                y: baseline,
+                width: w.width * scale_x,
+            })
+            .collect();
+        let line_key = (line[0].block, line[0].paragraph);
+        let line = Line::new(spans);
+        match blocks.last_mut() {
+            Some(block) if key == Some(line_key) => block.push(line),
+            _ => blocks.push(Block::new(line, Some(0))),
+        }
+        key = Some(line_key);
+    }
+    PageLayout {
+        number: page.number,
+        width: page.media_box.width(),
+        height: page.media_box.height(),
+        columns: 1,
+        blocks,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::scan::PageImage;
+    use scholar_search_pdf_layout::BBox;
+
+    #[test]
+    fn test_words() {
+        let tsv = "level\tpage_num\tblock_num\tpar_num\tline_num\tword_num\tleft\ttop\twidth\theight\tconf\ttext\n\
+            1\t1\t0\t0\t0\t0\t0\t0\t2550\t3300\t-1\t\n\
+            4\t1\t1\t1\t1\t0\t300\t400\t900\t50\t-1\t\n\
+            5\t1\t1\t1\t1\t1\t300\t400\t300\t50\t96.5\tDeep\n\
+            5\t1\t1\t1\t1\t2\t640\t410\t560\t40\t91.0\tlearning\n\
+            5\t1\t1\t1\t1\t3\t1300\t410\t20\t40\t-1\t \n";
+        let words = words(tsv);
+        assert_eq!(words.len(), 2);
+        assert_eq!(words[0].text, "Deep");
+        assert_eq!(words[1].left, 640.0);
+        assert_eq!(words[1].line, 1);
+    }
+
+    #[test]
+    fn test_layout() {
+        // a letter page scanned at 300 dpi
+        let page = ScannedPage {
+            number: 3,
+            media_box: BBox {
+                x0: 0.0,
+                y0: 0.0,
+                x1: 612.0,
+                y1: 792.0,
+            },
+            image_box: BBox {
+                x0: 0.0,
+                y0: 0.0,
+                x1: 612.0,
+                y1: 792.0,
+            },
+            image: PageImage {
+                width: 2550,
+                height: 3300,
+                data: vec![],
+            },
+        };
+        let word = |paragraph, line, left, top, text: &str| Word {
+            block: 1,
+            paragraph,
+            line,
+            left,
+            top,
+            width: 250.0,
+            height: 50.0,
+            text: text.to_string(),
+        };
+        let words = vec![
+            word(1, 1, 300.0, 400.0, "Deep"),
+            word(1, 1, 600.0, 400.0, "learning"),
+            word(1, 2, 300.0, 475.0, "works."),
+            word(2, 1, 300.0, 600.0, "Introduction"),
+        ];
+        let layout = layout(&page, &words);
+        assert_eq!(layout.number, 3);
+        assert_eq!(layout.blocks.len(), 2);
+        assert_eq!(layout.blocks[0].lines.len(), 2);
+        let span = &layout.blocks[0].lines[0].spans[1];
+        assert_eq!(span.text, "learning");
+        assert!((span.x - 144.0).abs() < 1e-3);
+        assert!((span.size - 12.0).abs() < 1e-3);
+        // bottom at 450 px is 108 pt down from the top
+        assert!((span.y - (792.0 - 108.0 + 2.4)).abs() < 1e-3);
+        assert!(layout.blocks[0].bbox.y1 > layout.blocks[1].bbox.y1);
+    }
+}
@@ -0,0 +1,488 @@
+//! Finds the pages of a PDF that are scans: no text is drawn, only one image
+//! covering the page. Those are the pages native extraction returns empty,
+//! so their image is pulled out for OCR.
+//!
+//! The image is handed over as a file leptonica can read: JPEG and JPEG 2000
+//! streams as they are, CCITT fax data wrapped in a TIFF header, and raw
+//! samples as PNM.
+
+use anyhow::{anyhow, bail, Error};
+use lopdf::{content::Content, Dictionary, Document, Object, ObjectId, Stream};
+use scholar_search_pdf_layout::{
+    geometry::{matrix, media_box, multiply, Matrix, IDENTITY, MAX_FORM_DEPTH},
+    BBox,
+};
+
+/// An image covering this share of the page is a full-page image.
+const FULL_PAGE: f32 = 0.8;
+/// Where the unit square an image is drawn into lands on the page.
+fn image_box(ctm: &Matrix) -> BBox {
+    let corners = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)];
+    let (xs, ys): (Vec<f32>, Vec<f32>) = corners
+        .iter()
+        .map(|(u, v)| {
+            (
+                ctm[0] * u + ctm[2] * v + ctm[4],
+                ctm[1] * u + ctm[3] * v + ctm[5],
+            )
+        })
+        .unzip();
+    BBox {
+        x0: xs.iter().cloned().fold(f32::MAX, f32::min),
+        y0: ys.iter().cloned().fold(f32::MAX, f32::min),
+        x1: xs.iter().cloned().fold(f32::MIN, f32::max),
+        y1: ys.iter().cloned().fold(f32::MIN, f32::max),
+    }
+}
+
+/// An image a page draws, with where it lands.
+struct Drawn<'a> {
+    stream: Option<&'a Stream>,
+    bbox: BBox,
+}
+
+/// What a page draws, as far as telling scans apart goes.
+#[derive(Default)]
+struct Marks<'a> {
+    text: bool,
+    images: Vec<Drawn<'a>>,
+}
+
+fn xobject<'a>(doc: &'a Document, resources: &[&'a Dictionary], name: &[u8]) -> Option<&'a Stream> {
+    resources.iter().find_map(|r| {
+        let xobjects = r.get(b"XObject").ok()?;
+        let xobjects = doc.dereference(xobjects).ok()?.1.as_dict().ok()?;
+        doc.dereference(xobjects.get(name).ok()?)
+            .ok()?
+            .1
+            .as_stream()
+            .ok()
+    })
+}
+
+fn subtype(stream: &Stream) -> Option<&[u8]> {
+    stream.dict.get(b"Subtype").and_then(Object::as_name).ok()
+}
+
+fn walk<'a>(
+    doc: &'a Document,
+    content: &[u8],
+    resources: &[&'a Dictionary],
+    ctm: Matrix,
+    depth: usize,
+    marks: &mut Marks<'a>,
+) {
+    let Ok(content) = Content::decode(content) else {
+        // can't tell, so don't take the page for a scan
+        marks.text = true;
+        return;
+    };
+    let mut ctm = ctm;
+    let mut stack = vec![];
+    for operation in &content.operations {
+        match operation.operator.as_ref() {
+            "q" => stack.push(ctm),
+            "Q" => ctm = stack.pop().unwrap_or(ctm),
+            "cm" => ctm = multiply(&matrix(&operation.operands), &ctm),
+            "Tj" | "TJ" | "'" | "\"" => marks.text = true,
+            "BI" | "ID" => marks.images.push(Drawn {
+                stream: None,
+                bbox: image_box(&ctm),
+            }),
+            "Do" => {
+                let Some(stream) = operation
+                    .operands
+                    .first()
+                    .and_then(|n| n.as_name().ok())
+                    .and_then(|name| xobject(doc, resources, name))
+                else {
+                    continue;
+                };
+                match subtype(stream) {
+                    Some(b"Image") => marks.images.push(Drawn {
+                        stream: Some(stream),
+                        bbox: image_box(&ctm),
+                    }),
+                    Some(b"Form") if depth < MAX_FORM_DEPTH => {
+                        let content = stream
+                            .decompressed_content()
+                            .unwrap_or_else(|_| stream.content.clone());
+                        let form_resources = stream
+                            .dict
+                            .get(b"Resources")
+                            .and_then(|r| doc.dereference(r))
+                            .and_then(|(_, r)| r.as_dict())
+                            .ok();
+                        let inner: Vec<&Dictionary> = match form_resources {
+                            Some(r) => vec![r],
+                            None => resources.to_vec(),
+                        };
+                        let form_ctm = match stream.dict.get(b"Matrix").and_then(Object::as_array) {
+                            Ok(m) => multiply(&matrix(m), &ctm),
+                            Err(_) => ctm,
+                        };
+                        walk(doc, &content, &inner, form_ctm, depth + 1, marks);
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// A page image as an encoded file, with its size in pixels.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageImage {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScannedPage {
+    /// 1-based page number
+    pub number: u32,
+    pub media_box: BBox,
+    /// where the image is drawn on the page
+    pub image_box: BBox,
+    pub image: PageImage,
+}
+
+/// The full-page image of a page that draws no text, `None` for pages
+/// with text or anything other than a single image.
+fn scanned_image(doc: &Document, page_id: ObjectId) -> Option<(Option<&Stream>, BBox)> {
+    let content = doc.get_page_content(page_id).ok()?;
+    let (resource_dict, resource_ids) = doc.get_page_resources(page_id);
+    let resources: Vec<&Dictionary> = resource_dict
+        .into_iter()
+        .chain(
+            resource_ids
+                .into_iter()
+                .filter_map(|id| doc.get_dictionary(id).ok()),
+        )
+        .collect();
+    let mut marks = Marks::default();
+    walk(doc, &content, &resources, IDENTITY, 0, &mut marks);
+    if marks.text || marks.images.len() != 1 {
+        return None;
+    }
+    let drawn = marks.images.pop()?;
+    let page = media_box(doc, page_id);
+    let covered = drawn.bbox.intersection(&page).area() / page.area();
+    (covered >= FULL_PAGE).then_some((drawn.stream, drawn.bbox))
+}
+
+/// The pages that are scans, with their image ready for OCR. A scanned page
+/// whose image can't be decoded fails the whole document, so it isn't
+/// silently left without text.
+pub fn scanned_pages(doc: &Document) -> Result<Vec<ScannedPage>, Error> {
+    let mut pages = vec![];
+    for (number, page_id) in doc.get_pages() {
+        let Some((stream, image_box)) = scanned_image(doc, page_id) else {
+            continue;
+        };
+        let stream =
+            stream.ok_or_else(|| anyhow!("page {}: inline images aren't supported", number))?;
+        let image = page_image(doc, stream).map_err(|e| anyhow!("page {}: {}", number, e))?;
+        pages.push(ScannedPage {
+            number,
+            media_box: media_box(doc, page_id),
+            image_box,
+            image,
+        });
+    }
+    Ok(pages)
+}
+
+fn integer(doc: &Document, dict: &Dictionary, key: &[u8]) -> Option<i64> {
+    doc.dereference(dict.get(key).ok()?).ok()?.1.as_i64().ok()
+}
+
+fn boolean(doc: &Document, dict: &Dictionary, key: &[u8]) -> Option<bool> {
+    doc.dereference(dict.get(key).ok()?).ok()?.1.as_bool().ok()
+}
+
+/// The decode parameters of the last filter, which is the one that gives
+/// the image format.
+fn decode_parms<'a>(doc: &'a Document, dict: &'a Dictionary) -> Option<&'a Dictionary> {
+    let parms = doc.dereference(dict.get(b"DecodeParms").ok()?).ok()?.1;
+    match parms {
+        Object::Array(parms) => doc.dereference(parms.last()?).ok()?.1.as_dict().ok(),
+        parms => parms.as_dict().ok(),
+    }
+}
+
+/// Colour components per pixel, for the colour spaces scans use.
+fn components(doc: &Document, dict: &Dictionary) -> Result<usize, Error> {
+    let space = match dict.get(b"ColorSpace") {
+        Ok(space) => doc.dereference(space)?.1,
+        // only image masks have none
+        Err(_) => return Ok(1),
+    };
+    let family = match space {
+        Object::Name(name) => name.as_slice(),
+        Object::Array(items) => items
+            .first()
+            .and_then(|o| o.as_name().ok())
+            .ok_or_else(|| anyhow!("bad colour space"))?,
+        _ => bail!("bad colour space"),
+    };
+    match family {
+        b"DeviceGray" | b"CalGray" | b"G" => Ok(1),
+        b"DeviceRGB" | b"CalRGB" | b"RGB" => Ok(3),
+        b"ICCBased" => {
+            let Object::Array(items) = space else {
+                bail!("bad colour space");
+            };
+            let profile =
+                doc.dereference(items.get(1).ok_or_else(|| anyhow!("bad ICC profile"))?)?;
+            let n = profile.1.as_stream()?.dict.get(b"N")?.as_i64()?;
+            Ok(n as usize)
+        }
+        family => bail!(
+            "{} images aren't supported",
+            String::from_utf8_lossy(family)
+        ),
+    }
+}
+
+fn page_image(doc: &Document, stream: &Stream) -> Result<PageImage, Error> {
+    let dict = &stream.dict;
+    let width = integer(doc, dict, b"Width").ok_or_else(|| anyhow!("no image width"))? as u32;
+    let height = integer(doc, dict, b"Height").ok_or_else(|| anyhow!("no image height"))? as u32;
+    let filters = stream.filters().unwrap_or_default();
+    let data = match filters.last().map(String::as_str) {
+        Some("DCTDecode") | Some("JPXDecode") if filters.len() == 1 => stream.content.clone(),
+        Some("CCITTFaxDecode") if filters.len() == 1 => {
+            let parms = decode_parms(doc, dict);
+            let k = parms.and_then(|p| integer(doc, p, b"K")).unwrap_or(0);
+            let black_is_1 = parms
+                .and_then(|p| boolean(doc, p, b"BlackIs1"))
+                .unwrap_or(false);
+            let columns = parms
+                .and_then(|p| integer(doc, p, b"Columns"))
+                .map_or(width, |c| c as u32);
+            ccitt_tiff(&stream.content, columns, height, k, black_is_1)
+        }
+        Some("DCTDecode") | Some("JPXDecode") | Some("CCITTFaxDecode") => {
+            bail!("chained image filters aren't supported")
+        }
+        Some("JBIG2Decode") => bail!("JBIG2 images aren't supported"),
+        _ => {
+            let bits = integer(doc, dict, b"BitsPerComponent").unwrap_or(1);
+            let mask = boolean(doc, dict, b"ImageMask").unwrap_or(false);
+            // lopdf only decompresses streams that aren't images
+            let mut plain = stream.clone();
+            plain.dict.remove(b"Subtype");
+            let samples = if filters.is_empty() {
+                plain.content
+            } else {
+                plain.decompressed_content()?
+            };
+            pnm(&samples, width, height, components(doc, dict)?, bits, mask)?
+        }
+    };
+    Ok(PageImage {
+        width,
+        height,
+        data,
+    })
+}
+
+/// Raw samples as a PGM, PPM or, for 1 bit images, a PBM.
+fn pnm(
+    samples: &[u8],
+    width: u32,
+    height: u32,
+    components: usize,
+    bits: i64,
+    mask: bool,
+) -> Result<Vec<u8>, Error> {
+    let (magic, row) = match (components, bits) {
+        (1, 1) => ("P4", (width as usize).div_ceil(8)),
+        (1, 8) => ("P5", width as usize),
+        (3, 8) => ("P6", width as usize * 3),
+        (components, bits) => bail!(
+            "{} bit images with {} components aren't supported",
+            bits,
+            components
+        ),
+    };
+    let size = row * height as usize;
+    if samples.len() < size {
+        bail!("image data is short: {} of {} bytes", samples.len(), size);
+    }
+    let mut data = match magic {
+        "P4" => format!("P4\n{} {}\n", width, height),
+        magic => format!("{}\n{} {}\n255\n", magic, width, height),
+    }
+    .into_bytes();
+    // a PBM 1 is black, a PDF gray 1 is white; stencil masks paint their 1s
+    match magic {
+        "P4" if !mask => data.extend(samples[..size].iter().map(|b| !b)),
+        _ => data.extend_from_slice(&samples[..size]),
+    }
+    Ok(data)
+}
+
+/// CCITT fax data behind a minimal single-strip TIFF header.
+fn ccitt_tiff(data: &[u8], width: u32, height: u32, k: i64, black_is_1: bool) -> Vec<u8> {
+    // T.6 (Group 4) for K < 0, T.4 (Group 3) otherwise
+    let (compression, t4_options) = match k {
+        k if k < 0 => (4, None),
+        0 => (3, Some(0)),
+        _ => (3, Some(1)),
+    };
+    let mut entries: Vec<(u16, u16, u32)> = vec![
+        // ImageWidth, ImageLength, BitsPerSample, Compression
+        (256, 4, width),
+        (257, 4, height),
+        (258, 3, 1),
+        (259, 3, compression),
+        // PhotometricInterpretation: 0 is WhiteIsZero
+        (262, 3, black_is_1 as u32),
+        // StripOffsets, filled in below
+        (273, 4, 0),
+        // SamplesPerPixel, RowsPerStrip, StripByteCounts
+        (277, 3, 1),
+        (278, 4, height),
+        (279, 4, data.len() as u32),
+    ];
+    if let Some(options) = t4_options {
+        entries.push((292, 4, options));
+    }
+    let ifd_size = 2 + entries.len() * 12 + 4;
+    let offset = (8 + ifd_size) as u32;
+    let mut tiff = b"II*\0".to_vec();
+    tiff.extend(8u32.to_le_bytes());
+    tiff.extend((entries.len() as u16).to_le_bytes());
+    for (tag, kind, value) in entries {
+        let value = if tag == 273 { offset } else { value };
+        tiff.extend(tag.to_le_bytes());
+        tiff.extend(kind.to_le_bytes());
+        tiff.extend(1u32.to_le_bytes());
+        match kind {
+            3 => {
+                tiff.extend((value as u16).to_le_bytes());
+                tiff.extend([0, 0]);
+            }
+            _ => tiff.extend(value.to_le_bytes()),
+        }
+    }
+    tiff.extend(0u32.to_le_bytes());
+    tiff.extend_from_slice(data);
+    tiff
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use lopdf::{
+        content::{Content, Operation},
+        dictionary,
+    };
+
+    /// A one page document drawing `operations` with the given image.
+    fn test_document(image: Stream, operations: Vec<Operation>) -> Document {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let image_id = doc.add_object(image);
+        let font_id = doc.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Helvetica",
+        });
+        let resources_id = doc.add_object(dictionary! {
+            "XObject" => dictionary! { "Im0" => image_id },
+            "Font" => dictionary! { "F1" => font_id },
+        });
+        let content = Content { operations };
+        let content_id = doc.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "Contents" => content_id,
+        });
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![page_id.into()],
+                "Count" => 1,
+                "Resources" => resources_id,
+                "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+            }),
+        );
+        let catalog_id = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        doc.trailer.set("Root", catalog_id);
+        doc
+    }
+
+    fn gray_image() -> Stream {
+        Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Image",
+                "Width" => 4,
+                "Height" => 2,
+                "ColorSpace" => "DeviceGray",
+                "BitsPerComponent" => 8,
+            },
+            vec![0, 64, 128, 255, 255, 128, 64, 0],
+        )
+    }
+
+    fn draw(a: f32, d: f32, e: f32, f: f32) -> Vec<Operation> {
+        vec![
+            Operation::new("q", vec![]),
+            Operation::new(
+                "cm",
+                vec![a.into(), 0.into(), 0.into(), d.into(), e.into(), f.into()],
+            ),
+            Operation::new("Do", vec![Object::Name(b"Im0".to_vec())]),
+            Operation::new("Q", vec![]),
+        ]
+    }
+
+    #[test]
+    fn test_scanned_pages() {
+        let doc = test_document(gray_image(), draw(612.0, 792.0, 0.0, 0.0));
+        let pages = scanned_pages(&doc).unwrap();
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[0].number, 1);
+        assert_eq!(pages[0].image_box.x1, 612.0);
+        assert_eq!(pages[0].image.width, 4);
+        assert_eq!(
+            pages[0].image.data,
+            b"P5\n4 2\n255\n\0\x40\x80\xff\xff\x80\x40\0"
+        );
+
+        // a figure isn't a scan
+        let doc = test_document(gray_image(), draw(200.0, 100.0, 72.0, 500.0));
+        assert!(scanned_pages(&doc).unwrap().is_empty());
+
+        // nor is a page that also draws text
+        let mut operations = draw(612.0, 792.0, 0.0, 0.0);
+        operations.extend([
+            Operation::new("BT", vec![]),
+            Operation::new("Tf", vec![Object::Name(b"F1".to_vec()), 12.into()]),
+            Operation::new("Tj", vec![Object::string_literal("Abstract")]),
+            Operation::new("ET", vec![]),
+        ]);
+        let doc = test_document(gray_image(), operations);
+        assert!(scanned_pages(&doc).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_ccitt_tiff() {
+        let tiff = ccitt_tiff(&[1, 2, 3], 2480, 3508, -1, false);
+        assert_eq!(&tiff[..4], b"II*\0");
+        // the strip follows the header and its nine entries
+        assert_eq!(tiff.len(), 8 + 2 + 9 * 12 + 4 + 3);
+        assert_eq!(&tiff[tiff.len() - 3..], &[1, 2, 3]);
+    }
+}
@@ -0,0 +1,249 @@
+use crate::alerts::{public_url, AlertPayload};
+use lettre::{
+    message::{header::ContentType, Mailbox},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use std::str::FromStr;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SmtpSecurity {
+    /// plain text, for a local sink such as mailpit or MailHog
+    None,
+    StartTls,
+    Tls,
+}
+
+impl FromStr for SmtpSecurity {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" | "plain" => Ok(SmtpSecurity::None),
+            "starttls" => Ok(SmtpSecurity::StartTls),
+            "tls" | "smtps" => Ok(SmtpSecurity::Tls),
+            _ => Err(anyhow::anyhow!("unknown SMTP security: {}", s)),
+        }
+    }
+}
+
+/// The SMTP relay digests are sent through, read from `SMTP_*` variables.
+#[derive(Debug, Clone)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub security: SmtpSecurity,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: String,
+}
+
+impl SmtpConfig {
+    /// `None` unless `SMTP_HOST` is set, which disables email delivery; an
+    /// unknown `SMTP_SECURITY` or a bad `SMTP_PORT` is an error.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> anyhow::Result<Option<Self>> {
+        let var = |name: &str| var(name).filter(|v| !v.is_empty());
+        let Some(host) = var("SMTP_HOST") else {
+            return Ok(None);
+        };
+        let security = match var("SMTP_SECURITY") {
+            Some(security) => security.parse()?,
+            None => SmtpSecurity::StartTls,
+        };
+        let port = match var("SMTP_PORT") {
+            Some(port) => port
+                .parse()
+                .map_err(|e| anyhow::anyhow!("bad SMTP_PORT {}: {}", port, e))?,
+            None => match security {
+                SmtpSecurity::None => 25,
+                SmtpSecurity::StartTls => 587,
+                SmtpSecurity::Tls => 465,
+            },
+        };
+        Ok(Some(Self {
+            host,
+            port,
+            security,
+            username: var("SMTP_USERNAME"),
+            password: var("SMTP_PASSWORD"),
+            from: var("SMTP_FROM").unwrap_or("scholar-search@localhost".to_string()),
+        }))
+    }
+
+    fn transport(&self) -> anyhow::Result<AsyncSmtpTransport<Tokio1Executor>> {
+        let builder = match self.security {
+            SmtpSecurity::None => {
+                AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&self.host)
+            }
+            SmtpSecurity::StartTls => {
+                AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&self.host)?
+            }
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&self.host)?,
+        }
+        .port(self.port)
+        .timeout(Some(Duration::from_secs(30)));
+        let builder = match (&self.username, &self.password) {
+            (Some(username), Some(password)) => {
+                builder.credentials(Credentials::new(username.to_owned(), password.to_owned()))
+            }
+            _ => builder,
+        };
+        Ok(builder.build())
+    }
+}
+
+pub fn digest_subject(payload: &AlertPayload) -> String {
+    format!(
+        "{} new paper{} for \"{}\"",
+        payload.papers.len(),
+        if payload.papers.len() == 1 { "" } else { "s" },
+        payload.name
+    )
+}
+
+pub fn digest_body(payload: &AlertPayload) -> String {
+    let mut body = format!(
+        "Saved search \"{}\" ({}) found {} new paper(s) on {}.\n\n",
+        payload.name,
+        payload.query,
+        payload.papers.len(),
+        payload.run_at.format("%Y-%m-%d %H:%M UTC")
+    );
+    for paper in payload.papers.iter() {
+        body.push_str(&format!("* {}\n", paper.title));
+        if !paper.authors.is_empty() {
+            body.push_str(&format!("  {}", paper.authors));
+            if let Some(year) = paper.year {
+                body.push_str(&format!(" ({})", year));
+            }
+            body.push('\n');
+        }
+        body.push_str(&format!("  {}\n\n", paper.url));
+    }
+    body.push_str(&format!(
+        "Inbox: {}/x/searches\nFeed: {}/feeds/searches/{}/atom\n",
+        public_url(),
+        public_url(),
+        payload.search_id
+    ));
+    body
+}
+
+/// Sends the digest to one recipient.
+pub async fn send(config: &SmtpConfig, to: &str, payload: &AlertPayload) -> Result<String, String> {
+    let message = Message::builder()
+        .from(config.from.parse::<Mailbox>().map_err(|e| e.to_string())?)
+        .to(to.parse::<Mailbox>().map_err(|e| e.to_string())?)
+        .subject(digest_subject(payload))
+        .header(ContentType::TEXT_PLAIN)
+        .body(digest_body(payload))
+        .map_err(|e| e.to_string())?;
+    let response = config
+        .transport()
+        .map_err(|e| e.to_string())?
+        .send(message)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(format!("SMTP {}", response.code()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::alerts::AlertPaper;
+    use chrono::Utc;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    /// Accepts one SMTP session and returns the message it carried.
+    async fn smtp_sink() -> (u16, tokio::task::JoinHandle<String>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let session = tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let (read, mut write) = socket.into_split();
+            let mut lines = BufReader::new(read).lines();
+            write.write_all(b"220 sink ESMTP\r\n").await.unwrap();
+            let mut data = String::new();
+            while let Some(line) = lines.next_line().await.unwrap() {
+                let command = line.to_uppercase();
+                let reply: &[u8] = if command.starts_with("DATA") {
+                    write.write_all(b"354 go ahead\r\n").await.unwrap();
+                    while let Some(line) = lines.next_line().await.unwrap() {
+                        if line == "." {
+                            break;
+                        }
+                        data.push_str(&line);
+                        data.push('\n');
+                    }
+                    b"250 queued\r\n"
+                } else if command.starts_with("QUIT") {
+                    write.write_all(b"221 bye\r\n").await.unwrap();
+                    break;
+                } else {
+                    b"250 ok\r\n"
+                };
+                write.write_all(reply).await.unwrap();
+            }
+            data
+        });
+        (port, session)
+    }
+
+    #[test]
+    fn test_config_from_vars() {
+        let config = |vars: &[(&str, &str)]| {
+            let vars = vars
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<std::collections::HashMap<String, String>>();
+            SmtpConfig::from_vars(|name| vars.get(name).cloned())
+        };
+        assert!(config(&[]).unwrap().is_none());
+        let tls = config(&[("SMTP_HOST", "mail"), ("SMTP_SECURITY", "SMTPS")])
+            .unwrap()
+            .unwrap();
+        assert_eq!((tls.security, tls.port), (SmtpSecurity::Tls, 465));
+        assert!(config(&[("SMTP_HOST", "mail"), ("SMTP_SECURITY", "ssl")]).is_err());
+        assert!(config(&[("SMTP_HOST", "mail"), ("SMTP_PORT", "25x")]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_send_digest() {
+        let (port, session) = smtp_sink().await;
+        let config = SmtpConfig {
+            host: "127.0.0.1".to_string(),
+            port,
+            security: SmtpSecurity::None,
+            username: None,
+            password: None,
+            from: "scholar-search@localhost".to_string(),
+        };
+        let payload = AlertPayload {
+            search_id: "nas".to_string(),
+            name: "NAS".to_string(),
+            query: "neural architecture search".to_string(),
+            run_at: Utc::now(),
+            papers: vec![AlertPaper {
+                paper_id: "abc123".to_string(),
+                title: "Efficient search".to_string(),
+                authors: "Haifeng Jin".to_string(),
+                year: Some(2019),
+                citation_count: None,
+                url: "https://www.semanticscholar.org/paper/abc123".to_string(),
+            }],
+        };
+
+        let sent = send(&config, "reader@example.org", &payload).await;
+        assert_eq!(sent, Ok("SMTP 250".to_string()));
+        let message = session.await.unwrap();
+        assert!(message.contains("To: reader@example.org"));
+        assert!(message.contains("Subject: 1 new paper for \"NAS\""));
+        assert!(message.contains("* Efficient search"));
+        assert!(message.contains("  Haifeng Jin (2019)"));
+    }
+}
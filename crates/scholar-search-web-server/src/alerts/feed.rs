@@ -0,0 +1,142 @@
+use crate::alerts::{paper_url, public_url};
+use crate::axum_server::state::search::{InboxItem, SavedSearch};
+use atom_syndication::{Entry, Feed, Link, Person, Text};
+use chrono::Utc;
+use rss::{ChannelBuilder, GuidBuilder, ItemBuilder};
+
+/// Feeds list the most recent inbox items, seen or not.
+pub const FEED_SIZE: usize = 50;
+
+fn feed_url(search: &SavedSearch, kind: &str) -> String {
+    format!(
+        "{}/feeds/searches/{}/{}",
+        public_url(),
+        search.search_id,
+        kind
+    )
+}
+
+fn summary(item: &InboxItem) -> String {
+    let mut summary = item.authors.to_owned();
+    if let Some(year) = item.year {
+        summary.push_str(&format!(" ({})", year));
+    }
+    if let Some(citation_count) = item.citation_count {
+        summary.push_str(&format!(", {} citations", citation_count));
+    }
+    summary
+}
+
+pub fn atom(search: &SavedSearch, items: &[InboxItem]) -> String {
+    let items = &items[..items.len().min(FEED_SIZE)];
+    let updated = items
+        .iter()
+        .map(|item| item.found_at)
+        .max()
+        .or(search.last_run)
+        .unwrap_or(search.created_at);
+    Feed {
+        title: Text::from(format!("Scholar search: {}", search.name)),
+        id: feed_url(search, "atom"),
+        updated: updated.into(),
+        subtitle: Some(Text::from(search.request.query.to_owned())),
+        links: vec![Link {
+            href: feed_url(search, "atom"),
+            rel: "self".to_string(),
+            ..Link::default()
+        }],
+        entries: items
+            .iter()
+            .map(|item| Entry {
+                title: Text::from(item.title.to_owned()),
+                id: paper_url(&item.paper_id),
+                updated: item.found_at.into(),
+                authors: item
+                    .authors
+                    .split(", ")
+                    .filter(|name| !name.is_empty())
+                    .map(|name| Person {
+                        name: name.to_string(),
+                        ..Person::default()
+                    })
+                    .collect(),
+                links: vec![Link {
+                    href: paper_url(&item.paper_id),
+                    ..Link::default()
+                }],
+                summary: Some(Text::from(summary(item))),
+                ..Entry::default()
+            })
+            .collect(),
+        ..Feed::default()
+    }
+    .to_string()
+}
+
+pub fn rss(search: &SavedSearch, items: &[InboxItem]) -> String {
+    let items = &items[..items.len().min(FEED_SIZE)];
+    ChannelBuilder::default()
+        .title(format!("Scholar search: {}", search.name))
+        .link(feed_url(search, "rss"))
+        .description(search.request.query.to_owned())
+        .last_build_date(Some(Utc::now().to_rfc2822()))
+        .items(
+            items
+                .iter()
+                .map(|item| {
+                    ItemBuilder::default()
+                        .title(Some(item.title.to_owned()))
+                        .link(Some(paper_url(&item.paper_id)))
+                        .description(Some(summary(item)))
+                        .guid(Some(
+                            GuidBuilder::default()
+                                .value(paper_url(&item.paper_id))
+                                .permalink(true)
+                                .build(),
+                        ))
+                        .pub_date(Some(item.found_at.to_rfc2822()))
+                        .build()
+                })
+                .collect::<Vec<_>>(),
+        )
+        .build()
+        .to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::semantic_scholar_api::paper_fetch::BulkRequest;
+
+    #[test]
+    fn test_feeds() {
+        let search = SavedSearch::new(
+            "Neural architecture search",
+            BulkRequest {
+                query: "neural architecture search".to_string(),
+                ..BulkRequest::default()
+            },
+            "@daily",
+        )
+        .unwrap();
+        let items = vec![InboxItem {
+            search_id: search.search_id.to_owned(),
+            paper_id: "abc123".to_string(),
+            title: "Search <fast> & cheap".to_string(),
+            authors: "Haifeng Jin, Qingquan Song".to_string(),
+            year: Some(2019),
+            citation_count: Some(42),
+            found_at: Utc::now(),
+            seen: false,
+        }];
+
+        let atom = atom(&search, &items);
+        assert!(atom.contains("<title>Search &lt;fast&gt; &amp; cheap</title>"));
+        assert!(atom.contains("<name>Qingquan Song</name>"));
+        assert!(atom.contains("https://www.semanticscholar.org/paper/abc123"));
+
+        let rss = rss(&search, &items);
+        assert!(rss.contains("<title>Search &lt;fast&gt; &amp; cheap</title>"));
+        assert!(rss.contains("Haifeng Jin, Qingquan Song (2019), 42 citations"));
+    }
+}
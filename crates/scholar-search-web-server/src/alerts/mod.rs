@@ -0,0 +1,64 @@
+pub mod email;
+pub mod feed;
+pub mod webhook;
+
+use crate::axum_server::state::search::{InboxItem, SavedSearch};
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+
+/// Base url used for links back into the app from feeds and digests.
+pub fn public_url() -> String {
+    std::env::var("SCHOLAR_SEARCH_PUBLIC_URL")
+        .unwrap_or_else(|_| "http://localhost:3600".to_string())
+        .trim_end_matches('/')
+        .to_string()
+}
+
+pub fn paper_url(paper_id: &str) -> String {
+    format!("https://www.semanticscholar.org/paper/{}", paper_id)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AlertPaper {
+    pub paper_id: String,
+    pub title: String,
+    pub authors: String,
+    pub year: Option<i32>,
+    pub citation_count: Option<i32>,
+    pub url: String,
+}
+
+impl From<&InboxItem> for AlertPaper {
+    fn from(item: &InboxItem) -> Self {
+        Self {
+            paper_id: item.paper_id.to_owned(),
+            title: item.title.to_owned(),
+            authors: item.authors.to_owned(),
+            year: item.year,
+            citation_count: item.citation_count,
+            url: paper_url(&item.paper_id),
+        }
+    }
+}
+
+/// The new papers of one run, as posted to webhooks and mailed in digests.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AlertPayload {
+    pub search_id: String,
+    pub name: String,
+    pub query: String,
+    pub run_at: DateTime<Utc>,
+    pub papers: Vec<AlertPaper>,
+}
+
+impl AlertPayload {
+    pub fn new(search: &SavedSearch, run_at: DateTime<Utc>, items: &[InboxItem]) -> Self {
+        Self {
+            search_id: search.search_id.to_owned(),
+            name: search.name.to_owned(),
+            query: search.request.query.to_owned(),
+            run_at,
+            papers: items.iter().map(AlertPaper::from).collect(),
+        }
+    }
+}
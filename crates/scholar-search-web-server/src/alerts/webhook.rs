@@ -0,0 +1,66 @@
+use crate::alerts::AlertPayload;
+use std::time::Duration;
+
+/// Posts the payload as JSON; any non 2xx answer counts as a failure.
+pub async fn post(url: &str, payload: &AlertPayload) -> Result<String, String> {
+    let response = reqwest::Client::new()
+        .post(url)
+        .timeout(Duration::from_secs(10))
+        .json(payload)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    let status = response.status();
+    match status.is_success() {
+        true => Ok(format!("HTTP {}", status)),
+        false => Err(format!("HTTP {}", status)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::axum_server::fixture;
+    use axum::{extract::State, http::StatusCode, routing::post as route_post, Json, Router};
+    use chrono::Utc;
+    use std::sync::{Arc, Mutex};
+
+    #[tokio::test]
+    async fn test_post() {
+        let received: Arc<Mutex<Vec<AlertPayload>>> = Arc::default();
+        let app = Router::new()
+            .route(
+                "/hook",
+                route_post(
+                    |State(received): State<Arc<Mutex<Vec<AlertPayload>>>>,
+                     Json(payload): Json<AlertPayload>| async move {
+                        received.lock().unwrap().push(payload);
+                        StatusCode::NO_CONTENT
+                    },
+                ),
+            )
+            .route("/gone", route_post(|| async { StatusCode::GONE }))
+            .with_state(received.clone());
+        let base = fixture::serve(app).await;
+
+        let payload = AlertPayload {
+            search_id: "nas".to_string(),
+            name: "NAS".to_string(),
+            query: "neural architecture search".to_string(),
+            run_at: Utc::now(),
+            papers: vec![],
+        };
+        let hook = format!("{}/hook", base);
+        assert_eq!(
+            post(&hook, &payload).await,
+            Ok("HTTP 204 No Content".to_string())
+        );
+        assert_eq!(*received.lock().unwrap(), vec![payload.clone()]);
+
+        let gone = format!("{}/gone", base);
+        assert_eq!(
+            post(&gone, &payload).await,
+            Err("HTTP 410 Gone".to_string())
+        );
+    }
+}
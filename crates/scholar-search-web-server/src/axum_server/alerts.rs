@@ -0,0 +1,199 @@
+use crate::alerts::{email, feed, webhook, AlertPayload};
+use crate::axum_server::{
+    searches::saved_search_inbox,
+    state::{
+        delivery::{Delivery, DeliveryChannel, DeliveryConfig, DeliveryState, DeliveryStatus},
+        search::{InboxItem, SavedSearch, SearchState},
+        StateMach,
+    },
+    template::search::SavedSearchInboxTemplate,
+};
+
+use axum::{
+    extract::{Path, State},
+    http::{header::CONTENT_TYPE, StatusCode},
+    response::IntoResponse,
+    routing::{get, post},
+    Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+/// Queues one delivery per configured target for the newly found papers.
+pub fn enqueue_deliveries(
+    state_mach: &StateMach,
+    search: &SavedSearch,
+    run_at: DateTime<Utc>,
+    items: &[InboxItem],
+) {
+    if items.is_empty() {
+        return;
+    }
+    let payload = AlertPayload::new(search, run_at, items);
+    let targets = search
+        .delivery
+        .webhook_url
+        .iter()
+        .map(|url| (DeliveryChannel::Webhook, url))
+        .chain(
+            search
+                .delivery
+                .email_to
+                .iter()
+                .map(|to| (DeliveryChannel::Email, to)),
+        );
+    for (sequence, (channel, target)) in targets.enumerate() {
+        state_mach.set_delivery(&Delivery::new(&payload, channel, target, sequence));
+    }
+}
+
+async fn send(delivery: &Delivery) -> Result<String, String> {
+    match delivery.channel {
+        DeliveryChannel::Webhook => webhook::post(&delivery.target, &delivery.payload).await,
+        DeliveryChannel::Email => match email::SmtpConfig::from_env() {
+            Ok(Some(config)) => email::send(&config, &delivery.target, &delivery.payload).await,
+            Ok(None) => Err("SMTP_HOST is not configured".to_string()),
+            Err(e) => Err(e.to_string()),
+        },
+    }
+}
+
+pub async fn attempt_delivery(state_mach: &StateMach, mut delivery: Delivery) -> Delivery {
+    let result = send(&delivery).await;
+    match &result {
+        Ok(message) => info!(
+            "delivered {} to {} {}: {}",
+            delivery.search_id, delivery.channel, delivery.target, message
+        ),
+        Err(e) => warn!(
+            "delivery of {} to {} {} failed: {}",
+            delivery.search_id, delivery.channel, delivery.target, e
+        ),
+    }
+    delivery.record(Utc::now(), result);
+    state_mach.set_delivery(&delivery);
+    delivery
+}
+
+pub async fn process_due_deliveries(state_mach: &StateMach) {
+    for delivery in state_mach.list_due_deliveries(Utc::now()) {
+        attempt_delivery(state_mach, delivery).await;
+    }
+}
+
+fn feed_search(
+    state_mach: &StateMach,
+    search_id: &str,
+) -> Result<(SavedSearch, Vec<InboxItem>), (StatusCode, String)> {
+    let search = state_mach
+        .get_saved_search(search_id)
+        .ok_or((StatusCode::NOT_FOUND, "unknown saved search".to_string()))?;
+    let items = state_mach.list_inbox(search_id);
+    Ok((search, items))
+}
+
+pub async fn feed_atom(
+    State(state_mach): State<StateMach>,
+    Path(search_id): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let (search, items) = feed_search(&state_mach, &search_id)?;
+    Ok((
+        [(CONTENT_TYPE, "application/atom+xml; charset=utf-8")],
+        feed::atom(&search, &items),
+    ))
+}
+
+pub async fn feed_rss(
+    State(state_mach): State<StateMach>,
+    Path(search_id): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let (search, items) = feed_search(&state_mach, &search_id)?;
+    Ok((
+        [(CONTENT_TYPE, "application/rss+xml; charset=utf-8")],
+        feed::rss(&search, &items),
+    ))
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct DeliveryConfigRequest {
+    #[serde(default)]
+    webhook_url: String,
+    /// comma or whitespace separated addresses
+    #[serde(default)]
+    email_to: String,
+}
+
+pub async fn saved_search_delivery(
+    State(state_mach): State<StateMach>,
+    Path(search_id): Path<String>,
+    axum::Form(payload): axum::Form<DeliveryConfigRequest>,
+) -> Result<SavedSearchInboxTemplate, (StatusCode, String)> {
+    let webhook_url = Some(payload.webhook_url.trim().to_string()).filter(|u| !u.is_empty());
+    if let Some(url) = &webhook_url {
+        let url = reqwest::Url::parse(url).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "webhook url must be http(s)".to_string(),
+            ));
+        }
+    }
+    let email_to = payload
+        .email_to
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|to| !to.is_empty())
+        .map(|to| to.to_string())
+        .collect::<Vec<String>>();
+    if let Some(to) = email_to
+        .iter()
+        .find(|to| to.parse::<lettre::Address>().is_err())
+    {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("invalid email address: {}", to),
+        ));
+    }
+    if let Some(mut search) = state_mach.get_saved_search(&search_id) {
+        search.delivery = DeliveryConfig {
+            webhook_url,
+            email_to,
+        };
+        state_mach.set_saved_search(&search);
+    }
+    saved_search_inbox(State(state_mach), Path(search_id)).await
+}
+
+/// Re-queues a delivery that gave up and tries it right away; 409 for one
+/// that is not failed.
+pub async fn delivery_retry(
+    State(state_mach): State<StateMach>,
+    Path((search_id, delivery_id)): Path<(String, String)>,
+) -> Result<SavedSearchInboxTemplate, (StatusCode, String)> {
+    let mut delivery = state_mach
+        .get_delivery(&search_id, &delivery_id)
+        .ok_or((StatusCode::NOT_FOUND, "unknown delivery".to_string()))?;
+    if delivery.status != DeliveryStatus::Failed {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("delivery is {}, not failed", delivery.status),
+        ));
+    }
+    delivery.retry();
+    attempt_delivery(&state_mach, delivery).await;
+    saved_search_inbox(State(state_mach), Path(search_id)).await
+}
+
+pub fn alerts_router() -> Router<StateMach> {
+    Router::new()
+        .route("/feeds/searches/:search_id/atom", get(feed_atom))
+        .route("/feeds/searches/:search_id/rss", get(feed_rss))
+        .route(
+            "/x/searches/:search_id/delivery",
+            post(saved_search_delivery),
+        )
+        .route(
+            "/x/searches/:search_id/deliveries/:delivery_id/retry",
+            post(delivery_retry),
+        )
+}
@@ -0,0 +1,631 @@
+//! Streams PDFs from the web into the blob store.
+//!
+//! The body goes to a partial file under the blob store's `tmp` directory,
+//! named after the url, and only becomes a blob once it is complete and has
+//! been sniffed as a PDF. A partial left by a dropped connection is resumed
+//! with a `Range` request when the server gave a validator for it.
+
+use crate::axum_server::state::blob::{BlobRef, BlobStore};
+
+use regex::Regex;
+use reqwest::{
+    header::{self, HeaderMap, HeaderValue},
+    redirect, StatusCode, Url,
+};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// The PDF header must start within this many bytes.
+const SNIFF_BYTES: usize = 1024;
+
+/// How much of an HTML page is read to tell a landing page from a paywall.
+const HTML_BYTES: usize = 256 * 1024;
+
+const PDF_TYPES: &[&str] = &[
+    "",
+    "application/pdf",
+    "application/x-pdf",
+    "application/octet-stream",
+    "binary/octet-stream",
+    "application/download",
+    "application/force-download",
+];
+
+/// Phrases of publisher pages that sell or gate access to the PDF.
+const PAYWALL_HINTS: &[&str] = &[
+    "purchase this article",
+    "purchase pdf",
+    "buy this article",
+    "buy article",
+    "rent this article",
+    "get access",
+    "access through your institution",
+    "institutional access",
+    "subscribe to",
+    "log in to access",
+    "sign in to access",
+];
+
+/// Limits of a download, read from `SCHOLAR_SEARCH_DOWNLOAD_*` variables.
+#[derive(Debug, Clone)]
+pub struct DownloadConfig {
+    pub max_bytes: u64,
+    pub connect_timeout: Duration,
+    /// longest wait for the next bytes of the body
+    pub read_timeout: Duration,
+    pub max_redirects: usize,
+}
+
+impl Default for DownloadConfig {
+    fn default() -> Self {
+        Self {
+            max_bytes: 100 * 1024 * 1024,
+            connect_timeout: Duration::from_secs(10),
+            read_timeout: Duration::from_secs(30),
+            max_redirects: 10,
+        }
+    }
+}
+
+impl DownloadConfig {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let var = |name: &str| std::env::var(format!("SCHOLAR_SEARCH_DOWNLOAD_{}", name)).ok();
+        let default = Self::default();
+        Ok(Self {
+            max_bytes: match var("MAX_MB") {
+                Some(mb) => mb.trim().parse::<u64>()? * 1024 * 1024,
+                None => default.max_bytes,
+            },
+            connect_timeout: match var("CONNECT_TIMEOUT_SECS") {
+                Some(secs) => Duration::from_secs(secs.trim().parse()?),
+                None => default.connect_timeout,
+            },
+            read_timeout: match var("READ_TIMEOUT_SECS") {
+                Some(secs) => Duration::from_secs(secs.trim().parse()?),
+                None => default.read_timeout,
+            },
+            max_redirects: match var("MAX_REDIRECTS") {
+                Some(n) => n.trim().parse()?,
+                None => default.max_redirects,
+            },
+        })
+    }
+
+    fn client(&self) -> Result<reqwest::Client, DownloadError> {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::USER_AGENT, HeaderValue::from_static("Mozilla/5.0"));
+        headers.insert(
+            header::ACCEPT,
+            HeaderValue::from_static("application/pdf,*/*;q=0.8"),
+        );
+        reqwest::Client::builder()
+            .default_headers(headers)
+            .cookie_store(true)
+            // byte ranges have to refer to the file as stored
+            .no_gzip()
+            .no_brotli()
+            .no_deflate()
+            .connect_timeout(self.connect_timeout)
+            .read_timeout(self.read_timeout)
+            .redirect(redirect::Policy::limited(self.max_redirects))
+            .build()
+            .map_err(|e| DownloadError::Network(e.to_string()))
+    }
+}
+
+/// Why a url did not give a PDF.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DownloadError {
+    /// the server answered with an error status
+    Http {
+        status: u16,
+        url: String,
+    },
+    /// a login, purchase or institutional access page instead of the PDF
+    Paywall {
+        url: String,
+    },
+    /// an HTML page about the paper; `pdf_url` is the PDF link it declares
+    LandingPage {
+        url: String,
+        pdf_url: Option<String>,
+    },
+    /// neither a PDF nor a web page
+    NotPdf {
+        content_type: String,
+    },
+    TooLarge {
+        limit: u64,
+    },
+    Timeout,
+    /// connection or redirect failures; a partial body is kept for resuming
+    Network(String),
+    /// the blob store failed
+    Storage(String),
+}
+
+impl fmt::Display for DownloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DownloadError::Http { status, url } => write!(f, "HTTP {} from {}", status, url),
+            DownloadError::Paywall { url } => write!(f, "paywalled: {}", url),
+            DownloadError::LandingPage { url, pdf_url } => match pdf_url {
+                Some(pdf_url) => write!(f, "landing page {} links {}", url, pdf_url),
+                None => write!(f, "landing page without a pdf link: {}", url),
+            },
+            DownloadError::NotPdf { content_type } => write!(f, "not a pdf: {}", content_type),
+            DownloadError::TooLarge { limit } => write!(f, "larger than {} bytes", limit),
+            DownloadError::Timeout => write!(f, "timed out"),
+            DownloadError::Network(e) => write!(f, "network error: {}", e),
+            DownloadError::Storage(e) => write!(f, "storage error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for DownloadError {}
+
+impl From<reqwest::Error> for DownloadError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            DownloadError::Timeout
+        } else {
+            DownloadError::Network(e.to_string())
+        }
+    }
+}
+
+fn storage(e: impl fmt::Display) -> DownloadError {
+    DownloadError::Storage(e.to_string())
+}
+
+#[derive(Debug, PartialEq)]
+enum Sniffed {
+    Pdf,
+    Html,
+    Other,
+}
+
+/// The media type without parameters, lowercased.
+fn media_type(headers: &HeaderMap) -> String {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase()
+}
+
+/// Tells a PDF from a web page by the content type and the first bytes;
+/// a PDF needs both a PDF-compatible type and the `%PDF-` header.
+fn sniff(content_type: &str, head: &[u8]) -> Sniffed {
+    let head = &head[..head.len().min(SNIFF_BYTES)];
+    let text = String::from_utf8_lossy(head).to_lowercase();
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    if content_type.contains("html")
+        || text.starts_with("<!doctype html")
+        || text.starts_with("<html")
+        || text.contains("<head")
+    {
+        Sniffed::Html
+    } else if PDF_TYPES.contains(&content_type) && head.windows(5).any(|w| w == b"%PDF-") {
+        Sniffed::Pdf
+    } else {
+        Sniffed::Other
+    }
+}
+
+/// The `citation_pdf_url` meta tag of a landing page, resolved against the
+/// page's url.
+pub fn citation_pdf_url(html: &str, base: &str) -> Option<String> {
+    static META: OnceLock<Regex> = OnceLock::new();
+    static CONTENT: OnceLock<Regex> = OnceLock::new();
+    let meta = META.get_or_init(|| {
+        Regex::new(r#"(?is)<meta\b[^>]*\bname\s*=\s*["']citation_pdf_url["'][^>]*>"#).unwrap()
+    });
+    let content =
+        CONTENT.get_or_init(|| Regex::new(r#"(?is)\bcontent\s*=\s*["']([^"']+)["']"#).unwrap());
+    let tag = meta.find(html)?;
+    let href = content.captures(tag.as_str())?.get(1)?.as_str().trim();
+    let href = href.replace("&amp;", "&");
+    match Url::parse(base).and_then(|base| base.join(&href)) {
+        Ok(url) => Some(url.to_string()),
+        Err(_) => Some(href),
+    }
+}
+
+/// Names the HTML page a url gave instead of the PDF.
+fn classify_html(url: &str, status: StatusCode, html: &[u8]) -> DownloadError {
+    let html = String::from_utf8_lossy(html);
+    let pdf_url = citation_pdf_url(&html, url);
+    let lower = html.to_lowercase();
+    let gated = matches!(
+        status,
+        StatusCode::UNAUTHORIZED | StatusCode::PAYMENT_REQUIRED | StatusCode::FORBIDDEN
+    );
+    if gated || (pdf_url.is_none() && PAYWALL_HINTS.iter().any(|h| lower.contains(h))) {
+        DownloadError::Paywall {
+            url: url.to_string(),
+        }
+    } else {
+        DownloadError::LandingPage {
+            url: url.to_string(),
+            pdf_url,
+        }
+    }
+}
+
+/// Where a download of `url` keeps its partial body, and the validator
+/// (`ETag` or `Last-Modified`) a resume has to match.
+fn partial_paths(blobs: &BlobStore, url: &str) -> Result<(PathBuf, PathBuf), DownloadError> {
+    let partial = blobs.partial_path(url).map_err(storage)?;
+    let validator = partial.with_extension("validator");
+    Ok((partial, validator))
+}
+
+fn discard(partial: &Path, validator: &Path) {
+    let _ = fs::remove_file(partial);
+    let _ = fs::remove_file(validator);
+}
+
+/// The byte offset to resume from, with the validator to send as
+/// `If-Range`; partials without a validator are started over.
+fn resume_point(partial: &Path, validator: &Path) -> Option<(u64, String)> {
+    let validator = fs::read_to_string(validator).ok()?;
+    let len = fs::metadata(partial).ok()?.len();
+    (len > 0).then_some((len, validator))
+}
+
+/// Whether a 206 continues the partial body at `offset`.
+fn continues_at(headers: &HeaderMap, offset: u64) -> bool {
+    headers
+        .get(header::CONTENT_RANGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("bytes "))
+        .and_then(|v| v.split('-').next())
+        .and_then(|start| start.parse::<u64>().ok())
+        == Some(offset)
+}
+
+/// Downloads the PDF at `url` into the blob store.
+pub async fn pdf_download(
+    config: &DownloadConfig,
+    blobs: &BlobStore,
+    url: &str,
+) -> Result<BlobRef, DownloadError> {
+    let client = config.client()?;
+    let (partial, validator_path) = partial_paths(blobs, url)?;
+    let resume = resume_point(&partial, &validator_path);
+
+    let mut request = client.get(url);
+    if let Some((offset, validator)) = &resume {
+        info!("resuming {} at byte {}", url, offset);
+        request = request
+            .header(header::RANGE, format!("bytes={}-", offset))
+            .header(header::IF_RANGE, validator.as_str());
+    }
+    let mut resp = request.send().await?;
+    let final_url = resp.url().to_string();
+    let status = resp.status();
+    let content_type = media_type(resp.headers());
+
+    let offset = match &resume {
+        Some((offset, _))
+            if status == StatusCode::PARTIAL_CONTENT && continues_at(resp.headers(), *offset) =>
+        {
+            *offset
+        }
+        _ => 0,
+    };
+    if !status.is_success() {
+        if resume.is_some() {
+            // e.g. 416 when the partial already reaches the end: start over
+            discard(&partial, &validator_path);
+        }
+        if content_type.contains("html") {
+            let html = read_head(&mut resp, HTML_BYTES).await?;
+            return Err(classify_html(&final_url, status, &html));
+        }
+        return Err(DownloadError::Http {
+            status: status.as_u16(),
+            url: final_url,
+        });
+    }
+    if status == StatusCode::PARTIAL_CONTENT && offset == 0 {
+        // a range we did not ask for, or not where the partial ends
+        discard(&partial, &validator_path);
+        return Err(DownloadError::Network(format!(
+            "unexpected partial content from {}",
+            final_url
+        )));
+    }
+    let expected = resp.content_length().map(|len| offset + len);
+    if expected.is_some_and(|len| len > config.max_bytes) {
+        discard(&partial, &validator_path);
+        return Err(DownloadError::TooLarge {
+            limit: config.max_bytes,
+        });
+    }
+
+    let mut head = vec![];
+    if offset == 0 {
+        head = read_head(&mut resp, SNIFF_BYTES).await?;
+        match sniff(&content_type, &head) {
+            Sniffed::Pdf => {}
+            Sniffed::Html => {
+                let rest = HTML_BYTES.saturating_sub(head.len());
+                head.extend(read_head(&mut resp, rest).await?);
+                discard(&partial, &validator_path);
+                return Err(classify_html(&final_url, status, &head));
+            }
+            Sniffed::Other => {
+                discard(&partial, &validator_path);
+                return Err(DownloadError::NotPdf { content_type });
+            }
+        }
+        let validator = [header::ETAG, header::LAST_MODIFIED]
+            .iter()
+            .find_map(|name| resp.headers().get(name)?.to_str().ok());
+        match validator {
+            Some(validator) => fs::write(&validator_path, validator).map_err(storage)?,
+            None => {
+                let _ = fs::remove_file(&validator_path);
+            }
+        }
+    }
+
+    let mut out = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(offset > 0)
+        .truncate(offset == 0)
+        .open(&partial)
+        .map_err(storage)?;
+    out.write_all(&head).map_err(storage)?;
+    let mut written = offset + head.len() as u64;
+    loop {
+        let chunk = match resp.chunk().await {
+            Ok(Some(chunk)) => chunk,
+            Ok(None) => break,
+            Err(e) => {
+                // keep what arrived for the next attempt
+                warn!("{}: stopped at byte {}: {}", url, written, e);
+                return Err(e.into());
+            }
+        };
+        written += chunk.len() as u64;
+        if written > config.max_bytes {
+            drop(out);
+            discard(&partial, &validator_path);
+            return Err(DownloadError::TooLarge {
+                limit: config.max_bytes,
+            });
+        }
+        out.write_all(&chunk).map_err(storage)?;
+    }
+    out.sync_all().map_err(storage)?;
+    drop(out);
+    if expected.is_some_and(|len| len != written) {
+        return Err(DownloadError::Network(format!(
+            "body ended at byte {} of {:?}",
+            written, expected
+        )));
+    }
+    if offset > 0 && !starts_like_pdf(&partial) {
+        discard(&partial, &validator_path);
+        return Err(DownloadError::NotPdf { content_type });
+    }
+    let _ = fs::remove_file(&validator_path);
+    blobs.ingest(&partial).map_err(storage)
+}
+
+/// Reads up to `limit` bytes of the body, or all of a shorter one.
+async fn read_head(resp: &mut reqwest::Response, limit: usize) -> Result<Vec<u8>, DownloadError> {
+    let mut head = vec![];
+    while head.len() < limit {
+        match resp.chunk().await? {
+            Some(chunk) => head.extend_from_slice(&chunk),
+            None => break,
+        }
+    }
+    Ok(head)
+}
+
+fn starts_like_pdf(path: &Path) -> bool {
+    let mut head = vec![];
+    File::open(path)
+        .and_then(|f| f.take(SNIFF_BYTES as u64).read_to_end(&mut head))
+        .is_ok()
+        && sniff("", &head) == Sniffed::Pdf
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::axum_server::fixture;
+    use axum::{
+        http::{HeaderMap as AxumHeaders, StatusCode as AxumStatus},
+        response::IntoResponse,
+        routing::get,
+        Router,
+    };
+
+    const PDF: &[u8] = b"%PDF-1.4\n1 0 obj << >> endobj\ntrailer << >>\n%%EOF\n";
+
+    #[test]
+    fn test_sniff() {
+        assert_eq!(sniff("application/pdf", PDF), Sniffed::Pdf);
+        assert_eq!(sniff("", b"\n\n%PDF-1.7"), Sniffed::Pdf);
+        assert_eq!(sniff("image/png", PDF), Sniffed::Other);
+        assert_eq!(sniff("application/pdf", b"PK\x03\x04"), Sniffed::Other);
+        assert_eq!(sniff("text/html", b"%PDF-1.4"), Sniffed::Html);
+        assert_eq!(
+            sniff(
+                "application/octet-stream",
+                b"\xef\xbb\xbf<!DOCTYPE html><html>"
+            ),
+            Sniffed::Html
+        );
+
+        let landing = r#"<html><head><meta content="/doi/pdf/10.1/x?download=1&amp;a=b"
+            name="citation_pdf_url"></head><body>Get access</body></html>"#;
+        assert_eq!(
+            classify_html(
+                "https://pub.example/doi/10.1/x",
+                StatusCode::OK,
+                landing.as_bytes()
+            ),
+            DownloadError::LandingPage {
+                url: "https://pub.example/doi/10.1/x".to_string(),
+                pdf_url: Some("https://pub.example/doi/pdf/10.1/x?download=1&a=b".to_string()),
+            }
+        );
+        let paywall = b"<html><body>Purchase this article for $39.95</body></html>";
+        assert!(matches!(
+            classify_html("https://pub.example/x", StatusCode::OK, paywall),
+            DownloadError::Paywall { .. }
+        ));
+        assert!(matches!(
+            classify_html(
+                "https://pub.example/x",
+                StatusCode::FORBIDDEN,
+                landing.as_bytes()
+            ),
+            DownloadError::Paywall { .. }
+        ));
+    }
+
+    /// Serves the test PDF with range support, a landing page and a paywall.
+    async fn serve() -> String {
+        async fn pdf(headers: AxumHeaders) -> axum::response::Response {
+            let start = headers
+                .get("range")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.strip_prefix("bytes="))
+                .and_then(|v| v.trim_end_matches('-').parse::<usize>().ok())
+                .filter(|_| headers.get("if-range").is_some_and(|v| v == "\"v1\""));
+            match start {
+                Some(start) if start >= PDF.len() => {
+                    AxumStatus::RANGE_NOT_SATISFIABLE.into_response()
+                }
+                Some(start) => (
+                    AxumStatus::PARTIAL_CONTENT,
+                    [
+                        ("content-type", "application/pdf".to_string()),
+                        ("etag", "\"v1\"".to_string()),
+                        (
+                            "content-range",
+                            format!("bytes {}-{}/{}", start, PDF.len() - 1, PDF.len()),
+                        ),
+                    ],
+                    PDF[start..].to_vec(),
+                )
+                    .into_response(),
+                None => (
+                    AxumStatus::OK,
+                    [
+                        ("content-type", "application/pdf".to_string()),
+                        ("etag", "\"v1\"".to_string()),
+                        ("content-length", PDF.len().to_string()),
+                    ],
+                    PDF.to_vec(),
+                )
+                    .into_response(),
+            }
+        }
+        async fn landing() -> impl IntoResponse {
+            let meta = r#"<meta name="citation_pdf_url" content="/paper.pdf">"#;
+            (
+                [("content-type", "text/html")],
+                format!("<html><head>{}</head></html>", meta),
+            )
+        }
+        async fn paywall() -> impl IntoResponse {
+            let page = "<html>no</html>";
+            (AxumStatus::FORBIDDEN, [("content-type", "text/html")], page)
+        }
+        let app = Router::new()
+            .route("/paper.pdf", get(pdf))
+            .route("/landing", get(landing))
+            .route("/paywall", get(paywall))
+            .route(
+                "/redirect",
+                get(|| async { axum::response::Redirect::temporary("/redirect") }),
+            );
+        fixture::serve(app).await
+    }
+
+    #[tokio::test]
+    async fn test_download() {
+        let base = serve().await;
+        let root = std::env::temp_dir().join(format!("scholar-download-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let blobs = BlobStore::new(&root, None);
+        let config = DownloadConfig::default();
+        let url = format!("{}/paper.pdf", base);
+
+        let blob = pdf_download(&config, &blobs, &url).await.unwrap();
+        assert_eq!(blobs.read(&blob.sha256).unwrap(), PDF);
+
+        // a partial from an earlier attempt is completed, not restarted
+        let (partial, validator) = partial_paths(&blobs, &url).unwrap();
+        fs::write(&partial, &PDF[..10]).unwrap();
+        fs::write(&validator, "\"v1\"").unwrap();
+        assert_eq!(pdf_download(&config, &blobs, &url).await.unwrap(), blob);
+        assert!(!partial.exists() && !validator.exists());
+
+        // a partial the server can not resume is dropped, not retried forever
+        fs::write(&partial, PDF).unwrap();
+        fs::write(&validator, "\"v1\"").unwrap();
+        assert!(matches!(
+            pdf_download(&config, &blobs, &url).await,
+            Err(DownloadError::Http { status: 416, .. })
+        ));
+        assert!(!partial.exists() && !validator.exists());
+        assert_eq!(pdf_download(&config, &blobs, &url).await.unwrap(), blob);
+
+        assert_eq!(
+            pdf_download(&config, &blobs, &format!("{}/landing", base)).await,
+            Err(DownloadError::LandingPage {
+                url: format!("{}/landing", base),
+                pdf_url: Some(url.clone()),
+            })
+        );
+        assert!(matches!(
+            pdf_download(&config, &blobs, &format!("{}/paywall", base)).await,
+            Err(DownloadError::Paywall { .. })
+        ));
+        assert!(matches!(
+            pdf_download(&config, &blobs, &format!("{}/redirect", base)).await,
+            Err(DownloadError::Network(_))
+        ));
+        let small = DownloadConfig {
+            max_bytes: 10,
+            ..DownloadConfig::default()
+        };
+        assert_eq!(
+            pdf_download(&small, &blobs, &url).await,
+            Err(DownloadError::TooLarge { limit: 10 })
+        );
+        assert!(!partial.exists());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_pdf_download() {
+        let root =
+            std::env::temp_dir().join(format!("scholar-pdf-download-{}", std::process::id()));
+        pdf_download(
+            &DownloadConfig::default(),
+            &BlobStore::new(root, None),
+            "https://dl.acm.org/doi/pdf/10.1145/3292500.3330648",
+        )
+        .await
+        .unwrap();
+    }
+}
@@ -1 +1,3 @@
-pub mod pdf;
\ No newline at end of file
+pub mod download;
+pub mod pdf;
+pub mod resolver;
@@ -0,0 +1,383 @@
+//! In-text citations: the markers the body refers to its references by,
+//! `[12]`, `[3–5]`, `[Smi19]`, `(Smith et al., 2019; Lee, 2020a)` or `Smith
+//! and Jones (2019)`, with the sentence each one stands in.
+//!
+//! S2 only has citation contexts for papers it has parsed itself, which new
+//! papers often are not yet.
+
+use super::model::Reference;
+use crate::citation::resolve::normalize_title;
+use regex::Regex;
+use serde_derive::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// Numeric ranges spanning more references than this, e.g. `[1–200]`, are
+/// taken for something else.
+const MAX_RANGE: u32 = 30;
+
+/// Words ending in a period that do not end a sentence.
+const ABBREVIATIONS: [&str; 10] = [
+    "al", "cf", "Fig", "Figs", "Eq", "Eqs", "Sec", "Tab", "vs", "resp",
+];
+
+/// Where a reference is cited in the body.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CitationContext {
+    /// the marker as printed
+    pub marker: String,
+    pub sentence: String,
+    /// the title of the section the sentence is in
+    pub section: Option<String>,
+    pub page: u32,
+}
+
+/// What a marker points at.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cited {
+    /// a reference label, `12` or `Smi19`
+    Label(String),
+    /// the first author's family name and the year, with the letter that
+    /// tells apart entries of the same author and year
+    AuthorYear {
+        family: String,
+        year: i32,
+        suffix: Option<char>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Citation {
+    pub cited: Vec<Cited>,
+    pub context: CitationContext,
+}
+
+fn bracket() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\[(?<labels>[^\[\]]{1,80})\]").unwrap())
+}
+
+/// `3`, `3–5` or `Smi19` inside brackets
+fn bracket_label() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(concat!(
+            r"^(?:(?<first>\d{1,3})(?:\s*[-–—]\s*(?<last>\d{1,3}))?",
+            r"|(?<key>\p{Lu}[\p{L}+]*\d{2}[a-z]?))$"
+        ))
+        .unwrap()
+    })
+}
+
+fn parenthesis() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\((?<cites>[^()]{4,200})\)").unwrap())
+}
+
+/// `Smith et al., 2019`, `see Smith and Jones 2019a, 2020` inside parentheses
+fn author_year() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(concat!(
+            r"^(?:(?:see|e\.g\.|cf\.|i\.e\.)[,\s]+)?(?<names>\p{Lu}[^,;]*?),?\s+",
+            r"(?<years>(?:19|20)\d{2}[a-z]?(?:\s*,\s*(?:19|20)\d{2}[a-z]?)*)$"
+        ))
+        .unwrap()
+    })
+}
+
+/// `Smith et al. (2019)`, `Smith and Jones (2019a, 2020)`
+fn narrative() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(concat!(
+            r"(?<names>\p{Lu}[\p{L}'’-]+(?:\s+et\s+al\.?|\s+(?:and|&)\s+\p{Lu}[\p{L}'’-]+)?)",
+            r"\s+\((?<years>(?:19|20)\d{2}[a-z]?(?:\s*,\s*(?:19|20)\d{2}[a-z]?)*)\)"
+        ))
+        .unwrap()
+    })
+}
+
+/// The labels of `[3–5, 9]` or `[Smi19; Lee20]`; `None` when any part is
+/// something else, e.g. `[0, 1]` in a formula or `[cs.LG]`.
+fn bracket_cited(labels: &str) -> Option<Vec<Cited>> {
+    let mut cited = vec![];
+    for part in labels.split([',', ';']) {
+        let caps = bracket_label().captures(part.trim())?;
+        if let Some(key) = caps.name("key") {
+            cited.push(Cited::Label(key.as_str().to_string()));
+            continue;
+        }
+        let first = caps["first"].parse::<u32>().ok()?;
+        let last = match caps.name("last") {
+            Some(last) => last.as_str().parse::<u32>().ok()?,
+            None => first,
+        };
+        if first == 0 || last < first || last - first > MAX_RANGE {
+            return None;
+        }
+        cited.extend((first..=last).map(|n| Cited::Label(n.to_string())));
+    }
+    Some(cited)
+}
+
+fn author_year_cited(names: &str, years: &str) -> Vec<Cited> {
+    let family = names
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches([',', '.']);
+    years
+        .split(',')
+        .filter_map(|year| {
+            let year = year.trim();
+            Some(Cited::AuthorYear {
+                family: family.to_string(),
+                year: year.get(..4)?.parse().ok()?,
+                suffix: year[4..].chars().next(),
+            })
+        })
+        .collect()
+}
+
+/// Byte offsets just past each sentence end.
+fn sentence_ends(text: &str) -> Vec<usize> {
+    let mut ends = vec![];
+    let mut word_start = 0;
+    for (i, c) in text.char_indices() {
+        if c.is_whitespace() {
+            word_start = i + c.len_utf8();
+            continue;
+        }
+        let end = i + c.len_utf8();
+        if !matches!(c, '.' | '?' | '!') || !text[end..].starts_with(char::is_whitespace) {
+            continue;
+        }
+        let word = text[word_start..i].trim_start_matches(['(', '[']);
+        let initial = word.chars().count() == 1 && word.starts_with(char::is_uppercase);
+        if c == '.' && (initial || word.contains('.') || ABBREVIATIONS.contains(&word)) {
+            continue;
+        }
+        ends.push(end);
+    }
+    ends
+}
+
+/// The sentence holding `start..end`, on one line.
+fn sentence(text: &str, ends: &[usize], start: usize, end: usize) -> String {
+    let from = ends
+        .iter()
+        .rev()
+        .find(|e| **e <= start)
+        .copied()
+        .unwrap_or(0);
+    let to = ends
+        .iter()
+        .find(|e| **e >= end)
+        .copied()
+        .unwrap_or(text.len());
+    text[from..to]
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// The citation markers of a block of body text, in reading order.
+pub fn find(text: &str, page: u32, section: Option<&str>) -> Vec<Citation> {
+    let mut found = vec![];
+    for caps in bracket().captures_iter(text) {
+        if let Some(cited) = bracket_cited(&caps["labels"]) {
+            found.push((caps.get(0).unwrap(), cited));
+        }
+    }
+    for caps in parenthesis().captures_iter(text) {
+        let cited = caps["cites"]
+            .split(';')
+            .filter_map(|cite| author_year().captures(cite.trim()))
+            .flat_map(|cite| author_year_cited(&cite["names"], &cite["years"]))
+            .collect::<Vec<Cited>>();
+        if !cited.is_empty() {
+            found.push((caps.get(0).unwrap(), cited));
+        }
+    }
+    for caps in narrative().captures_iter(text) {
+        found.push((
+            caps.get(0).unwrap(),
+            author_year_cited(&caps["names"], &caps["years"]),
+        ));
+    }
+    found.sort_by_key(|(m, _)| m.start());
+    let ends = sentence_ends(text);
+    found
+        .into_iter()
+        .map(|(m, cited)| Citation {
+            cited,
+            context: CitationContext {
+                marker: m
+                    .as_str()
+                    .split_whitespace()
+                    .collect::<Vec<&str>>()
+                    .join(" "),
+                sentence: sentence(text, &ends, m.start(), m.end()),
+                section: section.map(str::to_string),
+                page,
+            },
+        })
+        .collect()
+}
+
+/// The reference a marker points at. Author year markers match on the
+/// first author and year; where several entries share both, the letter
+/// after the year picks one in list order, and without it none is picked.
+fn target(references: &[Reference], cited: &Cited) -> Option<usize> {
+    match cited {
+        Cited::Label(label) => references
+            .iter()
+            .position(|r| r.label.as_ref() == Some(label)),
+        Cited::AuthorYear {
+            family,
+            year,
+            suffix,
+        } => {
+            let family = normalize_title(family);
+            let same = references
+                .iter()
+                .enumerate()
+                .filter(|(_, r)| {
+                    r.fields.year == Some(*year)
+                        && r.fields
+                            .authors
+                            .first()
+                            .is_some_and(|a| normalize_title(&a.family) == family)
+                })
+                .map(|(i, _)| i)
+                .collect::<Vec<usize>>();
+            match suffix {
+                Some(suffix) => same
+                    .get((*suffix as usize).checked_sub('a' as usize)?)
+                    .copied(),
+                None if same.len() == 1 => Some(same[0]),
+                None => None,
+            }
+        }
+    }
+}
+
+/// Adds each citation's context to the references it points at; returns
+/// how many markers pointed at no reference.
+pub fn link(references: &mut [Reference], citations: Vec<Citation>) -> usize {
+    let mut unlinked = 0;
+    for citation in citations {
+        for cited in &citation.cited {
+            match target(references, cited) {
+                Some(index) => references[index].citations.push(citation.context.clone()),
+                None => unlinked += 1,
+            }
+        }
+    }
+    unlinked
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::citation::import::reference::parse;
+
+    fn reference(label: Option<&str>, text: &str) -> Reference {
+        Reference {
+            label: label.map(str::to_string),
+            page: 9,
+            text: text.to_string(),
+            fields: parse(text),
+            citations: vec![],
+        }
+    }
+
+    #[test]
+    fn test_find_citations() {
+        let text = "Bandits are well studied [1, 3–5]. Fig. 2 shows the regret over [0, 1] of\nthe method of Smith et al. (2019a), unlike earlier work (see Lee and Kim, 2018; Lee, 2020).";
+        let citations = find(text, 2, Some("2 Related Work"));
+        let markers = citations
+            .iter()
+            .map(|c| c.context.marker.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(
+            markers,
+            vec![
+                "[1, 3–5]",
+                "Smith et al. (2019a)",
+                "(see Lee and Kim, 2018; Lee, 2020)"
+            ]
+        );
+        assert_eq!(citations[0].cited.len(), 4);
+        assert_eq!(
+            citations[0].context.sentence,
+            "Bandits are well studied [1, 3–5]."
+        );
+        assert_eq!(
+            citations[1].context.sentence,
+            "Fig. 2 shows the regret over [0, 1] of the method of Smith et al. (2019a), unlike earlier work (see Lee and Kim, 2018; Lee, 2020)."
+        );
+        assert_eq!(
+            citations[1].cited,
+            vec![Cited::AuthorYear {
+                family: "Smith".to_string(),
+                year: 2019,
+                suffix: Some('a'),
+            }]
+        );
+        assert_eq!(citations[2].cited.len(), 2);
+        assert_eq!(
+            citations[2].context.section.as_deref(),
+            Some("2 Related Work")
+        );
+        assert!(find("see [cs.LG] and [1-200]", 1, None).is_empty());
+    }
+
+    #[test]
+    fn test_link_citations() {
+        let mut numbered = vec![
+            reference(
+                Some("1"),
+                "P. Auer. 2002. Finite-time analysis. Machine learning.",
+            ),
+            reference(
+                Some("2"),
+                "B. Baker. 2016. Designing networks. arXiv:1611.02167",
+            ),
+        ];
+        let unlinked = link(&mut numbered, find("As in [2] and [1–3].", 1, None));
+        assert_eq!(unlinked, 1);
+        assert_eq!(
+            (numbered[0].citations.len(), numbered[1].citations.len()),
+            (1, 2)
+        );
+        assert_eq!(numbered[1].citations[0].marker, "[2]");
+
+        let mut author_year = vec![
+            reference(
+                None,
+                "Smith, J. (2019a). Deep graphs. Journal of Graphs, 3, 1–10.",
+            ),
+            reference(
+                None,
+                "Smith, J. (2019b). Shallow trees. Tree Letters, 12, 5–9.",
+            ),
+            reference(None, "Lee, K. (2020). Forests. Botany, 1, 2–3."),
+        ];
+        let unlinked = link(
+            &mut author_year,
+            find(
+                "Trees (Smith, 2019b; Lee 2020) beat graphs (Smith, 2019).",
+                4,
+                None,
+            ),
+        );
+        assert_eq!(unlinked, 1);
+        let counts = author_year
+            .iter()
+            .map(|r| r.citations.len())
+            .collect::<Vec<usize>>();
+        assert_eq!(counts, vec![0, 1, 1]);
+        assert_eq!(author_year[2].citations[0].page, 4);
+    }
+}
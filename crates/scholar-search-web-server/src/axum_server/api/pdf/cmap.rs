@@ -0,0 +1,305 @@
+//! CMaps: the `/ToUnicode` streams mapping character codes to text, and the
+//! embedded `/Encoding` CMaps of Type0 fonts mapping codes to CIDs.
+//!
+//! Only the operators those streams are built from are read; PostScript
+//! around them is skipped.
+
+use std::collections::HashMap;
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Hex(Vec<u8>),
+    Number(u32),
+    ArrayStart,
+    ArrayEnd,
+    Keyword(String),
+}
+
+fn is_delimiter(c: u8) -> bool {
+    c.is_ascii_whitespace() || b"<>[](){}%/".contains(&c)
+}
+
+fn tokens(data: &[u8]) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < data.len() {
+        let c = data[i];
+        match c {
+            b'%' => {
+                while i < data.len() && data[i] != b'\n' && data[i] != b'\r' {
+                    i += 1;
+                }
+            }
+            b'<' if data.get(i + 1) == Some(&b'<') => i += 2,
+            b'>' if data.get(i + 1) == Some(&b'>') => i += 2,
+            b'<' => {
+                let end = data[i..]
+                    .iter()
+                    .position(|c| *c == b'>')
+                    .map_or(data.len(), |p| i + p);
+                let digits = data[i + 1..end]
+                    .iter()
+                    .filter(|c| c.is_ascii_hexdigit())
+                    .map(|c| (*c as char).to_digit(16).unwrap() as u8)
+                    .collect::<Vec<u8>>();
+                // an odd last digit is followed by an implicit 0
+                let bytes = digits
+                    .chunks(2)
+                    .map(|d| d[0] << 4 | d.get(1).copied().unwrap_or(0))
+                    .collect();
+                tokens.push(Token::Hex(bytes));
+                i = end + 1;
+            }
+            b'(' => {
+                // literal strings only appear in the header
+                let mut depth = 0;
+                while i < data.len() {
+                    match data[i] {
+                        b'\\' => i += 1,
+                        b'(' => depth += 1,
+                        b')' => depth -= 1,
+                        _ => {}
+                    }
+                    i += 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+            }
+            b'[' => {
+                tokens.push(Token::ArrayStart);
+                i += 1;
+            }
+            b']' => {
+                tokens.push(Token::ArrayEnd);
+                i += 1;
+            }
+            b'/' => {
+                // names (`/CIDInit`, `/CMapName`) carry nothing we need
+                i += 1;
+                while i < data.len() && !is_delimiter(data[i]) {
+                    i += 1;
+                }
+            }
+            c if c.is_ascii_whitespace() || is_delimiter(c) => i += 1,
+            _ => {
+                let start = i;
+                while i < data.len() && !is_delimiter(data[i]) {
+                    i += 1;
+                }
+                let word = String::from_utf8_lossy(&data[start..i]).to_string();
+                match word.parse::<u32>() {
+                    Ok(n) => tokens.push(Token::Number(n)),
+                    Err(_) => tokens.push(Token::Keyword(word)),
+                }
+            }
+        }
+    }
+    tokens
+}
+
+fn code(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0, |code, b| code << 8 | *b as u32)
+}
+
+/// Destination strings are UTF-16BE.
+fn utf16(bytes: &[u8]) -> String {
+    let units = bytes
+        .chunks(2)
+        .map(|c| u16::from_be_bytes([c[0], c.get(1).copied().unwrap_or(0)]))
+        .collect::<Vec<u16>>();
+    String::from_utf16_lossy(&units)
+}
+
+/// `dst` with `offset` added to its last code unit, as `bfrange` steps.
+fn utf16_offset(bytes: &[u8], offset: u32) -> String {
+    let mut units = bytes
+        .chunks(2)
+        .map(|c| u16::from_be_bytes([c[0], c.get(1).copied().unwrap_or(0)]))
+        .collect::<Vec<u16>>();
+    if let Some(last) = units.last_mut() {
+        *last = last.wrapping_add(offset as u16);
+    }
+    String::from_utf16_lossy(&units)
+}
+
+/// The sections holding mappings, each closed by its `end...` keyword.
+const SECTIONS: [&str; 5] = [
+    "begincodespacerange",
+    "beginbfchar",
+    "beginbfrange",
+    "begincidrange",
+    "begincidchar",
+];
+
+#[derive(Debug, Default, Clone)]
+pub struct CMap {
+    /// `(low, high)` byte ranges of the valid codes, by code length
+    codespace: Vec<(Vec<u8>, Vec<u8>)>,
+    unicode: HashMap<u32, String>,
+    /// `(low, high, first cid)`
+    cid_ranges: Vec<(u32, u32, u32)>,
+}
+
+impl CMap {
+    pub fn parse(data: &[u8]) -> Self {
+        let mut cmap = CMap::default();
+        let mut tokens = tokens(data).into_iter();
+        while let Some(token) = tokens.next() {
+            let Token::Keyword(keyword) = token else {
+                continue;
+            };
+            if !SECTIONS.contains(&keyword.as_str()) {
+                continue;
+            }
+            let end = Token::Keyword(keyword.replacen("begin", "end", 1));
+            let operands = tokens.by_ref().take_while(|t| *t != end).collect();
+            cmap.read_section(&keyword, operands);
+        }
+        cmap
+    }
+
+    fn read_section(&mut self, keyword: &str, operands: Vec<Token>) {
+        let mut operands = operands.into_iter();
+        match keyword {
+            "begincodespacerange" => {
+                while let (Some(Token::Hex(low)), Some(Token::Hex(high))) =
+                    (operands.next(), operands.next())
+                {
+                    self.codespace.push((low, high));
+                }
+            }
+            "beginbfchar" => {
+                while let (Some(Token::Hex(src)), Some(Token::Hex(dst))) =
+                    (operands.next(), operands.next())
+                {
+                    self.unicode.insert(code(&src), utf16(&dst));
+                }
+            }
+            "beginbfrange" => {
+                while let (Some(Token::Hex(low)), Some(Token::Hex(high))) =
+                    (operands.next(), operands.next())
+                {
+                    let (low, high) = (code(&low), code(&high));
+                    match operands.next() {
+                        Some(Token::Hex(dst)) => {
+                            for c in low..=high.min(low.saturating_add(0xffff)) {
+                                self.unicode.insert(c, utf16_offset(&dst, c - low));
+                            }
+                        }
+                        Some(Token::ArrayStart) => {
+                            let mut c = low;
+                            for token in operands.by_ref() {
+                                match token {
+                                    Token::Hex(dst) => {
+                                        self.unicode.insert(c, utf16(&dst));
+                                        c += 1;
+                                    }
+                                    _ => break,
+                                }
+                            }
+                        }
+                        _ => break,
+                    }
+                }
+            }
+            "begincidrange" => {
+                while let (
+                    Some(Token::Hex(low)),
+                    Some(Token::Hex(high)),
+                    Some(Token::Number(cid)),
+                ) = (operands.next(), operands.next(), operands.next())
+                {
+                    self.cid_ranges.push((code(&low), code(&high), cid));
+                }
+            }
+            "begincidchar" => {
+                while let (Some(Token::Hex(src)), Some(Token::Number(cid))) =
+                    (operands.next(), operands.next())
+                {
+                    self.cid_ranges.push((code(&src), code(&src), cid));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Splits a shown string into codes by the codespace ranges; without any,
+    /// codes are `default_len` bytes long.
+    pub fn codes(&self, bytes: &[u8], default_len: usize) -> Vec<u32> {
+        let mut codes = vec![];
+        let mut i = 0;
+        while i < bytes.len() {
+            let len = self
+                .codespace
+                .iter()
+                .filter(|(low, high)| {
+                    let candidate = &bytes[i..(i + low.len()).min(bytes.len())];
+                    candidate.len() == low.len()
+                        && candidate
+                            .iter()
+                            .zip(low.iter().zip(high))
+                            .all(|(b, (l, h))| l <= b && b <= h)
+                })
+                .map(|(low, _)| low.len())
+                .min()
+                .unwrap_or(default_len)
+                .max(1);
+            let end = (i + len).min(bytes.len());
+            codes.push(code(&bytes[i..end]));
+            i = end;
+        }
+        codes
+    }
+
+    pub fn unicode(&self, code: u32) -> Option<&str> {
+        self.unicode.get(&code).map(String::as_str)
+    }
+
+    pub fn cid(&self, code: u32) -> Option<u32> {
+        self.cid_ranges
+            .iter()
+            .find(|(low, high, _)| (*low..=*high).contains(&code))
+            .map(|(low, _, cid)| cid + code - low)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_to_unicode() {
+        let data = br#"/CIDInit /ProcSet findresource begin
+12 dict begin
+begincmap
+/CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def
+/CMapName /Adobe-Identity-UCS def
+1 begincodespacerange
+<0000> <FFFF>
+endcodespacerange
+2 beginbfchar
+<0003> <0020>
+<000C> <00660069>
+endbfchar
+2 beginbfrange
+<0024> <0026> <0041>
+<0030> <0031> [<0066006C> <D835DC00>]
+endbfrange
+1 begincidrange
+<0100> <01FF> 10
+endcidrange
+endcmap
+CMapName currentdict /CMap defineresource pop
+end
+end"#;
+        let cmap = CMap::parse(data);
+        assert_eq!(cmap.codes(&[0, 0x0c, 0, 0x26], 1), vec![0x0c, 0x26]);
+        assert_eq!(cmap.unicode(0x0c), Some("fi"));
+        assert_eq!(cmap.unicode(0x26), Some("C"));
+        assert_eq!(cmap.unicode(0x30), Some("fl"));
+        assert_eq!(cmap.unicode(0x31), Some("𝐀"));
+        assert_eq!(cmap.unicode(0x32), None);
+        assert_eq!(cmap.cid(0x0102), Some(12));
+    }
+}
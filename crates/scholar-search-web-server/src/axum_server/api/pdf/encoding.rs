@@ -0,0 +1,489 @@
+//! The standard simple font encodings and the glyph names `/Differences`
+//! arrays use, mapped to Unicode.
+
+/// Glyph names of ASCII 0x20..=0x7e, in code order.
+const ASCII_NAMES: [&str; 95] = [
+    "space",
+    "exclam",
+    "quotedbl",
+    "numbersign",
+    "dollar",
+    "percent",
+    "ampersand",
+    "quotesingle",
+    "parenleft",
+    "parenright",
+    "asterisk",
+    "plus",
+    "comma",
+    "hyphen",
+    "period",
+    "slash",
+    "zero",
+    "one",
+    "two",
+    "three",
+    "four",
+    "five",
+    "six",
+    "seven",
+    "eight",
+    "nine",
+    "colon",
+    "semicolon",
+    "less",
+    "equal",
+    "greater",
+    "question",
+    "at",
+    "A",
+    "B",
+    "C",
+    "D",
+    "E",
+    "F",
+    "G",
+    "H",
+    "I",
+    "J",
+    "K",
+    "L",
+    "M",
+    "N",
+    "O",
+    "P",
+    "Q",
+    "R",
+    "S",
+    "T",
+    "U",
+    "V",
+    "W",
+    "X",
+    "Y",
+    "Z",
+    "bracketleft",
+    "backslash",
+    "bracketright",
+    "asciicircum",
+    "underscore",
+    "grave",
+    "a",
+    "b",
+    "c",
+    "d",
+    "e",
+    "f",
+    "g",
+    "h",
+    "i",
+    "j",
+    "k",
+    "l",
+    "m",
+    "n",
+    "o",
+    "p",
+    "q",
+    "r",
+    "s",
+    "t",
+    "u",
+    "v",
+    "w",
+    "x",
+    "y",
+    "z",
+    "braceleft",
+    "bar",
+    "braceright",
+    "asciitilde",
+];
+
+/// Glyph names of Latin-1 0xa0..=0xff, in code order.
+const LATIN1_NAMES: [&str; 96] = [
+    "nbspace",
+    "exclamdown",
+    "cent",
+    "sterling",
+    "currency",
+    "yen",
+    "brokenbar",
+    "section",
+    "dieresis",
+    "copyright",
+    "ordfeminine",
+    "guillemotleft",
+    "logicalnot",
+    "sfthyphen",
+    "registered",
+    "macron",
+    "degree",
+    "plusminus",
+    "twosuperior",
+    "threesuperior",
+    "acute",
+    "mu",
+    "paragraph",
+    "periodcentered",
+    "cedilla",
+    "onesuperior",
+    "ordmasculine",
+    "guillemotright",
+    "onequarter",
+    "onehalf",
+    "threequarters",
+    "questiondown",
+    "Agrave",
+    "Aacute",
+    "Acircumflex",
+    "Atilde",
+    "Adieresis",
+    "Aring",
+    "AE",
+    "Ccedilla",
+    "Egrave",
+    "Eacute",
+    "Ecircumflex",
+    "Edieresis",
+    "Igrave",
+    "Iacute",
+    "Icircumflex",
+    "Idieresis",
+    "Eth",
+    "Ntilde",
+    "Ograve",
+    "Oacute",
+    "Ocircumflex",
+    "Otilde",
+    "Odieresis",
+    "multiply",
+    "Oslash",
+    "Ugrave",
+    "Uacute",
+    "Ucircumflex",
+    "Udieresis",
+    "Yacute",
+    "Thorn",
+    "germandbls",
+    "agrave",
+    "aacute",
+    "acircumflex",
+    "atilde",
+    "adieresis",
+    "aring",
+    "ae",
+    "ccedilla",
+    "egrave",
+    "eacute",
+    "ecircumflex",
+    "edieresis",
+    "igrave",
+    "iacute",
+    "icircumflex",
+    "idieresis",
+    "eth",
+    "ntilde",
+    "ograve",
+    "oacute",
+    "ocircumflex",
+    "otilde",
+    "odieresis",
+    "divide",
+    "oslash",
+    "ugrave",
+    "uacute",
+    "ucircumflex",
+    "udieresis",
+    "yacute",
+    "thorn",
+    "ydieresis",
+];
+
+/// Other glyph names common in papers: punctuation, ligatures, accents,
+/// Greek and mathematics.
+const OTHER_NAMES: [(&str, char); 113] = [
+    ("quoteleft", '‘'),
+    ("quoteright", '’'),
+    ("quotedblleft", '“'),
+    ("quotedblright", '”'),
+    ("quotesinglbase", '‚'),
+    ("quotedblbase", '„'),
+    ("guilsinglleft", '‹'),
+    ("guilsinglright", '›'),
+    ("endash", '–'),
+    ("emdash", '—'),
+    ("ellipsis", '…'),
+    ("bullet", '•'),
+    ("dagger", '†'),
+    ("daggerdbl", '‡'),
+    ("perthousand", '‰'),
+    ("trademark", '™'),
+    ("fraction", '⁄'),
+    ("Euro", '€'),
+    ("florin", 'ƒ'),
+    ("fi", 'ﬁ'),
+    ("fl", 'ﬂ'),
+    ("ff", 'ﬀ'),
+    ("ffi", 'ﬃ'),
+    ("ffl", 'ﬄ'),
+    ("dotlessi", 'ı'),
+    ("dotlessj", 'ȷ'),
+    ("Lslash", 'Ł'),
+    ("lslash", 'ł'),
+    ("OE", 'Œ'),
+    ("oe", 'œ'),
+    ("Scaron", 'Š'),
+    ("scaron", 'š'),
+    ("Zcaron", 'Ž'),
+    ("zcaron", 'ž'),
+    ("Ydieresis", 'Ÿ'),
+    ("circumflex", 'ˆ'),
+    ("tilde", '˜'),
+    ("breve", '˘'),
+    ("dotaccent", '˙'),
+    ("ring", '˚'),
+    ("hungarumlaut", '˝'),
+    ("ogonek", '˛'),
+    ("caron", 'ˇ'),
+    ("minus", '−'),
+    ("infinity", '∞'),
+    ("lessequal", '≤'),
+    ("greaterequal", '≥'),
+    ("notequal", '≠'),
+    ("approxequal", '≈'),
+    ("equivalence", '≡'),
+    ("proportional", '∝'),
+    ("partialdiff", '∂'),
+    ("gradient", '∇'),
+    ("summation", '∑'),
+    ("product", '∏'),
+    ("integral", '∫'),
+    ("radical", '√'),
+    ("element", '∈'),
+    ("notelement", '∉'),
+    ("universal", '∀'),
+    ("existential", '∃'),
+    ("emptyset", '∅'),
+    ("intersection", '∩'),
+    ("union", '∪'),
+    ("propersubset", '⊂'),
+    ("reflexsubset", '⊆'),
+    ("logicaland", '∧'),
+    ("logicalor", '∨'),
+    ("arrowright", '→'),
+    ("arrowleft", '←'),
+    ("arrowup", '↑'),
+    ("arrowdown", '↓'),
+    ("arrowboth", '↔'),
+    ("arrowdblright", '⇒'),
+    ("arrowdblboth", '⇔'),
+    ("similar", '∼'),
+    ("prime", '′'),
+    ("alpha", 'α'),
+    ("beta", 'β'),
+    ("gamma", 'γ'),
+    ("delta", 'δ'),
+    ("epsilon", 'ε'),
+    ("zeta", 'ζ'),
+    ("eta", 'η'),
+    ("theta", 'θ'),
+    ("iota", 'ι'),
+    ("kappa", 'κ'),
+    ("lambda", 'λ'),
+    ("nu", 'ν'),
+    ("xi", 'ξ'),
+    ("omicron", 'ο'),
+    ("pi", 'π'),
+    ("rho", 'ρ'),
+    ("sigma", 'σ'),
+    ("sigma1", 'ς'),
+    ("tau", 'τ'),
+    ("upsilon", 'υ'),
+    ("phi", 'φ'),
+    ("chi", 'χ'),
+    ("psi", 'ψ'),
+    ("omega", 'ω'),
+    ("Gamma", 'Γ'),
+    ("Delta", 'Δ'),
+    ("Theta", 'Θ'),
+    ("Lambda", 'Λ'),
+    ("Xi", 'Ξ'),
+    ("Pi", 'Π'),
+    ("Sigma", 'Σ'),
+    ("Upsilon", 'Υ'),
+    ("Phi", 'Φ'),
+    ("Psi", 'Ψ'),
+    ("Omega", 'Ω'),
+    ("Ohm", 'Ω'),
+];
+
+/// StandardEncoding above 0xa0, where it leaves Latin-1 behind.
+const STANDARD_HIGH: [(u8, char); 54] = [
+    (0xa1, '¡'),
+    (0xa2, '¢'),
+    (0xa3, '£'),
+    (0xa4, '⁄'),
+    (0xa5, '¥'),
+    (0xa6, 'ƒ'),
+    (0xa7, '§'),
+    (0xa8, '¤'),
+    (0xa9, '\''),
+    (0xaa, '“'),
+    (0xab, '«'),
+    (0xac, '‹'),
+    (0xad, '›'),
+    (0xae, 'ﬁ'),
+    (0xaf, 'ﬂ'),
+    (0xb1, '–'),
+    (0xb2, '†'),
+    (0xb3, '‡'),
+    (0xb4, '·'),
+    (0xb6, '¶'),
+    (0xb7, '•'),
+    (0xb8, '‚'),
+    (0xb9, '„'),
+    (0xba, '”'),
+    (0xbb, '»'),
+    (0xbc, '…'),
+    (0xbd, '‰'),
+    (0xbf, '¿'),
+    (0xc1, '`'),
+    (0xc2, '´'),
+    (0xc3, 'ˆ'),
+    (0xc4, '˜'),
+    (0xc5, '¯'),
+    (0xc6, '˘'),
+    (0xc7, '˙'),
+    (0xc8, '¨'),
+    (0xca, '˚'),
+    (0xcb, '¸'),
+    (0xcd, '˝'),
+    (0xce, '˛'),
+    (0xcf, 'ˇ'),
+    (0xd0, '—'),
+    (0xe1, 'Æ'),
+    (0xe3, 'ª'),
+    (0xe8, 'Ł'),
+    (0xe9, 'Ø'),
+    (0xea, 'Œ'),
+    (0xeb, 'º'),
+    (0xf1, 'æ'),
+    (0xf5, 'ı'),
+    (0xf8, 'ł'),
+    (0xf9, 'ø'),
+    (0xfa, 'œ'),
+    (0xfb, 'ß'),
+];
+
+/// WinAnsiEncoding of 0x80..=0x9f, the only range where it differs from
+/// Latin-1; `\0` marks the undefined codes.
+const WIN_ANSI_80: [char; 32] = [
+    '€', '\0', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\0', 'Ž', '\0', '\0', '‘',
+    '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\0', 'ž', 'Ÿ',
+];
+
+/// MacRomanEncoding of 0x80..=0xff.
+const MAC_ROMAN_80: &str = "ÄÅÇÉÑÖÜáàâäãåçéèêëíìîïñóòôöõúùûü†°¢£§•¶ß®©™´¨≠ÆØ∞±≤≥¥µ∂∑∏π∫ªºΩæø\
+    ¿¡¬√ƒ≈∆«»…\u{a0}ÀÃÕŒœ–—“”‘’÷◊ÿŸ⁄¤‹›ﬁﬂ‡·‚„‰ÂÊÁËÈÍÎÏÌÓÔ\u{f8ff}ÒÚÛÙıˆ˜¯˘˙˚¸˝˛ˇ";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BaseEncoding {
+    Standard,
+    WinAnsi,
+    MacRoman,
+}
+
+impl BaseEncoding {
+    pub fn from_name(name: &[u8]) -> Option<Self> {
+        match name {
+            b"StandardEncoding" => Some(BaseEncoding::Standard),
+            b"WinAnsiEncoding" => Some(BaseEncoding::WinAnsi),
+            b"MacRomanEncoding" => Some(BaseEncoding::MacRoman),
+            _ => None,
+        }
+    }
+
+    pub fn decode(&self, code: u8) -> Option<char> {
+        let c = match (self, code) {
+            (BaseEncoding::Standard, 0x27) => '’',
+            (BaseEncoding::Standard, 0x60) => '‘',
+            (_, 0x20..=0x7e) => code as char,
+            (BaseEncoding::Standard, _) => STANDARD_HIGH
+                .iter()
+                .find(|(c, _)| *c == code)
+                .map(|(_, c)| *c)?,
+            (BaseEncoding::WinAnsi, 0x80..=0x9f) => WIN_ANSI_80[code as usize - 0x80],
+            (BaseEncoding::WinAnsi, 0xa0..=0xff) => code as char,
+            (BaseEncoding::MacRoman, 0x80..=0xff) => {
+                MAC_ROMAN_80.chars().nth(code as usize - 0x80)?
+            }
+            _ => return None,
+        };
+        Some(c).filter(|c| *c != '\0')
+    }
+}
+
+/// The text of a glyph name: a standard name, `uniXXXX` (possibly several
+/// code points), `uXXXX[XX]`, or components joined by `_`, with any `.suffix`
+/// naming a variant ignored.
+pub fn glyph_text(name: &str) -> Option<String> {
+    let name = name.split('.').next().unwrap_or_default();
+    if name.is_empty() {
+        return None;
+    }
+    if let Some(i) = ASCII_NAMES.iter().position(|n| *n == name) {
+        return Some(char::from(0x20 + i as u8).to_string());
+    }
+    if let Some(i) = LATIN1_NAMES.iter().position(|n| *n == name) {
+        return Some(char::from(0xa0 + i as u8).to_string());
+    }
+    if let Some((_, c)) = OTHER_NAMES.iter().find(|(n, _)| *n == name) {
+        return Some(c.to_string());
+    }
+    if let Some(hex) = name.strip_prefix("uni") {
+        if hex.len() % 4 == 0 && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            let units = (0..hex.len())
+                .step_by(4)
+                .map(|i| u16::from_str_radix(&hex[i..i + 4], 16))
+                .collect::<Result<Vec<u16>, _>>()
+                .ok()?;
+            return String::from_utf16(&units).ok();
+        }
+    }
+    if let Some(hex) = name.strip_prefix('u') {
+        if (4..=6).contains(&hex.len()) {
+            if let Ok(code) = u32::from_str_radix(hex, 16) {
+                return char::from_u32(code).map(String::from);
+            }
+        }
+    }
+    if name.contains('_') {
+        return name.split('_').map(glyph_text).collect();
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_encodings() {
+        assert_eq!(MAC_ROMAN_80.chars().count(), 128);
+        assert_eq!(BaseEncoding::Standard.decode(0xae), Some('ﬁ'));
+        assert_eq!(BaseEncoding::Standard.decode(0x27), Some('’'));
+        assert_eq!(BaseEncoding::WinAnsi.decode(0x93), Some('“'));
+        assert_eq!(BaseEncoding::WinAnsi.decode(0x81), None);
+        assert_eq!(BaseEncoding::MacRoman.decode(0xde), Some('ﬁ'));
+
+        assert_eq!(glyph_text("quotesingle").unwrap(), "'");
+        assert_eq!(glyph_text("eacute").unwrap(), "é");
+        assert_eq!(glyph_text("ffi").unwrap(), "ﬃ");
+        assert_eq!(glyph_text("uni00660069").unwrap(), "fi");
+        assert_eq!(glyph_text("u1D400").unwrap(), "𝐀");
+        assert_eq!(glyph_text("f_f_l").unwrap(), "ffl");
+        assert_eq!(glyph_text("a.sc").unwrap(), "a");
+        assert_eq!(glyph_text("g123"), None);
+    }
+}
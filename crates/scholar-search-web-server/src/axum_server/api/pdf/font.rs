@@ -0,0 +1,353 @@
+//! Glyph widths and text of the fonts a page's content stream shows text
+//! with, as far as the layout extractor needs them.
+//!
+//! A glyph's text comes from the font's `/ToUnicode` CMap when it has one,
+//! then from the glyph names of its `/Differences`, then from its base
+//! encoding. Text is NFKC normalised, which also expands ligatures.
+
+use super::{
+    cmap::CMap,
+    encoding::{glyph_text, BaseEncoding},
+};
+use lopdf::{Dictionary, Document, Object};
+use std::collections::HashMap;
+use unicode_normalization::UnicodeNormalization;
+
+/// Width used when a font gives neither `/Widths` nor `/MissingWidth`.
+const FALLBACK_WIDTH: f32 = 500.0;
+
+fn resolve<'a>(doc: &'a Document, object: &'a Object) -> &'a Object {
+    doc.dereference(object).map(|(_, o)| o).unwrap_or(object)
+}
+
+fn number(object: &Object) -> Option<f32> {
+    object.as_float().ok()
+}
+
+fn cmap(doc: &Document, object: &Object) -> Option<CMap> {
+    let stream = resolve(doc, object).as_stream().ok()?;
+    let data = stream
+        .decompressed_content()
+        .unwrap_or_else(|_| stream.content.clone());
+    Some(CMap::parse(&data))
+}
+
+#[derive(Debug, Clone)]
+pub struct PdfFont {
+    /// `/BaseFont` without the subset tag, e.g. `Times-Bold`
+    pub name: String,
+    /// Type0 fonts show multi-byte codes that select CIDs
+    composite: bool,
+    /// the embedded `/Encoding` CMap of a Type0 font; `None` is Identity
+    cid_map: Option<CMap>,
+    to_unicode: Option<CMap>,
+    base_encoding: BaseEncoding,
+    /// the text of the glyph names in `/Differences`, by code
+    differences: HashMap<u32, String>,
+    /// glyph widths in glyph space, by code for simple fonts and by CID
+    /// for Type0 fonts
+    widths: HashMap<u32, f32>,
+    default_width: f32,
+    /// glyph space to text space; Type3 fonts give their own `/FontMatrix`
+    scale: f32,
+}
+
+impl PdfFont {
+    pub fn load(doc: &Document, font: &Dictionary) -> Self {
+        let name = font
+            .get(b"BaseFont")
+            .and_then(Object::as_name_str)
+            .unwrap_or_default();
+        // subset fonts are named like `ABCDEF+Times-Roman`
+        let name = match name.split_once('+') {
+            Some((tag, base)) if tag.len() == 6 => base,
+            _ => name,
+        }
+        .to_string();
+        let subtype = font
+            .get(b"Subtype")
+            .and_then(Object::as_name)
+            .unwrap_or_default();
+        let composite = subtype == b"Type0";
+        let scale = font
+            .get(b"FontMatrix")
+            .map(|m| resolve(doc, m))
+            .and_then(Object::as_array)
+            .ok()
+            .and_then(|m| m.first())
+            .and_then(number)
+            .unwrap_or(0.001);
+        let mut pdf_font = Self {
+            name,
+            composite,
+            cid_map: None,
+            to_unicode: font.get(b"ToUnicode").ok().and_then(|o| cmap(doc, o)),
+            // the built-in encoding of a Type1 font is usually Standard
+            base_encoding: if subtype == b"TrueType" {
+                BaseEncoding::WinAnsi
+            } else {
+                BaseEncoding::Standard
+            },
+            differences: HashMap::new(),
+            widths: HashMap::new(),
+            default_width: FALLBACK_WIDTH,
+            scale,
+        };
+        if composite {
+            pdf_font.cid_map = font.get(b"Encoding").ok().and_then(|o| cmap(doc, o));
+            pdf_font.load_cid_widths(doc, font);
+        } else {
+            pdf_font.load_encoding(doc, font);
+            pdf_font.load_simple_widths(doc, font);
+        }
+        pdf_font
+    }
+
+    fn load_encoding(&mut self, doc: &Document, font: &Dictionary) {
+        let Ok(encoding) = font.get(b"Encoding").map(|e| resolve(doc, e)) else {
+            return;
+        };
+        let (base, differences) = match encoding {
+            Object::Name(name) => (Some(name.as_slice()), None),
+            Object::Dictionary(dict) => (
+                dict.get(b"BaseEncoding").and_then(Object::as_name).ok(),
+                dict.get(b"Differences")
+                    .map(|d| resolve(doc, d))
+                    .and_then(Object::as_array)
+                    .ok(),
+            ),
+            _ => (None, None),
+        };
+        if let Some(base) = base.and_then(BaseEncoding::from_name) {
+            self.base_encoding = base;
+        }
+        // `[code name name ... code name ...]`, names taking successive codes
+        let mut code = 0;
+        for item in differences.into_iter().flatten() {
+            match resolve(doc, item) {
+                Object::Integer(c) => code = *c as u32,
+                Object::Name(name) => {
+                    if let Some(text) = glyph_text(&String::from_utf8_lossy(name)) {
+                        self.differences.insert(code, text);
+                    }
+                    code += 1;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn load_simple_widths(&mut self, doc: &Document, font: &Dictionary) {
+        if self.name.starts_with("Courier") {
+            self.default_width = 600.0;
+        }
+        if let Some(missing) = font
+            .get(b"FontDescriptor")
+            .map(|d| resolve(doc, d))
+            .and_then(Object::as_dict)
+            .and_then(|d| d.get(b"MissingWidth"))
+            .ok()
+            .and_then(number)
+        {
+            self.default_width = missing;
+        }
+        let first_char = font.get(b"FirstChar").ok().and_then(number).unwrap_or(0.0) as u32;
+        if let Ok(widths) = font
+            .get(b"Widths")
+            .map(|w| resolve(doc, w))
+            .and_then(Object::as_array)
+        {
+            for (i, width) in widths.iter().enumerate() {
+                if let Some(width) = number(resolve(doc, width)) {
+                    self.widths.insert(first_char + i as u32, width);
+                }
+            }
+        }
+    }
+
+    /// `/W` of the descendant CIDFont holds runs of `c [w1 w2 ...]` and
+    /// `c_first c_last w`.
+    fn load_cid_widths(&mut self, doc: &Document, font: &Dictionary) {
+        let Some(descendant) = font
+            .get(b"DescendantFonts")
+            .map(|d| resolve(doc, d))
+            .and_then(Object::as_array)
+            .ok()
+            .and_then(|fonts| fonts.first())
+            .and_then(|d| resolve(doc, d).as_dict().ok())
+        else {
+            return;
+        };
+        self.default_width = descendant
+            .get(b"DW")
+            .ok()
+            .and_then(number)
+            .unwrap_or(1000.0);
+        let Ok(w) = descendant
+            .get(b"W")
+            .map(|w| resolve(doc, w))
+            .and_then(Object::as_array)
+        else {
+            return;
+        };
+        let mut items = w.iter().map(|o| resolve(doc, o));
+        while let Some(first) = items.next().and_then(number) {
+            match items.next() {
+                Some(Object::Array(widths)) => {
+                    for (i, width) in widths.iter().enumerate() {
+                        if let Some(width) = number(resolve(doc, width)) {
+                            self.widths.insert(first as u32 + i as u32, width);
+                        }
+                    }
+                }
+                Some(last) => {
+                    let (Some(last), Some(width)) = (number(last), items.next().and_then(number))
+                    else {
+                        break;
+                    };
+                    for code in first as u32..=last as u32 {
+                        self.widths.insert(code, width);
+                    }
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// Splits a shown string into character codes.
+    pub fn codes(&self, bytes: &[u8]) -> Vec<u32> {
+        match (&self.cid_map, self.composite) {
+            (Some(cid_map), _) => cid_map.codes(bytes, 2),
+            (None, true) => CMap::default().codes(bytes, 2),
+            (None, false) => bytes.iter().map(|b| *b as u32).collect(),
+        }
+    }
+
+    /// Glyph width in text space, i.e. in units of the font size.
+    pub fn width(&self, code: u32) -> f32 {
+        let key = match &self.cid_map {
+            Some(cid_map) => cid_map.cid(code).unwrap_or(code),
+            None => code,
+        };
+        self.widths.get(&key).copied().unwrap_or(self.default_width) * self.scale
+    }
+
+    /// Word spacing applies to the single byte code 32 only.
+    pub fn is_space(&self, code: u32) -> bool {
+        !self.composite && code == 32
+    }
+
+    /// The text of a glyph; empty when the font doesn't say, or U+FFFD for
+    /// a Type0 font without a `/ToUnicode` map.
+    pub fn text(&self, code: u32) -> String {
+        let text = if let Some(text) = self.to_unicode.as_ref().and_then(|m| m.unicode(code)) {
+            text.to_string()
+        } else if let Some(text) = self.differences.get(&code) {
+            text.to_owned()
+        } else if self.composite {
+            return char::REPLACEMENT_CHARACTER.to_string();
+        } else {
+            match u8::try_from(code)
+                .ok()
+                .and_then(|c| self.base_encoding.decode(c))
+            {
+                Some(c) => c.to_string(),
+                None => return String::new(),
+            }
+        };
+        text.nfkc().collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use lopdf::{dictionary, Stream};
+
+    #[test]
+    fn test_font_widths() {
+        let doc = Document::with_version("1.5");
+        let simple = dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "ABCDEF+Times-Roman",
+            "FirstChar" => 65,
+            "Widths" => vec![722.into(), 667.into()],
+        };
+        let font = PdfFont::load(&doc, &simple);
+        assert_eq!(font.name, "Times-Roman");
+        assert_eq!(font.codes(b"AB"), vec![65, 66]);
+        let width = |code| (font.width(code) * 1000.0).round();
+        assert_eq!((width(66), width(67)), (667.0, 500.0));
+
+        let composite = dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type0",
+            "BaseFont" => "Noto",
+            "DescendantFonts" => vec![dictionary! {
+                "DW" => 900,
+                "W" => vec![
+                    3.into(),
+                    vec![250.into(), 300.into()].into(),
+                    10.into(),
+                    12.into(),
+                    400.into(),
+                ],
+            }
+            .into()],
+        };
+        let font = PdfFont::load(&doc, &composite);
+        assert_eq!(font.codes(&[0, 4, 0, 11]), vec![4, 11]);
+        let width = |code| (font.width(code) * 1000.0).round();
+        assert_eq!((width(4), width(11), width(20)), (300.0, 400.0, 900.0));
+        assert!(!font.is_space(32));
+        assert_eq!(font.text(4), "\u{fffd}");
+    }
+
+    #[test]
+    fn test_font_text() {
+        let mut doc = Document::with_version("1.5");
+        let simple = dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "CMR10",
+            "Encoding" => dictionary! {
+                "BaseEncoding" => "WinAnsiEncoding",
+                "Differences" => vec![
+                    12.into(),
+                    "fi".into(),
+                    "ffi".into(),
+                    39.into(),
+                    "quoteright".into(),
+                ],
+            },
+        };
+        let font = PdfFont::load(&doc, &simple);
+        let text = |bytes: &[u8]| {
+            let codes = font.codes(bytes);
+            codes.iter().map(|c| font.text(*c)).collect::<String>()
+        };
+        assert_eq!(text(b"\x0cndings"), "findings");
+        assert_eq!(text(b"E\x0dcient"), "Efficient");
+        assert_eq!(text(b"author's \x93x\x94"), "author’s “x”");
+        assert_eq!(text(b"\x01"), "");
+
+        let to_unicode = doc.add_object(Stream::new(
+            dictionary! {},
+            b"1 begincodespacerange <0000> <FFFF> endcodespacerange\n\
+              2 beginbfchar <0001> <0041> <0002> <FB01> endbfchar"
+                .to_vec(),
+        ));
+        let composite = dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type0",
+            "BaseFont" => "ABCDEF+Libertine",
+            "Encoding" => "Identity-H",
+            "ToUnicode" => to_unicode,
+        };
+        let font = PdfFont::load(&doc, &composite);
+        let codes = font.codes(&[0, 1, 0, 2, 0, 3]);
+        let text = codes.iter().map(|c| font.text(*c)).collect::<String>();
+        assert_eq!(text, "Afi\u{fffd}");
+    }
+}
@@ -0,0 +1,612 @@
+//! Layout-aware text extraction.
+//!
+//! The content stream is interpreted with its text and graphics state, so
+//! every glyph gets a position on the page. Words are rebuilt from the gaps
+//! between glyphs rather than from the way the producer happened to split
+//! its strings; words then make lines, lines make blocks, and the blocks are
+//! put in reading order across columns.
+//!
+//! Coordinates are PDF user space: points, with the origin at the bottom
+//! left of the page, so `y` grows upwards.
+
+use super::font::PdfFont;
+use anyhow::Error;
+use lopdf::{content::Content, Dictionary, Document, Object, ObjectId};
+use scholar_search_pdf_layout::{
+    geometry::{matrix, media_box, multiply, translate, Matrix, IDENTITY, MAX_FORM_DEPTH},
+    WORD_GAP,
+};
+pub use scholar_search_pdf_layout::{Block, Line, PageLayout, Span};
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+
+/// Baselines closer than this, in font sizes, are on the same row.
+const ROW_TOLERANCE: f32 = 0.35;
+/// A gap wider than this, in font sizes, splits a row into separate lines.
+const LINE_GAP: f32 = 1.5;
+/// Lines of a block are at most this far apart, baseline to baseline.
+const BLOCK_LEADING: f32 = 1.6;
+/// Lines wider than this share of the text width span the columns.
+const COLUMN_MAX_WIDTH: f32 = 0.6;
+/// Lines with fewer words don't count as evidence for a column.
+const COLUMN_MIN_WORDS: usize = 3;
+/// One glyph as drawn, before words are rebuilt.
+#[derive(Debug, Clone)]
+pub struct Glyph {
+    pub text: String,
+    pub font: Rc<PdfFont>,
+    pub size: f32,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+}
+
+/// The part of the graphics state that text showing depends on; `q`/`Q`
+/// save and restore it with the CTM.
+#[derive(Debug, Clone)]
+struct TextState {
+    ctm: Matrix,
+    char_spacing: f32,
+    word_spacing: f32,
+    /// `Tz` over 100
+    horizontal_scaling: f32,
+    leading: f32,
+    rise: f32,
+    font: Option<Rc<PdfFont>>,
+    size: f32,
+}
+
+impl Default for TextState {
+    fn default() -> Self {
+        Self {
+            ctm: IDENTITY,
+            char_spacing: 0.0,
+            word_spacing: 0.0,
+            horizontal_scaling: 1.0,
+            leading: 0.0,
+            rise: 0.0,
+            font: None,
+            size: 0.0,
+        }
+    }
+}
+
+type Fonts = HashMap<Vec<u8>, Rc<PdfFont>>;
+
+fn fonts_of(doc: &Document, fonts: BTreeMap<Vec<u8>, &Dictionary>) -> Fonts {
+    fonts
+        .into_iter()
+        .map(|(name, font)| (name, Rc::new(PdfFont::load(doc, font))))
+        .collect()
+}
+
+fn operand(operands: &[Object], i: usize) -> f32 {
+    operands
+        .get(i)
+        .and_then(|o| o.as_float().ok())
+        .unwrap_or(0.0)
+}
+
+struct Interpreter<'a> {
+    doc: &'a Document,
+    state: TextState,
+    stack: Vec<TextState>,
+    tm: Matrix,
+    tlm: Matrix,
+    glyphs: Vec<Glyph>,
+}
+
+impl<'a> Interpreter<'a> {
+    fn new(doc: &'a Document) -> Self {
+        Self {
+            doc,
+            state: TextState::default(),
+            stack: vec![],
+            tm: IDENTITY,
+            tlm: IDENTITY,
+            glyphs: vec![],
+        }
+    }
+
+    fn next_line(&mut self, tx: f32, ty: f32) {
+        self.tlm = multiply(&translate(tx, ty), &self.tlm);
+        self.tm = self.tlm;
+    }
+
+    fn show(&mut self, bytes: &[u8]) {
+        let Some(font) = self.state.font.clone() else {
+            return;
+        };
+        let s = &self.state;
+        let scaling = s.horizontal_scaling;
+        for code in font.codes(bytes) {
+            let trm = multiply(
+                &[s.size * scaling, 0.0, 0.0, s.size, 0.0, s.rise],
+                &multiply(&self.tm, &s.ctm),
+            );
+            let w = font.width(code);
+            let mut advance = w * s.size + s.char_spacing;
+            if font.is_space(code) {
+                advance += s.word_spacing;
+            }
+            advance *= scaling;
+            let device = multiply(&self.tm, &s.ctm);
+            self.glyphs.push(Glyph {
+                text: font.text(code),
+                font: font.clone(),
+                size: trm[2].hypot(trm[3]),
+                x: trm[4],
+                y: trm[5],
+                width: w * s.size * scaling * device[0].hypot(device[1]),
+            });
+            self.tm = multiply(&translate(advance, 0.0), &self.tm);
+        }
+    }
+
+    fn run(&mut self, content: &[u8], fonts: &Fonts, resources: &[&Dictionary], depth: usize) {
+        let Ok(content) = Content::decode(content) else {
+            return;
+        };
+        for operation in &content.operations {
+            let operands = &operation.operands;
+            match operation.operator.as_ref() {
+                "q" => self.stack.push(self.state.clone()),
+                "Q" => {
+                    if let Some(state) = self.stack.pop() {
+                        self.state = state;
+                    }
+                }
+                "cm" => self.state.ctm = multiply(&matrix(operands), &self.state.ctm),
+                "BT" => {
+                    self.tm = IDENTITY;
+                    self.tlm = IDENTITY;
+                }
+                "Tf" => {
+                    self.state.font = operands
+                        .first()
+                        .and_then(|name| name.as_name().ok())
+                        .and_then(|name| fonts.get(name))
+                        .cloned();
+                    self.state.size = operand(operands, 1);
+                }
+                "Tc" => self.state.char_spacing = operand(operands, 0),
+                "Tw" => self.state.word_spacing = operand(operands, 0),
+                "Tz" => self.state.horizontal_scaling = operand(operands, 0) / 100.0,
+                "TL" => self.state.leading = operand(operands, 0),
+                "Ts" => self.state.rise = operand(operands, 0),
+                "Td" => self.next_line(operand(operands, 0), operand(operands, 1)),
+                "TD" => {
+                    self.state.leading = -operand(operands, 1);
+                    self.next_line(operand(operands, 0), operand(operands, 1));
+                }
+                "Tm" => {
+                    self.tlm = matrix(operands);
+                    self.tm = self.tlm;
+                }
+                "T*" => self.next_line(0.0, -self.state.leading),
+                "Tj" | "'" | "\"" => {
+                    if operation.operator == "\"" {
+                        self.state.word_spacing = operand(operands, 0);
+                        self.state.char_spacing = operand(operands, 1);
+                    }
+                    if operation.operator != "Tj" {
+                        self.next_line(0.0, -self.state.leading);
+                    }
+                    if let Some(Object::String(bytes, _)) = operands.last() {
+                        self.show(bytes);
+                    }
+                }
+                "TJ" => {
+                    let Some(Ok(items)) = operands.first().map(Object::as_array) else {
+                        continue;
+                    };
+                    for item in items {
+                        match item {
+                            Object::String(bytes, _) => self.show(bytes),
+                            item => {
+                                let adjust = item.as_float().unwrap_or(0.0);
+                                let tx = -adjust / 1000.0
+                                    * self.state.size
+                                    * self.state.horizontal_scaling;
+                                self.tm = multiply(&translate(tx, 0.0), &self.tm);
+                            }
+                        }
+                    }
+                }
+                "Do" if depth < MAX_FORM_DEPTH => {
+                    if let Some(name) = operands.first().and_then(|n| n.as_name().ok()) {
+                        self.draw_form(name, fonts, resources, depth);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Draws a Form XObject, which may carry text; images are skipped.
+    fn draw_form(&mut self, name: &[u8], fonts: &Fonts, resources: &[&Dictionary], depth: usize) {
+        let doc = self.doc;
+        let Some(form) = resources.iter().find_map(|r| {
+            let xobjects = r.get(b"XObject").ok()?;
+            let xobjects = doc.dereference(xobjects).ok()?.1.as_dict().ok()?;
+            let form = doc.dereference(xobjects.get(name).ok()?).ok()?.1;
+            form.as_stream().ok()
+        }) else {
+            return;
+        };
+        if !form
+            .dict
+            .get(b"Subtype")
+            .and_then(Object::as_name)
+            .is_ok_and(|subtype| subtype == b"Form")
+        {
+            return;
+        }
+        let content = form
+            .decompressed_content()
+            .unwrap_or_else(|_| form.content.clone());
+        let form_resources = form
+            .dict
+            .get(b"Resources")
+            .and_then(|r| doc.dereference(r))
+            .and_then(|(_, r)| r.as_dict())
+            .ok();
+        let form_fonts = form_resources
+            .and_then(|r| r.get(b"Font").ok())
+            .and_then(|f| doc.dereference(f).ok()?.1.as_dict().ok())
+            .map(|f| {
+                let fonts = f
+                    .iter()
+                    .filter_map(|(name, font)| {
+                        let font = doc.dereference(font).ok()?.1.as_dict().ok()?;
+                        Some((name.clone(), font))
+                    })
+                    .collect();
+                fonts_of(doc, fonts)
+            });
+        let inner: Vec<&Dictionary> = match form_resources {
+            Some(r) => vec![r],
+            None => resources.to_vec(),
+        };
+
+        self.stack.push(self.state.clone());
+        if let Ok(m) = form.dict.get(b"Matrix").and_then(Object::as_array) {
+            self.state.ctm = multiply(&matrix(m), &self.state.ctm);
+        }
+        let (tm, tlm) = (self.tm, self.tlm);
+        self.run(
+            &content,
+            form_fonts.as_ref().unwrap_or(fonts),
+            &inner,
+            depth + 1,
+        );
+        (self.tm, self.tlm) = (tm, tlm);
+        if let Some(state) = self.stack.pop() {
+            self.state = state;
+        }
+    }
+}
+
+/// The glyphs a page draws, in content stream order.
+pub fn page_glyphs(doc: &Document, page_id: ObjectId) -> Result<Vec<Glyph>, Error> {
+    let fonts = fonts_of(doc, doc.get_page_fonts(page_id));
+    let (resource_dict, resource_ids) = doc.get_page_resources(page_id);
+    let resources = resource_dict
+        .into_iter()
+        .chain(
+            resource_ids
+                .into_iter()
+                .filter_map(|id| doc.get_dictionary(id).ok()),
+        )
+        .collect::<Vec<&Dictionary>>();
+    let content = doc.get_page_content(page_id)?;
+    let mut interpreter = Interpreter::new(doc);
+    interpreter.run(&content, &fonts, &resources, 0);
+    Ok(interpreter.glyphs)
+}
+
+/// Joins glyphs into spans, breaking at spaces, at gaps wider than
+/// `WORD_GAP` and wherever the font changes or the text jumps.
+pub fn spans(glyphs: &[Glyph]) -> Vec<Span> {
+    let mut spans: Vec<Span> = vec![];
+    let mut open = false;
+    for glyph in glyphs {
+        if glyph.text.trim().is_empty() {
+            open = false;
+            continue;
+        }
+        if open {
+            let span = spans.last_mut().unwrap();
+            let gap = glyph.x - span.right();
+            if span.font == glyph.font.name
+                && (span.size - glyph.size).abs() < 0.01 * span.size
+                && (span.y - glyph.y).abs() < 0.2 * span.size
+                && gap > -0.2 * span.size
+                && gap < WORD_GAP * span.size
+            {
+                span.text.push_str(&glyph.text);
+                span.width = glyph.x + glyph.width - span.x;
+                continue;
+            }
+        }
+        spans.push(Span {
+            text: glyph.text.to_owned(),
+            font: glyph.font.name.to_owned(),
+            size: glyph.size,
+            x: glyph.x,
+            y: glyph.y,
+            width: glyph.width,
+        });
+        open = true;
+    }
+    spans
+}
+
+/// Groups spans sharing a baseline into rows, and splits rows at gaps
+/// wider than `LINE_GAP` so side by side columns give separate lines.
+pub fn lines(mut spans: Vec<Span>) -> Vec<Line> {
+    spans.sort_by(|a, b| b.y.total_cmp(&a.y));
+    let mut rows: Vec<Vec<Span>> = vec![];
+    for span in spans {
+        let tolerance = |row: &[Span]| ROW_TOLERANCE * row[0].size.max(span.size);
+        match rows.last_mut() {
+            Some(row) if (row[0].y - span.y).abs() < tolerance(row) => row.push(span),
+            _ => rows.push(vec![span]),
+        }
+    }
+    let mut lines = vec![];
+    for mut row in rows {
+        row.sort_by(|a, b| a.x.total_cmp(&b.x));
+        let mut line: Vec<Span> = vec![];
+        for span in row {
+            if let Some(last) = line.last() {
+                if span.x - last.right() > LINE_GAP * last.size.max(span.size) {
+                    lines.push(Line::new(std::mem::take(&mut line)));
+                }
+            }
+            line.push(span);
+        }
+        if !line.is_empty() {
+            lines.push(Line::new(line));
+        }
+    }
+    lines
+}
+
+/// The gutters between columns, as x ranges, found as gaps in the union of
+/// the lines that are narrow enough to sit in one column. Only lines sharing
+/// their left edge with another count, so a lone centred title or page
+/// number doesn't bridge a gutter.
+pub fn gutters(lines: &[Line]) -> Vec<(f32, f32)> {
+    let Some(text) = lines.iter().map(|l| l.bbox).reduce(|a, b| a.union(&b)) else {
+        return vec![];
+    };
+    let narrow = lines
+        .iter()
+        .filter(|l| {
+            l.spans.len() >= COLUMN_MIN_WORDS && l.bbox.width() < COLUMN_MAX_WIDTH * text.width()
+        })
+        .collect::<Vec<&Line>>();
+    let mut ranges = narrow
+        .iter()
+        .filter(|l| {
+            narrow
+                .iter()
+                .filter(|other| (other.bbox.x0 - l.bbox.x0).abs() < 1.0)
+                .count()
+                > 1
+        })
+        .map(|l| (l.bbox.x0, l.bbox.x1))
+        .collect::<Vec<(f32, f32)>>();
+    ranges.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut gutters = vec![];
+    let mut covered: Option<f32> = None;
+    for (x0, x1) in ranges {
+        match covered {
+            Some(end) if x0 > end => {
+                gutters.push((end, x0));
+                covered = Some(x1);
+            }
+            Some(end) => covered = Some(end.max(x1)),
+            None => covered = Some(x1),
+        }
+    }
+    gutters
+}
+
+/// The column a line sits in, `None` when it crosses a gutter.
+fn column_of(line: &Line, gutters: &[(f32, f32)]) -> Option<usize> {
+    if gutters
+        .iter()
+        .any(|(x0, x1)| line.bbox.x0 < *x1 && *x0 < line.bbox.x1)
+    {
+        return None;
+    }
+    Some(gutters.iter().filter(|(_, x1)| *x1 <= line.bbox.x0).count())
+}
+
+/// Stacks lines of one column and a similar size into blocks, top to
+/// bottom.
+pub fn blocks(mut lines: Vec<Line>, gutters: &[(f32, f32)]) -> Vec<Block> {
+    lines.sort_by(|a, b| {
+        b.baseline()
+            .total_cmp(&a.baseline())
+            .then(a.bbox.x0.total_cmp(&b.bbox.x0))
+    });
+    let mut blocks: Vec<Block> = vec![];
+    for line in lines {
+        let column = column_of(&line, gutters);
+        let size = line.size();
+        let block = blocks.iter_mut().rev().find(|b| {
+            let last = b.lines.last().unwrap();
+            let leading = last.baseline() - line.baseline();
+            b.column == column
+                && leading > 0.0
+                && leading <= BLOCK_LEADING * size.max(last.size())
+                && (last.size() - size).abs() <= 0.15 * size.max(last.size())
+                && b.bbox.overlaps_x(&line.bbox)
+        });
+        match block {
+            Some(block) => block.push(line),
+            None => blocks.push(Block::new(line, column)),
+        }
+    }
+    blocks
+}
+
+/// Puts blocks in reading order: blocks spanning the columns cut the page
+/// into bands, and within a band the columns are read left to right.
+pub fn reading_order(mut blocks: Vec<Block>) -> Vec<Block> {
+    blocks.sort_by(|a, b| b.bbox.y1.total_cmp(&a.bbox.y1));
+    let (spanning, mut columned): (Vec<Block>, Vec<Block>) =
+        blocks.into_iter().partition(|b| b.column.is_none());
+    let mut ordered = vec![];
+    for block in spanning {
+        let (mut band, rest): (Vec<Block>, Vec<Block>) = columned
+            .into_iter()
+            .partition(|b| b.bbox.y1 > block.bbox.y1);
+        band.sort_by_key(|b| b.column);
+        ordered.extend(band);
+        ordered.push(block);
+        columned = rest;
+    }
+    columned.sort_by_key(|b| b.column);
+    ordered.extend(columned);
+    ordered
+}
+
+pub fn page_layout(doc: &Document, number: u32, page_id: ObjectId) -> Result<PageLayout, Error> {
+    let lines = lines(spans(&page_glyphs(doc, page_id)?));
+    let gutters = gutters(&lines);
+    let media_box = media_box(doc, page_id);
+    Ok(PageLayout {
+        number,
+        width: media_box.width(),
+        height: media_box.y1 - media_box.y0,
+        columns: gutters.len() + 1,
+        blocks: reading_order(blocks(lines, &gutters)),
+    })
+}
+
+pub fn document_layout(doc: &Document) -> Result<Vec<PageLayout>, Error> {
+    doc.get_pages()
+        .into_iter()
+        .map(|(number, page_id)| page_layout(doc, number, page_id))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::axum_server::api::pdf::fixture::{test_document, text_at};
+    use lopdf::content::Operation;
+
+    #[test]
+    fn test_words() {
+        let ops = vec![
+            Operation::new("BT", vec![]),
+            Operation::new("Tf", vec!["F1".into(), 10.into()]),
+            Operation::new("TL", vec![12.into()]),
+            Operation::new("Td", vec![72.into(), 700.into()]),
+            Operation::new(
+                "TJ",
+                vec![vec![
+                    Object::string_literal("Th"),
+                    20.into(),
+                    Object::string_literal("is"),
+                    (-400).into(),
+                    Object::string_literal("work"),
+                    (-400).into(),
+                    Object::string_literal("is,"),
+                ]
+                .into()],
+            ),
+            Operation::new("'", vec![Object::string_literal("in part")]),
+            Operation::new("ET", vec![]),
+            // drawn at twice the size through the CTM
+            Operation::new("q", vec![]),
+            Operation::new(
+                "cm",
+                vec![2.into(), 0.into(), 0.into(), 2.into(), 0.into(), 0.into()],
+            ),
+            Operation::new("BT", vec![]),
+            Operation::new("Tf", vec!["F1".into(), 10.into()]),
+            Operation::new(
+                "Tm",
+                vec![
+                    1.into(),
+                    0.into(),
+                    0.into(),
+                    1.into(),
+                    36.into(),
+                    300.into(),
+                ],
+            ),
+            Operation::new("Tj", vec![Object::string_literal("big")]),
+            Operation::new("ET", vec![]),
+            Operation::new("Q", vec![]),
+        ];
+        let doc = test_document(vec![ops]);
+        let page_id = doc.get_pages()[&1];
+        let spans = spans(&page_glyphs(&doc, page_id).unwrap());
+        let words = spans.iter().map(|s| s.text.as_str()).collect::<Vec<&str>>();
+        assert_eq!(words, vec!["This", "work", "is,", "in", "part", "big"]);
+        // Courier is 0.6 em wide, less the 0.2 pt kern
+        assert!((spans[0].width - 23.8).abs() < 0.01);
+        assert_eq!((spans[3].x, spans[3].y), (72.0, 688.0));
+        assert_eq!((spans[5].x, spans[5].y, spans[5].size), (72.0, 600.0, 20.0));
+
+        let lines = lines(spans);
+        assert_eq!(lines[0].text(), "This work is,");
+        assert_eq!(lines[1].text(), "in part");
+    }
+
+    #[test]
+    fn test_columns() {
+        let mut ops = text_at(180.0, 740.0, 16.0, "A Two Column Paper");
+        for i in 0..4 {
+            let y = 700.0 - 12.0 * i as f32;
+            ops.extend(text_at(
+                72.0,
+                y,
+                10.0,
+                &format!("left column line {} of text", i),
+            ));
+            ops.extend(text_at(
+                320.0,
+                y,
+                10.0,
+                &format!("right column line {} of text", i),
+            ));
+        }
+        ops.extend(text_at(
+            72.0,
+            600.0,
+            10.0,
+            "a footnote running across both of the columns of the page at the bottom",
+        ));
+        let doc = test_document(vec![ops]);
+        let layout = document_layout(&doc).unwrap().remove(0);
+        assert_eq!(layout.columns, 2);
+        assert_eq!((layout.width, layout.height), (612.0, 792.0));
+        let blocks = layout
+            .blocks
+            .iter()
+            .map(|b| (b.column, b.lines.len(), b.lines[0].text()))
+            .collect::<Vec<(Option<usize>, usize, String)>>();
+        assert_eq!(
+            blocks,
+            vec![
+                (None, 1, "A Two Column Paper".to_string()),
+                (Some(0), 4, "left column line 0 of text".to_string()),
+                (Some(1), 4, "right column line 0 of text".to_string()),
+                (
+                    None,
+                    1,
+                    "a footnote running across both of the columns of the page at the bottom"
+                        .to_string()
+                ),
+            ]
+        );
+    }
+}
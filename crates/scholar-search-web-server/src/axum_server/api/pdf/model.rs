@@ -0,0 +1,362 @@
+//! The document model a converted PDF is kept as: the page layouts, down to
+//! the position of every span, and the sections, footnotes and references
+//! found in them, with their text cleaned up by the post-processor.
+//!
+//! A model belongs to a blob rather than a paper, as papers can share their
+//! PDF, and is stored as JSON beside it.
+
+use super::{
+    citations::{self, CitationContext},
+    layout::{Block, PageLayout},
+    outline::OutlineItem,
+    postprocess::{StageReport, TextPipeline},
+    sections::{body_size, document_title, headings, section_tree, Section, SectionKind},
+};
+use crate::axum_server::state::blob::BlobStore;
+use crate::citation::import::reference::{self, ParsedReference};
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use serde_derive::{Deserialize, Serialize};
+use std::{fs, path::PathBuf, sync::OnceLock};
+
+/// Footnotes sit in this bottom part of the page...
+const FOOTNOTE_ZONE: f32 = 0.25;
+/// ...and are set smaller than the body.
+const FOOTNOTE_SCALE: f32 = 0.9;
+/// Continuation lines of a reference are indented by at least this, in
+/// points, under a hanging indent.
+const HANGING_INDENT: f32 = 2.0;
+
+/// `[12] ...`, `[Smi19] ...`, `12. ...`
+fn reference_label() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"^\s*(?:\[(?<bracket>[^\]\s]{1,12})\]|(?<number>\d{1,3})\.)\s*").unwrap()
+    })
+}
+
+/// `1 This work was...`, `* Equal contribution`
+fn footnote_marker() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"(?s)^(?<marker>\d{1,2}|[*†‡§¶]+)\s*(?<text>\S.*)$").unwrap())
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Footnote {
+    pub page: u32,
+    pub block: usize,
+    /// the number or symbol the body refers to the footnote by
+    pub marker: String,
+    pub text: String,
+}
+
+/// An entry of the reference section, as printed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Reference {
+    /// `12` for `[12]` or `12.`, `Smi19` for `[Smi19]`; `None` for author
+    /// year styles
+    pub label: Option<String>,
+    /// the page the entry starts on
+    pub page: u32,
+    pub text: String,
+    #[serde(default)]
+    pub fields: ParsedReference,
+    /// where the body cites the entry
+    #[serde(default)]
+    pub citations: Vec<CitationContext>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DocumentModel {
+    /// the blob the model was converted from
+    pub sha256: String,
+    pub converted_at: DateTime<Utc>,
+    pub title: Option<String>,
+    pub pages: Vec<PageLayout>,
+    /// the top level sections, holding their subsections
+    pub sections: Vec<Section>,
+    pub footnotes: Vec<Footnote>,
+    pub references: Vec<Reference>,
+    /// what the text clean up did, over the whole document
+    pub reports: Vec<StageReport>,
+}
+
+/// The marker and text of a footnote block.
+fn footnote(block: &Block, page: &PageLayout, body_size: f32) -> Option<(String, String)> {
+    if block.bbox.y1 > page.height * FOOTNOTE_ZONE
+        || block
+            .lines
+            .iter()
+            .any(|l| l.size() > body_size * FOOTNOTE_SCALE)
+    {
+        return None;
+    }
+    let text = block.text();
+    let caps = footnote_marker().captures(&text)?;
+    Some((caps["marker"].to_string(), caps["text"].to_string()))
+}
+
+/// Adds the lines of a reference section block to `entries`. An entry starts
+/// at a label, or, when the entries have none, at each line flush left
+/// under a hanging indent, or else at the start of the block.
+fn reference_entries(block: &Block, page: u32, entries: &mut Vec<Reference>) {
+    let left = block
+        .lines
+        .iter()
+        .map(|l| l.bbox.x0)
+        .fold(f32::MAX, f32::min);
+    let hanging = block
+        .lines
+        .iter()
+        .any(|l| l.bbox.x0 > left + HANGING_INDENT);
+    let labelled = entries.last().is_some_and(|e| e.label.is_some());
+    for (i, line) in block.lines.iter().enumerate() {
+        let text = line.text();
+        let label = reference_label().captures(&text).map(|caps| {
+            let label = caps.name("bracket").or(caps.name("number")).unwrap();
+            (label.as_str().to_string(), caps[0].len())
+        });
+        let starts = label.is_some()
+            || entries.is_empty()
+            || (!labelled && hanging && line.bbox.x0 <= left + HANGING_INDENT)
+            || (!labelled && !hanging && i == 0);
+        match (starts, entries.last_mut()) {
+            (false, Some(entry)) => {
+                entry.text.push('\n');
+                entry.text.push_str(&text);
+            }
+            _ => {
+                let (label, skip) = label.map_or((None, 0), |(l, skip)| (Some(l), skip));
+                entries.push(Reference {
+                    label,
+                    page,
+                    text: text[skip..].to_string(),
+                    fields: ParsedReference::default(),
+                    citations: vec![],
+                })
+            }
+        }
+    }
+}
+
+impl DocumentModel {
+    /// Finds the structure of the pages and cleans up its text.
+    pub fn build(
+        sha256: &str,
+        pages: Vec<PageLayout>,
+        outline: &[OutlineItem],
+        pipeline: &TextPipeline,
+    ) -> Self {
+        let body_size = body_size(&pages);
+        let title = document_title(&pages, body_size).map(|(_, title)| title);
+        let mut pending = headings(&pages, outline, body_size).into_iter().peekable();
+        let mut reports = vec![];
+        let mut clean = |text: &str| {
+            let (text, stage_reports) = pipeline.run(text);
+            StageReport::merge(&mut reports, stage_reports);
+            text
+        };
+        let mut sections: Vec<Section> = vec![];
+        let mut footnotes = vec![];
+        let mut references = vec![];
+        let mut found = vec![];
+        for page in &pages {
+            for (index, block) in page.blocks.iter().enumerate() {
+                let here = (page.number, index);
+                let mut is_heading = false;
+                while let Some(heading) = pending.next_if(|h| (h.page, h.block) <= here) {
+                    is_heading |= heading.found && (heading.page, heading.block) == here;
+                    let mut section = Section::new(&heading);
+                    if let Some(rest) = &heading.rest {
+                        section.text = clean(rest);
+                        found.extend(citations::find(
+                            &section.text,
+                            page.number,
+                            Some(&section.title),
+                        ));
+                    }
+                    sections.push(section);
+                }
+                let text = block.text();
+                // page numbers
+                if is_heading || text.trim().chars().all(|c| c.is_ascii_digit()) {
+                    continue;
+                }
+                if sections
+                    .last()
+                    .is_some_and(|s| s.kind == SectionKind::References)
+                {
+                    reference_entries(block, page.number, &mut references);
+                } else if let Some((marker, text)) = footnote(block, page, body_size) {
+                    footnotes.push(Footnote {
+                        page: page.number,
+                        block: index,
+                        marker,
+                        text: clean(&text),
+                    });
+                } else if let Some(section) = sections.last_mut() {
+                    let text = clean(&text);
+                    found.extend(citations::find(&text, page.number, Some(&section.title)));
+                    if !section.text.is_empty() {
+                        section.text.push_str("\n\n");
+                    }
+                    section.text.push_str(&text);
+                }
+            }
+        }
+        // headings past the last block, such as outline entries pointing at
+        // an empty page
+        sections.extend(pending.map(|h| Section::new(&h)));
+        // author year entries the layout ran together are told apart by
+        // their years
+        let mut references = references
+            .into_iter()
+            .flat_map(|r| {
+                let text = clean(&r.text);
+                let entries = match r.label {
+                    Some(_) => vec![(r.label, text)],
+                    None => reference::split(&text),
+                };
+                entries.into_iter().map(move |(label, text)| Reference {
+                    label,
+                    page: r.page,
+                    fields: reference::parse(&text),
+                    text,
+                    citations: vec![],
+                })
+            })
+            .collect::<Vec<Reference>>();
+        citations::link(&mut references, found);
+        Self {
+            sha256: sha256.to_string(),
+            converted_at: Utc::now(),
+            title,
+            pages,
+            sections: section_tree(sections),
+            footnotes,
+            references,
+            reports,
+        }
+    }
+
+    pub fn path(blobs: &BlobStore, sha256: &str) -> PathBuf {
+        blobs.sidecar_path(sha256, "json")
+    }
+
+    pub fn save(&self, blobs: &BlobStore) -> anyhow::Result<()> {
+        let path = Self::path(blobs, &self.sha256);
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(path, serde_json::to_vec(self)?)?;
+        Ok(())
+    }
+
+    /// The model stored for the blob; `None` when it was never converted.
+    pub fn load(blobs: &BlobStore, sha256: &str) -> anyhow::Result<Option<Self>> {
+        match fs::read(Self::path(blobs, sha256)) {
+            Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::axum_server::api::pdf::{
+        fixture::{test_document, text_at},
+        layout::document_layout,
+        postprocess::{Lexicon, Stage},
+    };
+
+    #[test]
+    fn test_document_model() {
+        let page_one = [
+            text_at(72.0, 700.0, 18.0, "A Study"),
+            text_at(72.0, 650.0, 10.0, "1 Introduction"),
+            text_at(72.0, 630.0, 10.0, "We study the ef-"),
+            text_at(72.0, 618.0, 10.0, "fect of things [2].1"),
+            text_at(72.0, 80.0, 8.0, "1 Supported by a grant."),
+            text_at(300.0, 40.0, 10.0, "1"),
+        ]
+        .concat();
+        let page_two = [
+            text_at(72.0, 700.0, 10.0, "References"),
+            text_at(72.0, 680.0, 10.0, "[1] P. Auer. 2002. Finite-time"),
+            text_at(84.0, 668.0, 10.0, "analysis. Machine learning."),
+            text_at(72.0, 656.0, 10.0, "[2] B. Baker. 2016. Designing"),
+            text_at(84.0, 644.0, 10.0, "networks. arXiv:1611.02167"),
+        ]
+        .concat();
+        let pages = document_layout(&test_document(vec![page_one, page_two])).unwrap();
+        let pipeline = TextPipeline {
+            stages: vec![Stage::Dehyphenate],
+            lexicon: Lexicon::new(["effect 1"], []),
+        };
+        let model = DocumentModel::build("abc", pages, &[], &pipeline);
+
+        assert_eq!(model.title.as_deref(), Some("A Study"));
+        let titles = model
+            .sections
+            .iter()
+            .map(|s| (s.level, s.title.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(titles, vec![(1, "1 Introduction"), (1, "References")]);
+        assert_eq!(
+            model.sections[0].text,
+            "We study the effect of things [2].1"
+        );
+        assert_eq!(model.sections[1].page, 2);
+        assert_eq!(
+            model.footnotes,
+            vec![Footnote {
+                page: 1,
+                block: 3,
+                marker: "1".to_string(),
+                text: "Supported by a grant.".to_string(),
+            }]
+        );
+        let references = model
+            .references
+            .iter()
+            .map(|r| (r.label.as_deref(), r.text.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            references,
+            vec![
+                (
+                    Some("1"),
+                    "P. Auer. 2002. Finite-time analysis. Machine learning."
+                ),
+                (
+                    Some("2"),
+                    "B. Baker. 2016. Designing networks. arXiv:1611.02167"
+                ),
+            ]
+        );
+        assert_eq!(model.references[0].fields.year, Some(2002));
+        assert_eq!(
+            model.references[1].fields.arxiv.as_deref(),
+            Some("1611.02167")
+        );
+        assert_eq!(
+            model.references[1].citations,
+            vec![CitationContext {
+                marker: "[2]".to_string(),
+                sentence: "We study the effect of things [2].1".to_string(),
+                section: Some("1 Introduction".to_string()),
+                page: 1,
+            }]
+        );
+        assert_eq!(model.reports[0].changes, 1);
+
+        let blobs = BlobStore::new(
+            std::env::temp_dir().join(format!("scholar-model-{}", std::process::id())),
+            None,
+        );
+        assert_eq!(DocumentModel::load(&blobs, "abc").unwrap(), None);
+        model.save(&blobs).unwrap();
+        assert_eq!(DocumentModel::load(&blobs, "abc").unwrap(), Some(model));
+        fs::remove_dir_all(blobs.root()).unwrap();
+    }
+}
@@ -0,0 +1,152 @@
+//! OCR of scanned pages, which draw an image and no text. The pdf service
+//! (`scholar-search-pdf-service ocr <pdf>`) finds those pages, runs
+//! Tesseract on them and prints their layout as JSON; its pages stand in
+//! for the empty ones native extraction gives.
+
+use super::layout::PageLayout;
+use anyhow::{anyhow, Error};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::warn;
+
+/// The pdf service binary, from `SCHOLAR_SEARCH_OCR_COMMAND`; `None` when
+/// unset, which leaves scanned pages empty. Its `SCHOLAR_SEARCH_TESSDATA`
+/// and `SCHOLAR_SEARCH_OCR_LANGUAGES` are passed through.
+pub fn ocr_command() -> Option<PathBuf> {
+    std::env::var("SCHOLAR_SEARCH_OCR_COMMAND")
+        .ok()
+        .filter(|c| !c.trim().is_empty())
+        .map(PathBuf::from)
+}
+
+/// The layouts of the scanned pages of the PDF at `pdf`.
+pub fn ocr_pages(command: &Path, pdf: &Path) -> Result<Vec<PageLayout>, Error> {
+    let output = Command::new(command)
+        .arg("ocr")
+        .arg(pdf)
+        .output()
+        .map_err(|e| anyhow!("{}: {}", command.display(), e))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "{} ocr: {}: {}",
+            command.display(),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(serde_json::from_slice(&output.stdout)?)
+}
+
+/// Whether any page came out of native extraction without text.
+pub fn has_empty_pages(pages: &[PageLayout]) -> bool {
+    pages.iter().any(|p| p.blocks.is_empty())
+}
+
+/// Puts the OCR layouts in place of the pages with no text; returns how
+/// many were filled in. Pages native extraction found text on are kept.
+pub fn fill_empty_pages(pages: &mut [PageLayout], ocr: Vec<PageLayout>) -> usize {
+    let mut filled = 0;
+    for layout in ocr {
+        if let Some(page) = pages
+            .iter_mut()
+            .find(|p| p.number == layout.number && p.blocks.is_empty())
+        {
+            *page = layout;
+            filled += 1;
+        }
+    }
+    filled
+}
+
+/// OCRs the PDF at `pdf` and fills in its pages with no text; returns how
+/// many were filled in. A failed OCR run is logged and leaves the pages
+/// empty, so the text of the other pages is still kept.
+pub fn ocr_empty_pages(command: &Path, pdf: &Path, pages: &mut [PageLayout]) -> usize {
+    match ocr_pages(command, pdf) {
+        Ok(ocr) => fill_empty_pages(pages, ocr),
+        Err(e) => {
+            warn!("ocr {}: {}", pdf.display(), e);
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    /// What `scholar-search-pdf-service ocr` prints for one scanned page.
+    const OCR_OUTPUT: &str = r#"[{"number":2,"width":612.0,"height":792.0,"columns":1,
+        "blocks":[{"lines":[{"spans":[
+            {"text":"Scanned","font":"ocr","size":12.0,"x":72.0,"y":700.0,"width":48.0},
+            {"text":"page","font":"ocr","size":12.0,"x":124.0,"y":700.0,"width":26.0}],
+            "bbox":{"x0":72.0,"y0":697.6,"x1":150.0,"y1":709.6}}],
+        "bbox":{"x0":72.0,"y0":697.6,"x1":150.0,"y1":709.6},"column":0}]}]"#;
+
+    #[test]
+    fn test_ocr_pages() {
+        let dir = std::env::temp_dir().join(format!("scholar-ocr-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let output = dir.join("output.json");
+        fs::write(&output, OCR_OUTPUT).unwrap();
+        let command = dir.join("pdf-service");
+        fs::write(
+            &command,
+            format!(
+                "#!/bin/sh\n[ \"$1\" = ocr ] || exit 2\ncat {}\n",
+                output.display()
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&command, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let page = |number| PageLayout {
+            number,
+            width: 612.0,
+            height: 792.0,
+            columns: 1,
+            blocks: vec![],
+        };
+        let mut pages = vec![page(1), page(2)];
+        assert!(has_empty_pages(&pages));
+        let ocr = ocr_pages(&command, &dir.join("paper.pdf")).unwrap();
+        assert_eq!(fill_empty_pages(&mut pages, ocr), 1);
+        assert!(pages[0].blocks.is_empty());
+        assert_eq!(pages[1].blocks[0].text(), "Scanned page");
+        assert_eq!(pages[1].blocks[0].column, Some(0));
+
+        fs::write(&command, "#!/bin/sh\necho 'no language data' >&2\nexit 1\n").unwrap();
+        let error = ocr_pages(&command, &dir.join("paper.pdf")).unwrap_err();
+        assert!(error.to_string().ends_with("no language data"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_ocr_empty_pages_failing_command() {
+        let dir = std::env::temp_dir().join(format!("scholar-ocr-fail-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let command = dir.join("pdf-service");
+        fs::write(&command, "#!/bin/sh\necho 'JBIG2 images' >&2\nexit 1\n").unwrap();
+        fs::set_permissions(&command, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut pages = vec![PageLayout {
+            number: 1,
+            width: 612.0,
+            height: 792.0,
+            columns: 1,
+            blocks: vec![],
+        }];
+        assert_eq!(
+            ocr_empty_pages(&command, &dir.join("paper.pdf"), &mut pages),
+            0
+        );
+        assert!(pages[0].blocks.is_empty());
+        assert_eq!(
+            ocr_empty_pages(&dir.join("missing"), &dir.join("paper.pdf"), &mut pages),
+            0
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
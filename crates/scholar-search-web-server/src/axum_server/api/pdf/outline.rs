@@ -0,0 +1,293 @@
+//! The document outline, the bookmarks a PDF viewer shows beside the pages.
+//!
+//! The tree is walked directly rather than through `Document::get_toc`,
+//! which orders the entries by title, drops repeated titles and gives up on
+//! the whole outline over one broken destination.
+
+use lopdf::{Dictionary, Document, Object, ObjectId};
+use std::collections::{BTreeMap, HashSet};
+
+/// Outlines deeper than this are cut off.
+const MAX_DEPTH: usize = 8;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutlineItem {
+    pub title: String,
+    /// 1 for the top level entries
+    pub level: usize,
+    /// the 1-based page the entry points at, when it points into the file
+    pub page: Option<u32>,
+    /// the top of the view the entry opens, in user space
+    pub top: Option<f32>,
+}
+
+/// A PDF text string: UTF-16BE with a byte order mark, or else
+/// PDFDocEncoding, which agrees with Latin-1 outside a few symbols.
+pub fn text_string(bytes: &[u8]) -> String {
+    match bytes {
+        [0xfe, 0xff, rest @ ..] => {
+            let units = rest
+                .chunks(2)
+                .map(|c| u16::from_be_bytes([c[0], c.get(1).copied().unwrap_or(0)]))
+                .collect::<Vec<u16>>();
+            String::from_utf16_lossy(&units)
+        }
+        [0xef, 0xbb, 0xbf, rest @ ..] => String::from_utf8_lossy(rest).to_string(),
+        _ => bytes.iter().map(|b| *b as char).collect(),
+    }
+}
+
+fn resolve<'a>(doc: &'a Document, object: &'a Object) -> &'a Object {
+    doc.dereference(object).map(|(_, o)| o).unwrap_or(object)
+}
+
+struct Walker<'a> {
+    doc: &'a Document,
+    pages: BTreeMap<ObjectId, u32>,
+    /// the catalog's `/Dests` dictionary, or the leaves of its `/Names`
+    /// `/Dests` tree
+    named: BTreeMap<Vec<u8>, &'a Object>,
+    seen: HashSet<ObjectId>,
+    items: Vec<OutlineItem>,
+}
+
+impl<'a> Walker<'a> {
+    fn name_tree(&mut self, node: &'a Dictionary, depth: usize) {
+        if depth > MAX_DEPTH {
+            return;
+        }
+        if let Ok(names) = node.get(b"Names").and_then(Object::as_array) {
+            for pair in names.chunks(2) {
+                if let [Object::String(key, _), value] = pair {
+                    self.named.insert(key.to_owned(), value);
+                }
+            }
+        }
+        if let Ok(kids) = node.get(b"Kids").and_then(Object::as_array) {
+            for kid in kids {
+                if let Ok(kid) = resolve(self.doc, kid).as_dict() {
+                    self.name_tree(kid, depth + 1);
+                }
+            }
+        }
+    }
+
+    /// The page and top of an explicit destination, `[page /XYZ left top
+    /// zoom]` or `[page /FitH top]`, or of a named one.
+    fn destination(&self, dest: &Object) -> (Option<u32>, Option<f32>) {
+        let dest = match resolve(self.doc, dest) {
+            Object::Name(name) | Object::String(name, _) => match self.named.get(name) {
+                Some(dest) => resolve(self.doc, dest),
+                None => return (None, None),
+            },
+            dest => dest,
+        };
+        // named destinations may be wrapped in a dictionary
+        let dest = match dest {
+            Object::Dictionary(dict) => match dict.get(b"D") {
+                Ok(d) => resolve(self.doc, d),
+                Err(_) => return (None, None),
+            },
+            dest => dest,
+        };
+        let Ok(array) = dest.as_array() else {
+            return (None, None);
+        };
+        let page = match array.first() {
+            Some(Object::Reference(id)) => self.pages.get(id).copied(),
+            // destinations into other files count pages from 0
+            Some(Object::Integer(n)) => u32::try_from(*n).ok().map(|n| n + 1),
+            _ => None,
+        };
+        let top = match array.get(1).and_then(|fit| fit.as_name().ok()) {
+            Some(b"XYZ") => array.get(3),
+            Some(b"FitH") | Some(b"FitBH") => array.get(2),
+            _ => None,
+        }
+        .and_then(|top| top.as_float().ok());
+        (page, top)
+    }
+
+    fn walk(&mut self, first: &'a Object, level: usize) {
+        let mut next = Some(first);
+        while let Some(Object::Reference(id)) = next {
+            if level > MAX_DEPTH || !self.seen.insert(*id) {
+                return;
+            }
+            let Ok(item) = self.doc.get_dictionary(*id) else {
+                return;
+            };
+            let title = match item.get(b"Title").map(|t| resolve(self.doc, t)) {
+                Ok(Object::String(bytes, _)) => text_string(bytes),
+                _ => String::new(),
+            };
+            let dest = match (item.get(b"Dest"), item.get(b"A")) {
+                (Ok(dest), _) => Some(dest),
+                (_, Ok(action)) => resolve(self.doc, action)
+                    .as_dict()
+                    .ok()
+                    .filter(|a| {
+                        a.get(b"S").and_then(Object::as_name).ok() == Some(b"GoTo".as_slice())
+                    })
+                    .and_then(|a| a.get(b"D").ok()),
+                _ => None,
+            };
+            let (page, top) = dest.map_or((None, None), |d| self.destination(d));
+            self.items.push(OutlineItem {
+                title: title.trim().to_string(),
+                level,
+                page,
+                top,
+            });
+            if let Ok(child) = item.get(b"First") {
+                self.walk(child, level + 1);
+            }
+            next = item.get(b"Next").ok();
+        }
+    }
+}
+
+/// The outline entries in document order; empty when there is no outline.
+pub fn outline(doc: &Document) -> Vec<OutlineItem> {
+    let Ok(catalog) = doc.catalog() else {
+        return vec![];
+    };
+    let mut walker = Walker {
+        doc,
+        pages: doc.get_pages().into_iter().map(|(n, id)| (id, n)).collect(),
+        named: BTreeMap::new(),
+        seen: HashSet::new(),
+        items: vec![],
+    };
+    if let Ok(dests) = catalog
+        .get(b"Dests")
+        .map(|d| resolve(doc, d))
+        .and_then(Object::as_dict)
+    {
+        for (name, dest) in dests.iter() {
+            walker.named.insert(name.to_owned(), dest);
+        }
+    }
+    if let Ok(tree) = catalog
+        .get(b"Names")
+        .map(|n| resolve(doc, n))
+        .and_then(Object::as_dict)
+        .and_then(|names| names.get(b"Dests"))
+        .map(|d| resolve(doc, d))
+        .and_then(Object::as_dict)
+    {
+        walker.name_tree(tree, 0);
+    }
+    if let Ok(first) = catalog
+        .get(b"Outlines")
+        .map(|o| resolve(doc, o))
+        .and_then(Object::as_dict)
+        .and_then(|outlines| outlines.get(b"First"))
+    {
+        walker.walk(first, 1);
+    }
+    walker.items
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::axum_server::api::pdf::fixture::{test_document, text_at};
+    use lopdf::{dictionary, StringFormat};
+
+    #[test]
+    fn test_outline() {
+        let mut doc = test_document(vec![
+            text_at(72.0, 700.0, 10.0, "one"),
+            text_at(72.0, 700.0, 10.0, "two"),
+        ]);
+        let pages = doc.get_pages();
+        let (page_one, page_two) = (pages[&1], pages[&2]);
+        let outlines_id = doc.new_object_id();
+        let intro_id = doc.new_object_id();
+        let method_id = doc.new_object_id();
+        let search_id = doc.new_object_id();
+        doc.objects.insert(
+            intro_id,
+            dictionary! {
+                "Title" => Object::string_literal("1 Introduction"),
+                "Parent" => outlines_id,
+                "Next" => method_id,
+                "Dest" => vec![page_one.into(), "XYZ".into(), 0.into(), 720.into(), 0.into()],
+            }
+            .into(),
+        );
+        let title = [
+            vec![0xfe, 0xff],
+            "2 Méthode"
+                .encode_utf16()
+                .flat_map(u16::to_be_bytes)
+                .collect(),
+        ]
+        .concat();
+        doc.objects.insert(
+            method_id,
+            dictionary! {
+                "Title" => Object::String(title, StringFormat::Hexadecimal),
+                "Parent" => outlines_id,
+                "First" => search_id,
+                "Next" => intro_id,
+                "A" => dictionary! { "S" => "GoTo", "D" => Object::string_literal("sec2") },
+            }
+            .into(),
+        );
+        doc.objects.insert(
+            search_id,
+            dictionary! {
+                "Title" => Object::string_literal("2.1 Search"),
+                "Parent" => method_id,
+                "Dest" => vec![page_two.into(), "FitH".into(), 400.into()],
+            }
+            .into(),
+        );
+        doc.objects.insert(
+            outlines_id,
+            dictionary! { "Type" => "Outlines", "First" => intro_id }.into(),
+        );
+        let catalog = doc.catalog_mut().unwrap();
+        catalog.set("Outlines", outlines_id);
+        catalog.set(
+            "Names",
+            dictionary! {
+                "Dests" => dictionary! {
+                    "Names" => vec![
+                        Object::string_literal("sec2"),
+                        dictionary! { "D" => vec![page_two.into(), "Fit".into()] }.into(),
+                    ],
+                },
+            },
+        );
+
+        let items = outline(&doc);
+        // the loop back to the first entry is cut
+        assert_eq!(
+            items,
+            vec![
+                OutlineItem {
+                    title: "1 Introduction".to_string(),
+                    level: 1,
+                    page: Some(1),
+                    top: Some(720.0),
+                },
+                OutlineItem {
+                    title: "2 Méthode".to_string(),
+                    level: 1,
+                    page: Some(2),
+                    top: None,
+                },
+                OutlineItem {
+                    title: "2.1 Search".to_string(),
+                    level: 2,
+                    page: Some(2),
+                    top: Some(400.0),
+                },
+            ]
+        );
+        assert!(outline(&test_document(vec![vec![]])).is_empty());
+    }
+}
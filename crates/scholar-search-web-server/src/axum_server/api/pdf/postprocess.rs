@@ -0,0 +1,545 @@
+//! Cleans extracted text up in configurable stages: joining words hyphenated
+//! across line breaks, and splitting tokens that lost their spaces in the
+//! PDF. Both stages consult a word frequency dictionary and leave URLs,
+//! identifiers, math and reference numbers alone. Each stage reports how the
+//! text's quality moved, so a stage that does more harm than good shows.
+
+use anyhow::anyhow;
+use fast_symspell::{SymSpell, SymSpellBuilder, UnicodeStringStrategy};
+use regex::Regex;
+use serde_derive::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt, ops::AddAssign, path::PathBuf, str::FromStr, sync::OnceLock};
+
+pub const DEFAULT_DICTIONARY: &str = "data/frequency_dictionary_en_82_765.txt";
+
+/// Shorter runs are left whole; they are mostly real words.
+const MIN_SEGMENT_LEN: usize = 6;
+/// Words at least this frequent are function words like `the` and `is`.
+const COMMON_COUNT: i64 = 1_000_000_000;
+/// Unknown runs this long are counted as run-together words.
+const RUN_TOGETHER_LEN: usize = 15;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// joins words hyphenated across line breaks, then the lines
+    Dehyphenate,
+    /// splits run-together tokens into dictionary words
+    Segment,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Stage::Dehyphenate => write!(f, "dehyphenate"),
+            Stage::Segment => write!(f, "segment"),
+        }
+    }
+}
+
+impl FromStr for Stage {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "dehyphenate" => Ok(Stage::Dehyphenate),
+            "segment" => Ok(Stage::Segment),
+            other => Err(anyhow!("unknown text stage: {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TextConfig {
+    pub stages: Vec<Stage>,
+    /// `word count` lines
+    pub dictionary: PathBuf,
+    /// `word word count` lines, e.g. SymSpell's
+    /// `frequency_bigramdictionary_en_243_342.txt`. Not shipped and off by
+    /// default: without them a split must involve a function word, so
+    /// `hyperparameters` stays whole rather than risk wrong splits.
+    pub bigrams: Option<PathBuf>,
+}
+
+impl Default for TextConfig {
+    fn default() -> Self {
+        Self {
+            stages: vec![Stage::Dehyphenate, Stage::Segment],
+            dictionary: PathBuf::from(DEFAULT_DICTIONARY),
+            bigrams: None,
+        }
+    }
+}
+
+impl TextConfig {
+    /// `SCHOLAR_SEARCH_TEXT_STAGES` is a comma separated list of stages, in
+    /// order; empty turns post-processing off. `SCHOLAR_SEARCH_DICTIONARY`
+    /// and `SCHOLAR_SEARCH_BIGRAM_DICTIONARY` point at the dictionaries.
+    pub fn from_env() -> anyhow::Result<Self> {
+        let default = Self::default();
+        Ok(Self {
+            stages: match std::env::var("SCHOLAR_SEARCH_TEXT_STAGES") {
+                Ok(stages) => stages
+                    .split(',')
+                    .filter(|s| !s.trim().is_empty())
+                    .map(Stage::from_str)
+                    .collect::<anyhow::Result<Vec<Stage>>>()?,
+                Err(_) => default.stages,
+            },
+            dictionary: std::env::var("SCHOLAR_SEARCH_DICTIONARY")
+                .map(PathBuf::from)
+                .unwrap_or(default.dictionary),
+            bigrams: std::env::var("SCHOLAR_SEARCH_BIGRAM_DICTIONARY")
+                .ok()
+                .filter(|path| !path.trim().is_empty())
+                .map(PathBuf::from)
+                .or(default.bigrams),
+        })
+    }
+}
+
+/// The unigram and bigram dictionaries.
+pub struct Lexicon {
+    symspell: SymSpell<UnicodeStringStrategy>,
+    bigrams: HashMap<String, i64>,
+}
+
+impl Lexicon {
+    pub fn new<'a>(
+        words: impl IntoIterator<Item = &'a str>,
+        bigrams: impl IntoIterator<Item = &'a str>,
+    ) -> Self {
+        // exact lookups only: segmentation must not turn into spelling
+        // correction
+        let mut symspell = SymSpellBuilder::default()
+            .max_dictionary_edit_distance(0)
+            .count_threshold(1)
+            .build()
+            .unwrap();
+        for line in words {
+            symspell.load_dictionary_line(line, 0, 1, " ");
+        }
+        let bigrams = bigrams
+            .into_iter()
+            .filter_map(|line| {
+                let mut parts = line.split_whitespace();
+                let pair = format!("{} {}", parts.next()?, parts.next()?);
+                Some((pair, parts.next()?.parse().ok()?))
+            })
+            .collect();
+        Self { symspell, bigrams }
+    }
+
+    pub fn load(config: &TextConfig) -> anyhow::Result<Self> {
+        let words = std::fs::read_to_string(&config.dictionary)
+            .map_err(|e| anyhow!("{}: {}", config.dictionary.display(), e))?;
+        // bigrams that were asked for must load, or segmentation quietly
+        // gets more conservative
+        let bigrams = match &config.bigrams {
+            Some(path) => {
+                std::fs::read_to_string(path).map_err(|e| anyhow!("{}: {}", path.display(), e))?
+            }
+            None => String::new(),
+        };
+        Ok(Self::new(words.lines(), bigrams.lines()))
+    }
+
+    pub fn knows(&self, word: &str) -> bool {
+        self.symspell.get_freq(&word.to_lowercase()).is_some()
+    }
+
+    fn common(&self, word: &str) -> bool {
+        self.symspell
+            .get_freq(word)
+            .is_some_and(|count| *count >= COMMON_COUNT)
+    }
+
+    /// Splits a run of letters into dictionary words, keeping its case, or
+    /// `None` when it is a word already or doesn't split cleanly.
+    pub fn segment(&self, run: &str) -> Option<String> {
+        let lower = run.to_lowercase();
+        if run.chars().count() < MIN_SEGMENT_LEN
+            || lower.chars().count() != run.chars().count()
+            || self.knows(run)
+        {
+            return None;
+        }
+        let composition = self.symspell.word_segmentation(&lower, 0);
+        let parts = composition
+            .segmented_string
+            .split_whitespace()
+            .collect::<Vec<&str>>();
+        if parts.len() < 2
+            || parts.concat() != lower
+            || parts
+                .iter()
+                .any(|p| !self.knows(p) || (p.chars().count() == 1 && *p != "a" && *p != "i"))
+        {
+            return None;
+        }
+        // glued words nearly always involve a function word; without one, a
+        // split like `hyper parameters` needs the bigram to back it
+        if !parts.windows(2).all(|w| {
+            self.common(w[0])
+                || self.common(w[1])
+                || self.bigrams.contains_key(&format!("{} {}", w[0], w[1]))
+        }) {
+            return None;
+        }
+        let mut chars = run.chars();
+        let words = parts
+            .iter()
+            .map(|p| chars.by_ref().take(p.chars().count()).collect::<String>())
+            .collect::<Vec<String>>();
+        Some(words.join(" "))
+    }
+}
+
+/// Tokens that must come through untouched: URLs, emails, anything with a
+/// digit (reference numbers, years, identifiers), math, and mixed case or
+/// all-caps names.
+pub fn protected(token: &str) -> bool {
+    let lowercase_then_upper = token
+        .chars()
+        .zip(token.chars().skip(1))
+        .any(|(a, b)| a.is_lowercase() && b.is_uppercase());
+    token.contains("://")
+        || token.starts_with("www.")
+        || token.starts_with("doi:")
+        || token.starts_with('[')
+        || lowercase_then_upper
+        || token.chars().filter(|c| c.is_uppercase()).count() > 1
+        || token.chars().any(|c| {
+            c.is_ascii_digit()
+                || "=+<>^_\\{}|/@#$%&*~".contains(c)
+                // Greek and the mathematical operators
+                || ('\u{0370}'..='\u{03ff}').contains(&c)
+                || ('\u{2200}'..='\u{22ff}').contains(&c)
+        })
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct TextMetrics {
+    /// runs of two or more letters
+    pub words: usize,
+    /// words found in the dictionary
+    pub known: usize,
+    /// unknown words long enough to be several glued together
+    pub run_together: usize,
+    /// hyphens left before a line break or a space
+    pub hyphen_breaks: usize,
+}
+
+impl TextMetrics {
+    pub fn measure(lexicon: &Lexicon, text: &str) -> Self {
+        let mut metrics = Self::default();
+        for word in text.split(|c: char| !c.is_alphabetic()) {
+            let len = word.chars().count();
+            if len < 2 {
+                continue;
+            }
+            metrics.words += 1;
+            if lexicon.knows(word) {
+                metrics.known += 1;
+            } else if len >= RUN_TOGETHER_LEN {
+                metrics.run_together += 1;
+            }
+        }
+        metrics.hyphen_breaks = hyphen_break().find_iter(text).count();
+        metrics
+    }
+
+    pub fn known_ratio(&self) -> f32 {
+        if self.words == 0 {
+            return 1.0;
+        }
+        self.known as f32 / self.words as f32
+    }
+}
+
+impl AddAssign for TextMetrics {
+    fn add_assign(&mut self, other: Self) {
+        self.words += other.words;
+        self.known += other.known;
+        self.run_together += other.run_together;
+        self.hyphen_breaks += other.hyphen_breaks;
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StageReport {
+    pub stage: Stage,
+    pub changes: usize,
+    pub before: TextMetrics,
+    pub after: TextMetrics,
+}
+
+impl StageReport {
+    /// Adds the reports of another run to the totals of the same stages.
+    pub fn merge(totals: &mut Vec<StageReport>, reports: Vec<StageReport>) {
+        for report in reports {
+            match totals.iter_mut().find(|t| t.stage == report.stage) {
+                Some(total) => {
+                    total.changes += report.changes;
+                    total.before += report.before;
+                    total.after += report.after;
+                }
+                None => totals.push(report),
+            }
+        }
+    }
+}
+
+impl fmt::Display for StageReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} changes, known words {:.1}% -> {:.1}%, run-together {} -> {}, \
+             hyphen breaks {} -> {}",
+            self.stage,
+            self.changes,
+            self.before.known_ratio() * 100.0,
+            self.after.known_ratio() * 100.0,
+            self.before.run_together,
+            self.after.run_together,
+            self.before.hyphen_breaks,
+            self.after.hyphen_breaks,
+        )
+    }
+}
+
+/// A letter run, a hyphen, and a line break or space before the next word.
+fn hyphen_break() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"(\S*?)(\p{L}+)-([ \t]*\n[ \t]*|[ \t]+)(\p{L}+)").unwrap())
+}
+
+fn token() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\S+").unwrap())
+}
+
+pub struct TextPipeline {
+    pub stages: Vec<Stage>,
+    pub lexicon: Lexicon,
+}
+
+impl TextPipeline {
+    pub fn load(config: &TextConfig) -> anyhow::Result<Self> {
+        Ok(Self {
+            stages: config.stages.to_owned(),
+            lexicon: Lexicon::load(config)?,
+        })
+    }
+
+    pub fn run(&self, text: &str) -> (String, Vec<StageReport>) {
+        let mut text = text.to_string();
+        let mut reports = vec![];
+        for stage in &self.stages {
+            let before = TextMetrics::measure(&self.lexicon, &text);
+            let (after_text, changes) = match stage {
+                Stage::Dehyphenate => self.dehyphenate(&text),
+                Stage::Segment => self.segment(&text),
+            };
+            text = after_text;
+            reports.push(StageReport {
+                stage: *stage,
+                changes,
+                before,
+                after: TextMetrics::measure(&self.lexicon, &text),
+            });
+        }
+        (text, reports)
+    }
+
+    /// At a line break, a hyphen goes when the joined word is known, or
+    /// when neither half is a word of its own; between two known words it
+    /// stays, as in `self-attention`. Within a line, a hyphen followed by a
+    /// space only goes when the joined word is known, which keeps `pre- and
+    /// post-processing`. Single line breaks then become spaces.
+    pub fn dehyphenate(&self, text: &str) -> (String, usize) {
+        let mut changes = 0;
+        let text = hyphen_break().replace_all(text, |caps: &regex::Captures| {
+            let (prefix, head, gap, tail) = (&caps[1], &caps[2], &caps[3], &caps[4]);
+            let joined = format!("{}{}", head, tail);
+            let keep = if protected(&format!("{}{}", prefix, head)) {
+                gap.contains('\n')
+            } else if gap.contains('\n') {
+                !self.lexicon.knows(&joined) && self.lexicon.knows(head) && self.lexicon.knows(tail)
+            } else {
+                !self.lexicon.knows(&joined)
+            };
+            if keep && !gap.contains('\n') {
+                return caps[0].to_string();
+            }
+            changes += 1;
+            if keep {
+                format!("{}{}-{}", prefix, head, tail)
+            } else {
+                format!("{}{}", prefix, joined)
+            }
+        });
+        let text = text
+            .split("\n\n")
+            .map(|paragraph| paragraph.replace('\n', " "))
+            .collect::<Vec<String>>()
+            .join("\n\n");
+        (text, changes)
+    }
+
+    /// Splits the letter runs of each unprotected token, and puts a space
+    /// after a comma or semicolon glued to the next word.
+    pub fn segment(&self, text: &str) -> (String, usize) {
+        let mut changes = 0;
+        let text = token().replace_all(text, |caps: &regex::Captures| {
+            let token = &caps[0];
+            if protected(token) {
+                return token.to_string();
+            }
+            let mut out = String::new();
+            let mut run = String::new();
+            let mut chars = token.chars().peekable();
+            while let Some(c) = chars.next() {
+                if c.is_alphabetic() {
+                    run.push(c);
+                    continue;
+                }
+                out.push_str(&self.flush(&mut run, &mut changes));
+                out.push(c);
+                if (c == ',' || c == ';') && chars.peek().is_some_and(|n| n.is_alphabetic()) {
+                    out.push(' ');
+                    changes += 1;
+                }
+            }
+            out.push_str(&self.flush(&mut run, &mut changes));
+            out
+        });
+        (text.to_string(), changes)
+    }
+
+    fn flush(&self, run: &mut String, changes: &mut usize) -> String {
+        let run = std::mem::take(run);
+        match self.lexicon.segment(&run) {
+            Some(segmented) => {
+                *changes += 1;
+                segmented
+            }
+            None => run,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::path::Path;
+
+    fn lexicon(bigrams: &[&str]) -> Lexicon {
+        let words = [
+            "the 23135851162",
+            "and 12997637966",
+            "in 8469404971",
+            "is 4705743816",
+            "this 3228469771",
+            "work 419483948",
+            "part 302729303",
+            "supported 40",
+            "by 30",
+            "comments 20",
+            "feature 20",
+            "self 10",
+            "attention 10",
+            "post 10",
+            "processing 10",
+            "a 50",
+            "views 10",
+            "opinions 10",
+            "pre 5",
+            "hyper 3153749",
+            "parameters 29922419",
+        ];
+        Lexicon::new(words, bigrams.iter().copied())
+    }
+
+    #[test]
+    fn test_segment() {
+        let lexicon = lexicon(&[]);
+        assert_eq!(lexicon.segment("Thisworkis").unwrap(), "This work is");
+        assert_eq!(lexicon.segment("supported"), None);
+        assert_eq!(lexicon.segment("Keras"), None);
+        assert_eq!(lexicon.segment("hyperparameters"), None);
+        // an unknown remainder leaves the run alone
+        assert_eq!(lexicon.segment("thisworkisx"), None);
+
+        let with_bigrams = Lexicon::new(
+            ["this 90", "work 80", "is 70", "part 50", "in 60"],
+            ["this work 10", "work is 10"],
+        );
+        assert_eq!(with_bigrams.segment("thisworkis").unwrap(), "this work is");
+        assert_eq!(with_bigrams.segment("partinpart"), None);
+
+        // the shipped dictionary, with and without a bigram list
+        let dictionary = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../..")
+            .join(DEFAULT_DICTIONARY);
+        let mut config = TextConfig {
+            dictionary,
+            ..TextConfig::default()
+        };
+        assert_eq!(config.bigrams, None);
+        let lexicon = Lexicon::load(&config).unwrap();
+        assert_eq!(lexicon.segment("Thisworkis").unwrap(), "This work is");
+        assert_eq!(lexicon.segment("hyperparameters"), None);
+        let bigrams = std::env::temp_dir().join(format!("scholar-bigrams-{}", std::process::id()));
+        std::fs::write(&bigrams, "hyper parameters 2001\n").unwrap();
+        config.bigrams = Some(bigrams.clone());
+        let lexicon = Lexicon::load(&config).unwrap();
+        assert_eq!(
+            lexicon.segment("hyperparameters").unwrap(),
+            "hyper parameters"
+        );
+        std::fs::remove_file(&bigrams).unwrap();
+        assert!(Lexicon::load(&config).is_err());
+
+        assert!(protected("arXiv:1611.02167"));
+        assert!(protected("https://keras.io."));
+        assert!(protected("[12]"));
+        assert!(protected("x=y"));
+        assert!(protected("ProxylessNAS"));
+        assert!(!protected("Thisworkis,"));
+    }
+
+    #[test]
+    fn test_pipeline() {
+        let pipeline = TextPipeline {
+            stages: vec![Stage::Dehyphenate, Stage::Segment],
+            lexicon: lexicon(&[]),
+        };
+        let text = "Thisworkis,inpart,supported by com-\nments on fea-\nture and self-\n\
+                    attention, pre- and post-processing [12] arXiv:1611.02167\n\nthe views";
+        let (out, reports) = pipeline.run(text);
+        assert_eq!(
+            out,
+            "This work is, in part, supported by comments on feature and self-attention, \
+             pre- and post-processing [12] arXiv:1611.02167\n\nthe views"
+        );
+        assert_eq!(reports.len(), 2);
+        assert_eq!(
+            (reports[0].stage, reports[0].changes),
+            (Stage::Dehyphenate, 3)
+        );
+        assert_eq!(reports[0].before.hyphen_breaks, 4);
+        assert_eq!(reports[0].after.hyphen_breaks, 1);
+        assert_eq!((reports[1].stage, reports[1].changes), (Stage::Segment, 4));
+        assert!(reports[1].after.known_ratio() > reports[1].before.known_ratio());
+        assert!(reports[1].to_string().starts_with("segment: 4 changes"));
+
+        assert_eq!(
+            "dehyphenate,segment"
+                .split(',')
+                .map(Stage::from_str)
+                .collect::<anyhow::Result<Vec<Stage>>>()
+                .unwrap(),
+            TextConfig::default().stages
+        );
+    }
+}
@@ -0,0 +1,649 @@
+//! Section segmentation.
+//!
+//! Headings come from the outline when the PDF has a usable one, each entry
+//! found again on its page. Otherwise they come from the layout: heading
+//! numbers (`2.1`, `IV.`, and `A.` once the back matter starts), the names
+//! papers give their sections, and blocks set larger or bolder than the
+//! body. Unnumbered headings take the level of numbered ones set in the same
+//! style. The headings are then nested into a tree by level.
+
+use super::{
+    layout::{Block, Line, PageLayout},
+    outline::OutlineItem,
+};
+use regex::Regex;
+use serde_derive::{Deserialize, Serialize};
+use std::{collections::HashMap, sync::OnceLock};
+
+/// A heading is at most this many words...
+const MAX_HEADING_WORDS: usize = 12;
+/// ...on at most this many lines.
+const MAX_HEADING_LINES: usize = 2;
+/// Lines this many times the body size are headings, numbered or not.
+const HEADING_SCALE: f32 = 1.2;
+/// Numbered headings need to be a little larger than the body, or bold.
+const NUMBERED_HEADING_SCALE: f32 = 1.05;
+/// An outline entry's title may take up this many lines of its block.
+const OUTLINE_TITLE_LINES: usize = 3;
+/// The title is within this many blocks of the top of the first page.
+const TITLE_BLOCKS: usize = 4;
+
+/// Unnumbered headings recognised by name alone.
+const NAMED_SECTIONS: [&str; 17] = [
+    "abstract",
+    "introduction",
+    "related work",
+    "background",
+    "method",
+    "methods",
+    "methodology",
+    "experiments",
+    "results",
+    "discussion",
+    "conclusion",
+    "conclusions",
+    "acknowledgments",
+    "acknowledgements",
+    "references",
+    "bibliography",
+    "appendix",
+];
+
+/// `1 Introduction`, `2.1. Search space`, `IV. RESULTS`, `A.1 Proofs`
+fn numbered_heading() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(concat!(
+            r"^(?:(?<number>\d+(?:\.\d+)*)\.?|(?<roman>[IVX]+)\.|(?<letter>[A-Z](?:\.\d+)*)\.?)",
+            r"\s+(?<title>\p{Lu}.*)$"
+        ))
+        .unwrap()
+    })
+}
+
+/// `Abstract—We propose...`, `ABSTRACT. We...`
+fn run_in_abstract() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"(?s)^(?i:abstract)\s*[—–\-.:]?\s+(?<rest>\S.*)$").unwrap())
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SectionKind {
+    Abstract,
+    Introduction,
+    RelatedWork,
+    Background,
+    Method,
+    Experiments,
+    Results,
+    Discussion,
+    Conclusion,
+    Acknowledgments,
+    References,
+    Appendix,
+    Other,
+}
+
+/// Words of a title that give its section's role away, tried in order.
+const KIND_KEYWORDS: [(&str, SectionKind); 24] = [
+    ("abstract", SectionKind::Abstract),
+    ("introduction", SectionKind::Introduction),
+    ("related", SectionKind::RelatedWork),
+    ("prior work", SectionKind::RelatedWork),
+    ("literature", SectionKind::RelatedWork),
+    ("background", SectionKind::Background),
+    ("preliminar", SectionKind::Background),
+    ("acknowledg", SectionKind::Acknowledgments),
+    ("reference", SectionKind::References),
+    ("bibliograph", SectionKind::References),
+    ("appendi", SectionKind::Appendix),
+    ("supplementa", SectionKind::Appendix),
+    ("conclusion", SectionKind::Conclusion),
+    ("future work", SectionKind::Conclusion),
+    ("discussion", SectionKind::Discussion),
+    ("limitation", SectionKind::Discussion),
+    ("experiment", SectionKind::Experiments),
+    ("evaluation", SectionKind::Experiments),
+    ("result", SectionKind::Results),
+    ("method", SectionKind::Method),
+    ("approach", SectionKind::Method),
+    ("model", SectionKind::Method),
+    ("framework", SectionKind::Method),
+    ("algorithm", SectionKind::Method),
+];
+
+impl SectionKind {
+    /// Guesses the role of a section from its title.
+    pub fn of(title: &str) -> Self {
+        let name = section_name(title);
+        KIND_KEYWORDS
+            .iter()
+            .find(|(keyword, _)| name.contains(keyword))
+            .map_or(SectionKind::Other, |(_, kind)| *kind)
+    }
+
+    /// Sections after the body of the paper.
+    pub fn is_back_matter(&self) -> bool {
+        matches!(self, SectionKind::References | SectionKind::Appendix)
+    }
+}
+
+/// Where a section starts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heading {
+    pub title: String,
+    /// 1 for top level sections, 2 for their subsections, ...
+    pub level: usize,
+    pub page: u32,
+    pub block: usize,
+    /// whether `block` is the heading itself, rather than the first block
+    /// of a section whose heading wasn't found on the page
+    pub found: bool,
+    /// body text sharing the heading's block
+    pub rest: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Section {
+    pub title: String,
+    pub kind: SectionKind,
+    pub level: usize,
+    /// where the section starts: the page number and the block's index on
+    /// it
+    pub page: u32,
+    pub block: usize,
+    /// the body up to the first subsection or the next heading
+    pub text: String,
+    pub children: Vec<Section>,
+}
+
+impl Section {
+    pub fn new(heading: &Heading) -> Self {
+        Self {
+            title: heading.title.to_owned(),
+            kind: SectionKind::of(&heading.title),
+            level: heading.level,
+            page: heading.page,
+            block: heading.block,
+            text: String::new(),
+            children: vec![],
+        }
+    }
+}
+
+/// The title without its number, in lower case.
+pub fn section_name(title: &str) -> String {
+    match numbered_heading().captures(title) {
+        Some(caps) => caps["title"].to_lowercase(),
+        None => title.to_lowercase(),
+    }
+}
+
+/// The depth of a heading's number, `IV.` counting as one; letters only
+/// number appendices, in the back matter.
+fn number_depth(title: &str, back_matter: bool) -> Option<usize> {
+    let caps = numbered_heading().captures(title)?;
+    let number = match (caps.name("number"), caps.name("letter")) {
+        (Some(number), _) => number.as_str(),
+        (_, Some(letter)) if back_matter => letter.as_str(),
+        (_, Some(_)) => return None,
+        _ => "I",
+    };
+    Some(number.split('.').count())
+}
+
+/// Letters and digits only, for comparing titles across the outline and
+/// the page.
+fn normalize(text: &str) -> String {
+    section_name(text)
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect()
+}
+
+/// The span size most of the text is set in.
+pub fn body_size(pages: &[PageLayout]) -> f32 {
+    let mut chars = HashMap::<i32, usize>::new();
+    for span in pages
+        .iter()
+        .flat_map(|p| &p.blocks)
+        .flat_map(|b| &b.lines)
+        .flat_map(|l| &l.spans)
+    {
+        // to the half point
+        *chars.entry((span.size * 2.0).round() as i32).or_default() += span.text.len();
+    }
+    chars
+        .into_iter()
+        .max_by_key(|(size, count)| (*count, -size))
+        .map_or(10.0, |(size, _)| size as f32 / 2.0)
+}
+
+fn block_size(block: &Block) -> f32 {
+    block.lines.iter().map(Line::size).fold(0.0, f32::max)
+}
+
+fn is_bold(block: &Block) -> bool {
+    block
+        .lines
+        .iter()
+        .flat_map(|l| &l.spans)
+        .all(|s| s.font.contains("Bold") || s.font.contains("Black"))
+}
+
+/// The index and text of the block the paper's title is set in: the
+/// largest text near the top of the first page, when it stands out from
+/// the body.
+pub fn document_title(pages: &[PageLayout], body_size: f32) -> Option<(usize, String)> {
+    let page = pages.first()?;
+    let (index, block) = page
+        .blocks
+        .iter()
+        .take(TITLE_BLOCKS)
+        .enumerate()
+        .max_by(|(_, a), (_, b)| block_size(a).total_cmp(&block_size(b)))?;
+    let text = block
+        .lines
+        .iter()
+        .map(Line::text)
+        .collect::<Vec<String>>()
+        .join(" ");
+    (block_size(block) >= body_size * HEADING_SCALE && number_depth(&text, false).is_none())
+        .then_some((index, text))
+}
+
+/// A heading found in the layout, before its level is known.
+struct Candidate {
+    title: String,
+    page: u32,
+    block: usize,
+    /// the depth of its number, `IV.` and `A.` counting as one
+    depth: Option<usize>,
+    /// size to the half point, and weight
+    style: (i32, bool),
+    large: bool,
+    named: bool,
+    rest: Option<String>,
+}
+
+fn candidate(
+    block: &Block,
+    page: u32,
+    index: usize,
+    body_size: f32,
+    back_matter: bool,
+) -> Option<Candidate> {
+    let text = block.text();
+    if let Some(caps) = run_in_abstract().captures(&text) {
+        return Some(Candidate {
+            title: "Abstract".to_string(),
+            page,
+            block: index,
+            depth: None,
+            style: ((body_size * 2.0).round() as i32, false),
+            large: false,
+            named: true,
+            rest: Some(caps["rest"].to_string()),
+        });
+    }
+    if block.lines.len() > MAX_HEADING_LINES {
+        return None;
+    }
+    let title = text.replace('\n', " ");
+    let title = title.trim().trim_end_matches(':').trim();
+    let words = title.split_whitespace().count();
+    if words == 0 || words > MAX_HEADING_WORDS || title.ends_with('.') {
+        return None;
+    }
+    let size = block_size(block);
+    let bold = is_bold(block);
+    let depth = number_depth(title, back_matter);
+    let numbered = depth.is_some() && (size >= body_size * NUMBERED_HEADING_SCALE || bold);
+    let large = size >= body_size * HEADING_SCALE;
+    let named = NAMED_SECTIONS.contains(&section_name(title).as_str());
+    (numbered || large || named || bold).then(|| Candidate {
+        title: title.to_string(),
+        page,
+        block: index,
+        depth: depth.filter(|_| numbered),
+        style: ((size * 2.0).round() as i32, bold),
+        large,
+        named,
+        rest: None,
+    })
+}
+
+fn layout_headings(pages: &[PageLayout], body_size: f32) -> Vec<Heading> {
+    let title = document_title(pages, body_size).map(|(index, _)| index);
+    let mut candidates: Vec<Candidate> = vec![];
+    let mut back_matter = false;
+    for page in pages {
+        for (index, block) in page.blocks.iter().enumerate() {
+            if page.number == pages[0].number && Some(index) == title {
+                continue;
+            }
+            let Some(candidate) = candidate(block, page.number, index, body_size, back_matter)
+            else {
+                continue;
+            };
+            // bold lines before the first real heading are names and
+            // affiliations
+            let started = candidates.iter().any(|c| c.depth.is_some() || c.named);
+            if candidate.depth.is_none() && !candidate.named && !candidate.large && !started {
+                continue;
+            }
+            back_matter |= SectionKind::of(&candidate.title).is_back_matter();
+            candidates.push(candidate);
+        }
+    }
+    let mut style_levels = HashMap::new();
+    for candidate in &candidates {
+        if let Some(depth) = candidate.depth {
+            let level = style_levels.entry(candidate.style).or_insert(depth);
+            *level = depth.min(*level);
+        }
+    }
+    let deepest = style_levels.values().copied().max().unwrap_or(1);
+    candidates
+        .into_iter()
+        .map(|c| {
+            let level = match c.depth {
+                Some(depth) => depth,
+                None => match style_levels.get(&c.style) {
+                    Some(level) => *level,
+                    None if c.named || c.large => 1,
+                    None => deepest + 1,
+                },
+            };
+            Heading {
+                title: c.title,
+                level,
+                page: c.page,
+                block: c.block,
+                found: true,
+                rest: c.rest,
+            }
+        })
+        .collect()
+}
+
+/// The block on `page` holding the entry's title, preferring the one
+/// nearest the top of the entry's view, or else the first block below it.
+fn locate(page: &PageLayout, item: &OutlineItem) -> Heading {
+    let target = normalize(&item.title);
+    let distance = |block: &Block| item.top.map_or(0.0, |top| (block.bbox.y1 - top).abs());
+    let found = page
+        .blocks
+        .iter()
+        .enumerate()
+        .filter_map(|(index, block)| {
+            (1..=block.lines.len().min(OUTLINE_TITLE_LINES))
+                .find(|n| {
+                    let lines = block.lines[..*n].iter().map(Line::text);
+                    normalize(&lines.collect::<Vec<String>>().join(" ")) == target
+                })
+                .map(|n| (index, block, n))
+        })
+        .min_by(|(_, a, _), (_, b, _)| distance(a).total_cmp(&distance(b)));
+    let (block, found, rest) = match found {
+        Some((index, block, n)) => {
+            let rest = block.lines[n..]
+                .iter()
+                .map(Line::text)
+                .collect::<Vec<String>>()
+                .join("\n");
+            (index, true, Some(rest).filter(|r| !r.is_empty()))
+        }
+        None => {
+            let below = page.blocks.iter().position(|b| {
+                // a point of slack
+                item.top.is_none_or(|top| b.bbox.y1 <= top + 1.0)
+            });
+            (below.unwrap_or(page.blocks.len()), false, None)
+        }
+    };
+    Heading {
+        title: item.title.to_owned(),
+        level: item.level,
+        page: page.number,
+        block,
+        found,
+        rest,
+    }
+}
+
+/// Headings from the outline; `None` when it has fewer than two entries
+/// pointing into the pages. A single top level entry holding all others is
+/// the paper's title and is left out.
+fn outline_headings(pages: &[PageLayout], outline: &[OutlineItem]) -> Option<Vec<Heading>> {
+    let mut items = outline
+        .iter()
+        .filter(|item| item.page.is_some() && !item.title.is_empty())
+        .cloned()
+        .collect::<Vec<OutlineItem>>();
+    // only an entry every later one is nested under wraps the others
+    while items.len() > 1 && items[1..].iter().all(|i| i.level > items[0].level) {
+        items.remove(0);
+        for item in items.iter_mut() {
+            item.level -= 1;
+        }
+    }
+    if items.len() < 2 {
+        return None;
+    }
+    let mut headings = items
+        .iter()
+        .filter_map(|item| {
+            let page = pages.iter().find(|p| Some(p.number) == item.page)?;
+            Some(locate(page, item))
+        })
+        .collect::<Vec<Heading>>();
+    // keep the reading order, which an outline need not follow
+    headings.sort_by_key(|h| (h.page, h.block));
+    Some(headings)
+}
+
+/// The headings of the document in reading order.
+pub fn headings(pages: &[PageLayout], outline: &[OutlineItem], body_size: f32) -> Vec<Heading> {
+    outline_headings(pages, outline).unwrap_or_else(|| layout_headings(pages, body_size))
+}
+
+/// Nests sections, given in reading order, under the nearest earlier
+/// section of a lower level. Unnamed subsections of an appendix, and
+/// sections numbered with letters after the references, are appendices.
+pub fn section_tree(sections: Vec<Section>) -> Vec<Section> {
+    fn close(open: &mut Vec<Section>, roots: &mut Vec<Section>) {
+        let section = open.pop().unwrap();
+        match open.last_mut() {
+            Some(parent) => parent.children.push(section),
+            None => roots.push(section),
+        }
+    }
+    let mut roots = vec![];
+    let mut open: Vec<Section> = vec![];
+    let mut back_matter = false;
+    for mut section in sections {
+        while open.last().is_some_and(|o| o.level >= section.level) {
+            close(&mut open, &mut roots);
+        }
+        let lettered = numbered_heading()
+            .captures(&section.title)
+            .is_some_and(|caps| caps.name("letter").is_some());
+        if section.kind == SectionKind::Other
+            && (open.last().is_some_and(|o| o.kind == SectionKind::Appendix)
+                || back_matter && lettered)
+        {
+            section.kind = SectionKind::Appendix;
+        }
+        back_matter |= section.kind.is_back_matter();
+        open.push(section);
+    }
+    while !open.is_empty() {
+        close(&mut open, &mut roots);
+    }
+    roots
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::axum_server::api::pdf::{
+        fixture::{test_document, text_at},
+        layout::document_layout,
+    };
+
+    fn titles(headings: &[Heading]) -> Vec<(usize, &str)> {
+        headings
+            .iter()
+            .map(|h| (h.level, h.title.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn test_headings() {
+        let page_one = [
+            text_at(72.0, 740.0, 18.0, "A Study of Things"),
+            text_at(72.0, 700.0, 10.0, "Abstract. We study things in"),
+            text_at(72.0, 688.0, 10.0, "depth and breadth."),
+            text_at(72.0, 650.0, 12.0, "1 Introduction"),
+            text_at(72.0, 630.0, 10.0, "Things matter a great deal."),
+            text_at(72.0, 600.0, 11.0, "1.1 Scope"),
+            text_at(72.0, 580.0, 10.0, "We look at some things."),
+            text_at(72.0, 550.0, 12.0, "Related Work"),
+            text_at(72.0, 530.0, 10.0, "1 thing was studied before."),
+        ]
+        .concat();
+        let page_two = [
+            text_at(72.0, 700.0, 12.0, "References"),
+            text_at(72.0, 680.0, 10.0, "[1] A. Author. 2020. Things."),
+            text_at(72.0, 650.0, 12.0, "A Proofs"),
+            text_at(72.0, 630.0, 10.0, "Obvious."),
+        ]
+        .concat();
+        let pages = document_layout(&test_document(vec![page_one, page_two])).unwrap();
+        let body = body_size(&pages);
+        assert_eq!(body, 10.0);
+        assert_eq!(
+            document_title(&pages, body),
+            Some((0, "A Study of Things".to_string()))
+        );
+        let headings = headings(&pages, &[], body);
+        assert_eq!(
+            titles(&headings),
+            vec![
+                (1, "Abstract"),
+                (1, "1 Introduction"),
+                (2, "1.1 Scope"),
+                (1, "Related Work"),
+                (1, "References"),
+                (1, "A Proofs"),
+            ]
+        );
+        assert_eq!(
+            headings[0].rest.as_deref(),
+            Some("We study things in\ndepth and breadth.")
+        );
+
+        let sections = section_tree(headings.iter().map(Section::new).collect());
+        let kinds = sections.iter().map(|s| s.kind).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                SectionKind::Abstract,
+                SectionKind::Introduction,
+                SectionKind::RelatedWork,
+                SectionKind::References,
+                SectionKind::Appendix,
+            ]
+        );
+        assert_eq!(sections[1].children[0].title, "1.1 Scope");
+
+        // the outline wins, wrapped in an entry for the title
+        let item = |title: &str, level, page, top| OutlineItem {
+            title: title.to_string(),
+            level,
+            page: Some(page),
+            top,
+        };
+        let outline = [
+            item("A Study of Things", 1, 1, None),
+            item("Introduction", 2, 1, Some(662.0)),
+            item("Background", 2, 1, Some(560.0)),
+            item("Bibliography", 2, 2, Some(720.0)),
+        ];
+        let headings = super::headings(&pages, &outline, body);
+        assert_eq!(
+            titles(&headings),
+            vec![(1, "Introduction"), (1, "Background"), (1, "Bibliography")]
+        );
+        let blocks = headings
+            .iter()
+            .map(|h| (h.page, h.block, h.found))
+            .collect::<Vec<_>>();
+        // `Background` is not on the page: its section starts below the
+        // entry's top, at `Related Work`
+        assert_eq!(blocks, vec![(1, 2, true), (1, 6, false), (2, 0, false)]);
+    }
+
+    #[test]
+    fn test_outline_headings_uneven_levels() {
+        let page = [
+            text_at(72.0, 700.0, 12.0, "Method"),
+            text_at(72.0, 680.0, 10.0, "We do things."),
+            text_at(72.0, 650.0, 11.0, "Model"),
+            text_at(72.0, 630.0, 10.0, "A model of things."),
+            text_at(72.0, 600.0, 12.0, "References"),
+            text_at(72.0, 580.0, 10.0, "[1] A. Author. 2020. Things."),
+        ]
+        .concat();
+        let pages = document_layout(&test_document(vec![page])).unwrap();
+        let item = |title: &str, level, page: Option<u32>, top| OutlineItem {
+            title: title.to_string(),
+            level,
+            page,
+            top,
+        };
+        // the root points nowhere, and `References` sits above `Method`
+        let outline = [
+            item("A Study of Things", 1, None, None),
+            item("Method", 2, Some(1), Some(712.0)),
+            item("Model", 3, Some(1), Some(662.0)),
+            item("References", 1, Some(1), Some(612.0)),
+        ];
+        let headings = outline_headings(&pages, &outline).unwrap();
+        assert_eq!(
+            titles(&headings),
+            vec![(2, "Method"), (3, "Model"), (1, "References")]
+        );
+    }
+
+    #[test]
+    fn test_section_tree() {
+        let heading = |title: &str, level| Heading {
+            title: title.to_string(),
+            level,
+            page: 1,
+            block: 0,
+            found: true,
+            rest: None,
+        };
+        let sections = section_tree(
+            [
+                heading("1 Method", 1),
+                heading("1.1 Model", 2),
+                heading("1.1.1 Loss", 3),
+                heading("1.2 Training", 2),
+                heading("Appendix", 1),
+                heading("Proofs", 2),
+            ]
+            .iter()
+            .map(Section::new)
+            .collect(),
+        );
+        assert_eq!(sections.len(), 2);
+        assert_eq!(sections[0].children.len(), 2);
+        assert_eq!(sections[0].children[0].children[0].title, "1.1.1 Loss");
+        assert_eq!(sections[1].children[0].kind, SectionKind::Appendix);
+    }
+}
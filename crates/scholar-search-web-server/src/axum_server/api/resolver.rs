@@ -0,0 +1,233 @@
+//! Finds a paper's PDF among its open access sources.
+//!
+//! The S2 `openAccessPdf` url often leads to a publisher landing page, so
+//! the external ids give more candidates, and the `citation_pdf_url` of
+//! each landing page reached is queued behind them.
+
+use crate::axum_server::{
+    api::download::{pdf_download, DownloadConfig, DownloadError},
+    state::{
+        blob::{BlobRef, BlobStore},
+        source::{PdfSource, PdfSourceLog, SourceAttempt},
+    },
+};
+use crate::semantic_scholar_api::data::ExternalIds;
+
+use std::collections::VecDeque;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SourceCandidate {
+    pub source: PdfSource,
+    pub url: String,
+}
+
+impl SourceCandidate {
+    fn new(source: PdfSource, url: String) -> Self {
+        Self { source, url }
+    }
+}
+
+pub fn arxiv_pdf_url(arxiv_id: &str) -> String {
+    format!(
+        "https://arxiv.org/pdf/{}",
+        arxiv_id.trim().trim_start_matches("arXiv:")
+    )
+}
+
+/// S2 gives PMCIDs without the `PMC` prefix.
+pub fn pmc_pdf_url(pmcid: &str) -> String {
+    format!(
+        "https://www.ncbi.nlm.nih.gov/pmc/articles/PMC{}/pdf/",
+        pmcid.trim().trim_start_matches("PMC")
+    )
+}
+
+/// The DOI's resolver url, each part percent-encoded. Downloads ask for
+/// `application/pdf` first, which doi.org content negotiation passes on to
+/// agencies that can serve the PDF itself; a landing page is followed
+/// through its `citation_pdf_url`.
+pub fn doi_url(doi: &str) -> String {
+    let mut url = reqwest::Url::parse("https://doi.org/").unwrap();
+    url.path_segments_mut()
+        .unwrap()
+        .pop_if_empty()
+        .extend(doi.trim().split('/'));
+    url.to_string()
+}
+
+fn non_empty(id: &Option<String>) -> Option<&str> {
+    id.as_deref().map(str::trim).filter(|id| !id.is_empty())
+}
+
+/// The candidate sources of a paper, in the order they are tried.
+pub fn pdf_sources(
+    open_access_url: Option<&str>,
+    external_ids: Option<&ExternalIds>,
+) -> Vec<SourceCandidate> {
+    let mut candidates = vec![];
+    if let Some(url) = open_access_url.map(str::trim).filter(|u| !u.is_empty()) {
+        candidates.push(SourceCandidate::new(
+            PdfSource::OpenAccessPdf,
+            url.to_string(),
+        ));
+    }
+    if let Some(ids) = external_ids {
+        if let Some(arxiv) = non_empty(&ids.ar_xiv) {
+            candidates.push(SourceCandidate::new(PdfSource::ArXiv, arxiv_pdf_url(arxiv)));
+        }
+        if let Some(pmcid) = non_empty(&ids.pub_med_central) {
+            candidates.push(SourceCandidate::new(
+                PdfSource::PubMedCentral,
+                pmc_pdf_url(pmcid),
+            ));
+        }
+        if let Some(doi) = non_empty(&ids.doi) {
+            candidates.push(SourceCandidate::new(PdfSource::Doi, doi_url(doi)));
+        }
+    }
+    candidates
+}
+
+/// Tries the candidates in order until one gives a PDF, logging every
+/// attempt. A url is tried once even when several sources lead to it.
+pub async fn resolve_pdf(
+    config: &DownloadConfig,
+    blobs: &BlobStore,
+    paper_id: &str,
+    candidates: Vec<SourceCandidate>,
+) -> (Option<BlobRef>, PdfSourceLog) {
+    let mut log = PdfSourceLog::new(paper_id);
+    let mut queue = VecDeque::from(candidates);
+    while let Some(candidate) = queue.pop_front() {
+        if log.attempts.iter().any(|a| a.url == candidate.url) {
+            continue;
+        }
+        let result = pdf_download(config, blobs, &candidate.url).await;
+        log.attempts.push(SourceAttempt {
+            source: candidate.source,
+            url: candidate.url.to_owned(),
+            error: result.as_ref().err().map(|e| e.to_string()),
+        });
+        match result {
+            Ok(blob) => return (Some(blob), log),
+            Err(DownloadError::LandingPage {
+                pdf_url: Some(pdf_url),
+                ..
+            }) => queue.push_back(SourceCandidate::new(PdfSource::CitationPdfUrl, pdf_url)),
+            Err(_) => {}
+        }
+    }
+    (None, log)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::axum_server::fixture;
+    use axum::{http::StatusCode, response::IntoResponse, routing::get, Router};
+
+    #[test]
+    fn test_pdf_sources() {
+        let ids = ExternalIds {
+            ar_xiv: Some("1806.10282".to_string()),
+            doi: Some("10.1145/3292500.3330648".to_string()),
+            pub_med_central: Some("".to_string()),
+            ..ExternalIds::default()
+        };
+        assert_eq!(
+            pdf_sources(Some("https://oa.example/x.pdf"), Some(&ids)),
+            vec![
+                SourceCandidate::new(
+                    PdfSource::OpenAccessPdf,
+                    "https://oa.example/x.pdf".to_string()
+                ),
+                SourceCandidate::new(
+                    PdfSource::ArXiv,
+                    "https://arxiv.org/pdf/1806.10282".to_string()
+                ),
+                SourceCandidate::new(
+                    PdfSource::Doi,
+                    "https://doi.org/10.1145/3292500.3330648".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            pmc_pdf_url("PMC6123456"),
+            "https://www.ncbi.nlm.nih.gov/pmc/articles/PMC6123456/pdf/"
+        );
+        assert_eq!(
+            doi_url("10.1002/(SICI)1097-4636<305::AID>3.0.CO;2-#"),
+            "https://doi.org/10.1002/(SICI)1097-4636%3C305::AID%3E3.0.CO;2-%23"
+        );
+        assert!(pdf_sources(Some(" "), None).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_pdf() {
+        async fn landing() -> impl IntoResponse {
+            let meta = r#"<meta name="citation_pdf_url" content="/files/paper.pdf">"#;
+            (
+                [("content-type", "text/html")],
+                format!("<html><head>{}</head></html>", meta),
+            )
+        }
+        async fn pdf() -> impl IntoResponse {
+            ([("content-type", "application/pdf")], "%PDF-1.4\n%%EOF\n")
+        }
+        // a resolver that serves the PDF to clients asking for one
+        async fn negotiated(headers: axum::http::HeaderMap) -> axum::response::Response {
+            let accept = headers.get("accept").and_then(|v| v.to_str().ok());
+            match accept {
+                Some(accept) if accept.starts_with("application/pdf") => {
+                    pdf().await.into_response()
+                }
+                _ => ([("content-type", "text/html")], "<html></html>").into_response(),
+            }
+        }
+        let app = Router::new()
+            .route("/10.1000/182", get(negotiated))
+            .route("/missing.pdf", get(|| async { StatusCode::NOT_FOUND }))
+            .route("/landing", get(landing))
+            .route("/files/paper.pdf", get(pdf));
+        let base = fixture::serve(app).await;
+
+        let root = std::env::temp_dir().join(format!("scholar-resolve-{}", std::process::id()));
+        let blobs = BlobStore::new(&root, None);
+        let candidates = vec![
+            SourceCandidate::new(PdfSource::OpenAccessPdf, format!("{}/missing.pdf", base)),
+            SourceCandidate::new(PdfSource::ArXiv, format!("{}/missing.pdf", base)),
+            SourceCandidate::new(PdfSource::Doi, format!("{}/landing", base)),
+        ];
+        let (blob, log) = resolve_pdf(&DownloadConfig::default(), &blobs, "p1", candidates).await;
+        assert!(blob.is_some());
+        assert_eq!(
+            log.attempts
+                .iter()
+                .map(|a| (a.source, a.error.is_some()))
+                .collect::<Vec<(PdfSource, bool)>>(),
+            vec![
+                (PdfSource::OpenAccessPdf, true),
+                (PdfSource::Doi, true),
+                (PdfSource::CitationPdfUrl, false),
+            ]
+        );
+        assert_eq!(
+            log.succeeded().unwrap().url,
+            format!("{}/files/paper.pdf", base)
+        );
+        assert!(log.failures().starts_with("open access pdf: HTTP 404"));
+
+        let candidates = vec![SourceCandidate::new(
+            PdfSource::Doi,
+            doi_url("10.1000/182").replace("https://doi.org", &base),
+        )];
+        let (blob, log) = resolve_pdf(&DownloadConfig::default(), &blobs, "p3", candidates).await;
+        assert!(blob.is_some());
+        assert_eq!(log.attempts.len(), 1);
+
+        let (blob, log) = resolve_pdf(&DownloadConfig::default(), &blobs, "p2", vec![]).await;
+        assert!(blob.is_none());
+        assert_eq!(log.failures(), "no pdf source known");
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
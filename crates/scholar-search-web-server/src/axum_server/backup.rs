@@ -0,0 +1,125 @@
+use crate::axum_server::state::{
+    backup::{backup_to_dir, export_json, BackupInfo},
+    search::parse_schedule,
+    StateMach,
+};
+
+use axum::{
+    extract::State,
+    http::{
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+        StatusCode,
+    },
+    response::IntoResponse,
+    routing::{get, post},
+    Router,
+};
+use chrono::Utc;
+use cron::Schedule;
+use std::path::PathBuf;
+use tracing::{info, warn};
+
+/// Where and how often the server backs up its store, read from
+/// `SCHOLAR_SEARCH_BACKUP_*` variables.
+#[derive(Debug, Clone)]
+pub struct BackupConfig {
+    pub dir: PathBuf,
+    /// `None` when scheduled backups are off
+    pub schedule: Option<Schedule>,
+    pub keep: usize,
+}
+
+impl BackupConfig {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let var = |name: &str, default: &str| {
+            std::env::var(format!("SCHOLAR_SEARCH_BACKUP_{}", name)).unwrap_or(default.to_string())
+        };
+        let schedule = var("SCHEDULE", "0 3 * * *");
+        Ok(Self {
+            dir: PathBuf::from(var("DIR", "data/backups")),
+            schedule: match schedule.trim() {
+                "" | "off" => None,
+                schedule => Some(parse_schedule(schedule)?),
+            },
+            keep: var("KEEP", "7").parse()?,
+        })
+    }
+}
+
+/// Writes a backup off the async runtime and applies the retention.
+pub async fn run_backup(
+    state_mach: &StateMach,
+    config: &BackupConfig,
+) -> anyhow::Result<BackupInfo> {
+    let store = state_mach.store().clone();
+    let (dir, keep) = (config.dir.clone(), config.keep);
+    let info =
+        tokio::task::spawn_blocking(move || backup_to_dir(store.as_ref(), &dir, keep)).await??;
+    info!(
+        "backup {}: {} entries, sha256 {}",
+        info.path.display(),
+        info.entries,
+        info.sha256
+    );
+    Ok(info)
+}
+
+/// Background task that backs the store up on the configured schedule.
+pub fn spawn_backup_scheduler(state_mach: StateMach) {
+    let config = match BackupConfig::from_env() {
+        Ok(config) => config,
+        Err(e) => {
+            warn!("backups disabled: {}", e);
+            return;
+        }
+    };
+    let Some(schedule) = config.schedule.clone() else {
+        return;
+    };
+    tokio::spawn(async move {
+        for next in schedule.upcoming(Utc) {
+            let wait = (next - Utc::now()).to_std().unwrap_or_default();
+            tokio::time::sleep(wait).await;
+            if let Err(e) = run_backup(&state_mach, &config).await {
+                warn!("backup failed: {}", e);
+            }
+        }
+    });
+}
+
+/// Takes a backup now, into the configured directory.
+pub async fn api_backup(
+    State(state_mach): State<StateMach>,
+) -> Result<axum::Json<BackupInfo>, (StatusCode, String)> {
+    let config =
+        BackupConfig::from_env().map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    run_backup(&state_mach, &config)
+        .await
+        .map(axum::Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+/// Every entity as JSON lines, for moving data between installations.
+pub async fn api_export_jsonl(
+    State(state_mach): State<StateMach>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let mut body = vec![];
+    export_json(state_mach.store(), &mut body)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok((
+        [
+            (CONTENT_TYPE, "application/x-ndjson"),
+            (
+                CONTENT_DISPOSITION,
+                "attachment; filename=\"scholar-search.jsonl\"",
+            ),
+        ],
+        body,
+    ))
+}
+
+pub fn backup_router() -> Router<StateMach> {
+    Router::new()
+        .route("/api/backup", post(api_backup))
+        .route("/api/export.jsonl", get(api_export_jsonl))
+}
@@ -0,0 +1,194 @@
+use crate::axum_server::{
+    api::pdf::model::DocumentModel,
+    import::local_doi_match,
+    state::{
+        bibliography::{BibliographyState, LinkedReference, PaperBibliography},
+        blob::{BlobStore, PdfBlobState},
+        StateMach,
+    },
+    template::bibliography::BibliographyPanelTemplate,
+};
+use crate::citation::resolve::resolve_reference;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use chrono::Utc;
+use std::collections::BTreeSet;
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Papers whose references are being linked. Each run saves its own copy
+/// of the bibliography after every reference, so two at once would undo
+/// each other's progress.
+static LINKING: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// Holds the paper in `LINKING` until dropped.
+struct LinkGuard(String);
+
+impl LinkGuard {
+    /// `None` while another run links the paper.
+    fn acquire(paper_id: &str) -> Option<Self> {
+        let mut linking = LINKING.lock().unwrap_or_else(|e| e.into_inner());
+        linking
+            .insert(paper_id.to_string())
+            .then(|| Self(paper_id.to_string()))
+    }
+}
+
+impl Drop for LinkGuard {
+    fn drop(&mut self) {
+        LINKING
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.0);
+    }
+}
+
+/// Matches the references of a converted PDF against S2 one by one, saving
+/// after each so the list fills in progressively.
+pub async fn link_bibliography(state_mach: &StateMach, paper_id: &str, model: &DocumentModel) {
+    let mut bibliography = PaperBibliography {
+        paper_id: paper_id.to_string(),
+        sha256: model.sha256.to_owned(),
+        linked_at: Utc::now(),
+        references: model
+            .references
+            .iter()
+            .map(|r| LinkedReference {
+                label: r.label.to_owned(),
+                page: r.page,
+                text: r.text.to_owned(),
+                fields: r.fields.to_owned(),
+                candidate: None,
+                resolved: false,
+                citations: r.citations.to_owned(),
+            })
+            .collect(),
+    };
+    state_mach.set_bibliography(&bibliography);
+    for index in 0..bibliography.references.len() {
+        let reference = &bibliography.references[index];
+        let entry = reference
+            .fields
+            .entry(reference.label.as_deref().unwrap_or(&index.to_string()));
+        let candidate = match local_doi_match(state_mach, &entry) {
+            Some(candidate) => Some(candidate),
+            None => {
+                let candidate = resolve_reference(&entry).await;
+                // stay under the unauthenticated S2 rate limit
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                candidate
+            }
+        };
+        let reference = &mut bibliography.references[index];
+        reference.candidate = candidate;
+        reference.resolved = true;
+        bibliography.linked_at = Utc::now();
+        state_mach.set_bibliography(&bibliography);
+    }
+    info!(
+        "pdf_bibliography: {} {} of {} references linked",
+        paper_id,
+        bibliography.linked(),
+        bibliography.references.len()
+    );
+}
+
+/// Links the references in the background; returns false without starting
+/// when the paper is being linked already.
+pub fn spawn_link_bibliography(
+    state_mach: &StateMach,
+    paper_id: &str,
+    model: DocumentModel,
+) -> bool {
+    let Some(guard) = LinkGuard::acquire(paper_id) else {
+        warn!("pdf_bibliography: {} is being linked already", paper_id);
+        return false;
+    };
+    let state_mach = state_mach.clone();
+    let paper_id = paper_id.to_string();
+    tokio::spawn(async move {
+        link_bibliography(&state_mach, &paper_id, &model).await;
+        drop(guard);
+    });
+    true
+}
+
+pub async fn paper_bibliography(
+    State(state_mach): State<StateMach>,
+    Path(paper_id): Path<String>,
+) -> BibliographyPanelTemplate {
+    BibliographyPanelTemplate::new(&paper_id, state_mach.get_bibliography(&paper_id))
+}
+
+/// The references of the paper's PDF and the S2 papers they were matched to.
+pub async fn api_paper_bibliography(
+    State(state_mach): State<StateMach>,
+    Path(paper_id): Path<String>,
+) -> Result<Json<PaperBibliography>, (StatusCode, String)> {
+    state_mach.get_bibliography(&paper_id).map(Json).ok_or((
+        StatusCode::NOT_FOUND,
+        format!("{}: no bibliography", paper_id),
+    ))
+}
+
+/// Matches the references of the paper's converted PDF again, e.g. after a
+/// parser change; runs in the background. 409 while a link is running.
+pub async fn api_paper_bibliography_link(
+    State(state_mach): State<StateMach>,
+    Path(paper_id): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let internal = |e: anyhow::Error| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    let not_found = || {
+        (
+            StatusCode::NOT_FOUND,
+            format!("{}: not converted", paper_id),
+        )
+    };
+    let blob = state_mach.get_pdf_blob(&paper_id).ok_or_else(not_found)?;
+    let blobs = BlobStore::from_env().map_err(internal)?;
+    let model = tokio::task::spawn_blocking(move || DocumentModel::load(&blobs, &blob.sha256))
+        .await
+        .map_err(|e| internal(e.into()))?
+        .map_err(internal)?
+        .ok_or_else(not_found)?;
+    if !spawn_link_bibliography(&state_mach, &paper_id, model) {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("{}: references are being linked already", paper_id),
+        ));
+    }
+    Ok(StatusCode::ACCEPTED)
+}
+
+pub fn bibliography_router() -> Router<StateMach> {
+    Router::new()
+        .route("/x/paper/:paper_id/bibliography", get(paper_bibliography))
+        .route(
+            "/api/paper/:paper_id/bibliography",
+            get(api_paper_bibliography),
+        )
+        .route(
+            "/api/paper/:paper_id/bibliography/link",
+            post(api_paper_bibliography_link),
+        )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_link_guard() {
+        let guard = LinkGuard::acquire("p1").unwrap();
+        assert!(LinkGuard::acquire("p1").is_none());
+        assert!(LinkGuard::acquire("p2").is_some());
+        drop(guard);
+        assert!(LinkGuard::acquire("p1").is_some());
+    }
+}
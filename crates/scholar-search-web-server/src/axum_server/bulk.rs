@@ -0,0 +1,339 @@
+use crate::axum_server::{
+    api::resolver::pdf_sources,
+    export::{export_format, export_response},
+    files::download_file,
+    state::{
+        library::{Bookmark, LibraryState},
+        paper::PaperState,
+        PdfFileState, PdfFileStatus, StateMach,
+    },
+    template::bulk::{CommonPaperRowTemplate, CommonPapersTemplate},
+};
+use crate::semantic_scholar_api::{
+    critions::{fetch_citing, fetch_references, CitingRequest, CitingResponse},
+    data::PaperDetail,
+    paper_fetch::fetch_paper_batch,
+};
+
+use axum::{
+    extract::{RawForm, State},
+    http::StatusCode,
+    response::Response,
+    routing::post,
+    Router,
+};
+use query_map::QueryMap;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::{sync::Semaphore, task::JoinSet};
+use tracing::warn;
+
+/// Reads the repeated `paper_ids` field submitted by the bulk action bar.
+pub fn selected_paper_ids(form_set: &QueryMap) -> Vec<String> {
+    let mut paper_ids: Vec<String> = vec![];
+    for paper_id in form_set.all("paper_ids").unwrap_or_default() {
+        let paper_id = paper_id.trim();
+        if !paper_id.is_empty() && !paper_ids.iter().any(|p| p == paper_id) {
+            paper_ids.push(paper_id.to_string());
+        }
+    }
+    paper_ids
+}
+
+fn parse_form(form_set: &[u8]) -> QueryMap {
+    String::from_utf8_lossy(form_set)
+        .parse::<QueryMap>()
+        .unwrap()
+}
+
+/// Paper details for the selection, taken from the library where possible.
+pub async fn selected_papers(state_mach: &StateMach, paper_ids: &[String]) -> Vec<PaperDetail> {
+    let mut papers: Vec<PaperDetail> = vec![];
+    let mut missing: Vec<String> = vec![];
+    for paper_id in paper_ids {
+        match state_mach
+            .get_bookmark(paper_id)
+            .map(|bookmark| bookmark.paper)
+            .or_else(|| state_mach.get_cached_paper(paper_id))
+        {
+            Some(paper) => papers.push(paper),
+            None => missing.push(paper_id.to_owned()),
+        }
+    }
+    // the batch endpoint accepts at most 500 ids per call
+    for chunk in missing.chunks(500) {
+        match fetch_paper_batch(chunk.to_vec()).await {
+            Ok(batch) => {
+                for paper in batch.into_iter().flatten() {
+                    state_mach.cache_paper(&paper);
+                    papers.push(paper);
+                }
+            }
+            Err(e) => warn!("fetch_paper_batch error: {:?}", e),
+        }
+    }
+    papers
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct BulkActionResponse {
+    status: String,
+    count: usize,
+}
+
+pub async fn api_bulk_bookmark(
+    State(state_mach): State<StateMach>,
+    RawForm(form_set): RawForm,
+) -> axum::Json<BulkActionResponse> {
+    let paper_ids = selected_paper_ids(&parse_form(&form_set));
+    let papers = selected_papers(&state_mach, &paper_ids).await;
+    for paper in papers.iter() {
+        if state_mach.get_bookmark(&paper.paper_id).is_none() {
+            state_mach.set_bookmark(&Bookmark::new(paper.clone()));
+        }
+    }
+    axum::Json(BulkActionResponse {
+        status: "bookmarked".to_string(),
+        count: papers.len(),
+    })
+}
+
+pub async fn api_bulk_collection(
+    State(state_mach): State<StateMach>,
+    RawForm(form_set): RawForm,
+) -> axum::Json<BulkActionResponse> {
+    let form_set = parse_form(&form_set);
+    let collection_id = form_set.first("collection_id").unwrap_or_default();
+    if state_mach.get_collection(collection_id).is_none() {
+        return axum::Json(BulkActionResponse {
+            status: "unknown collection".to_string(),
+            count: 0,
+        });
+    }
+    let paper_ids = selected_paper_ids(&form_set);
+    let papers = selected_papers(&state_mach, &paper_ids).await;
+    for paper in papers.iter() {
+        let mut bookmark = state_mach
+            .get_bookmark(&paper.paper_id)
+            .unwrap_or_else(|| Bookmark::new(paper.clone()));
+        if !bookmark.collections.iter().any(|c| c == collection_id) {
+            bookmark.collections.push(collection_id.to_string());
+        }
+        state_mach.set_bookmark(&bookmark);
+    }
+    axum::Json(BulkActionResponse {
+        status: format!("added to {}", collection_id),
+        count: papers.len(),
+    })
+}
+
+pub async fn api_bulk_clone(
+    State(state_mach): State<StateMach>,
+    RawForm(form_set): RawForm,
+) -> axum::Json<BulkActionResponse> {
+    let paper_ids = selected_paper_ids(&parse_form(&form_set));
+    let jobs = selected_papers(&state_mach, &paper_ids)
+        .await
+        .into_iter()
+        .filter_map(|paper| {
+            let url = paper.open_access_pdf.and_then(|pdf| pdf.url);
+            let candidates = pdf_sources(url.as_deref(), paper.external_ids.as_ref());
+            (!candidates.is_empty()).then_some((
+                paper.paper_id,
+                url.unwrap_or_default(),
+                candidates,
+            ))
+        })
+        .filter(|(paper_id, ..)| state_mach.check_file_status(paper_id) == PdfFileStatus::None)
+        .collect::<Vec<_>>();
+    let count = jobs.len();
+
+    // downloads run one after another in the background so a large selection
+    // does not hammer the publishers
+    tokio::spawn(async move {
+        for (paper_id, url, candidates) in jobs {
+            let downloaded = match state_mach.accept_file(&paper_id, &url) {
+                Ok(_) => download_file(&state_mach, &paper_id, candidates)
+                    .await
+                    .map_err(|e| e.to_string()),
+                Err(e) => Err(e.to_string()),
+            };
+            if let Err(e) = downloaded {
+                warn!("{}: {}", paper_id, e);
+            }
+        }
+    });
+    axum::Json(BulkActionResponse {
+        status: "queued".to_string(),
+        count,
+    })
+}
+
+pub async fn api_bulk_export(
+    State(state_mach): State<StateMach>,
+    RawForm(form_set): RawForm,
+) -> Result<Response, (StatusCode, String)> {
+    let form_set = parse_form(&form_set);
+    let format = export_format(form_set.first("format"))?;
+    let paper_ids = selected_paper_ids(&form_set);
+    let papers = selected_papers(&state_mach, &paper_ids).await;
+    Ok(export_response(papers, format, "citations"))
+}
+
+/// Counts how many of the selected papers share each cited/citing paper.
+fn count_shared(
+    responses: Vec<CitingResponse>,
+    selected: &[String],
+) -> Vec<CommonPaperRowTemplate> {
+    let mut shared: HashMap<String, CommonPaperRowTemplate> = HashMap::new();
+    for response in responses {
+        for daum in response.data.unwrap_or_default() {
+            let Some(paper_id) = daum.citing_paper.paper_id else {
+                continue;
+            };
+            if selected.contains(&paper_id) {
+                continue;
+            }
+            shared
+                .entry(paper_id.to_owned())
+                .or_insert_with(|| CommonPaperRowTemplate {
+                    paper_id,
+                    title: daum.citing_paper.title,
+                    year: daum.citing_paper.year.unwrap_or_default(),
+                    shared_by: 0,
+                })
+                .shared_by += 1;
+        }
+    }
+    let mut rows = shared
+        .into_values()
+        .filter(|row| row.shared_by > 1)
+        .collect::<Vec<CommonPaperRowTemplate>>();
+    rows.sort_by(|a, b| b.shared_by.cmp(&a.shared_by).then(a.title.cmp(&b.title)));
+    rows
+}
+
+/// Most S2 reference/citation requests `bulk_common` runs at once.
+const COMMON_CONCURRENCY: usize = 4;
+/// Most selected papers `bulk_common` compares; the rest are left out.
+const COMMON_MAX_PAPERS: usize = 50;
+
+type CitingTasks = JoinSet<Result<CitingResponse, String>>;
+
+/// The responses of the tasks that succeeded; failures are logged.
+async fn join_responses(mut tasks: CitingTasks, what: &str) -> Vec<CitingResponse> {
+    let mut responses = vec![];
+    while let Some(result) = tasks.join_next().await {
+        match result {
+            Ok(Ok(response)) => responses.push(response),
+            Ok(Err(e)) => warn!("{} error: {}", what, e),
+            Err(e) => warn!("{} task failed: {}", what, e),
+        }
+    }
+    responses
+}
+
+pub async fn bulk_common(RawForm(form_set): RawForm) -> CommonPapersTemplate {
+    let mut paper_ids = selected_paper_ids(&parse_form(&form_set));
+    if paper_ids.len() > COMMON_MAX_PAPERS {
+        warn!(
+            "bulk_common: comparing the first {} of {} papers",
+            COMMON_MAX_PAPERS,
+            paper_ids.len()
+        );
+        paper_ids.truncate(COMMON_MAX_PAPERS);
+    }
+
+    let permits = Arc::new(Semaphore::new(COMMON_CONCURRENCY));
+    let mut references = JoinSet::new();
+    let mut citations = JoinSet::new();
+    for paper_id in paper_ids.iter() {
+        let request = CitingRequest {
+            paper_id: paper_id.to_owned(),
+            ..CitingRequest::default()
+        };
+        references.spawn({
+            let request = request.clone();
+            let permits = permits.clone();
+            async move {
+                let _permit = permits.acquire().await.map_err(|e| e.to_string())?;
+                fetch_references(request).await.map_err(|e| e.to_string())
+            }
+        });
+        let permits = permits.clone();
+        citations.spawn(async move {
+            let _permit = permits.acquire().await.map_err(|e| e.to_string())?;
+            fetch_citing(request).await.map_err(|e| e.to_string())
+        });
+    }
+
+    let reference_responses = join_responses(references, "fetch_references").await;
+    let citation_responses = join_responses(citations, "fetch_citing").await;
+
+    CommonPapersTemplate {
+        selected_count: paper_ids.len(),
+        references: count_shared(reference_responses, &paper_ids),
+        citations: count_shared(citation_responses, &paper_ids),
+    }
+}
+
+pub fn bulk_router() -> Router<StateMach> {
+    Router::new()
+        .route("/api/bulk/bookmark", post(api_bulk_bookmark))
+        .route("/api/bulk/collection", post(api_bulk_collection))
+        .route("/api/bulk/clone", post(api_bulk_clone))
+        .route("/api/bulk/export", post(api_bulk_export))
+        .route("/x/bulk/common", post(bulk_common))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::semantic_scholar_api::{critions::CitingDaum, data::Paper};
+
+    fn response(paper_ids: &[&str]) -> CitingResponse {
+        CitingResponse {
+            data: Some(
+                paper_ids
+                    .iter()
+                    .map(|paper_id| CitingDaum {
+                        citing_paper: Paper {
+                            paper_id: Some(paper_id.to_string()),
+                            title: paper_id.to_uppercase(),
+                            ..Paper::default()
+                        },
+                        ..CitingDaum::default()
+                    })
+                    .collect(),
+            ),
+            ..CitingResponse::default()
+        }
+    }
+
+    #[test]
+    fn test_count_shared() {
+        let selected = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let rows = count_shared(
+            vec![
+                response(&["x", "y", "b"]),
+                response(&["x", "z"]),
+                response(&["x", "y"]),
+            ],
+            &selected,
+        );
+        let counts = rows
+            .into_iter()
+            .map(|row| (row.paper_id, row.shared_by))
+            .collect::<Vec<(String, usize)>>();
+        assert_eq!(counts, vec![("x".to_string(), 3), ("y".to_string(), 2)]);
+    }
+
+    #[test]
+    fn test_selected_paper_ids() {
+        let form_set = "paper_ids=a&paper_ids=b&paper_ids=a&paper_ids=&collection_id=c"
+            .parse::<QueryMap>()
+            .unwrap();
+        assert_eq!(selected_paper_ids(&form_set), vec!["a", "b"]);
+    }
+}
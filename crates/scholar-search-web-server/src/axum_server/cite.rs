@@ -0,0 +1,113 @@
+use crate::axum_server::{
+    export::{collection_papers, paper_for_export},
+    state::StateMach,
+    template::cite::CitePanelTemplate,
+};
+use crate::citation::style::{format, list_styles, load_style, FormattedCitations};
+use crate::semantic_scholar_api::data::PaperDetail;
+
+use axum::{
+    extract::{Path, RawQuery, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use query_map::QueryMap;
+
+fn cite_query(raw_query: Option<String>) -> (String, String) {
+    let query = raw_query.unwrap_or_default().parse::<QueryMap>().unwrap();
+    let non_empty = |key: &str, default: &str| {
+        query
+            .first(key)
+            .filter(|v| !v.is_empty())
+            .unwrap_or(default)
+            .to_string()
+    };
+    (non_empty("style", "apa"), non_empty("format", "json"))
+}
+
+fn formatted(
+    papers: Vec<PaperDetail>,
+    style: &str,
+) -> Result<FormattedCitations, (StatusCode, String)> {
+    let style = load_style(style).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    Ok(format(papers, &style))
+}
+
+/// `format=text` and `format=html` return the bibliography alone, anything
+/// else the JSON with the in-text citations.
+fn cite_response(citations: FormattedCitations, format: &str) -> Response {
+    match format {
+        "text" => (
+            [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+            citations
+                .bibliography
+                .iter()
+                .map(|r| r.text.to_owned())
+                .collect::<Vec<String>>()
+                .join("\n"),
+        )
+            .into_response(),
+        "html" => (
+            [(header::CONTENT_TYPE, "text/html; charset=utf-8")],
+            format!(
+                "<div class=\"csl-bib-body\">\n{}\n</div>",
+                citations
+                    .bibliography
+                    .iter()
+                    .map(|r| format!("  <div class=\"csl-entry\">{}</div>", r.html))
+                    .collect::<Vec<String>>()
+                    .join("\n")
+            ),
+        )
+            .into_response(),
+        _ => axum::Json(citations).into_response(),
+    }
+}
+
+pub async fn api_paper_cite(
+    State(state_mach): State<StateMach>,
+    Path(paper_id): Path<String>,
+    RawQuery(raw_query): RawQuery,
+) -> Result<Response, (StatusCode, String)> {
+    let (style, format) = cite_query(raw_query);
+    let paper = paper_for_export(&state_mach, &paper_id).await?;
+    Ok(cite_response(formatted(vec![paper], &style)?, &format))
+}
+
+pub async fn api_collection_cite(
+    State(state_mach): State<StateMach>,
+    Path(collection_id): Path<String>,
+    RawQuery(raw_query): RawQuery,
+) -> Result<Response, (StatusCode, String)> {
+    let (style, format) = cite_query(raw_query);
+    let papers = collection_papers(&state_mach, &collection_id)?;
+    Ok(cite_response(formatted(papers, &style)?, &format))
+}
+
+pub async fn api_cite_styles() -> axum::Json<Vec<String>> {
+    axum::Json(list_styles())
+}
+
+pub async fn paper_cite(
+    State(state_mach): State<StateMach>,
+    Path(paper_id): Path<String>,
+    RawQuery(raw_query): RawQuery,
+) -> Result<CitePanelTemplate, (StatusCode, String)> {
+    let (style, _) = cite_query(raw_query);
+    let paper = paper_for_export(&state_mach, &paper_id).await?;
+    let citations = formatted(vec![paper], &style)?;
+    Ok(CitePanelTemplate::new(&paper_id, citations, list_styles()))
+}
+
+pub fn cite_router() -> Router<StateMach> {
+    Router::new()
+        .route("/x/paper/:paper_id/cite", get(paper_cite))
+        .route("/api/paper/:paper_id/cite", get(api_paper_cite))
+        .route(
+            "/api/library/collection/:collection_id/cite",
+            get(api_collection_cite),
+        )
+        .route("/api/cite/styles", get(api_cite_styles))
+}
@@ -0,0 +1,137 @@
+use crate::axum_server::state::{library::LibraryState, paper::PaperState, StateMach};
+use crate::citation::{export, ExportFormat};
+use crate::semantic_scholar_api::{data::PaperDetail, paper_fetch::fetch_paper_detail};
+
+use axum::{
+    extract::{Path, RawQuery, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use query_map::QueryMap;
+
+pub fn export_format(raw: Option<&str>) -> Result<ExportFormat, (StatusCode, String)> {
+    match raw.filter(|f| !f.is_empty()) {
+        Some(format) => format
+            .parse::<ExportFormat>()
+            .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string())),
+        None => Ok(ExportFormat::default()),
+    }
+}
+
+/// `name` with everything outside `[A-Za-z0-9._-]` replaced, so it can be
+/// quoted in a header.
+fn file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            'A'..='Z' | 'a'..='z' | '0'..='9' | '.' | '_' | '-' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+/// Serves the rendered papers as a file download named `{name}.{extension}`.
+pub fn export_response(papers: Vec<PaperDetail>, format: ExportFormat, name: &str) -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"{}.{}\"",
+                    file_name(name),
+                    format.extension()
+                ),
+            ),
+        ],
+        export(papers, format),
+    )
+        .into_response()
+}
+
+/// The library copy of a paper when bookmarked, otherwise a fresh S2 fetch;
+/// 404 when S2 does not know the paper, 502 when the fetch fails.
+pub async fn paper_for_export(
+    state_mach: &StateMach,
+    paper_id: &str,
+) -> Result<PaperDetail, (StatusCode, String)> {
+    if let Some(paper) = state_mach
+        .get_bookmark(paper_id)
+        .map(|bookmark| bookmark.paper)
+        .or_else(|| state_mach.get_cached_paper(paper_id))
+    {
+        return Ok(paper);
+    }
+    let paper = fetch_paper_detail(paper_id.to_owned()).await.map_err(|e| {
+        let status = e.downcast_ref::<reqwest::Error>().and_then(|e| e.status());
+        match status {
+            Some(reqwest::StatusCode::NOT_FOUND) => (
+                StatusCode::NOT_FOUND,
+                format!("{}: unknown paper", paper_id),
+            ),
+            _ => (StatusCode::BAD_GATEWAY, format!("{}: {}", paper_id, e)),
+        }
+    })?;
+    state_mach.cache_paper(&paper);
+    Ok(paper)
+}
+
+pub fn collection_papers(
+    state_mach: &StateMach,
+    collection_id: &str,
+) -> Result<Vec<PaperDetail>, (StatusCode, String)> {
+    if state_mach.get_collection(collection_id).is_none() {
+        return Err((StatusCode::NOT_FOUND, "unknown collection".to_string()));
+    }
+    Ok(state_mach
+        .list_collection_bookmarks(collection_id)
+        .into_iter()
+        .map(|b| b.paper)
+        .collect::<Vec<PaperDetail>>())
+}
+
+pub async fn api_paper_export(
+    State(state_mach): State<StateMach>,
+    Path(paper_id): Path<String>,
+    RawQuery(raw_query): RawQuery,
+) -> Result<Response, (StatusCode, String)> {
+    let query = raw_query.unwrap_or_default().parse::<QueryMap>().unwrap();
+    let format = export_format(query.first("format"))?;
+    let paper = paper_for_export(&state_mach, &paper_id).await?;
+    Ok(export_response(vec![paper], format, &paper_id))
+}
+
+pub async fn api_collection_export(
+    State(state_mach): State<StateMach>,
+    Path(collection_id): Path<String>,
+    RawQuery(raw_query): RawQuery,
+) -> Result<Response, (StatusCode, String)> {
+    let query = raw_query.unwrap_or_default().parse::<QueryMap>().unwrap();
+    let format = export_format(query.first("format"))?;
+    let papers = collection_papers(&state_mach, &collection_id)?;
+    Ok(export_response(papers, format, &collection_id))
+}
+
+pub fn export_router() -> Router<StateMach> {
+    Router::new()
+        .route("/api/paper/:paper_id/export", get(api_paper_export))
+        .route(
+            "/api/library/collection/:collection_id/export",
+            get(api_collection_export),
+        )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_export_response_file_name() {
+        let response = export_response(vec![], ExportFormat::default(), "DOI:10.1/a\"b;c");
+        let disposition = response.headers()[header::CONTENT_DISPOSITION]
+            .to_str()
+            .unwrap();
+        assert!(disposition.starts_with("attachment; filename=\"DOI_10.1_a_b_c."));
+    }
+}
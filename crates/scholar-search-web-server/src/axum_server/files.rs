@@ -0,0 +1,282 @@
+use crate::axum_server::{
+    api::{
+        download::DownloadConfig,
+        pdf::{convert_pdf_to_text, model::DocumentModel},
+        resolver::{pdf_sources, resolve_pdf, SourceCandidate},
+    },
+    bibliography::spawn_link_bibliography,
+    bulk::selected_papers,
+    state::{
+        blob::{BlobStore, GcReport, PdfBlobState, GC_GRACE},
+        source::PdfSourceState,
+        worker_id, ClaimError, PdfFile, PdfFileState, PdfFileStatus, PdfStage, StateMach,
+    },
+    template::files::{FileHistoryTemplate, FileRowTemplate, FilesPageTemplate},
+};
+
+use axum::{
+    extract::{Form, Path, RawQuery, State},
+    http::StatusCode,
+    routing::{get, post},
+    Router,
+};
+use chrono::Duration;
+use query_map::QueryMap;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+/// How long a download may run before another worker can take it over.
+const DOWNLOAD_LEASE_MINUTES: i64 = 10;
+/// How long a conversion may run before another worker can take it over.
+const CONVERT_LEASE_MINUTES: i64 = 10;
+
+/// The PDF sources of a paper: `url`, the S2 open access url kept with its
+/// file, and those given by its external ids.
+pub async fn paper_pdf_sources(
+    state_mach: &StateMach,
+    paper_id: &str,
+    url: &str,
+) -> Vec<SourceCandidate> {
+    let paper = selected_papers(state_mach, &[paper_id.to_string()])
+        .await
+        .pop();
+    pdf_sources(
+        Some(url),
+        paper.as_ref().and_then(|p| p.external_ids.as_ref()),
+    )
+}
+
+/// Claims an accepted paper's download, tries its sources in order and
+/// records the outcome; a downloaded paper is then converted in the
+/// background. Fails without downloading when another worker holds the
+/// claim or the paper is not waiting for a download.
+pub async fn download_file(
+    state_mach: &StateMach,
+    paper_id: &str,
+    candidates: Vec<SourceCandidate>,
+) -> Result<PdfFile, ClaimError> {
+    let owner = worker_id();
+    state_mach.claim_file(
+        paper_id,
+        PdfStage::Download,
+        &owner,
+        Duration::minutes(DOWNLOAD_LEASE_MINUTES),
+    )?;
+    let (result, blob) = match (DownloadConfig::from_env(), BlobStore::from_env()) {
+        (Ok(config), Ok(blobs)) => {
+            let (blob, log) = resolve_pdf(&config, &blobs, paper_id, candidates).await;
+            state_mach.set_pdf_sources(&log);
+            match blob {
+                Some(blob) => {
+                    let attempt = log.succeeded().unwrap();
+                    info!(
+                        "pdf_download success: {} from {} {} sha256 {}",
+                        paper_id, attempt.source, attempt.url, blob.sha256
+                    );
+                    (Ok(()), Some(blob))
+                }
+                None => {
+                    warn!("pdf_download error: {} {}", paper_id, log.failures());
+                    (Err(log.failures()), None)
+                }
+            }
+        }
+        (Err(e), _) | (_, Err(e)) => (Err(e.to_string()), None),
+    };
+    match state_mach.finish_claim(paper_id, &owner, result) {
+        Ok(file) => {
+            // only a worker that still held the lease points the paper at its PDF
+            if let Some(blob) = blob {
+                state_mach.set_pdf_blob(paper_id, &blob);
+            }
+            if file.status == PdfFileStatus::Downloaded {
+                spawn_convert(state_mach, paper_id);
+            }
+            Ok(file)
+        }
+        Err(e) => {
+            // the lease ran out and another worker took over
+            warn!("{}", e);
+            Ok(state_mach.get_file(paper_id).unwrap())
+        }
+    }
+}
+
+/// Claims a downloaded paper's conversion into a document model and records
+/// the outcome; the references of a converted paper are then linked in the
+/// background.
+pub async fn convert_file(state_mach: &StateMach, paper_id: &str) -> Result<PdfFile, ClaimError> {
+    let owner = worker_id();
+    state_mach.claim_file(
+        paper_id,
+        PdfStage::Convert,
+        &owner,
+        Duration::minutes(CONVERT_LEASE_MINUTES),
+    )?;
+    let (result, model) = match convert_pdf_to_text(state_mach, paper_id).await {
+        Ok(model) => (Ok(()), Some(model)),
+        Err(e) => {
+            warn!("pdf_convert error: {} {}", paper_id, e);
+            (Err(e.to_string()), None)
+        }
+    };
+    match state_mach.finish_claim(paper_id, &owner, result) {
+        Ok(file) => {
+            if let Some(model) = model {
+                spawn_link_bibliography(state_mach, paper_id, model);
+            }
+            Ok(file)
+        }
+        Err(e) => {
+            warn!("{}", e);
+            Ok(state_mach.get_file(paper_id).unwrap())
+        }
+    }
+}
+
+fn spawn_convert(state_mach: &StateMach, paper_id: &str) {
+    let state_mach = state_mach.clone();
+    let paper_id = paper_id.to_string();
+    tokio::spawn(async move {
+        if let Err(e) = convert_file(&state_mach, &paper_id).await {
+            warn!("{}: {}", paper_id, e);
+        }
+    });
+}
+
+fn files_page(state_mach: &StateMach, status: &str) -> FilesPageTemplate {
+    FilesPageTemplate {
+        status: status.to_string(),
+        counts: PdfFileStatus::NAMES
+            .iter()
+            .map(|name| {
+                (
+                    name.to_string(),
+                    state_mach.list_files_by_status(name).len(),
+                )
+            })
+            .collect(),
+        rows: state_mach
+            .list_files_by_status(status)
+            .into_iter()
+            .map(FileRowTemplate::from)
+            .collect(),
+    }
+}
+
+/// Papers in the PDF pipeline with the given `status`, failures by default.
+pub async fn files_list(
+    State(state_mach): State<StateMach>,
+    RawQuery(raw_query): RawQuery,
+) -> FilesPageTemplate {
+    let query = raw_query.unwrap_or_default().parse::<QueryMap>().unwrap();
+    let status = query
+        .first("status")
+        .filter(|s| PdfFileStatus::NAMES.contains(s))
+        .unwrap_or("failed");
+    files_page(&state_mach, status)
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct FileRequest {
+    paper_id: String,
+}
+
+pub async fn file_history(
+    State(state_mach): State<StateMach>,
+    RawQuery(raw_query): RawQuery,
+) -> FileHistoryTemplate {
+    let query = raw_query.unwrap_or_default().parse::<QueryMap>().unwrap();
+    let paper_id = query.first("paper_id").unwrap_or_default();
+    FileHistoryTemplate::new(
+        paper_id,
+        state_mach.list_file_history(paper_id),
+        state_mach.get_pdf_sources(paper_id),
+    )
+}
+
+/// Sends a failed paper back to the failed stage; failed downloads and
+/// conversions are started again right away.
+pub async fn file_retry(
+    State(state_mach): State<StateMach>,
+    Form(payload): Form<FileRequest>,
+) -> Result<FilesPageTemplate, (StatusCode, String)> {
+    let file = state_mach
+        .retry_file(&payload.paper_id)
+        .map_err(|e| (StatusCode::CONFLICT, e.to_string()))?;
+    if file.status == PdfFileStatus::Accpeted {
+        let state_mach = state_mach.clone();
+        tokio::spawn(async move {
+            let url = file.url.unwrap_or_default();
+            let candidates = paper_pdf_sources(&state_mach, &file.paper_id, &url).await;
+            if let Err(e) = download_file(&state_mach, &file.paper_id, candidates).await {
+                warn!("{}: {}", file.paper_id, e);
+            }
+        });
+    } else if file.status == PdfFileStatus::Downloaded {
+        spawn_convert(&state_mach, &file.paper_id);
+    }
+    Ok(files_page(&state_mach, "failed"))
+}
+
+pub async fn file_reset(
+    State(state_mach): State<StateMach>,
+    Form(payload): Form<FileRequest>,
+) -> Result<FilesPageTemplate, (StatusCode, String)> {
+    let file = state_mach
+        .get_file(&payload.paper_id)
+        .ok_or((StatusCode::NOT_FOUND, "unknown paper".to_string()))?;
+    state_mach
+        .reset_file(&payload.paper_id)
+        .map_err(|e| (StatusCode::CONFLICT, e.to_string()))?;
+    Ok(files_page(&state_mach, &file.status.to_string()))
+}
+
+/// Deletes stored PDFs no paper points at.
+pub async fn api_blob_gc(
+    State(state_mach): State<StateMach>,
+) -> Result<axum::Json<GcReport>, (StatusCode, String)> {
+    let internal = |e: anyhow::Error| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    let blobs = BlobStore::from_env().map_err(internal)?;
+    let referenced = state_mach.referenced_blobs();
+    let report = tokio::task::spawn_blocking(move || blobs.gc(&referenced, GC_GRACE, false))
+        .await
+        .map_err(|e| internal(e.into()))?
+        .map_err(internal)?;
+    info!(
+        "blob gc: removed {} files, {} bytes",
+        report.removed, report.freed
+    );
+    Ok(axum::Json(report))
+}
+
+/// The document model of the paper's converted PDF.
+pub async fn api_paper_document(
+    State(state_mach): State<StateMach>,
+    Path(paper_id): Path<String>,
+) -> Result<axum::Json<DocumentModel>, (StatusCode, String)> {
+    let internal = |e: anyhow::Error| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    let not_found = || {
+        (
+            StatusCode::NOT_FOUND,
+            format!("{}: not converted", paper_id),
+        )
+    };
+    let blob = state_mach.get_pdf_blob(&paper_id).ok_or_else(not_found)?;
+    let blobs = BlobStore::from_env().map_err(internal)?;
+    let model = tokio::task::spawn_blocking(move || DocumentModel::load(&blobs, &blob.sha256))
+        .await
+        .map_err(|e| internal(e.into()))?
+        .map_err(internal)?;
+    model.map(axum::Json).ok_or_else(not_found)
+}
+
+pub fn files_router() -> Router<StateMach> {
+    Router::new()
+        .route("/x/files", get(files_list))
+        .route("/x/files/history", get(file_history))
+        .route("/x/files/retry", post(file_retry))
+        .route("/x/files/reset", post(file_reset))
+        .route("/api/blobs/gc", post(api_blob_gc))
+        .route("/api/paper/:paper_id/document", get(api_paper_document))
+}
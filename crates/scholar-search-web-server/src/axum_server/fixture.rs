@@ -0,0 +1,12 @@
+//! A local HTTP server for the tests of code that fetches or posts urls.
+
+use axum::Router;
+
+/// Serves the router on a free local port until the test ends; returns its
+/// base url, e.g. `http://127.0.0.1:41234`.
+pub async fn serve(app: Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}", addr)
+}
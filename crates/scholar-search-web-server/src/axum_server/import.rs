@@ -0,0 +1,296 @@
+use crate::axum_server::{
+    bulk::selected_papers,
+    state::{
+        import::{ImportBatch, ImportEntryStatus, ImportState},
+        library::{Bookmark, LibraryState},
+        paper::PaperState,
+        StateMach,
+    },
+    template::{
+        import::{ImportPageTemplate, ImportReviewTemplate, ImportSummaryTemplate},
+        library::CollectionTreeItem,
+    },
+};
+use crate::citation::{
+    import::{parse, ImportFormat, ImportedEntry},
+    resolve::{resolve, MatchCandidate},
+};
+
+use axum::{
+    extract::{Form, Multipart, Path, State},
+    http::StatusCode,
+    routing::{get, post},
+    Router,
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+/// Parses an uploaded bibliography into a new, unresolved import batch.
+pub fn create_import(
+    state_mach: &StateMach,
+    file_name: &str,
+    content: &str,
+    collection_id: Option<String>,
+) -> Result<ImportBatch, (StatusCode, String)> {
+    let format = ImportFormat::detect(file_name, content).ok_or((
+        StatusCode::BAD_REQUEST,
+        "expected a .bib or .ris file".to_string(),
+    ))?;
+    let entries = parse(content, format);
+    if entries.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("no entries found in {}", file_name),
+        ));
+    }
+    let collection_id = collection_id.filter(|c| state_mach.get_collection(c).is_some());
+    let batch = ImportBatch::new(file_name, format, collection_id, entries);
+    state_mach.set_import(&batch);
+    Ok(batch)
+}
+
+/// Resolves the pending entries one by one, saving after each so the review
+/// page fills in progressively and reviewer choices are not overwritten.
+pub async fn resolve_import(state_mach: &StateMach, import_id: &str) {
+    let Some(batch) = state_mach.get_import(import_id) else {
+        return;
+    };
+    let pending = batch
+        .entries
+        .into_iter()
+        .filter(|e| e.status == ImportEntryStatus::Pending)
+        .collect::<Vec<_>>();
+    for pending_entry in pending {
+        let candidates = match local_doi_match(state_mach, &pending_entry.entry) {
+            Some(candidate) => vec![candidate],
+            None => resolve(&pending_entry.entry).await,
+        };
+        let Some(mut batch) = state_mach.get_import(import_id) else {
+            return;
+        };
+        if let Some(entry) = batch.entries.get_mut(pending_entry.index) {
+            entry.set_candidates(candidates);
+        }
+        state_mach.set_import(&batch);
+    }
+    info!("import resolved: {}", import_id);
+}
+
+/// A library or cached paper with the entry's DOI, found without the API.
+pub fn local_doi_match(state_mach: &StateMach, entry: &ImportedEntry) -> Option<MatchCandidate> {
+    let doi = entry.doi.as_ref()?;
+    let paper = match state_mach.find_bookmarks_by_doi(doi).into_iter().next() {
+        Some(bookmark) => bookmark.paper,
+        None => state_mach.find_cached_paper_by_doi(doi)?,
+    };
+    Some(MatchCandidate::from(&paper))
+}
+
+/// Bookmarks every matched or accepted entry, adding it to the batch's
+/// collection, and returns how many entries were imported.
+pub async fn commit_import(state_mach: &StateMach, import_id: &str) -> usize {
+    let Some(mut batch) = state_mach.get_import(import_id) else {
+        return 0;
+    };
+    let mut paper_ids = batch
+        .entries
+        .iter()
+        .filter(|e| e.is_ready())
+        .filter_map(|e| e.selected.to_owned())
+        .collect::<Vec<String>>();
+    paper_ids.sort();
+    paper_ids.dedup();
+    let papers = selected_papers(state_mach, &paper_ids).await;
+
+    let mut count = 0;
+    for entry in batch.entries.iter_mut().filter(|e| e.is_ready()) {
+        let Some(paper) = papers
+            .iter()
+            .find(|p| Some(&p.paper_id) == entry.selected.as_ref())
+        else {
+            continue;
+        };
+        let mut bookmark = state_mach
+            .get_bookmark(&paper.paper_id)
+            .unwrap_or_else(|| Bookmark::new(paper.clone()));
+        if let Some(collection_id) = &batch.collection_id {
+            if !bookmark.collections.contains(collection_id) {
+                bookmark.collections.push(collection_id.to_owned());
+            }
+        }
+        state_mach.set_bookmark(&bookmark);
+        entry.status = ImportEntryStatus::Imported;
+        count += 1;
+    }
+    state_mach.set_import(&batch);
+    count
+}
+
+struct ImportUpload {
+    file_name: String,
+    content: String,
+    collection_id: Option<String>,
+}
+
+async fn read_upload(mut multipart: Multipart) -> Result<ImportUpload, (StatusCode, String)> {
+    let mut upload = ImportUpload {
+        file_name: "".to_string(),
+        content: "".to_string(),
+        collection_id: None,
+    };
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?
+    {
+        match field.name() {
+            Some("file") => {
+                upload.file_name = field.file_name().unwrap_or("upload").to_string();
+                let bytes = field
+                    .bytes()
+                    .await
+                    .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+                upload.content = String::from_utf8_lossy(&bytes).to_string();
+            }
+            Some("collection_id") => {
+                let value = field.text().await.unwrap_or_default();
+                upload.collection_id = Some(value).filter(|v| !v.is_empty());
+            }
+            _ => {}
+        }
+    }
+    if upload.content.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "missing file".to_string()));
+    }
+    Ok(upload)
+}
+
+async fn start_import(
+    state_mach: StateMach,
+    multipart: Multipart,
+) -> Result<ImportBatch, (StatusCode, String)> {
+    let upload = read_upload(multipart).await?;
+    let batch = create_import(
+        &state_mach,
+        &upload.file_name,
+        &upload.content,
+        upload.collection_id,
+    )?;
+    let import_id = batch.import_id.to_owned();
+    tokio::spawn(async move { resolve_import(&state_mach, &import_id).await });
+    Ok(batch)
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ImportResponse {
+    import_id: String,
+    count: usize,
+}
+
+pub async fn api_import(
+    State(state_mach): State<StateMach>,
+    multipart: Multipart,
+) -> Result<axum::Json<ImportResponse>, (StatusCode, String)> {
+    let batch = start_import(state_mach, multipart).await?;
+    Ok(axum::Json(ImportResponse {
+        import_id: batch.import_id,
+        count: batch.entries.len(),
+    }))
+}
+
+pub async fn import_page(State(state_mach): State<StateMach>) -> ImportPageTemplate {
+    ImportPageTemplate {
+        collections: CollectionTreeItem::from_collections(state_mach.list_collections()),
+        imports: state_mach
+            .list_imports()
+            .into_iter()
+            .map(ImportSummaryTemplate::from)
+            .collect(),
+    }
+}
+
+pub async fn import_upload(
+    State(state_mach): State<StateMach>,
+    multipart: Multipart,
+) -> Result<ImportReviewTemplate, (StatusCode, String)> {
+    Ok(start_import(state_mach, multipart).await?.into())
+}
+
+pub async fn import_review(
+    State(state_mach): State<StateMach>,
+    Path(import_id): Path<String>,
+) -> Result<ImportReviewTemplate, (StatusCode, String)> {
+    state_mach
+        .get_import(&import_id)
+        .map(ImportReviewTemplate::from)
+        .ok_or((StatusCode::NOT_FOUND, "unknown import".to_string()))
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ImportChoiceRequest {
+    /// a candidate paper id, or `reject`
+    choice: String,
+}
+
+pub async fn import_entry_choose(
+    State(state_mach): State<StateMach>,
+    Path((import_id, index)): Path<(String, usize)>,
+    Form(payload): Form<ImportChoiceRequest>,
+) -> Result<ImportReviewTemplate, (StatusCode, String)> {
+    if let Some(mut batch) = state_mach.get_import(&import_id) {
+        if let Some(entry) = batch
+            .entries
+            .get_mut(index)
+            .filter(|e| e.status != ImportEntryStatus::Imported)
+        {
+            if payload.choice == "reject" {
+                entry.status = ImportEntryStatus::Rejected;
+                entry.selected = None;
+            } else if entry
+                .candidates
+                .iter()
+                .any(|c| c.paper_id == payload.choice)
+            {
+                entry.status = ImportEntryStatus::Accepted;
+                entry.selected = Some(payload.choice);
+            }
+            state_mach.set_import(&batch);
+        }
+    }
+    import_review(State(state_mach), Path(import_id)).await
+}
+
+pub async fn import_commit(
+    State(state_mach): State<StateMach>,
+    Path(import_id): Path<String>,
+) -> Result<ImportReviewTemplate, (StatusCode, String)> {
+    let count = commit_import(&state_mach, &import_id).await;
+    info!("import committed: {} {} papers", import_id, count);
+    import_review(State(state_mach), Path(import_id)).await
+}
+
+/// Restarts resolution of a batch left pending, e.g. after a server restart.
+pub async fn import_resolve(
+    State(state_mach): State<StateMach>,
+    Path(import_id): Path<String>,
+) -> Result<ImportReviewTemplate, (StatusCode, String)> {
+    tokio::spawn({
+        let state_mach = state_mach.clone();
+        let import_id = import_id.to_owned();
+        async move { resolve_import(&state_mach, &import_id).await }
+    });
+    import_review(State(state_mach), Path(import_id)).await
+}
+
+pub fn import_router() -> Router<StateMach> {
+    Router::new()
+        .route("/api/import", post(api_import))
+        .route("/x/import", get(import_page).post(import_upload))
+        .route("/x/import/:import_id", get(import_review))
+        .route(
+            "/x/import/:import_id/entry/:index",
+            post(import_entry_choose),
+        )
+        .route("/x/import/:import_id/commit", post(import_commit))
+        .route("/x/import/:import_id/resolve", post(import_resolve))
+}
@@ -0,0 +1,183 @@
+use crate::axum_server::{
+    state::{
+        library::{
+            parse_tags, Bookmark, CollectionError, LibraryFilter, LibrarySort, LibraryState,
+        },
+        StateMach,
+    },
+    template::library::{
+        BookmarkPanelTemplate, CollectionTreeItem, LibraryCollectionsTemplate,
+        LibraryPageTemplate, LibraryRowTemplate, LibraryRowsTemplate,
+    },
+};
+use crate::citation::style::list_styles;
+use crate::semantic_scholar_api::paper_fetch::fetch_paper_detail;
+
+use axum::{
+    extract::{Form, Path, RawForm, RawQuery, State},
+    http::StatusCode,
+    routing::{get, post},
+    Router,
+};
+use query_map::QueryMap;
+use serde::{Deserialize, Serialize};
+
+fn library_filter(raw_query: Option<String>) -> LibraryFilter {
+    let query = raw_query.unwrap_or_default().parse::<QueryMap>().unwrap();
+    let non_empty = |key: &str| {
+        query
+            .first(key)
+            .filter(|v| !v.is_empty())
+            .map(|v| v.to_string())
+    };
+    LibraryFilter {
+        collection: non_empty("collection"),
+        tag: non_empty("tag"),
+        query: non_empty("query"),
+        sort: non_empty("sort")
+            .and_then(|s| s.parse::<LibrarySort>().ok())
+            .unwrap_or_default(),
+        ascending: non_empty("order").as_deref() == Some("asc"),
+    }
+}
+
+pub async fn library_page(
+    State(state_mach): State<StateMach>,
+    RawQuery(raw_query): RawQuery,
+) -> LibraryPageTemplate {
+    let filter = library_filter(raw_query);
+    LibraryPageTemplate {
+        sort: filter.sort.to_string(),
+        collections: CollectionTreeItem::from_collections(state_mach.list_collections()),
+        styles: list_styles(),
+        tags: state_mach.list_tags(),
+        rows: filter
+            .apply(state_mach.list_bookmarks())
+            .into_iter()
+            .map(LibraryRowTemplate::from)
+            .collect::<Vec<LibraryRowTemplate>>(),
+        filter,
+    }
+}
+
+pub async fn library_bookmarks(
+    State(state_mach): State<StateMach>,
+    RawQuery(raw_query): RawQuery,
+) -> LibraryRowsTemplate {
+    let filter = library_filter(raw_query);
+    LibraryRowsTemplate {
+        rows: filter
+            .apply(state_mach.list_bookmarks())
+            .into_iter()
+            .map(LibraryRowTemplate::from)
+            .collect::<Vec<LibraryRowTemplate>>(),
+    }
+}
+
+pub async fn library_paper(
+    State(state_mach): State<StateMach>,
+    Path(paper_id): Path<String>,
+) -> BookmarkPanelTemplate {
+    BookmarkPanelTemplate::new(
+        &paper_id,
+        state_mach.get_bookmark(&paper_id),
+        state_mach.list_collections(),
+    )
+}
+
+pub async fn library_paper_add(
+    State(state_mach): State<StateMach>,
+    Path(paper_id): Path<String>,
+) -> BookmarkPanelTemplate {
+    if state_mach.get_bookmark(&paper_id).is_none() {
+        let paper = fetch_paper_detail(paper_id.to_owned()).await.unwrap();
+        state_mach.set_bookmark(&Bookmark::new(paper));
+    }
+    library_paper(State(state_mach), Path(paper_id)).await
+}
+
+pub async fn library_paper_update(
+    State(state_mach): State<StateMach>,
+    Path(paper_id): Path<String>,
+    RawForm(form_set): RawForm,
+) -> BookmarkPanelTemplate {
+    // `collections` is a repeated checkbox field, which `Form` can not collect
+    let form_set = String::from_utf8_lossy(&form_set)
+        .parse::<QueryMap>()
+        .unwrap();
+    if let Some(mut bookmark) = state_mach.get_bookmark(&paper_id) {
+        bookmark.tags = parse_tags(form_set.first("tags").unwrap_or_default());
+        bookmark.note = form_set.first("note").unwrap_or_default().to_string();
+        bookmark.collections = form_set
+            .all("collections")
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|c| state_mach.get_collection(c).is_some())
+            .map(|c| c.to_string())
+            .collect();
+        state_mach.set_bookmark(&bookmark);
+    }
+    library_paper(State(state_mach), Path(paper_id)).await
+}
+
+pub async fn library_paper_remove(
+    State(state_mach): State<StateMach>,
+    Path(paper_id): Path<String>,
+) -> BookmarkPanelTemplate {
+    state_mach.remove_bookmark(&paper_id);
+    library_paper(State(state_mach), Path(paper_id)).await
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct CollectionCreateRequest {
+    name: String,
+    parent_id: String,
+}
+
+pub async fn library_collection_create(
+    State(state_mach): State<StateMach>,
+    Form(payload): Form<CollectionCreateRequest>,
+) -> Result<LibraryCollectionsTemplate, (StatusCode, String)> {
+    let parent_id = Some(payload.parent_id)
+        .filter(|p| !p.is_empty() && state_mach.get_collection(p).is_some());
+    state_mach
+        .create_collection(&payload.name, parent_id)
+        .map_err(|e| match e {
+            CollectionError::EmptyName => (StatusCode::BAD_REQUEST, e.to_string()),
+            CollectionError::Exists(_) => (StatusCode::CONFLICT, e.to_string()),
+        })?;
+    Ok(LibraryCollectionsTemplate {
+        collections: CollectionTreeItem::from_collections(state_mach.list_collections()),
+        styles: list_styles(),
+    })
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct CollectionRemoveRequest {
+    collection_id: String,
+}
+
+pub async fn library_collection_remove(
+    State(state_mach): State<StateMach>,
+    Form(payload): Form<CollectionRemoveRequest>,
+) -> LibraryCollectionsTemplate {
+    state_mach.remove_collection(&payload.collection_id);
+    LibraryCollectionsTemplate {
+        collections: CollectionTreeItem::from_collections(state_mach.list_collections()),
+        styles: list_styles(),
+    }
+}
+
+pub fn library_router() -> Router<StateMach> {
+    Router::new()
+        .route("/x/library", get(library_page))
+        .route("/x/library/bookmarks", get(library_bookmarks))
+        .route(
+            "/x/library/paper/:paper_id",
+            get(library_paper).post(library_paper_update),
+        )
+        .route("/x/library/paper/:paper_id/add", post(library_paper_add))
+        .route("/x/library/paper/:paper_id/remove", post(library_paper_remove))
+        .route("/x/library/collection", post(library_collection_create))
+        .route("/x/library/collection/remove", post(library_collection_remove))
+}
@@ -1,14 +1,42 @@
+pub mod alerts;
 pub mod api;
+pub mod backup;
+pub mod bibliography;
+pub mod bulk;
+pub mod cite;
+pub mod export;
+pub mod files;
+#[cfg(test)]
+pub mod fixture;
+pub mod import;
+pub mod library;
+pub mod searches;
 pub mod state;
 pub mod template;
+pub mod tracking;
 use crate::axum_server::{
-    api::pdf::pdf_download,
-    state::{PdfFileState, PdfFileStatus},
+    alerts::alerts_router,
+    backup::{backup_router, spawn_backup_scheduler},
+    bibliography::bibliography_router,
+    bulk::bulk_router,
+    cite::cite_router,
+    export::export_router,
+    files::{download_file, files_router, paper_pdf_sources},
+    import::import_router,
+    library::library_router,
+    searches::{searches_router, spawn_search_scheduler},
+    state::{
+        library::{Bookmark, LibraryState},
+        paper::PaperState,
+        ClaimError, PdfFileState, PdfFileStatus,
+    },
     template::{
+        library::CollectionTreeItem,
         page_detail::{CitingListResponse, CitingListRowTemplate},
         search_page::{SearchPageLayoutTemplate, SearchResultTemplate},
         table::{PaperDetailTemplate, PaperDetailTemplateDetailPrint, TableRowTemplate},
     },
+    tracking::{spawn_citation_tracker, tracking_router},
 };
 
 use axum::{
@@ -27,12 +55,14 @@ use crate::axum_server::state::StateMach;
 use axum_htmx::HxBoosted;
 use query_map::QueryMap;
 use serde::{Deserialize, Serialize};
+use tracing::info;
 
-pub async fn paper_index() -> SearchPageLayoutTemplate {
+pub async fn paper_index(State(state_mach): State<StateMach>) -> SearchPageLayoutTemplate {
     let result = fetch_papers(BulkRequest {
         query: String::from(r#"AI ML NLP"#),
         publication_date_or_year: String::from("2019:"),
         min_citation_count: 3,
+        token: None,
         // ..BulkRequest::default()
     })
     .await
@@ -40,6 +70,8 @@ pub async fn paper_index() -> SearchPageLayoutTemplate {
     let papers = result.data.unwrap();
     SearchPageLayoutTemplate {
         total_count: result.total,
+        next_token: result.token.as_str().map(|t| t.to_string()),
+        collections: CollectionTreeItem::from_collections(state_mach.list_collections()),
         rows: papers
             .into_iter()
             .map(TableRowTemplate::from)
@@ -47,6 +79,24 @@ pub async fn paper_index() -> SearchPageLayoutTemplate {
     }
 }
 
+/// The search form fields as a `BulkRequest`; shared by the search page and
+/// saved searches.
+pub fn bulk_request_from_form(form_set: &QueryMap) -> BulkRequest {
+    BulkRequest {
+        query: form_set.first("query").unwrap_or_default().to_string(),
+        publication_date_or_year: form_set
+            .first("publication_date_or_year")
+            .unwrap_or_default()
+            .to_string(),
+        // min_citation_count: form_set.min_citation_count,
+        token: form_set
+            .first("token")
+            .filter(|t| !t.is_empty())
+            .map(|t| t.to_string()),
+        ..BulkRequest::default()
+    }
+}
+
 pub async fn search_paper(
     hx_boosted: HxBoosted,
     RawForm(form_set): RawForm,
@@ -56,21 +106,14 @@ pub async fn search_paper(
     let form_set_extract = String::from_utf8_lossy(&form_set)
         .parse::<QueryMap>()
         .unwrap();
-    let result = fetch_papers(BulkRequest {
-        query: form_set_extract.first("query").unwrap().to_string(),
-        publication_date_or_year: form_set_extract
-            .first("publication_date_or_year")
-            .unwrap()
-            .to_string(),
-        // min_citation_count: form_set.min_citation_count,
-        ..BulkRequest::default()
-    })
-    .await
-    .unwrap();
+    let result = fetch_papers(bulk_request_from_form(&form_set_extract))
+        .await
+        .unwrap();
     // println!("result: {:#?}", result);
     SearchResultTemplate {
         query: form_set_extract.to_query_string(),
         total_count: result.total,
+        next_token: result.token.as_str().map(|t| t.to_string()),
         rows: result
             .data
             .unwrap()
@@ -82,11 +125,13 @@ pub async fn search_paper(
 
 // async fn path(Path(user_id): Path<u32>) {}
 pub async fn paper_detail(
-    State(_state_mach): State<StateMach>,
+    State(state_mach): State<StateMach>,
     Path(paper_id): Path<String>,
 ) -> PaperDetailTemplate {
     // println!("paper_id: {:#?}", paper_id);
     let paper = fetch_paper_detail(paper_id.to_owned()).await.unwrap();
+    // cloning looks the paper's external ids up again
+    state_mach.cache_paper(&paper);
     PaperDetailTemplate {
         paper_id: paper_id.to_owned(),
         fetched: false,
@@ -142,7 +187,10 @@ pub async fn paper_citation(Path(paper_id): Path<String>) -> CitingListResponse
 #[derive(Deserialize, Serialize, Debug)]
 pub struct PaperCloneRequest {
     paper_id: String,
+    #[serde(default)]
     doi: String,
+    /// the S2 open access url, empty when S2 knows none
+    #[serde(default)]
     url: String,
 }
 #[derive(Deserialize, Serialize, Debug)]
@@ -159,25 +207,30 @@ pub async fn api_paper_clone(
     // tokio::spawn(async move {
     // pdf_download("10.1145/3292500.3330648", "https://dl.acm.org/doi/pdf/10.1145/3292500.3330648").await.unwrap();
     let mut status = state_mach.check_file_status(&payload.paper_id);
+    let candidates = paper_pdf_sources(&state_mach, &payload.paper_id, &payload.url).await;
     if status == PdfFileStatus::None {
-        state_mach.set_file_status(&payload.paper_id, PdfFileStatus::Accpeted);
-        match pdf_download(&payload.paper_id, &payload.url).await {
-            Ok(_) => {
-                state_mach.set_file_status(&payload.paper_id, PdfFileStatus::Downloaded);
-                println!("pdf_download success");
-                // if convert_pdf_to_text(&payload.paper_id).await.is_ok() {
-                //     state_mach.set_file_status(&payload.paper_id, PdfFileStatus::Converted);
-                //     println!("convert_pdf_to_text success");
-                // } else {
-                //     println!("convert_pdf_to_text error");
-                // }
-            }
-            Err(e) => {
-                println!("pdf_download error: {:#?}", e);
-            }
+        // a concurrent request may have moved the paper on already
+        let queued = if candidates.is_empty() {
+            // no open access pdf to fetch
+            state_mach.transition_file(&payload.paper_id, PdfFileStatus::Skipped)
+        } else {
+            state_mach.accept_file(&payload.paper_id, &payload.url)
+        };
+        if let Err(e) = queued {
+            info!("{}", e);
+        }
+    }
+    // accepted papers whose earlier download died are picked up again
+    if !candidates.is_empty() {
+        if let Err(ClaimError::InProgress(lease)) =
+            download_file(&state_mach, &payload.paper_id, candidates).await
+        {
+            info!("{}: download in progress by {}", payload.paper_id, lease.owner);
+            return axum::Json(PaperCloneResponse {
+                status: "already in progress".to_string(),
+            });
         }
     }
-    state_mach.println();
     status = state_mach.check_file_status(&payload.paper_id);
     axum::Json(PaperCloneResponse {
         status: status.to_string(),
@@ -186,8 +239,7 @@ pub async fn api_paper_clone(
 
 #[derive(Deserialize, Serialize, Debug)]
 pub struct PaperBookmarkRequest {
-    doi: String,
-    url: String,
+    paper_id: String,
 }
 #[derive(Deserialize, Serialize, Debug)]
 pub struct PaperBookmarkResponse {
@@ -195,37 +247,32 @@ pub struct PaperBookmarkResponse {
 }
 
 pub async fn api_paper_bookmark(
-    State(_state_mach): State<StateMach>,
+    State(state_mach): State<StateMach>,
     Form(payload): Form<PaperBookmarkRequest>,
 ) -> axum::Json<PaperBookmarkResponse> {
-    println!("payload: {:#?}", payload);
-    println!("here");
-    // tokio::spawn(async move {
-    // pdf_download("10.1145/3292500.3330648", "https://dl.acm.org/doi/pdf/10.1145/3292500.3330648").await.unwrap();
-    //    match  pdf_download(&payload.doi, &payload.url).await {
-    //         Ok(_) => {
-    //             println!("pdf_download success");
-    //         }
-    //         Err(e) => {
-    //             println!("pdf_download error: {:#?}", e);
-    //         }
-    //     }
-    // match convert_pdf_to_text(&payload.doi).await {
-    //     Ok(_) => {
-    //         println!("convert_pdf_to_text success");
-    //     }
-    //     Err(e) => {
-    //         println!("convert_pdf_to_text error: {:#?}", e);
-    //
-
-    // });
+    if state_mach.get_bookmark(&payload.paper_id).is_none() {
+        let paper = fetch_paper_detail(payload.paper_id.to_owned()).await.unwrap();
+        state_mach.set_bookmark(&Bookmark::new(paper));
+    }
+    axum::Json(PaperBookmarkResponse {
+        status: "bookmarked".to_string(),
+    })
+}
+
+pub async fn api_paper_bookmark_remove(
+    State(state_mach): State<StateMach>,
+    Form(payload): Form<PaperBookmarkRequest>,
+) -> axum::Json<PaperBookmarkResponse> {
+    state_mach.remove_bookmark(&payload.paper_id);
     axum::Json(PaperBookmarkResponse {
-        status: "".to_string(),
+        status: "removed".to_string(),
     })
 }
 
-pub fn create_router_service() -> Router {
-    let state_mach = StateMach::new();
+pub fn create_router_service(state_mach: StateMach) -> Router {
+    spawn_search_scheduler(state_mach.clone());
+    spawn_citation_tracker(state_mach.clone());
+    spawn_backup_scheduler(state_mach.clone());
     let page_route = Router::new()
         .route("/", get(paper_index))
         .route("/x/paper_search", post(search_paper))
@@ -235,10 +282,22 @@ pub fn create_router_service() -> Router {
 
     let api_route = Router::new()
         .route("/paper/clone", post(api_paper_clone))
-        .route("/paper/bookmark", post(api_paper_bookmark));
+        .route("/paper/bookmark", post(api_paper_bookmark))
+        .route("/paper/bookmark/remove", post(api_paper_bookmark_remove));
 
     Router::new()
         .nest("/", page_route)
+        .merge(library_router())
+        .merge(bulk_router())
+        .merge(export_router())
+        .merge(cite_router())
+        .merge(import_router())
+        .merge(searches_router())
+        .merge(alerts_router())
+        .merge(tracking_router())
+        .merge(files_router())
+        .merge(bibliography_router())
+        .merge(backup_router())
         .nest("/api", api_route)
         .with_state(state_mach)
     // .nest(
@@ -0,0 +1,286 @@
+use crate::axum_server::{
+    alerts::{enqueue_deliveries, process_due_deliveries},
+    bulk::selected_paper_ids,
+    bulk_request_from_form,
+    state::{
+        delivery::DeliveryState,
+        search::{
+            parse_schedule, InboxItem, SavedSearch, SearchRun, SearchState, DEFAULT_SCHEDULE,
+        },
+        StateMach,
+    },
+    template::search::{
+        SavedSearchInboxTemplate, SavedSearchRowTemplate, SavedSearchesPageTemplate,
+    },
+};
+use crate::semantic_scholar_api::{
+    data::Paper,
+    paper_fetch::{fetch_papers, BulkRequest},
+};
+
+use axum::{
+    extract::{Path, RawForm, State},
+    http::StatusCode,
+    routing::{get, post},
+    Router,
+};
+use chrono::Utc;
+use query_map::QueryMap;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Bulk search pages hold up to 1000 papers; a run stops after this many.
+const MAX_RUN_PAGES: usize = 5;
+
+/// How often the scheduler looks for due searches.
+const SCHEDULER_TICK: Duration = Duration::from_secs(60);
+
+/// Every page of results for the request, up to `MAX_RUN_PAGES`.
+async fn fetch_all_pages(request: &BulkRequest) -> Result<(i32, Vec<Paper>), String> {
+    let mut papers = vec![];
+    let mut request = request.clone();
+    let mut total = 0;
+    for _ in 0..MAX_RUN_PAGES {
+        let response = fetch_papers(request.clone())
+            .await
+            .map_err(|e| e.to_string())?;
+        total = response.total;
+        papers.extend(response.data.unwrap_or_default());
+        match response.token.as_str() {
+            Some(token) => request.token = Some(token.to_string()),
+            None => break,
+        }
+        // stay under the unauthenticated S2 rate limit
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+    Ok((total, papers))
+}
+
+/// Executes a saved search once, records the run and puts the papers not
+/// seen by the previous run into the search's inbox.
+pub async fn run_saved_search(state_mach: &StateMach, search_id: &str) -> Option<SearchRun> {
+    let mut search = state_mach.get_saved_search(search_id)?;
+    let mut found = vec![];
+    let run = match fetch_all_pages(&search.request).await {
+        Ok((total, papers)) => {
+            let previous = state_mach.last_search_run(search_id);
+            let paper_ids = papers
+                .iter()
+                .filter_map(|p| p.paper_id.to_owned())
+                .collect::<Vec<String>>();
+            let run = SearchRun::new(search_id, total, paper_ids, previous.as_ref());
+            for paper in papers.iter() {
+                let is_new = paper
+                    .paper_id
+                    .as_ref()
+                    .is_some_and(|paper_id| run.new_paper_ids.contains(paper_id));
+                if !is_new {
+                    continue;
+                }
+                if let Some(item) = InboxItem::new(search_id, paper) {
+                    // keep the seen flag of papers that dropped out and came back
+                    if state_mach
+                        .get_inbox_item(search_id, &item.paper_id)
+                        .is_none()
+                    {
+                        state_mach.set_inbox_item(&item);
+                        found.push(item);
+                    }
+                }
+            }
+            run
+        }
+        Err(e) => {
+            warn!("saved search {} failed: {}", search_id, e);
+            SearchRun {
+                error: Some(e),
+                ..SearchRun::new(search_id, 0, vec![], None)
+            }
+        }
+    };
+    state_mach.add_search_run(&run);
+
+    // re-read so a concurrent edit of the schedule is not lost
+    search = state_mach.get_saved_search(search_id).unwrap_or(search);
+    search.last_run = Some(run.run_at);
+    if let Err(e) = search.schedule_next(run.run_at) {
+        warn!("saved search {} schedule: {}", search_id, e);
+        search.next_run = None;
+    }
+    state_mach.set_saved_search(&search);
+    enqueue_deliveries(state_mach, &search, run.run_at, &found);
+    info!(
+        "saved search {} ran: {} papers, {} new",
+        search_id,
+        run.paper_ids.len(),
+        run.new_paper_ids.len()
+    );
+    Some(run)
+}
+
+/// Background task that re-runs due saved searches, one at a time, and
+/// sends or retries their alert deliveries.
+pub fn spawn_search_scheduler(state_mach: StateMach) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SCHEDULER_TICK);
+        loop {
+            interval.tick().await;
+            let now = Utc::now();
+            for search in state_mach.list_saved_searches() {
+                if search.is_due(now) {
+                    run_saved_search(&state_mach, &search.search_id).await;
+                }
+            }
+            process_due_deliveries(&state_mach).await;
+        }
+    });
+}
+
+fn saved_search_rows(state_mach: &StateMach) -> Vec<SavedSearchRowTemplate> {
+    state_mach
+        .list_saved_searches()
+        .into_iter()
+        .map(|search| {
+            let unseen = state_mach.count_unseen(&search.search_id);
+            SavedSearchRowTemplate::new(search, unseen)
+        })
+        .collect()
+}
+
+pub async fn saved_searches_page(State(state_mach): State<StateMach>) -> SavedSearchesPageTemplate {
+    SavedSearchesPageTemplate {
+        default_schedule: DEFAULT_SCHEDULE.to_string(),
+        rows: saved_search_rows(&state_mach),
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct SavedSearchResponse {
+    status: String,
+    search_id: String,
+}
+
+/// Saves the search form as a named search; saving under an existing name
+/// updates its filters and schedule but keeps the run history and inbox.
+pub async fn api_saved_search_create(
+    State(state_mach): State<StateMach>,
+    RawForm(form_set): RawForm,
+) -> Result<axum::Json<SavedSearchResponse>, (StatusCode, String)> {
+    let form_set = String::from_utf8_lossy(&form_set)
+        .parse::<QueryMap>()
+        .unwrap();
+    let name = form_set.first("name").unwrap_or_default();
+    let schedule = form_set
+        .first("schedule")
+        .filter(|s| !s.trim().is_empty())
+        .unwrap_or(DEFAULT_SCHEDULE);
+    let mut search = SavedSearch::new(name, bulk_request_from_form(&form_set), schedule)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    if search.search_id.is_empty() || search.request.query.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "a saved search needs a name and a query".to_string(),
+        ));
+    }
+    if let Some(existing) = state_mach.get_saved_search(&search.search_id) {
+        search.created_at = existing.created_at;
+        search.last_run = existing.last_run;
+        search.delivery = existing.delivery;
+    }
+    state_mach.set_saved_search(&search);
+    Ok(axum::Json(SavedSearchResponse {
+        status: "saved".to_string(),
+        search_id: search.search_id,
+    }))
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct SavedSearchUpdateRequest {
+    schedule: String,
+    #[serde(default)]
+    enabled: Option<String>,
+}
+
+pub async fn saved_search_update(
+    State(state_mach): State<StateMach>,
+    Path(search_id): Path<String>,
+    axum::Form(payload): axum::Form<SavedSearchUpdateRequest>,
+) -> Result<SavedSearchesPageTemplate, (StatusCode, String)> {
+    parse_schedule(&payload.schedule).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    if let Some(mut search) = state_mach.get_saved_search(&search_id) {
+        search.schedule = payload.schedule.trim().to_string();
+        search.enabled = payload.enabled.is_some();
+        search.schedule_next(Utc::now()).unwrap();
+        state_mach.set_saved_search(&search);
+    }
+    Ok(saved_searches_page(State(state_mach)).await)
+}
+
+pub async fn saved_search_remove(
+    State(state_mach): State<StateMach>,
+    Path(search_id): Path<String>,
+) -> SavedSearchesPageTemplate {
+    state_mach.remove_saved_search(&search_id);
+    saved_searches_page(State(state_mach)).await
+}
+
+pub async fn saved_search_inbox(
+    State(state_mach): State<StateMach>,
+    Path(search_id): Path<String>,
+) -> Result<SavedSearchInboxTemplate, (StatusCode, String)> {
+    let search = state_mach
+        .get_saved_search(&search_id)
+        .ok_or((StatusCode::NOT_FOUND, "unknown saved search".to_string()))?;
+    let last_error = state_mach
+        .list_search_runs(&search_id)
+        .pop()
+        .and_then(|run| run.error);
+    Ok(SavedSearchInboxTemplate::new(
+        search,
+        state_mach.last_search_run(&search_id),
+        last_error,
+        state_mach.list_inbox(&search_id),
+        state_mach.list_deliveries(&search_id),
+    ))
+}
+
+pub async fn saved_search_run(
+    State(state_mach): State<StateMach>,
+    Path(search_id): Path<String>,
+) -> Result<SavedSearchInboxTemplate, (StatusCode, String)> {
+    run_saved_search(&state_mach, &search_id).await;
+    saved_search_inbox(State(state_mach), Path(search_id)).await
+}
+
+/// Marks the submitted `paper_ids`, or every inbox item when `all` is set,
+/// as seen.
+pub async fn saved_search_seen(
+    State(state_mach): State<StateMach>,
+    Path(search_id): Path<String>,
+    RawForm(form_set): RawForm,
+) -> Result<SavedSearchInboxTemplate, (StatusCode, String)> {
+    let form_set = String::from_utf8_lossy(&form_set)
+        .parse::<QueryMap>()
+        .unwrap();
+    let all = form_set.first("all").is_some();
+    let paper_ids = selected_paper_ids(&form_set);
+    for mut item in state_mach.list_inbox(&search_id) {
+        if !item.seen && (all || paper_ids.contains(&item.paper_id)) {
+            item.seen = true;
+            state_mach.set_inbox_item(&item);
+        }
+    }
+    saved_search_inbox(State(state_mach), Path(search_id)).await
+}
+
+pub fn searches_router() -> Router<StateMach> {
+    Router::new()
+        .route("/api/searches", post(api_saved_search_create))
+        .route("/x/searches", get(saved_searches_page))
+        .route("/x/searches/:search_id", get(saved_search_inbox))
+        .route("/x/searches/:search_id/update", post(saved_search_update))
+        .route("/x/searches/:search_id/remove", post(saved_search_remove))
+        .route("/x/searches/:search_id/run", post(saved_search_run))
+        .route("/x/searches/:search_id/seen", post(saved_search_seen))
+}
@@ -0,0 +1,280 @@
+//! Whole-store archives, and a portable JSON-lines dump of every entity.
+//!
+//! An archive is a gzip stream of the trees' raw entries with a
+//! `sha256sum`-style checksum file next to it. The trees are read from one
+//! `Store::snapshot`, so a backup taken while the server runs holds the store
+//! as of one moment between repository writes, with every record matching
+//! its index entries; writes wait while it is read.
+
+use crate::axum_server::state::{
+    entity_kinds,
+    store::{store_is_empty, Store},
+};
+use chrono::Utc;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const MAGIC: &[u8] = b"SSBK1\n";
+
+/// Backups written by the scheduler are named `state-<timestamp>.ssbk`.
+const PREFIX: &str = "state-";
+const EXTENSION: &str = "ssbk";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BackupInfo {
+    pub path: PathBuf,
+    pub entries: usize,
+    pub sha256: String,
+}
+
+/// Passes writes through while hashing them.
+struct HashWriter<W: Write> {
+    inner: W,
+    hasher: Sha256,
+}
+
+impl<W: Write> Write for HashWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn checksum_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".sha256");
+    PathBuf::from(name)
+}
+
+fn write_chunk(w: &mut impl Write, bytes: &[u8]) -> std::io::Result<()> {
+    w.write_all(&(bytes.len() as u32).to_be_bytes())?;
+    w.write_all(bytes)
+}
+
+/// `None` at the end of the archive.
+fn read_chunk(r: &mut impl Read) -> std::io::Result<Option<Vec<u8>>> {
+    let mut len = [0; 4];
+    match r.read_exact(&mut len) {
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        result => result?,
+    }
+    let mut bytes = vec![0; u32::from_be_bytes(len) as usize];
+    r.read_exact(&mut bytes)?;
+    Ok(Some(bytes))
+}
+
+/// Writes every tree of `store` to `path`, through a temporary file so a
+/// failed backup never replaces a good one.
+pub fn write_backup(store: &dyn Store, path: &Path) -> anyhow::Result<BackupInfo> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("partial");
+    let writer = HashWriter {
+        inner: BufWriter::new(File::create(&tmp)?),
+        hasher: Sha256::new(),
+    };
+    let mut gz = GzEncoder::new(writer, Compression::default());
+    gz.write_all(MAGIC)?;
+    let mut entries = 0;
+    for (name, tree) in store.snapshot() {
+        for (key, value) in tree {
+            write_chunk(&mut gz, name.as_bytes())?;
+            write_chunk(&mut gz, &key)?;
+            write_chunk(&mut gz, &value)?;
+            entries += 1;
+        }
+    }
+    let mut writer = gz.finish()?;
+    writer.flush()?;
+    let sha256 = hex::encode(writer.hasher.finalize());
+    fs::rename(&tmp, path)?;
+    let file_name = path.file_name().unwrap().to_string_lossy();
+    fs::write(checksum_path(path), format!("{}  {}\n", sha256, file_name))?;
+    Ok(BackupInfo {
+        path: path.to_path_buf(),
+        entries,
+        sha256,
+    })
+}
+
+/// Checks the archive against its checksum file.
+pub fn verify_backup(path: &Path) -> anyhow::Result<String> {
+    let expected = fs::read_to_string(checksum_path(path))
+        .map_err(|e| anyhow::anyhow!("{}: no checksum file: {}", path.display(), e))?;
+    let expected = expected.split_whitespace().next().unwrap_or_default();
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut File::open(path)?, &mut hasher)?;
+    let actual = hex::encode(hasher.finalize());
+    if actual != expected {
+        return Err(anyhow::anyhow!(
+            "{}: checksum mismatch, expected {} got {}",
+            path.display(),
+            expected,
+            actual
+        ));
+    }
+    Ok(actual)
+}
+
+/// Verifies the archive and loads it into an empty store.
+pub fn restore_backup(path: &Path, store: &dyn Store) -> anyhow::Result<usize> {
+    verify_backup(path)?;
+    if !store_is_empty(store) {
+        return Err(anyhow::anyhow!("the target store already holds data"));
+    }
+    let mut gz = GzDecoder::new(BufReader::new(File::open(path)?));
+    let mut magic = [0; MAGIC.len()];
+    gz.read_exact(&mut magic)?;
+    if magic != MAGIC {
+        return Err(anyhow::anyhow!("{}: not a backup archive", path.display()));
+    }
+    let mut entries = 0;
+    while let Some(name) = read_chunk(&mut gz)? {
+        let truncated = || anyhow::anyhow!("{}: truncated archive", path.display());
+        let key = read_chunk(&mut gz)?.ok_or_else(truncated)?;
+        let value = read_chunk(&mut gz)?.ok_or_else(truncated)?;
+        store
+            .open_tree(&String::from_utf8(name)?)
+            .insert(&key, &value);
+        entries += 1;
+    }
+    store.flush();
+    Ok(entries)
+}
+
+/// Writes a timestamped backup into `dir` and deletes all but the newest
+/// `keep` ones.
+pub fn backup_to_dir(store: &dyn Store, dir: &Path, keep: usize) -> anyhow::Result<BackupInfo> {
+    let name = format!(
+        "{}{}.{}",
+        PREFIX,
+        Utc::now().format("%Y%m%dT%H%M%S%.3fZ"),
+        EXTENSION
+    );
+    let info = write_backup(store, &dir.join(name))?;
+    let mut backups = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            let name = path.file_name().unwrap().to_string_lossy();
+            name.starts_with(PREFIX) && name.ends_with(&format!(".{}", EXTENSION))
+        })
+        .collect::<Vec<PathBuf>>();
+    // timestamps sort by name
+    backups.sort();
+    let expired = backups.len().saturating_sub(keep.max(1));
+    for path in backups.into_iter().take(expired) {
+        fs::remove_file(&path)?;
+        let _ = fs::remove_file(checksum_path(&path));
+    }
+    Ok(info)
+}
+
+/// One line of the JSON-lines export.
+#[derive(Serialize, Deserialize, Debug)]
+struct ExportLine {
+    entity: String,
+    value: serde_json::Value,
+}
+
+/// Writes every entity as one JSON object per line; index trees are left
+/// out and rebuilt on import.
+pub fn export_json(store: &Arc<dyn Store>, w: &mut impl Write) -> anyhow::Result<usize> {
+    let mut lines = 0;
+    for kind in entity_kinds() {
+        for value in kind.export_json(store) {
+            let line = ExportLine {
+                entity: kind.tree().to_string(),
+                value,
+            };
+            serde_json::to_writer(&mut *w, &line)?;
+            w.write_all(b"\n")?;
+            lines += 1;
+        }
+    }
+    Ok(lines)
+}
+
+/// Loads a JSON-lines export, replacing records with the same keys.
+pub fn import_json(store: &Arc<dyn Store>, r: impl BufRead) -> anyhow::Result<usize> {
+    let kinds = entity_kinds();
+    let mut lines = 0;
+    for (number, line) in r.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let line = serde_json::from_str::<ExportLine>(&line)
+            .map_err(|e| anyhow::anyhow!("line {}: {}", number + 1, e))?;
+        let kind = kinds
+            .iter()
+            .find(|kind| kind.tree() == line.entity)
+            .ok_or_else(|| {
+                anyhow::anyhow!("line {}: unknown entity {}", number + 1, line.entity)
+            })?;
+        kind.import_json(store, line.value)
+            .map_err(|e| anyhow::anyhow!("line {}: {}", number + 1, e))?;
+        lines += 1;
+    }
+    store.flush();
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::axum_server::state::{
+        library::{Collection, LibraryState},
+        store::memory::MemoryStore,
+        PdfFileState, PdfFileStatus, StateMach,
+    };
+
+    #[test]
+    fn test_backup_and_export() {
+        let state_mach = StateMach::temporary();
+        state_mach.set_collection(&Collection::new("Reading", None));
+        state_mach
+            .transition_file("abc", PdfFileStatus::Skipped)
+            .unwrap();
+
+        let dir = std::env::temp_dir().join(format!("scholar-backup-{}", std::process::id()));
+        let first = backup_to_dir(state_mach.store().as_ref(), &dir, 1).unwrap();
+        // names only differ by the millisecond
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        let info = backup_to_dir(state_mach.store().as_ref(), &dir, 1).unwrap();
+        assert!(!first.path.exists());
+        assert_eq!(verify_backup(&info.path).unwrap(), info.sha256);
+
+        let restored = MemoryStore::default();
+        assert_eq!(restore_backup(&info.path, &restored).unwrap(), info.entries);
+        assert!(restore_backup(&info.path, &restored).is_err());
+        assert_eq!(
+            restored.open_tree("collections").iter(),
+            state_mach.store().open_tree("collections").iter()
+        );
+
+        let mut bytes = fs::read(&info.path).unwrap();
+        *bytes.last_mut().unwrap() ^= 1;
+        fs::write(&info.path, bytes).unwrap();
+        assert!(verify_backup(&info.path).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+
+        let mut export = vec![];
+        assert_eq!(export_json(state_mach.store(), &mut export).unwrap(), 3);
+        let imported: Arc<dyn Store> = Arc::new(MemoryStore::default());
+        assert_eq!(import_json(&imported, export.as_slice()).unwrap(), 3);
+        let imported = StateMach::from_store(imported).unwrap();
+        assert_eq!(imported.list_collections().len(), 1);
+        assert_eq!(imported.list_files_by_status("skipped").len(), 1);
+    }
+}
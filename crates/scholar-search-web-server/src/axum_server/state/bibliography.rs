@@ -0,0 +1,76 @@
+use crate::axum_server::{
+    api::pdf::citations::CitationContext,
+    state::{repo::Entity, StateMach},
+};
+use crate::citation::{
+    import::reference::ParsedReference,
+    resolve::{MatchCandidate, MATCH_THRESHOLD},
+};
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+
+/// A reference of a paper's PDF, with the S2 paper it was matched to.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct LinkedReference {
+    /// `12` for `[12]`, `None` for author year styles
+    pub label: Option<String>,
+    pub page: u32,
+    /// the reference as printed
+    pub text: String,
+    pub fields: ParsedReference,
+    /// the best candidate, when one was worth offering
+    pub candidate: Option<MatchCandidate>,
+    /// false until the match was looked for
+    pub resolved: bool,
+    /// where the body cites the reference
+    #[serde(default)]
+    pub citations: Vec<CitationContext>,
+}
+
+impl LinkedReference {
+    /// Matched confidently enough to link without review.
+    pub fn is_linked(&self) -> bool {
+        self.candidate
+            .as_ref()
+            .is_some_and(|c| c.confidence >= MATCH_THRESHOLD)
+    }
+}
+
+/// The reference list of a paper's converted PDF, linked to S2 papers.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct PaperBibliography {
+    pub paper_id: String,
+    /// the blob the references were read from
+    pub sha256: String,
+    pub linked_at: DateTime<Utc>,
+    pub references: Vec<LinkedReference>,
+}
+
+impl PaperBibliography {
+    pub fn linked(&self) -> usize {
+        self.references.iter().filter(|r| r.is_linked()).count()
+    }
+}
+
+impl Entity for PaperBibliography {
+    const TREE: &'static str = "pdf_bibliographies";
+
+    fn key(&self) -> String {
+        self.paper_id.to_owned()
+    }
+}
+
+pub trait BibliographyState {
+    fn get_bibliography(&self, paper_id: &str) -> Option<PaperBibliography>;
+    fn set_bibliography(&self, bibliography: &PaperBibliography);
+}
+
+impl BibliographyState for StateMach {
+    fn get_bibliography(&self, paper_id: &str) -> Option<PaperBibliography> {
+        self.repo::<PaperBibliography>().get(paper_id)
+    }
+
+    fn set_bibliography(&self, bibliography: &PaperBibliography) {
+        self.repo::<PaperBibliography>().put(bibliography);
+    }
+}
@@ -0,0 +1,454 @@
+//! Content-addressed storage for downloaded PDFs.
+//!
+//! A blob is stored once under the hex SHA-256 of its bytes, at
+//! `<root>/<first two hex digits>/<hash>`, so identical PDFs reached through
+//! different paper ids share a file. Papers point at their blob through a
+//! `PdfBlob` record; blobs no record points at are left for `gc`. Files
+//! derived from a blob, such as its converted document, sit beside it as
+//! `<hash>.<extension>` and are collected with it.
+
+use crate::axum_server::state::{repo::Entity, worker_id, StateMach};
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+/// Half written blobs live here until they are renamed into place.
+const TEMP_DIR: &str = "tmp";
+
+/// Blobs and temporary files younger than this are never collected, so a
+/// download between storing its blob and recording it is safe from `gc`.
+pub const GC_GRACE: Duration = Duration::from_secs(60 * 60);
+
+/// Serializes `ingest` within the process, which is the one writing blobs.
+static INGEST_LOCK: Mutex<()> = Mutex::new(());
+
+/// A stored blob.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct BlobRef {
+    pub sha256: String,
+    pub size: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Default)]
+pub struct GcReport {
+    pub removed: usize,
+    pub freed: u64,
+    pub kept: usize,
+}
+
+/// The blob directory, read from `SCHOLAR_SEARCH_BLOB_*` variables.
+#[derive(Debug, Clone)]
+pub struct BlobStore {
+    root: PathBuf,
+    /// bytes the blobs may take up; `None` for no limit
+    quota: Option<u64>,
+}
+
+impl BlobStore {
+    pub fn new(root: impl Into<PathBuf>, quota: Option<u64>) -> Self {
+        Self {
+            root: root.into(),
+            quota,
+        }
+    }
+
+    /// `SCHOLAR_SEARCH_BLOB_DIR`, `data/blobs` by default, and
+    /// `SCHOLAR_SEARCH_BLOB_QUOTA_MB`, unlimited when unset or 0.
+    pub fn from_env() -> anyhow::Result<Self> {
+        let root = std::env::var("SCHOLAR_SEARCH_BLOB_DIR").unwrap_or("data/blobs".to_string());
+        let quota = match std::env::var("SCHOLAR_SEARCH_BLOB_QUOTA_MB") {
+            Ok(mb) => Some(mb.trim().parse::<u64>()? * 1024 * 1024).filter(|q| *q > 0),
+            Err(_) => None,
+        };
+        Ok(Self::new(root, quota))
+    }
+
+    #[cfg(test)]
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn path(&self, sha256: &str) -> PathBuf {
+        self.root.join(&sha256[..2.min(sha256.len())]).join(sha256)
+    }
+
+    /// Where a file derived from the blob is kept.
+    pub fn sidecar_path(&self, sha256: &str, extension: &str) -> PathBuf {
+        self.path(sha256).with_extension(extension)
+    }
+
+    #[cfg(test)]
+    pub fn contains(&self, sha256: &str) -> bool {
+        self.path(sha256).is_file()
+    }
+
+    /// A fresh path to write a blob to before handing it to `ingest`.
+    pub fn temp_path(&self) -> anyhow::Result<PathBuf> {
+        let dir = self.root.join(TEMP_DIR);
+        fs::create_dir_all(&dir)?;
+        Ok(dir.join(format!("{}.partial", worker_id())))
+    }
+
+    /// A stable temporary path for a file built up over several attempts,
+    /// such as a resumable download of the url `key`.
+    pub fn partial_path(&self, key: &str) -> anyhow::Result<PathBuf> {
+        let dir = self.root.join(TEMP_DIR);
+        fs::create_dir_all(&dir)?;
+        Ok(dir.join(format!("{}.partial", hex::encode(Sha256::digest(key)))))
+    }
+
+    pub fn put(&self, bytes: &[u8]) -> anyhow::Result<BlobRef> {
+        let temp = self.temp_path()?;
+        let written = (|| {
+            let mut out = BufWriter::new(File::create(&temp)?);
+            out.write_all(bytes)?;
+            out.into_inner()?.sync_all()
+        })();
+        if let Err(e) = written {
+            let _ = fs::remove_file(&temp);
+            return Err(e.into());
+        }
+        self.ingest(&temp)
+    }
+
+    /// Moves a file written under `temp_path` into the store. A blob that
+    /// is already stored intact is kept, touched so `gc` gives it a fresh
+    /// grace period, and the file dropped; a truncated or corrupt one is
+    /// replaced; a new one that would take the store over its quota is
+    /// refused.
+    pub fn ingest(&self, temp: &Path) -> anyhow::Result<BlobRef> {
+        let result = (|| {
+            let blob = hash_file(temp)?;
+            let path = self.path(&blob.sha256);
+            // concurrent ingests would all pass the quota check on the same
+            // usage, so check and rename one at a time
+            let _guard = INGEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+            let existing = match fs::metadata(&path) {
+                Ok(meta) if meta.is_file() => {
+                    if meta.len() == blob.size && hash_file(&path)? == blob {
+                        File::options()
+                            .append(true)
+                            .open(&path)?
+                            .set_modified(SystemTime::now())?;
+                        return Ok(blob);
+                    }
+                    meta.len()
+                }
+                _ => 0,
+            };
+            if let Some(quota) = self.quota {
+                let used = self.usage()? - existing;
+                if used + blob.size > quota {
+                    return Err(anyhow::anyhow!(
+                        "blob store over quota: {} of {} bytes used, {} more needed",
+                        used,
+                        quota,
+                        blob.size
+                    ));
+                }
+            }
+            fs::create_dir_all(path.parent().unwrap())?;
+            fs::rename(temp, &path)?;
+            Ok(blob)
+        })();
+        let _ = fs::remove_file(temp);
+        result
+    }
+
+    /// The blob's bytes, checked against its hash.
+    pub fn read(&self, sha256: &str) -> anyhow::Result<Vec<u8>> {
+        let bytes =
+            fs::read(self.path(sha256)).map_err(|e| anyhow::anyhow!("blob {}: {}", sha256, e))?;
+        let actual = hex::encode(Sha256::digest(&bytes));
+        if actual != sha256 {
+            return Err(anyhow::anyhow!(
+                "blob {} is corrupt, hashes to {}",
+                sha256,
+                actual
+            ));
+        }
+        Ok(bytes)
+    }
+
+    /// Re-hashes every blob and returns the corrupt ones.
+    pub fn verify(&self) -> anyhow::Result<Vec<String>> {
+        let mut corrupt = vec![];
+        for (path, _) in self.blob_files()? {
+            let sha256 = path.file_name().unwrap().to_string_lossy().to_string();
+            if hash_file(&path)?.sha256 != sha256 {
+                corrupt.push(sha256);
+            }
+        }
+        Ok(corrupt)
+    }
+
+    /// Bytes taken up by the stored blobs and their sidecars.
+    pub fn usage(&self) -> anyhow::Result<u64> {
+        Ok(self.shard_files()?.iter().map(|(_, meta)| meta.len()).sum())
+    }
+
+    /// Deletes the blobs outside `referenced`, and temporary files left by
+    /// crashed downloads, once they are older than `grace`.
+    pub fn gc(
+        &self,
+        referenced: &HashSet<String>,
+        grace: Duration,
+        dry_run: bool,
+    ) -> anyhow::Result<GcReport> {
+        let cutoff = SystemTime::now() - grace;
+        let is_old = |meta: &fs::Metadata| meta.modified().is_ok_and(|m| m <= cutoff);
+        let mut report = GcReport::default();
+        let mut garbage = vec![];
+        for (path, meta) in self.shard_files()? {
+            let name = path.file_name().unwrap().to_string_lossy().to_string();
+            // sidecars go with their blob
+            let sha256 = name.split('.').next().unwrap_or_default();
+            if referenced.contains(sha256) || !is_old(&meta) {
+                report.kept += 1;
+            } else {
+                garbage.push((path, meta.len()));
+            }
+        }
+        for (path, meta) in list_dir(&self.root.join(TEMP_DIR))? {
+            if is_old(&meta) {
+                garbage.push((path, meta.len()));
+            }
+        }
+        for (path, size) in garbage {
+            if !dry_run {
+                fs::remove_file(&path)?;
+            }
+            report.removed += 1;
+            report.freed += size;
+        }
+        Ok(report)
+    }
+
+    fn blob_files(&self) -> anyhow::Result<Vec<(PathBuf, fs::Metadata)>> {
+        Ok(self
+            .shard_files()?
+            .into_iter()
+            .filter(|(path, _)| path.extension().is_none())
+            .collect())
+    }
+
+    /// The blobs and their sidecars.
+    fn shard_files(&self) -> anyhow::Result<Vec<(PathBuf, fs::Metadata)>> {
+        let mut files = vec![];
+        for (dir, meta) in list_dir(&self.root)? {
+            let name = dir.file_name().unwrap().to_string_lossy().to_string();
+            if meta.is_dir() && name != TEMP_DIR {
+                files.extend(list_dir(&dir)?.into_iter().filter(|(_, m)| m.is_file()));
+            }
+        }
+        Ok(files)
+    }
+}
+
+/// Entries of `dir`; nothing when it does not exist yet.
+fn list_dir(dir: &Path) -> anyhow::Result<Vec<(PathBuf, fs::Metadata)>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.into()),
+    };
+    let mut files = vec![];
+    for entry in entries {
+        let entry = entry?;
+        files.push((entry.path(), entry.metadata()?));
+    }
+    Ok(files)
+}
+
+fn hash_file(path: &Path) -> anyhow::Result<BlobRef> {
+    let mut hasher = Sha256::new();
+    let size = std::io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(BlobRef {
+        sha256: hex::encode(hasher.finalize()),
+        size,
+    })
+}
+
+/// Which blob holds a paper's PDF.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct PdfBlob {
+    pub paper_id: String,
+    pub sha256: String,
+    pub size: u64,
+    pub stored_at: DateTime<Utc>,
+}
+
+impl Entity for PdfBlob {
+    const TREE: &'static str = "pdf_blobs";
+
+    fn key(&self) -> String {
+        self.paper_id.to_owned()
+    }
+
+    fn indexes(&self) -> Vec<(&'static str, String)> {
+        vec![("sha256", self.sha256.to_owned())]
+    }
+}
+
+pub trait PdfBlobState {
+    fn get_pdf_blob(&self, paper_id: &str) -> Option<PdfBlob>;
+    /// Points the paper at `blob`, replacing any earlier PDF.
+    fn set_pdf_blob(&self, paper_id: &str, blob: &BlobRef) -> PdfBlob;
+    /// The papers sharing the blob.
+    fn list_blob_papers(&self, sha256: &str) -> Vec<String>;
+    /// The hashes of every blob a paper points at.
+    fn referenced_blobs(&self) -> HashSet<String>;
+    /// The paper's PDF, checked against its hash.
+    fn read_pdf(&self, blobs: &BlobStore, paper_id: &str) -> anyhow::Result<Vec<u8>>;
+    /// Stores the bytes and points the paper at them.
+    fn store_pdf(&self, blobs: &BlobStore, paper_id: &str, bytes: &[u8])
+        -> anyhow::Result<PdfBlob>;
+}
+
+impl PdfBlobState for StateMach {
+    fn get_pdf_blob(&self, paper_id: &str) -> Option<PdfBlob> {
+        self.repo::<PdfBlob>().get(paper_id)
+    }
+
+    fn set_pdf_blob(&self, paper_id: &str, blob: &BlobRef) -> PdfBlob {
+        let record = PdfBlob {
+            paper_id: paper_id.to_string(),
+            sha256: blob.sha256.to_owned(),
+            size: blob.size,
+            stored_at: Utc::now(),
+        };
+        self.repo::<PdfBlob>().put(&record);
+        record
+    }
+
+    fn list_blob_papers(&self, sha256: &str) -> Vec<String> {
+        self.repo::<PdfBlob>().keys_by("sha256", sha256)
+    }
+
+    fn referenced_blobs(&self) -> HashSet<String> {
+        self.repo::<PdfBlob>().iter().map(|b| b.sha256).collect()
+    }
+
+    fn read_pdf(&self, blobs: &BlobStore, paper_id: &str) -> anyhow::Result<Vec<u8>> {
+        let record = self
+            .get_pdf_blob(paper_id)
+            .ok_or_else(|| anyhow::anyhow!("{}: no stored pdf", paper_id))?;
+        blobs.read(&record.sha256)
+    }
+
+    fn store_pdf(
+        &self,
+        blobs: &BlobStore,
+        paper_id: &str,
+        bytes: &[u8],
+    ) -> anyhow::Result<PdfBlob> {
+        let blob = blobs.put(bytes)?;
+        Ok(self.set_pdf_blob(paper_id, &blob))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn temporary_blobs(name: &str, quota: Option<u64>) -> BlobStore {
+        let root =
+            std::env::temp_dir().join(format!("scholar-blobs-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        BlobStore::new(root, quota)
+    }
+
+    #[test]
+    fn test_blob_store() {
+        let state_mach = StateMach::temporary();
+        let blobs = temporary_blobs("store", Some(20));
+
+        let first = state_mach
+            .store_pdf(&blobs, "10.1/a", b"%PDF-1.4 one")
+            .unwrap();
+        let second = state_mach
+            .store_pdf(&blobs, "arxiv:a", b"%PDF-1.4 one")
+            .unwrap();
+        assert_eq!(first.sha256, second.sha256);
+        assert_eq!(blobs.usage().unwrap(), 12);
+        assert_eq!(
+            state_mach.list_blob_papers(&first.sha256),
+            vec!["10.1/a".to_string(), "arxiv:a".to_string()]
+        );
+        assert_eq!(
+            state_mach.read_pdf(&blobs, "arxiv:a").unwrap(),
+            b"%PDF-1.4 one"
+        );
+
+        // 12 + 12 bytes would pass the quota; a duplicate takes no room
+        assert!(blobs.put(b"%PDF-1.4 two").is_err());
+        assert!(blobs.put(b"%PDF-1.4 one").is_ok());
+        assert!(list_dir(&blobs.root().join(TEMP_DIR)).unwrap().is_empty());
+
+        fs::write(blobs.path(&first.sha256), b"%PDF-1.4 on3").unwrap();
+        assert!(state_mach.read_pdf(&blobs, "10.1/a").is_err());
+        assert_eq!(blobs.verify().unwrap(), vec![first.sha256.clone()]);
+
+        // storing the same bytes again repairs a corrupt or truncated copy
+        blobs.put(b"%PDF-1.4 one").unwrap();
+        assert!(blobs.verify().unwrap().is_empty());
+        fs::write(blobs.path(&first.sha256), b"%PDF-1.4").unwrap();
+        blobs.put(b"%PDF-1.4 one").unwrap();
+        assert_eq!(
+            state_mach.read_pdf(&blobs, "10.1/a").unwrap(),
+            b"%PDF-1.4 one"
+        );
+
+        // sidecars count against the quota
+        fs::write(blobs.sidecar_path(&first.sha256, "json"), b"{}").unwrap();
+        assert_eq!(blobs.usage().unwrap(), 14);
+        fs::remove_dir_all(blobs.root()).unwrap();
+    }
+
+    #[test]
+    fn test_blob_gc() {
+        let state_mach = StateMach::temporary();
+        let blobs = temporary_blobs("gc", None);
+        let kept = state_mach.store_pdf(&blobs, "a", b"%PDF kept").unwrap();
+        let replaced = state_mach.store_pdf(&blobs, "b", b"%PDF old").unwrap();
+        state_mach.store_pdf(&blobs, "b", b"%PDF new").unwrap();
+        fs::write(blobs.temp_path().unwrap(), b"%PDF crashed").unwrap();
+        let sidecar = blobs.sidecar_path(&replaced.sha256, "json");
+        fs::write(&sidecar, b"{}").unwrap();
+
+        let referenced = state_mach.referenced_blobs();
+        let fresh = blobs.gc(&referenced, GC_GRACE, false).unwrap();
+        assert_eq!(fresh.removed, 0);
+
+        let dry = blobs.gc(&referenced, Duration::ZERO, true).unwrap();
+        assert_eq!((dry.removed, dry.kept), (3, 2));
+        assert!(blobs.contains(&replaced.sha256));
+
+        let report = blobs.gc(&referenced, Duration::ZERO, false).unwrap();
+        assert_eq!(report, dry);
+        assert!(!blobs.contains(&replaced.sha256));
+        assert!(!sidecar.exists());
+        assert!(blobs.contains(&kept.sha256));
+        assert_eq!(state_mach.read_pdf(&blobs, "b").unwrap(), b"%PDF new");
+
+        // storing an old unreferenced blob again restarts its grace period
+        let orphan = blobs.put(b"%PDF orphan").unwrap();
+        let old = SystemTime::now() - 2 * GC_GRACE;
+        File::options()
+            .append(true)
+            .open(blobs.path(&orphan.sha256))
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+        blobs.put(b"%PDF orphan").unwrap();
+        assert_eq!(blobs.gc(&referenced, GC_GRACE, false).unwrap().removed, 0);
+        assert!(blobs.contains(&orphan.sha256));
+        fs::remove_dir_all(blobs.root()).unwrap();
+    }
+}
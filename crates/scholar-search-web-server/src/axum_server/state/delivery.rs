@@ -0,0 +1,221 @@
+use crate::alerts::AlertPayload;
+use crate::axum_server::state::{repo::Entity, StateMach};
+use chrono::{DateTime, Duration, Utc};
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+
+/// A delivery is given up after this many failed attempts.
+pub const MAX_ATTEMPTS: usize = 5;
+
+/// Where the new papers of a saved search are pushed to; the feeds need no
+/// configuration.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct DeliveryConfig {
+    pub webhook_url: Option<String>,
+    pub email_to: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Copy)]
+pub enum DeliveryChannel {
+    Webhook,
+    Email,
+}
+
+impl fmt::Display for DeliveryChannel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DeliveryChannel::Webhook => write!(f, "webhook"),
+            DeliveryChannel::Email => write!(f, "email"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Copy)]
+pub enum DeliveryStatus {
+    Pending,
+    Delivered,
+    Failed,
+}
+
+impl fmt::Display for DeliveryStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DeliveryStatus::Pending => write!(f, "pending"),
+            DeliveryStatus::Delivered => write!(f, "delivered"),
+            DeliveryStatus::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DeliveryAttempt {
+    pub at: DateTime<Utc>,
+    pub ok: bool,
+    pub message: String,
+}
+
+/// One payload sent to one target, with every attempt logged.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Delivery {
+    pub delivery_id: String,
+    pub search_id: String,
+    pub channel: DeliveryChannel,
+    pub target: String,
+    pub payload: AlertPayload,
+    pub status: DeliveryStatus,
+    pub attempts: Vec<DeliveryAttempt>,
+    /// failed attempts since the delivery was last (re)queued
+    pub failures: usize,
+    pub created_at: DateTime<Utc>,
+    pub next_attempt: Option<DateTime<Utc>>,
+}
+
+impl Delivery {
+    pub fn new(
+        payload: &AlertPayload,
+        channel: DeliveryChannel,
+        target: &str,
+        sequence: usize,
+    ) -> Self {
+        let created_at = Utc::now();
+        Self {
+            delivery_id: format!(
+                "{}-{}-{}",
+                created_at.format("%Y%m%dT%H%M%S%.6f"),
+                channel,
+                sequence
+            ),
+            search_id: payload.search_id.to_owned(),
+            channel,
+            target: target.to_string(),
+            payload: payload.clone(),
+            status: DeliveryStatus::Pending,
+            attempts: vec![],
+            failures: 0,
+            created_at,
+            next_attempt: Some(created_at),
+        }
+    }
+
+    /// Logs an attempt; failures are retried after 1, 4, 16 and 64 minutes.
+    pub fn record(&mut self, at: DateTime<Utc>, result: Result<String, String>) {
+        let ok = result.is_ok();
+        self.attempts.push(DeliveryAttempt {
+            at,
+            ok,
+            message: result.unwrap_or_else(|e| e),
+        });
+        if ok {
+            self.status = DeliveryStatus::Delivered;
+            self.next_attempt = None;
+            return;
+        }
+        self.failures += 1;
+        if self.failures >= MAX_ATTEMPTS {
+            self.status = DeliveryStatus::Failed;
+            self.next_attempt = None;
+        } else {
+            self.status = DeliveryStatus::Pending;
+            let backoff = 4_i64.pow(self.failures as u32 - 1);
+            self.next_attempt = Some(at + Duration::minutes(backoff));
+        }
+    }
+
+    /// Queues the delivery again with a fresh set of attempts; the log of
+    /// earlier attempts is kept.
+    pub fn retry(&mut self) {
+        self.status = DeliveryStatus::Pending;
+        self.failures = 0;
+        self.next_attempt = Some(Utc::now());
+    }
+
+    pub fn is_due(&self, now: DateTime<Utc>) -> bool {
+        self.status == DeliveryStatus::Pending
+            && self
+                .next_attempt
+                .is_some_and(|next_attempt| next_attempt <= now)
+    }
+}
+
+impl Entity for Delivery {
+    const TREE: &'static str = "deliveries";
+
+    fn key(&self) -> String {
+        format!("{}/{}", self.search_id, self.delivery_id)
+    }
+
+    fn indexes(&self) -> Vec<(&'static str, String)> {
+        vec![("status", self.status.to_string())]
+    }
+}
+
+pub trait DeliveryState {
+    fn get_delivery(&self, search_id: &str, delivery_id: &str) -> Option<Delivery>;
+    fn set_delivery(&self, delivery: &Delivery);
+    fn list_deliveries(&self, search_id: &str) -> Vec<Delivery>;
+    fn list_due_deliveries(&self, now: DateTime<Utc>) -> Vec<Delivery>;
+}
+
+impl DeliveryState for StateMach {
+    fn get_delivery(&self, search_id: &str, delivery_id: &str) -> Option<Delivery> {
+        self.repo::<Delivery>()
+            .get(&format!("{}/{}", search_id, delivery_id))
+    }
+
+    fn set_delivery(&self, delivery: &Delivery) {
+        self.repo::<Delivery>().put(delivery);
+    }
+
+    /// Newest first.
+    fn list_deliveries(&self, search_id: &str) -> Vec<Delivery> {
+        self.repo::<Delivery>()
+            .scan_prefix(&format!("{}/", search_id))
+            .rev()
+            .collect()
+    }
+
+    fn list_due_deliveries(&self, now: DateTime<Utc>) -> Vec<Delivery> {
+        self.repo::<Delivery>()
+            .find_by("status", &DeliveryStatus::Pending.to_string())
+            .into_iter()
+            .filter(|delivery| delivery.is_due(now))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_delivery_backoff() {
+        let payload = AlertPayload {
+            search_id: "nas".to_string(),
+            name: "NAS".to_string(),
+            query: "neural architecture search".to_string(),
+            run_at: Utc::now(),
+            papers: vec![],
+        };
+        let mut delivery = Delivery::new(&payload, DeliveryChannel::Webhook, "http://x", 0);
+        let start = Utc::now();
+        assert!(delivery.is_due(start));
+
+        delivery.record(start, Err("HTTP 503".to_string()));
+        assert_eq!(delivery.status, DeliveryStatus::Pending);
+        assert_eq!(delivery.next_attempt, Some(start + Duration::minutes(1)));
+        assert!(!delivery.is_due(start));
+
+        delivery.record(start, Err("HTTP 503".to_string()));
+        assert_eq!(delivery.next_attempt, Some(start + Duration::minutes(4)));
+        for _ in 2..MAX_ATTEMPTS {
+            delivery.record(start, Err("HTTP 503".to_string()));
+        }
+        assert_eq!(delivery.status, DeliveryStatus::Failed);
+        assert!(!delivery.is_due(start + Duration::days(1)));
+
+        delivery.retry();
+        delivery.record(Utc::now(), Ok("HTTP 200 OK".to_string()));
+        assert_eq!(delivery.status, DeliveryStatus::Delivered);
+        assert_eq!(delivery.attempts.len(), MAX_ATTEMPTS + 1);
+    }
+}
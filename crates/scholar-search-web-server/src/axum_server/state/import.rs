@@ -0,0 +1,194 @@
+use crate::axum_server::state::{repo::Entity, StateMach};
+use crate::citation::{
+    import::{ImportFormat, ImportedEntry},
+    resolve::{MatchCandidate, CANDIDATE_THRESHOLD, MATCH_THRESHOLD},
+};
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum ImportEntryStatus {
+    Pending,
+    Matched,
+    Ambiguous,
+    Unmatched,
+    Accepted,
+    Rejected,
+    Imported,
+}
+
+impl fmt::Display for ImportEntryStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportEntryStatus::Pending => write!(f, "pending"),
+            ImportEntryStatus::Matched => write!(f, "matched"),
+            ImportEntryStatus::Ambiguous => write!(f, "ambiguous"),
+            ImportEntryStatus::Unmatched => write!(f, "unmatched"),
+            ImportEntryStatus::Accepted => write!(f, "accepted"),
+            ImportEntryStatus::Rejected => write!(f, "rejected"),
+            ImportEntryStatus::Imported => write!(f, "imported"),
+        }
+    }
+}
+
+/// One parsed entry with its S2 candidates; `selected` is the paper that
+/// will be bookmarked when the batch is committed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ImportEntry {
+    pub index: usize,
+    pub entry: ImportedEntry,
+    pub candidates: Vec<MatchCandidate>,
+    pub selected: Option<String>,
+    pub status: ImportEntryStatus,
+}
+
+impl ImportEntry {
+    /// Stores the resolver result, auto-selecting a single confident match.
+    pub fn set_candidates(&mut self, candidates: Vec<MatchCandidate>) {
+        let best = candidates.first().map(|c| c.confidence).unwrap_or_default();
+        // a runner-up close behind the best title hit needs a human decision
+        let contested = candidates
+            .get(1)
+            .is_some_and(|c| best - c.confidence < 0.05);
+        self.status = if best >= MATCH_THRESHOLD && !contested {
+            ImportEntryStatus::Matched
+        } else if best >= CANDIDATE_THRESHOLD {
+            ImportEntryStatus::Ambiguous
+        } else {
+            ImportEntryStatus::Unmatched
+        };
+        self.selected = match self.status {
+            ImportEntryStatus::Matched => candidates.first().map(|c| c.paper_id.to_owned()),
+            _ => None,
+        };
+        self.candidates = candidates;
+    }
+
+    /// Whether committing the batch should bookmark this entry.
+    pub fn is_ready(&self) -> bool {
+        self.selected.is_some()
+            && matches!(
+                self.status,
+                ImportEntryStatus::Matched | ImportEntryStatus::Accepted
+            )
+    }
+}
+
+/// An uploaded bibliography file awaiting review.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ImportBatch {
+    pub import_id: String,
+    pub file_name: String,
+    pub format: ImportFormat,
+    pub collection_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub entries: Vec<ImportEntry>,
+}
+
+impl ImportBatch {
+    pub fn new(
+        file_name: &str,
+        format: ImportFormat,
+        collection_id: Option<String>,
+        entries: Vec<ImportedEntry>,
+    ) -> Self {
+        let created_at = Utc::now();
+        Self {
+            import_id: created_at.format("%Y%m%d%H%M%S%3f").to_string(),
+            file_name: file_name.to_string(),
+            format,
+            collection_id,
+            created_at,
+            entries: entries
+                .into_iter()
+                .enumerate()
+                .map(|(index, entry)| ImportEntry {
+                    index,
+                    entry,
+                    candidates: vec![],
+                    selected: None,
+                    status: ImportEntryStatus::Pending,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn count(&self, status: ImportEntryStatus) -> usize {
+        self.entries.iter().filter(|e| e.status == status).count()
+    }
+
+    pub fn is_resolved(&self) -> bool {
+        self.count(ImportEntryStatus::Pending) == 0
+    }
+}
+
+impl Entity for ImportBatch {
+    const TREE: &'static str = "imports";
+
+    fn key(&self) -> String {
+        self.import_id.to_owned()
+    }
+}
+
+pub trait ImportState {
+    fn get_import(&self, import_id: &str) -> Option<ImportBatch>;
+    fn set_import(&self, batch: &ImportBatch);
+    fn list_imports(&self) -> Vec<ImportBatch>;
+}
+
+impl ImportState for StateMach {
+    fn get_import(&self, import_id: &str) -> Option<ImportBatch> {
+        self.repo::<ImportBatch>().get(import_id)
+    }
+
+    fn set_import(&self, batch: &ImportBatch) {
+        self.repo::<ImportBatch>().put(batch);
+    }
+
+    /// Newest first; the ids are timestamps so the tree order is creation order.
+    fn list_imports(&self) -> Vec<ImportBatch> {
+        self.repo::<ImportBatch>().iter().rev().collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::citation::resolve::MatchMethod;
+
+    fn candidate(paper_id: &str, confidence: f64) -> MatchCandidate {
+        MatchCandidate {
+            paper_id: paper_id.to_string(),
+            title: paper_id.to_string(),
+            authors: "".to_string(),
+            year: None,
+            venue: None,
+            method: MatchMethod::Title,
+            confidence,
+        }
+    }
+
+    #[test]
+    fn test_set_candidates() {
+        let mut batch = ImportBatch::new(
+            "refs.bib",
+            ImportFormat::Bibtex,
+            None,
+            vec![ImportedEntry::default(); 4],
+        );
+        batch.entries[0].set_candidates(vec![candidate("a", 0.97), candidate("b", 0.6)]);
+        batch.entries[1].set_candidates(vec![candidate("a", 0.95), candidate("b", 0.93)]);
+        batch.entries[2].set_candidates(vec![candidate("a", 0.7)]);
+        assert_eq!(batch.entries[0].status, ImportEntryStatus::Matched);
+        assert_eq!(batch.entries[0].selected.as_deref(), Some("a"));
+        assert!(batch.entries[0].is_ready());
+        assert_eq!(batch.entries[1].status, ImportEntryStatus::Ambiguous);
+        assert_eq!(batch.entries[2].status, ImportEntryStatus::Ambiguous);
+        assert!(!batch.entries[2].is_ready());
+        assert!(!batch.is_resolved());
+        batch.entries[3].set_candidates(vec![]);
+        assert_eq!(batch.entries[3].status, ImportEntryStatus::Unmatched);
+        assert!(batch.is_resolved());
+    }
+}
@@ -0,0 +1,434 @@
+use crate::axum_server::state::{repo::Entity, StateMach};
+use crate::semantic_scholar_api::data::PaperDetail;
+use chrono::{DateTime, Utc};
+use convert_case::{Case, Casing};
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+/// Held by `create_collection` from the name check to the insert, so two
+/// creates of one name can not both pass the check.
+static CREATE_COLLECTION: Mutex<()> = Mutex::new(());
+
+/// A paper saved to the personal library, with the S2 detail snapshot taken
+/// at the time it was bookmarked.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Bookmark {
+    pub paper_id: String,
+    pub paper: PaperDetail,
+    pub collections: Vec<String>,
+    pub tags: Vec<String>,
+    pub note: String,
+    pub date_added: DateTime<Utc>,
+}
+
+impl Bookmark {
+    pub fn new(paper: PaperDetail) -> Self {
+        Self {
+            paper_id: paper.paper_id.to_owned(),
+            // the embedding is large and not needed for the library views
+            paper: PaperDetail {
+                embedding: None,
+                ..paper
+            },
+            collections: vec![],
+            tags: vec![],
+            note: String::new(),
+            date_added: Utc::now(),
+        }
+    }
+}
+
+/// A user-defined folder of bookmarks; `parent_id` allows nesting.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Collection {
+    pub collection_id: String,
+    pub name: String,
+    pub parent_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Collection {
+    /// A collection keyed by its kebab-case name; `create_collection` adds a
+    /// suffix where that id is taken.
+    pub fn new(name: &str, parent_id: Option<String>) -> Self {
+        Self {
+            collection_id: name.to_case(Case::Kebab),
+            name: name.trim().to_string(),
+            parent_id,
+            created_at: Utc::now(),
+        }
+    }
+}
+
+impl Entity for Bookmark {
+    const TREE: &'static str = "bookmarks";
+
+    fn key(&self) -> String {
+        self.paper_id.to_owned()
+    }
+
+    fn indexes(&self) -> Vec<(&'static str, String)> {
+        let mut indexes = self
+            .collections
+            .iter()
+            .map(|c| ("collection", c.to_owned()))
+            .collect::<Vec<_>>();
+        if let Some(doi) = self
+            .paper
+            .external_ids
+            .as_ref()
+            .and_then(|ids| ids.doi.as_ref())
+        {
+            indexes.push(("doi", doi.to_lowercase()));
+        }
+        indexes
+    }
+}
+
+impl Entity for Collection {
+    const TREE: &'static str = "collections";
+
+    fn key(&self) -> String {
+        self.collection_id.to_owned()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Default)]
+pub enum LibrarySort {
+    #[default]
+    DateAdded,
+    Title,
+    Year,
+    CitationCount,
+}
+
+impl fmt::Display for LibrarySort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LibrarySort::DateAdded => write!(f, "date_added"),
+            LibrarySort::Title => write!(f, "title"),
+            LibrarySort::Year => write!(f, "year"),
+            LibrarySort::CitationCount => write!(f, "citation_count"),
+        }
+    }
+}
+
+impl FromStr for LibrarySort {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "date_added" => Ok(LibrarySort::DateAdded),
+            "title" => Ok(LibrarySort::Title),
+            "year" => Ok(LibrarySort::Year),
+            "citation_count" => Ok(LibrarySort::CitationCount),
+            _ => Err(anyhow::anyhow!("unknown library sort: {}", s)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct LibraryFilter {
+    pub collection: Option<String>,
+    pub tag: Option<String>,
+    pub query: Option<String>,
+    pub sort: LibrarySort,
+    pub ascending: bool,
+}
+
+impl LibraryFilter {
+    pub fn apply(&self, bookmarks: Vec<Bookmark>) -> Vec<Bookmark> {
+        let query = self.query.as_ref().map(|q| q.to_lowercase());
+        let mut rows = bookmarks
+            .into_iter()
+            .filter(|b| match &self.collection {
+                Some(c) => b.collections.contains(c),
+                None => true,
+            })
+            .filter(|b| match &self.tag {
+                Some(t) => b.tags.contains(t),
+                None => true,
+            })
+            .filter(|b| match &query {
+                Some(q) => {
+                    b.paper.title.to_lowercase().contains(q)
+                        || b.note.to_lowercase().contains(q)
+                        || b.paper
+                            .authors
+                            .iter()
+                            .flatten()
+                            .any(|a| a.name.to_lowercase().contains(q))
+                }
+                None => true,
+            })
+            .collect::<Vec<Bookmark>>();
+
+        rows.sort_by(|a, b| match self.sort {
+            LibrarySort::DateAdded => a.date_added.cmp(&b.date_added),
+            LibrarySort::Title => a
+                .paper
+                .title
+                .to_lowercase()
+                .cmp(&b.paper.title.to_lowercase()),
+            LibrarySort::Year => a.paper.year.cmp(&b.paper.year),
+            LibrarySort::CitationCount => a.paper.citation_count.cmp(&b.paper.citation_count),
+        });
+        if !self.ascending {
+            rows.reverse();
+        }
+        rows
+    }
+}
+
+/// Splits a comma separated form value into trimmed, de-duplicated tags.
+pub fn parse_tags(raw: &str) -> Vec<String> {
+    let mut tags: Vec<String> = vec![];
+    for tag in raw.split(',').map(|t| t.trim().to_lowercase()) {
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+pub trait LibraryState {
+    fn get_bookmark(&self, paper_id: &str) -> Option<Bookmark>;
+    fn set_bookmark(&self, bookmark: &Bookmark);
+    fn remove_bookmark(&self, paper_id: &str);
+    fn list_bookmarks(&self) -> Vec<Bookmark>;
+    fn list_collection_bookmarks(&self, collection_id: &str) -> Vec<Bookmark>;
+    fn find_bookmarks_by_doi(&self, doi: &str) -> Vec<Bookmark>;
+    fn list_tags(&self) -> Vec<String>;
+    fn get_collection(&self, collection_id: &str) -> Option<Collection>;
+    fn set_collection(&self, collection: &Collection);
+    fn remove_collection(&self, collection_id: &str);
+    fn list_collections(&self) -> Vec<Collection>;
+    /// The collection named `name` directly under `parent_id`, ignoring
+    /// case.
+    fn find_collection(&self, name: &str, parent_id: Option<&str>) -> Option<Collection>;
+    /// Adds a collection under a fresh id, so `My Papers` and `my-papers`,
+    /// or one name under two parents, don't overwrite each other. Fails when
+    /// the name is empty or already used under the same parent.
+    fn create_collection(
+        &self,
+        name: &str,
+        parent_id: Option<String>,
+    ) -> Result<Collection, CollectionError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CollectionError {
+    EmptyName,
+    Exists(Collection),
+}
+
+impl fmt::Display for CollectionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CollectionError::EmptyName => write!(f, "collection name is empty"),
+            CollectionError::Exists(c) => {
+                write!(f, "{}: collection exists as {}", c.name, c.collection_id)
+            }
+        }
+    }
+}
+
+impl LibraryState for StateMach {
+    fn get_bookmark(&self, paper_id: &str) -> Option<Bookmark> {
+        self.repo::<Bookmark>().get(paper_id)
+    }
+
+    fn set_bookmark(&self, bookmark: &Bookmark) {
+        self.repo::<Bookmark>().put(bookmark);
+    }
+
+    fn remove_bookmark(&self, paper_id: &str) {
+        self.repo::<Bookmark>().remove(paper_id);
+    }
+
+    fn list_bookmarks(&self) -> Vec<Bookmark> {
+        self.repo::<Bookmark>().iter().collect()
+    }
+
+    fn list_collection_bookmarks(&self, collection_id: &str) -> Vec<Bookmark> {
+        self.repo::<Bookmark>().find_by("collection", collection_id)
+    }
+
+    fn find_bookmarks_by_doi(&self, doi: &str) -> Vec<Bookmark> {
+        self.repo::<Bookmark>().find_by("doi", &doi.to_lowercase())
+    }
+
+    fn list_tags(&self) -> Vec<String> {
+        let mut tags = self
+            .list_bookmarks()
+            .into_iter()
+            .flat_map(|b| b.tags)
+            .collect::<Vec<String>>();
+        tags.sort();
+        tags.dedup();
+        tags
+    }
+
+    fn get_collection(&self, collection_id: &str) -> Option<Collection> {
+        self.repo::<Collection>().get(collection_id)
+    }
+
+    fn set_collection(&self, collection: &Collection) {
+        self.repo::<Collection>().put(collection);
+    }
+
+    fn remove_collection(&self, collection_id: &str) {
+        let Some(removed) = self.repo::<Collection>().remove(collection_id) else {
+            return;
+        };
+        // sub-collections move up to the removed collection's parent
+        let parent_id = removed.parent_id;
+        for mut child in self.list_collections() {
+            if child.parent_id.as_deref() == Some(collection_id) {
+                child.parent_id = parent_id.clone();
+                self.set_collection(&child);
+            }
+        }
+        for mut bookmark in self.list_collection_bookmarks(collection_id) {
+            bookmark.collections.retain(|c| c != collection_id);
+            self.set_bookmark(&bookmark);
+        }
+    }
+
+    fn list_collections(&self) -> Vec<Collection> {
+        self.repo::<Collection>().iter().collect()
+    }
+
+    fn find_collection(&self, name: &str, parent_id: Option<&str>) -> Option<Collection> {
+        let name = name.trim().to_lowercase();
+        self.repo::<Collection>()
+            .iter()
+            .find(|c| c.parent_id.as_deref() == parent_id && c.name.to_lowercase() == name)
+    }
+
+    fn create_collection(
+        &self,
+        name: &str,
+        parent_id: Option<String>,
+    ) -> Result<Collection, CollectionError> {
+        let collection = Collection::new(name, parent_id);
+        if collection.name.is_empty() || collection.collection_id.is_empty() {
+            return Err(CollectionError::EmptyName);
+        }
+        let _creating = CREATE_COLLECTION.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(existing) = self.find_collection(name, collection.parent_id.as_deref()) {
+            return Err(CollectionError::Exists(existing));
+        }
+        let base = collection.collection_id.to_owned();
+        for n in 1.. {
+            let collection_id = match n {
+                1 => base.to_owned(),
+                n => format!("{}-{}", base, n),
+            };
+            let candidate = Collection {
+                collection_id: collection_id.to_owned(),
+                ..collection.clone()
+            };
+            // insert only if the id is free, also against a concurrent create
+            let created =
+                self.repo::<Collection>()
+                    .update(&collection_id, |current| match current {
+                        Some(_) => Err(anyhow::anyhow!("{}: taken", collection_id)),
+                        None => Ok(candidate.clone()),
+                    });
+            if let Ok((_, created)) = created {
+                return Ok(created);
+            }
+        }
+        unreachable!()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn bookmark(paper_id: &str, title: &str, year: i32, tags: &[&str]) -> Bookmark {
+        let mut b = Bookmark::new(PaperDetail {
+            paper_id: paper_id.to_string(),
+            title: title.to_string(),
+            year,
+            ..PaperDetail::default()
+        });
+        b.tags = tags.iter().map(|t| t.to_string()).collect();
+        b
+    }
+
+    #[test]
+    fn test_library_filter() {
+        let rows = vec![
+            bookmark("a", "Neural Architecture Search", 2019, &["nas"]),
+            bookmark("b", "Attention is all you need", 2017, &["nlp"]),
+            bookmark("c", "Auto-Keras", 2018, &["nas", "automl"]),
+        ];
+        let filter = LibraryFilter {
+            tag: Some("nas".to_string()),
+            sort: LibrarySort::Year,
+            ascending: true,
+            ..LibraryFilter::default()
+        };
+        let ids = filter
+            .apply(rows.clone())
+            .into_iter()
+            .map(|b| b.paper_id)
+            .collect::<Vec<String>>();
+        assert_eq!(ids, vec!["c", "a"]);
+
+        let filter = LibraryFilter {
+            query: Some("attention".to_string()),
+            ..LibraryFilter::default()
+        };
+        assert_eq!(filter.apply(rows)[0].paper_id, "b");
+    }
+
+    #[test]
+    fn test_create_collection() {
+        let state_mach = StateMach::temporary();
+        let papers = state_mach.create_collection("My Papers", None).unwrap();
+        assert_eq!(papers.collection_id, "my-papers");
+        let other = state_mach.create_collection("my-papers", None).unwrap();
+        assert_eq!(other.collection_id, "my-papers-2");
+        let nested = state_mach
+            .create_collection("My Papers", Some(papers.collection_id.to_owned()))
+            .unwrap();
+        assert_eq!(nested.collection_id, "my-papers-3");
+        assert_eq!(
+            state_mach.create_collection(" my papers ", None),
+            Err(CollectionError::Exists(papers.clone()))
+        );
+        assert_eq!(
+            state_mach.create_collection("  ", None),
+            Err(CollectionError::EmptyName)
+        );
+        assert_eq!(state_mach.list_collections().len(), 3);
+        assert_eq!(
+            state_mach.find_collection("MY PAPERS", Some("my-papers")),
+            Some(nested)
+        );
+
+        // concurrent creates of one name make one collection
+        let created = (0..8)
+            .map(|_| {
+                let state_mach = state_mach.clone();
+                std::thread::spawn(move || state_mach.create_collection("Reading", None))
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .filter(Result::is_ok)
+            .count();
+        assert_eq!(created, 1);
+        assert_eq!(state_mach.list_collections().len(), 4);
+    }
+
+    #[test]
+    fn test_parse_tags() {
+        assert_eq!(parse_tags(" NAS, automl,,nas "), vec!["nas", "automl"]);
+    }
+}
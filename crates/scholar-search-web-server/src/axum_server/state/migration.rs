@@ -0,0 +1,266 @@
+//! Ordered data migrations, applied at startup.
+//!
+//! The store records the schema version it was last migrated to in the
+//! `meta` tree. Each migration moves the data one version up; add new ones
+//! at the end of `MIGRATIONS` and never reorder or remove them.
+
+use crate::axum_server::state::{
+    blob::{BlobStore, PdfBlobState},
+    entity_kinds,
+    store::{Store, DEFAULT_TREE},
+    PdfFile, PdfFileStatus, StateMach,
+};
+use tracing::{info, warn};
+
+const META_TREE: &str = "meta";
+const SCHEMA_VERSION_KEY: &[u8] = b"schema_version";
+
+pub struct Migration {
+    pub description: &'static str,
+    /// Returns the number of records changed, or that would be in a dry run.
+    pub run: fn(&StateMach, bool) -> anyhow::Result<usize>,
+}
+
+/// Migration `i` brings the data to schema version `i + 1`.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        description: "move file statuses out of the default tree",
+        run: migrate_file_status,
+    },
+    Migration {
+        description: "rewrite JSON records in the binary encoding",
+        run: rewrite_outdated,
+    },
+    Migration {
+        description: "copy downloaded PDFs into the blob store",
+        run: migrate_pdf_temp,
+    },
+];
+
+/// The schema version this binary writes.
+pub fn current_version() -> u32 {
+    MIGRATIONS.len() as u32
+}
+
+/// The version the store was migrated to; 0 before versions were stored.
+pub fn stored_version(store: &dyn Store) -> anyhow::Result<u32> {
+    match store.open_tree(META_TREE).get(SCHEMA_VERSION_KEY) {
+        Some(bytes) => Ok(String::from_utf8(bytes)?.parse()?),
+        None => Ok(0),
+    }
+}
+
+fn set_stored_version(store: &dyn Store, version: u32) {
+    store
+        .open_tree(META_TREE)
+        .insert(SCHEMA_VERSION_KEY, version.to_string().as_bytes());
+    store.flush();
+}
+
+#[derive(Debug, PartialEq)]
+pub struct MigrationStep {
+    pub version: u32,
+    pub description: &'static str,
+    pub changed: usize,
+}
+
+/// Runs the pending migrations in order, recording the version after each
+/// one so an interrupted run resumes where it stopped. A dry run reports
+/// what would change without writing anything.
+pub fn migrate(state_mach: &StateMach, dry_run: bool) -> anyhow::Result<Vec<MigrationStep>> {
+    let store = state_mach.store().as_ref();
+    let stored = stored_version(store)?;
+    if stored > current_version() {
+        return Err(anyhow::anyhow!(
+            "the data has schema version {}, newer than this binary's {}; \
+             run a newer scholar-search or restore a backup",
+            stored,
+            current_version()
+        ));
+    }
+    let mut steps = vec![];
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(stored as usize) {
+        let version = i as u32 + 1;
+        info!(
+            "migration {}/{}: {}{}",
+            version,
+            current_version(),
+            migration.description,
+            if dry_run { " (dry run)" } else { "" }
+        );
+        let changed = (migration.run)(state_mach, dry_run)
+            .map_err(|e| anyhow::anyhow!("migration {} failed: {}", version, e))?;
+        info!("migration {}: {} records", version, changed);
+        if !dry_run {
+            set_stored_version(store, version);
+        }
+        steps.push(MigrationStep {
+            version,
+            description: migration.description,
+            changed,
+        });
+    }
+    Ok(steps)
+}
+
+/// File statuses used to be plain strings in the default tree, keyed by
+/// paper id; they move to the `files` tree.
+fn migrate_file_status(state_mach: &StateMach, dry_run: bool) -> anyhow::Result<usize> {
+    let default_tree = state_mach.store().open_tree(DEFAULT_TREE);
+    let legacy = default_tree
+        .iter()
+        .into_iter()
+        .filter_map(|(k, v)| {
+            let paper_id = String::from_utf8(k.to_vec()).ok()?;
+            match std::str::from_utf8(&v).ok()?.parse::<PdfFileStatus>() {
+                Ok(status) => Some((paper_id, status)),
+                Err(e) => {
+                    warn!("{}: {}", paper_id, e);
+                    None
+                }
+            }
+        })
+        .collect::<Vec<(String, PdfFileStatus)>>();
+    if dry_run {
+        return Ok(legacy.len());
+    }
+    let files = state_mach.repo::<PdfFile>();
+    for (paper_id, status) in legacy.iter() {
+        if !files.contains(paper_id) {
+            files.put(&PdfFile::new(paper_id, status.clone()));
+        }
+        default_tree.remove(paper_id.as_bytes());
+    }
+    Ok(legacy.len())
+}
+
+/// Values written as JSON before the repository layer get the binary
+/// encoding and their secondary indexes.
+fn rewrite_outdated(state_mach: &StateMach, dry_run: bool) -> anyhow::Result<usize> {
+    let mut rewritten = 0;
+    for kind in entity_kinds() {
+        let count = kind.rewrite_outdated(state_mach.store(), dry_run);
+        if count > 0 {
+            info!("  {}: {} records", kind.tree(), count);
+        }
+        rewritten += count;
+    }
+    Ok(rewritten)
+}
+
+/// PDFs used to be written to `data/pdf_temp`, named after the paper id
+/// with `/` and `.` replaced; the files are left in place.
+fn migrate_pdf_temp(state_mach: &StateMach, dry_run: bool) -> anyhow::Result<usize> {
+    let blobs = BlobStore::from_env()?;
+    let legacy = state_mach
+        .repo::<PdfFile>()
+        .iter()
+        .filter(|file| state_mach.get_pdf_blob(&file.paper_id).is_none())
+        .filter_map(|file| {
+            let name = file.paper_id.replace(['/', '.'], "__");
+            let path = std::path::PathBuf::from(format!("data/pdf_temp/{}.pdf", name));
+            path.is_file().then_some((file.paper_id, path))
+        })
+        .collect::<Vec<(String, std::path::PathBuf)>>();
+    if dry_run {
+        return Ok(legacy.len());
+    }
+    Ok(copy_legacy_pdfs(state_mach, &blobs, legacy))
+}
+
+/// Stores each file as the paper's PDF and returns how many were copied.
+/// A file that can not be read, is not a PDF or does not fit the quota is
+/// logged and skipped, so one bad file does not keep the server from
+/// starting; its paper can be downloaded again.
+fn copy_legacy_pdfs(
+    state_mach: &StateMach,
+    blobs: &BlobStore,
+    legacy: Vec<(String, std::path::PathBuf)>,
+) -> usize {
+    let mut copied = 0;
+    let mut skipped = 0;
+    for (paper_id, path) in legacy {
+        let stored = match std::fs::read(&path) {
+            Ok(bytes) if bytes.starts_with(b"%PDF") => {
+                state_mach.store_pdf(blobs, &paper_id, &bytes)
+            }
+            Ok(_) => Err(anyhow::anyhow!("not a pdf")),
+            Err(e) => Err(e.into()),
+        };
+        match stored {
+            Ok(_) => copied += 1,
+            Err(e) => {
+                warn!("{}: {} skipped: {}", paper_id, path.display(), e);
+                skipped += 1;
+            }
+        }
+    }
+    if skipped > 0 {
+        warn!("  {} legacy pdfs skipped", skipped);
+    }
+    copied
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::axum_server::state::{
+        library::LibraryState, store::memory::MemoryStore, PdfFileState,
+    };
+    use std::sync::Arc;
+
+    #[test]
+    fn test_migrate() {
+        let store: Arc<dyn Store> = Arc::new(MemoryStore::default());
+        store.open_tree(DEFAULT_TREE).insert(b"abc", b"downloaded");
+        store
+            .open_tree("collections")
+            .insert(b"reading", br#"{"collection_id":"reading","name":"Reading","parent_id":null,"created_at":"2024-01-01T00:00:00Z"}"#);
+
+        let state_mach = StateMach::unmigrated(store.clone());
+        let steps = migrate(&state_mach, true).unwrap();
+        assert_eq!(
+            steps.iter().map(|s| s.changed).collect::<Vec<usize>>(),
+            vec![1, 1, 0]
+        );
+        assert_eq!(stored_version(store.as_ref()).unwrap(), 0);
+        assert_eq!(state_mach.check_file_status("abc"), PdfFileStatus::None);
+
+        let state_mach = StateMach::from_store(store.clone()).unwrap();
+        assert_eq!(stored_version(store.as_ref()).unwrap(), current_version());
+        assert_eq!(
+            state_mach.check_file_status("abc"),
+            PdfFileStatus::Downloaded
+        );
+        assert_eq!(state_mach.list_collections().len(), 1);
+        assert!(store.open_tree(DEFAULT_TREE).iter().is_empty());
+        assert!(migrate(&state_mach, false).unwrap().is_empty());
+
+        set_stored_version(store.as_ref(), current_version() + 1);
+        assert!(StateMach::from_store(store).is_err());
+    }
+
+    #[test]
+    fn test_copy_legacy_pdfs() {
+        let state_mach = StateMach::temporary();
+        let dir = std::env::temp_dir().join(format!("scholar-legacy-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let blobs = BlobStore::new(dir.join("blobs"), Some(20));
+        let file = |name: &str, bytes: &[u8]| {
+            let path = dir.join(name);
+            std::fs::write(&path, bytes).unwrap();
+            path
+        };
+        let legacy = vec![
+            ("a".to_string(), file("a.pdf", b"%PDF-1.4 one")),
+            ("b".to_string(), dir.join("missing.pdf")),
+            ("c".to_string(), file("c.pdf", b"<html>")),
+            // over the quota
+            ("d".to_string(), file("d.pdf", b"%PDF-1.4 two")),
+        ];
+        assert_eq!(copy_legacy_pdfs(&state_mach, &blobs, legacy), 1);
+        assert!(state_mach.get_pdf_blob("a").is_some());
+        assert!(state_mach.get_pdf_blob("d").is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
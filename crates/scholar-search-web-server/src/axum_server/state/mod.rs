@@ -1,69 +1,164 @@
+pub mod backup;
+pub mod bibliography;
+pub mod blob;
+pub mod delivery;
+pub mod import;
+pub mod library;
+pub mod migration;
+pub mod paper;
+pub mod repo;
+pub mod search;
+pub mod source;
+pub mod store;
+pub mod tracking;
+
+use crate::axum_server::state::{
+    repo::{kind, Entity, EntityKind, Repository},
+    migration::migrate,
+    store::{open_store, Store, DEFAULT_STORE},
+};
+use chrono::{DateTime, Utc};
 use serde_derive::{Deserialize, Serialize};
-use sled;
 use std::fmt;
 use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 
+/// The application state: one `Store` with a tree per entity, each accessed
+/// through a typed `Repository`.
 #[derive(Debug, Clone)]
 pub struct StateMach {
-    db: sled::Db,
+    store: Arc<dyn Store>,
 }
 
 impl StateMach {
+    /// Opens the store named by `SCHOLAR_SEARCH_STORE`.
     pub fn new() -> Self {
-        let db = sled::open("data/state_mach").unwrap();
-        Self { db }
+        Self::open(&store_spec()).unwrap()
     }
 
-    pub fn get(&self, key: &str) -> Option<String> {
-        self.db
-            .get(key)
-            .unwrap()
-            .map(|x| String::from_utf8(x.to_vec()).unwrap())
+    pub fn open(spec: &str) -> anyhow::Result<Self> {
+        Self::from_store(open_store(spec)?)
     }
 
-    pub fn set(&self, key: &str, value: &str) {
-        if let Some(_) = self.get(key) {
-            self.db.insert(key, value).unwrap();
-            return;
-        }
-        self.db.insert(key, value).unwrap();
+    /// Brings the store up to the current schema; fails on data written by
+    /// a newer version.
+    pub fn from_store(store: Arc<dyn Store>) -> anyhow::Result<Self> {
+        let state_mach = Self::unmigrated(store);
+        migrate(&state_mach, false)?;
+        Ok(state_mach)
     }
 
-    pub fn delete(&self, key: &str) {
-        self.db.remove(key).unwrap();
+    /// The store as it is, for inspecting pending migrations.
+    pub fn unmigrated(store: Arc<dyn Store>) -> Self {
+        Self { store }
     }
 
-    pub fn get_all(&self) -> Vec<(String, String)> {
-        self.db
-            .iter()
-            .map(|x| {
-                let (k, v) = x.unwrap();
-                (
-                    String::from_utf8(k.to_vec()).unwrap(),
-                    String::from_utf8(v.to_vec()).unwrap(),
-                )
-            })
-            .collect()
+    /// A throwaway in-memory store, for tests.
+    #[cfg(test)]
+    pub fn temporary() -> Self {
+        Self::open("memory:").unwrap()
+    }
+
+    pub fn repo<E: Entity>(&self) -> Repository<E> {
+        Repository::new(&self.store)
     }
 
-    pub fn clear(&self) {
-        self.db.clear().unwrap();
+    pub fn store(&self) -> &Arc<dyn Store> {
+        &self.store
     }
 
+    #[allow(dead_code)]
     pub fn snapshot(&self) {
-        self.db.flush().unwrap();
+        self.store.flush();
     }
 
-    pub fn println(&self) {
-        println!("StateMach");
-        println!("---------");
-        for (k, v) in self.get_all() {
-            println!("{}: {}", k, v);
+    /// Applies `f` to the paper's file with a compare and swap, checks that
+    /// a status change is a valid transition and appends it to the history.
+    fn update_file<F>(&self, paper_id: &str, mut f: F) -> anyhow::Result<PdfFile>
+    where
+        F: FnMut(&mut PdfFile) -> anyhow::Result<()>,
+    {
+        let (old, file) = self.repo::<PdfFile>().update(paper_id, |current| {
+            let mut file = current
+                .cloned()
+                .unwrap_or_else(|| PdfFile::new(paper_id, PdfFileStatus::None));
+            let from = file.status.clone();
+            f(&mut file)?;
+            if file.status != from {
+                if !from.can_transition(&file.status) {
+                    return Err(anyhow::anyhow!(
+                        "{}: can not go from {} to {}",
+                        paper_id,
+                        from,
+                        file.status
+                    ));
+                }
+                file.updated_at = Utc::now();
+            }
+            Ok(file)
+        })?;
+        let from = old.map(|old| old.status).unwrap_or(PdfFileStatus::None);
+        if from != file.status {
+            // history keys are timestamps, keep them strictly increasing
+            let last = self
+                .repo::<PdfTransition>()
+                .scan_prefix(&format!("{}/", paper_id))
+                .next_back();
+            let at = match last {
+                Some(last) if last.at >= file.updated_at => {
+                    last.at + chrono::Duration::microseconds(1)
+                }
+                _ => file.updated_at,
+            };
+            self.repo::<PdfTransition>().put(&PdfTransition {
+                paper_id: paper_id.to_string(),
+                from,
+                to: file.status.clone(),
+                at,
+            });
         }
-        println!();
-        // println!("{:?}", self.db.stats().unwrap());
-        println!("---------");
+        Ok(file)
     }
+
+    /// Counts the failures of each stage since the paper was last reset.
+    fn count_failures(&self, paper_id: &str, stage: PdfStage) -> u32 {
+        self.list_file_history(paper_id)
+            .into_iter()
+            .rev()
+            .take_while(|t| t.to != PdfFileStatus::None)
+            .filter(|t| matches!(t.to, PdfFileStatus::Failed { stage: s, .. } if s == stage))
+            .count() as u32
+    }
+}
+
+/// The store `StateMach::new` opens: `SCHOLAR_SEARCH_STORE`, or
+/// `sled:data/state_mach`.
+pub fn store_spec() -> String {
+    std::env::var("SCHOLAR_SEARCH_STORE").unwrap_or(DEFAULT_STORE.to_string())
+}
+
+/// Every entity type with a tree of its own.
+pub fn entity_kinds() -> Vec<Box<dyn EntityKind>> {
+    vec![
+        kind::<library::Bookmark>(),
+        kind::<library::Collection>(),
+        kind::<import::ImportBatch>(),
+        kind::<search::SavedSearch>(),
+        kind::<search::SearchRun>(),
+        kind::<search::InboxItem>(),
+        kind::<delivery::Delivery>(),
+        kind::<tracking::TrackedPaper>(),
+        kind::<crate::citation::growth::CitationSnapshot>(),
+        kind::<tracking::CitationAlert>(),
+        kind::<tracking::CitationAlertEvent>(),
+        kind::<paper::CachedPaper>(),
+        kind::<PdfFile>(),
+        kind::<PdfTransition>(),
+        kind::<blob::PdfBlob>(),
+        kind::<source::PdfSourceLog>(),
+        kind::<bibliography::PaperBibliography>(),
+    ]
 }
 
 impl Default for StateMach {
@@ -74,7 +169,7 @@ impl Default for StateMach {
 
 impl Drop for StateMach {
     fn drop(&mut self) {
-        self.db.flush().unwrap();
+        self.store.flush();
     }
 }
 
@@ -84,15 +179,101 @@ impl std::fmt::Display for StateMach {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+/// A step of the PDF pipeline that can fail.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PdfStage {
+    Download,
+    Convert,
+    Index,
+    Patch,
+}
+
+impl PdfStage {
+    /// The status a paper is in while waiting for this stage; a retry goes
+    /// back to it.
+    pub fn input(&self) -> PdfFileStatus {
+        match self {
+            PdfStage::Download => PdfFileStatus::Accpeted,
+            PdfStage::Convert => PdfFileStatus::Downloaded,
+            PdfStage::Index => PdfFileStatus::Converted,
+            PdfStage::Patch => PdfFileStatus::Indexed,
+        }
+    }
+
+    pub fn output(&self) -> PdfFileStatus {
+        match self {
+            PdfStage::Download => PdfFileStatus::Downloaded,
+            PdfStage::Convert => PdfFileStatus::Converted,
+            PdfStage::Index => PdfFileStatus::Indexed,
+            PdfStage::Patch => PdfFileStatus::Patched,
+        }
+    }
+}
+
+impl fmt::Display for PdfStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PdfStage::Download => write!(f, "download"),
+            PdfStage::Convert => write!(f, "convert"),
+            PdfStage::Index => write!(f, "index"),
+            PdfStage::Patch => write!(f, "patch"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub enum PdfFileStatus {
-    None = 0,
-    Accpeted = 1,
-    Downloaded = 2,
-    Converted = 3,
-    Indexed = 4,
-    Patched = 5,
+    None,
+    Accpeted,
+    Downloaded,
+    Converted,
+    Indexed,
+    Patched,
+    Failed {
+        stage: PdfStage,
+        reason: String,
+        attempts: u32,
+    },
+    Skipped,
 }
+
+impl PdfFileStatus {
+    /// Status names, in pipeline order; also the values of the `status` index.
+    pub const NAMES: [&'static str; 8] = [
+        "none",
+        "accpeted",
+        "downloaded",
+        "converted",
+        "indexed",
+        "patched",
+        "failed",
+        "skipped",
+    ];
+
+    /// Whether the pipeline may move from this status to `to`. A failed
+    /// stage goes back to its input status on retry, and every status can be
+    /// reset to `None`.
+    pub fn can_transition(&self, to: &PdfFileStatus) -> bool {
+        use PdfFileStatus::*;
+        match (self, to) {
+            (_, None) => true,
+            (None, Accpeted | Skipped) => true,
+            (Accpeted, Skipped) => true,
+            (Failed { .. }, Skipped) => true,
+            (Failed { stage, .. }, to) if *to == stage.input() => true,
+            (from, Failed { stage, .. }) => *from == stage.input(),
+            (from, to) => [
+                PdfStage::Download,
+                PdfStage::Convert,
+                PdfStage::Index,
+                PdfStage::Patch,
+            ]
+            .iter()
+            .any(|stage| *from == stage.input() && *to == stage.output()),
+        }
+    }
+}
+
 impl fmt::Display for PdfFileStatus {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -102,52 +283,391 @@ impl fmt::Display for PdfFileStatus {
             PdfFileStatus::Converted => write!(f, "converted"),
             PdfFileStatus::Indexed => write!(f, "indexed"),
             PdfFileStatus::Patched => write!(f, "patched"),
+            PdfFileStatus::Failed { .. } => write!(f, "failed"),
+            PdfFileStatus::Skipped => write!(f, "skipped"),
+        }
+    }
+}
+
+/// Parses the statuses stored as plain strings before the `files` tree;
+/// failures were never stored that way.
+impl std::str::FromStr for PdfFileStatus {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(PdfFileStatus::None),
+            "accpeted" => Ok(PdfFileStatus::Accpeted),
+            "downloaded" => Ok(PdfFileStatus::Downloaded),
+            "converted" => Ok(PdfFileStatus::Converted),
+            "indexed" => Ok(PdfFileStatus::Indexed),
+            "patched" => Ok(PdfFileStatus::Patched),
+            "skipped" => Ok(PdfFileStatus::Skipped),
+            _ => Err(anyhow::anyhow!("unknown file status: {}", s)),
+        }
+    }
+}
+
+/// The processing state of a paper's open access PDF.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct PdfFile {
+    pub paper_id: String,
+    pub status: PdfFileStatus,
+    pub updated_at: DateTime<Utc>,
+    /// where the PDF is downloaded from, kept for retries
+    #[serde(default)]
+    pub url: Option<String>,
+    /// set while a worker runs the next stage
+    #[serde(default)]
+    pub lease: Option<PdfLease>,
+}
+
+impl PdfFile {
+    pub fn new(paper_id: &str, status: PdfFileStatus) -> Self {
+        Self {
+            paper_id: paper_id.to_string(),
+            status,
+            updated_at: Utc::now(),
+            url: None,
+            lease: None,
         }
     }
 }
 
+/// A worker's claim on the next stage of a paper; it lapses at `expires_at`
+/// so the work of a crashed worker can be claimed again.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct PdfLease {
+    pub owner: String,
+    pub stage: PdfStage,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl PdfLease {
+    pub fn is_live(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at > now
+    }
+}
+
+/// Why a claim was refused.
+#[derive(Debug, PartialEq, Clone)]
+pub enum ClaimError {
+    /// another worker holds a live lease
+    InProgress(PdfLease),
+    /// the paper is not waiting for the stage
+    NotClaimable(PdfFileStatus),
+    /// the file record could not be read or written
+    Other(String),
+}
+
+impl fmt::Display for ClaimError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClaimError::InProgress(lease) => write!(
+                f,
+                "{} already in progress, claimed by {} until {}",
+                lease.stage, lease.owner, lease.expires_at
+            ),
+            ClaimError::NotClaimable(status) => write!(f, "nothing to do in status {}", status),
+            ClaimError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ClaimError {}
+
+/// A unique lease owner for this process.
+pub fn worker_id() -> String {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    format!(
+        "{}-{}",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+impl Entity for PdfFile {
+    const TREE: &'static str = "files";
+
+    fn key(&self) -> String {
+        self.paper_id.to_owned()
+    }
+
+    fn indexes(&self) -> Vec<(&'static str, String)> {
+        vec![("status", self.status.to_string())]
+    }
+}
+
+/// One entry of a paper's append-only pipeline history.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct PdfTransition {
+    pub paper_id: String,
+    pub from: PdfFileStatus,
+    pub to: PdfFileStatus,
+    pub at: DateTime<Utc>,
+}
+
+impl Entity for PdfTransition {
+    const TREE: &'static str = "file_history";
+
+    fn key(&self) -> String {
+        format!(
+            "{}/{}",
+            self.paper_id,
+            self.at.format("%Y%m%dT%H%M%S%.6f")
+        )
+    }
+}
+
 pub trait PdfFileState {
+    fn get_file(&self, paper_id: &str) -> Option<PdfFile>;
     fn check_file_status(&self, paper_id: &str) -> PdfFileStatus;
-    fn set_file_status_raw(&self, paper_id: &str, status: &str);
-    fn set_file_status(&self, paper_id: &str, status: PdfFileStatus);
+    /// Moves a paper to `to`, recording the transition, or fails if the
+    /// pipeline does not allow it.
+    fn transition_file(&self, paper_id: &str, to: PdfFileStatus) -> anyhow::Result<PdfFile>;
+    /// Puts a paper into the pipeline, remembering its S2 open access url,
+    /// if it has one.
+    fn accept_file(&self, paper_id: &str, url: &str) -> anyhow::Result<PdfFile>;
+    /// Leases the paper to `owner` for `stage`, if it is waiting for the
+    /// stage and no other live lease exists.
+    fn claim_file(
+        &self,
+        paper_id: &str,
+        stage: PdfStage,
+        owner: &str,
+        lease: chrono::Duration,
+    ) -> Result<PdfFile, ClaimError>;
+    /// Ends `owner`'s lease with the stage's output status, or a failure of
+    /// the stage; attempts count the failures since the paper was last reset.
+    fn finish_claim(
+        &self,
+        paper_id: &str,
+        owner: &str,
+        result: Result<(), String>,
+    ) -> anyhow::Result<PdfFile>;
+    /// Sends a failed paper back to the input status of the failed stage.
+    fn retry_file(&self, paper_id: &str) -> anyhow::Result<PdfFile>;
+    fn reset_file(&self, paper_id: &str) -> anyhow::Result<PdfFile>;
+    fn list_file_history(&self, paper_id: &str) -> Vec<PdfTransition>;
+    /// Files whose status has the given name; `failed` covers every stage.
+    fn list_files_by_status(&self, status: &str) -> Vec<PdfFile>;
 }
 
 impl PdfFileState for StateMach {
+    fn get_file(&self, paper_id: &str) -> Option<PdfFile> {
+        self.repo::<PdfFile>().get(paper_id)
+    }
+
     fn check_file_status(&self, paper_id: &str) -> PdfFileStatus {
-        match self.get(paper_id) {
-            Some(paper_val) => match paper_val.as_str() {
-                "accpeted" => PdfFileStatus::Accpeted,
-                "downloaded" => PdfFileStatus::Downloaded,
-                "converted" => PdfFileStatus::Converted,
-                "indexed" => PdfFileStatus::Indexed,
-                "patched" => PdfFileStatus::Patched,
-                _ => PdfFileStatus::None,
-            },
-            None => PdfFileStatus::None,
-        }
+        self.get_file(paper_id)
+            .map(|file| file.status)
+            .unwrap_or(PdfFileStatus::None)
+    }
+
+    fn transition_file(&self, paper_id: &str, to: PdfFileStatus) -> anyhow::Result<PdfFile> {
+        self.update_file(paper_id, |file| {
+            file.status = to.clone();
+            file.lease = None;
+            Ok(())
+        })
     }
 
-    fn set_file_status_raw(&self, paper_id: &str, status: &str) {
-        self.set(paper_id, status);
+    fn accept_file(&self, paper_id: &str, url: &str) -> anyhow::Result<PdfFile> {
+        self.update_file(paper_id, |file| {
+            file.status = PdfFileStatus::Accpeted;
+            file.url = Some(url.to_string()).filter(|url| !url.is_empty());
+            Ok(())
+        })
     }
-    fn set_file_status(&self, paper_id: &str, status: PdfFileStatus) {
-        self.set(paper_id, status.to_string().as_str());
+
+    fn claim_file(
+        &self,
+        paper_id: &str,
+        stage: PdfStage,
+        owner: &str,
+        lease: chrono::Duration,
+    ) -> Result<PdfFile, ClaimError> {
+        let now = Utc::now();
+        self.update_file(paper_id, |file| {
+            if let Some(lease) = file.lease.as_ref().filter(|l| l.is_live(now)) {
+                return Err(ClaimError::InProgress(lease.clone()).into());
+            }
+            if file.status != stage.input() {
+                return Err(ClaimError::NotClaimable(file.status.clone()).into());
+            }
+            file.lease = Some(PdfLease {
+                owner: owner.to_string(),
+                stage,
+                expires_at: now + lease,
+            });
+            Ok(())
+        })
+        .map_err(|e| {
+            e.downcast::<ClaimError>()
+                .unwrap_or_else(|e| ClaimError::Other(e.to_string()))
+        })
     }
-}
 
+    fn finish_claim(
+        &self,
+        paper_id: &str,
+        owner: &str,
+        result: Result<(), String>,
+    ) -> anyhow::Result<PdfFile> {
+        self.update_file(paper_id, |file| {
+            // a lapsed lease still counts unless someone else took over
+            let stage = match &file.lease {
+                Some(lease) if lease.owner == owner => lease.stage,
+                _ => return Err(anyhow::anyhow!("{}: not claimed by {}", paper_id, owner)),
+            };
+            file.status = match &result {
+                Ok(_) => stage.output(),
+                Err(reason) => PdfFileStatus::Failed {
+                    stage,
+                    reason: reason.to_owned(),
+                    // read on every try, so a retried swap sees the latest history
+                    attempts: self.count_failures(paper_id, stage) + 1,
+                },
+            };
+            file.lease = None;
+            Ok(())
+        })
+    }
+
+    fn retry_file(&self, paper_id: &str) -> anyhow::Result<PdfFile> {
+        match self.check_file_status(paper_id) {
+            PdfFileStatus::Failed { stage, .. } => self.transition_file(paper_id, stage.input()),
+            status => Err(anyhow::anyhow!("{}: {} is not a failure", paper_id, status)),
+        }
+    }
 
+    fn reset_file(&self, paper_id: &str) -> anyhow::Result<PdfFile> {
+        self.transition_file(paper_id, PdfFileStatus::None)
+    }
+
+    fn list_file_history(&self, paper_id: &str) -> Vec<PdfTransition> {
+        self.repo::<PdfTransition>()
+            .scan_prefix(&format!("{}/", paper_id))
+            .collect()
+    }
+
+    fn list_files_by_status(&self, status: &str) -> Vec<PdfFile> {
+        self.repo::<PdfFile>().find_by("status", status)
+    }
+}
 
+#[cfg(test)]
 mod test {
-    
     use super::*;
 
     #[tokio::test]
     async fn test_set_file_status() {
-        let state_mach = StateMach::new();
-        state_mach.set_file_status("10.1145/3292500.3330648", PdfFileStatus::Accpeted);
+        let state_mach = StateMach::temporary();
+        state_mach
+            .transition_file("10.1145/3292500.3330648", PdfFileStatus::Accpeted)
+            .unwrap();
         assert_eq!(
             state_mach.check_file_status("10.1145/3292500.3330648"),
             PdfFileStatus::Accpeted
         );
+        assert_eq!(state_mach.list_files_by_status("accpeted").len(), 1);
+    }
+
+    fn download(state_mach: &StateMach, result: Result<(), &str>) -> PdfFile {
+        state_mach
+            .claim_file("abc", PdfStage::Download, "w", chrono::Duration::minutes(1))
+            .unwrap();
+        state_mach
+            .finish_claim("abc", "w", result.map_err(|e| e.to_string()))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_file_transitions() {
+        let state_mach = StateMach::temporary();
+        assert!(state_mach
+            .transition_file("abc", PdfFileStatus::Downloaded)
+            .is_err());
+        state_mach.accept_file("abc", "http://x/abc.pdf").unwrap();
+        download(&state_mach, Err("not a pdf"));
+        state_mach.retry_file("abc").unwrap();
+        let file = download(&state_mach, Err("timeout"));
+        assert_eq!(
+            file.status,
+            PdfFileStatus::Failed {
+                stage: PdfStage::Download,
+                reason: "timeout".to_string(),
+                attempts: 2
+            }
+        );
+        assert_eq!(file.url.as_deref(), Some("http://x/abc.pdf"));
+        assert_eq!(state_mach.list_files_by_status("failed").len(), 1);
+        // a failure is only retried from the stage that failed
+        assert!(state_mach
+            .transition_file("abc", PdfFileStatus::Converted)
+            .is_err());
+        state_mach.retry_file("abc").unwrap();
+        download(&state_mach, Ok(()));
+        assert!(state_mach.retry_file("abc").is_err());
+
+        // attempts start over after a reset
+        state_mach.reset_file("abc").unwrap();
+        state_mach.accept_file("abc", "http://x/abc.pdf").unwrap();
+        let file = download(&state_mach, Err("gone"));
+        assert!(matches!(file.status, PdfFileStatus::Failed { attempts: 1, .. }));
+        assert_eq!(state_mach.list_file_history("abc").len(), 9);
+        assert!(state_mach.list_files_by_status("downloaded").is_empty());
+    }
+
+    #[test]
+    fn test_claim_file() {
+        let state_mach = StateMach::temporary();
+        state_mach.accept_file("abc", "http://x/abc.pdf").unwrap();
+        let claimed = std::thread::scope(|scope| {
+            let workers = (0..8)
+                .map(|_| {
+                    scope.spawn(|| {
+                        state_mach.claim_file(
+                            "abc",
+                            PdfStage::Download,
+                            &worker_id(),
+                            chrono::Duration::minutes(1),
+                        )
+                    })
+                })
+                .collect::<Vec<_>>();
+            workers
+                .into_iter()
+                .filter_map(|w| w.join().unwrap().ok())
+                .count()
+        });
+        assert_eq!(claimed, 1);
+        assert!(matches!(
+            state_mach.claim_file("abc", PdfStage::Download, "late", chrono::Duration::minutes(1)),
+            Err(ClaimError::InProgress(_))
+        ));
+
+        // a crashed worker's lapsed lease can be taken over
+        state_mach
+            .transition_file("abc", PdfFileStatus::Accpeted)
+            .unwrap();
+        state_mach
+            .claim_file("abc", PdfStage::Download, "crashed", chrono::Duration::zero())
+            .unwrap();
+        state_mach
+            .claim_file("abc", PdfStage::Download, "next", chrono::Duration::minutes(1))
+            .unwrap();
+        assert!(state_mach.finish_claim("abc", "crashed", Ok(())).is_err());
+        state_mach.finish_claim("abc", "next", Ok(())).unwrap();
+        assert!(matches!(
+            state_mach.claim_file("abc", PdfStage::Download, "again", chrono::Duration::minutes(1)),
+            Err(ClaimError::NotClaimable(PdfFileStatus::Downloaded))
+        ));
+
+        // other failures come back as errors, not panics
+        state_mach.store().open_tree("files").insert(b"bad", b"garbage");
+        assert!(matches!(
+            state_mach.claim_file("bad", PdfStage::Download, "w", chrono::Duration::minutes(1)),
+            Err(ClaimError::Other(_))
+        ));
     }
 }
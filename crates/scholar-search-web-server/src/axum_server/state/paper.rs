@@ -0,0 +1,66 @@
+use crate::axum_server::state::{repo::Entity, StateMach};
+use crate::semantic_scholar_api::data::PaperDetail;
+use chrono::{DateTime, Duration, Utc};
+use serde_derive::{Deserialize, Serialize};
+
+/// S2 paper details are re-fetched once they are older than this.
+pub const PAPER_CACHE_TTL: i64 = 24;
+
+/// A fetched paper detail, kept so repeated lookups skip the S2 API.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CachedPaper {
+    pub paper: PaperDetail,
+    pub fetched_at: DateTime<Utc>,
+}
+
+impl Entity for CachedPaper {
+    const TREE: &'static str = "papers";
+
+    fn key(&self) -> String {
+        self.paper.paper_id.to_owned()
+    }
+
+    fn indexes(&self) -> Vec<(&'static str, String)> {
+        self.paper
+            .external_ids
+            .as_ref()
+            .and_then(|ids| ids.doi.as_ref())
+            .map(|doi| vec![("doi", doi.to_lowercase())])
+            .unwrap_or_default()
+    }
+}
+
+pub trait PaperState {
+    fn get_cached_paper(&self, paper_id: &str) -> Option<PaperDetail>;
+    fn cache_paper(&self, paper: &PaperDetail);
+    fn find_cached_paper_by_doi(&self, doi: &str) -> Option<PaperDetail>;
+}
+
+impl PaperState for StateMach {
+    /// `None` when missing or stale.
+    fn get_cached_paper(&self, paper_id: &str) -> Option<PaperDetail> {
+        self.repo::<CachedPaper>()
+            .get(paper_id)
+            .filter(|cached| Utc::now() - cached.fetched_at < Duration::hours(PAPER_CACHE_TTL))
+            .map(|cached| cached.paper)
+    }
+
+    fn cache_paper(&self, paper: &PaperDetail) {
+        self.repo::<CachedPaper>().put(&CachedPaper {
+            // the embedding is large and not needed by the cache users
+            paper: PaperDetail {
+                embedding: None,
+                ..paper.clone()
+            },
+            fetched_at: Utc::now(),
+        });
+    }
+
+    fn find_cached_paper_by_doi(&self, doi: &str) -> Option<PaperDetail> {
+        self.repo::<CachedPaper>()
+            .find_by("doi", &doi.to_lowercase())
+            .into_iter()
+            .next()
+            .map(|cached| cached.paper)
+    }
+}
@@ -0,0 +1,430 @@
+use crate::axum_server::state::store::{Entry, Store, StoreTree};
+use serde::{de::DeserializeOwned, Serialize};
+use std::marker::PhantomData;
+use std::ops::RangeBounds;
+use std::sync::Arc;
+use tracing::warn;
+
+/// First byte of every value written by a `Repository`. It is never used in
+/// MessagePack and can not start a JSON document, so values written before
+/// the repository layer are still told apart.
+const MARKER: u8 = 0xc1;
+
+/// Separates the indexed value from the primary key in index trees.
+const INDEX_SEPARATOR: u8 = 0;
+
+/// A record type with its own sled tree.
+pub trait Entity: Serialize + DeserializeOwned {
+    const TREE: &'static str;
+    /// Bumped when the serialized shape changes in a way serde defaults can
+    /// not absorb; older values then go through `upgrade`.
+    const VERSION: u8 = 1;
+
+    /// Primary key; composite keys use `/` so prefix scans group records.
+    fn key(&self) -> String;
+
+    /// `(index name, value)` pairs; one name may appear several times.
+    fn indexes(&self) -> Vec<(&'static str, String)> {
+        vec![]
+    }
+
+    fn upgrade(version: u8, _payload: &[u8]) -> anyhow::Result<Self> {
+        Err(anyhow::anyhow!(
+            "{}: can not read version {} values",
+            Self::TREE,
+            version
+        ))
+    }
+}
+
+pub fn encode<E: Entity>(value: &E) -> Vec<u8> {
+    let mut out = vec![MARKER, E::VERSION];
+    out.extend(rmp_serde::to_vec_named(value).unwrap());
+    out
+}
+
+pub fn decode<E: Entity>(bytes: &[u8]) -> anyhow::Result<E> {
+    match bytes {
+        [MARKER, version, payload @ ..] if *version == E::VERSION => {
+            Ok(rmp_serde::from_slice(payload)?)
+        }
+        [MARKER, version, payload @ ..] => E::upgrade(*version, payload),
+        // written as JSON before the repository layer
+        _ => Ok(serde_json::from_slice(bytes)?),
+    }
+}
+
+fn index_key(value: &str, key: &str) -> Vec<u8> {
+    let mut out = value.as_bytes().to_vec();
+    out.push(INDEX_SEPARATOR);
+    out.extend(key.as_bytes());
+    out
+}
+
+/// Typed access to one entity tree and its `{tree}.by_{index}` trees.
+#[derive(Clone)]
+pub struct Repository<E: Entity> {
+    store: Arc<dyn Store>,
+    tree: Arc<dyn StoreTree>,
+    entity: PhantomData<E>,
+}
+
+impl<E: Entity> Repository<E> {
+    pub fn new(store: &Arc<dyn Store>) -> Self {
+        Self {
+            store: store.clone(),
+            tree: store.open_tree(E::TREE),
+            entity: PhantomData,
+        }
+    }
+
+    fn index_tree(&self, name: &str) -> Arc<dyn StoreTree> {
+        self.store.open_tree(&format!("{}.by_{}", E::TREE, name))
+    }
+
+    fn decode_logged(key: &[u8], bytes: &[u8]) -> Option<E> {
+        match decode(bytes) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                warn!(
+                    "{}: skipping undecodable {}: {}",
+                    E::TREE,
+                    String::from_utf8_lossy(key),
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<E> {
+        let bytes = self.tree.get(key.as_bytes())?;
+        Self::decode_logged(key.as_bytes(), &bytes)
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.tree.contains_key(key.as_bytes())
+    }
+
+    /// Inserts or replaces the record and moves its index entries.
+    pub fn put(&self, value: &E) {
+        let key = value.key();
+        let _writing = self.store.gate().write();
+        let previous = self.tree.insert(key.as_bytes(), &encode(value));
+        let old = previous.and_then(|bytes| Self::decode_logged(key.as_bytes(), &bytes));
+        self.move_indexes(&key, old.as_ref(), Some(value));
+    }
+
+    /// Replaces the record with `f(current)` using the store's compare and swap,
+    /// calling `f` again when another writer got in between; an error from
+    /// `f` leaves the record alone, and so does a stored value that can not
+    /// be decoded. Returns the old and the new record.
+    ///
+    /// Index trees are updated after the swap, so reads may see them briefly
+    /// lag; snapshots do not, as both happen under the store's write gate.
+    pub fn update<F>(&self, key: &str, mut f: F) -> anyhow::Result<(Option<E>, E)>
+    where
+        F: FnMut(Option<&E>) -> anyhow::Result<E>,
+    {
+        loop {
+            let current = self.tree.get(key.as_bytes());
+            let old = match current.as_ref() {
+                Some(bytes) => Some(
+                    decode::<E>(bytes)
+                        .map_err(|e| anyhow::anyhow!("{}: can not read {}: {}", E::TREE, key, e))?,
+                ),
+                None => None,
+            };
+            let new = f(old.as_ref())?;
+            debug_assert_eq!(new.key(), key);
+            let _writing = self.store.gate().write();
+            let swapped =
+                self.tree
+                    .compare_and_swap(key.as_bytes(), current.as_deref(), Some(&encode(&new)));
+            if swapped {
+                self.move_indexes(key, old.as_ref(), Some(&new));
+                return Ok((old, new));
+            }
+        }
+    }
+
+    pub fn remove(&self, key: &str) -> Option<E> {
+        let _writing = self.store.gate().write();
+        let removed = self
+            .tree
+            .remove(key.as_bytes())
+            .and_then(|bytes| Self::decode_logged(key.as_bytes(), &bytes))?;
+        self.move_indexes(key, Some(&removed), None);
+        Some(removed)
+    }
+
+    fn move_indexes(&self, key: &str, old: Option<&E>, new: Option<&E>) {
+        let old_indexes = old.map(|old| old.indexes()).unwrap_or_default();
+        let new_indexes = new.map(|new| new.indexes()).unwrap_or_default();
+        for (name, indexed) in old_indexes.iter() {
+            if !new_indexes.contains(&(name, indexed.to_owned())) {
+                self.index_tree(name).remove(&index_key(indexed, key));
+            }
+        }
+        for (name, indexed) in new_indexes.iter() {
+            self.index_tree(name).insert(&index_key(indexed, key), &[]);
+        }
+    }
+
+    /// Every record in key order.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = E> {
+        Self::decode_all(self.tree.iter())
+    }
+
+    /// Records whose key starts with `prefix`, in key order.
+    pub fn scan_prefix(&self, prefix: &str) -> impl DoubleEndedIterator<Item = E> {
+        Self::decode_all(self.tree.scan_prefix(prefix.as_bytes()))
+    }
+
+    /// Records with keys in `range`, in key order.
+    pub fn range<R: RangeBounds<String>>(&self, range: R) -> impl DoubleEndedIterator<Item = E> {
+        let start = range.start_bound().map(|s| s.as_bytes().to_vec());
+        let end = range.end_bound().map(|s| s.as_bytes().to_vec());
+        Self::decode_all(self.tree.range(start, end))
+    }
+
+    fn decode_all(entries: Vec<Entry>) -> impl DoubleEndedIterator<Item = E> {
+        entries
+            .into_iter()
+            .filter_map(|(k, v)| Self::decode_logged(&k, &v))
+    }
+
+    pub fn remove_prefix(&self, prefix: &str) -> usize {
+        let keys = self
+            .tree
+            .scan_prefix(prefix.as_bytes())
+            .into_iter()
+            .map(|(k, _)| String::from_utf8(k).unwrap())
+            .collect::<Vec<String>>();
+        for key in keys.iter() {
+            self.remove(key);
+        }
+        keys.len()
+    }
+
+    /// Primary keys of the records indexed under `value`.
+    pub fn keys_by(&self, index: &str, value: &str) -> Vec<String> {
+        let prefix = index_key(value, "");
+        self.index_tree(index)
+            .scan_prefix(&prefix)
+            .into_iter()
+            .map(|(k, _)| String::from_utf8(k[prefix.len()..].to_vec()).unwrap())
+            .collect()
+    }
+
+    pub fn find_by(&self, index: &str, value: &str) -> Vec<E> {
+        self.keys_by(index, value)
+            .iter()
+            .filter_map(|key| self.get(key))
+            .collect()
+    }
+
+    /// Re-writes values stored as JSON or with an older version in the
+    /// current encoding, which also creates their index entries; a dry run
+    /// only counts them.
+    pub fn rewrite_outdated(&self, dry_run: bool) -> usize {
+        let outdated = self
+            .tree
+            .iter()
+            .into_iter()
+            .filter(|(_, v)| !v.starts_with(&[MARKER, E::VERSION]))
+            .filter_map(|(k, v)| Self::decode_logged(&k, &v))
+            .collect::<Vec<E>>();
+        if dry_run {
+            return outdated.len();
+        }
+        for value in outdated.iter() {
+            // drop the old value first, its indexes were never written
+            self.tree.remove(value.key().as_bytes());
+            self.put(value);
+        }
+        outdated.len()
+    }
+}
+
+/// One entity type, for tools that walk every tree.
+pub trait EntityKind {
+    fn tree(&self) -> &'static str;
+    fn rewrite_outdated(&self, store: &Arc<dyn Store>, dry_run: bool) -> usize;
+    fn export_json(&self, store: &Arc<dyn Store>) -> Vec<serde_json::Value>;
+    fn import_json(&self, store: &Arc<dyn Store>, value: serde_json::Value) -> anyhow::Result<()>;
+}
+
+struct Kind<E>(PhantomData<E>);
+
+pub fn kind<E: Entity + 'static>() -> Box<dyn EntityKind> {
+    Box::new(Kind::<E>(PhantomData))
+}
+
+impl<E: Entity> EntityKind for Kind<E> {
+    fn tree(&self) -> &'static str {
+        E::TREE
+    }
+
+    fn rewrite_outdated(&self, store: &Arc<dyn Store>, dry_run: bool) -> usize {
+        Repository::<E>::new(store).rewrite_outdated(dry_run)
+    }
+
+    fn export_json(&self, store: &Arc<dyn Store>) -> Vec<serde_json::Value> {
+        Repository::<E>::new(store)
+            .iter()
+            .map(|value| serde_json::to_value(value).unwrap())
+            .collect()
+    }
+
+    fn import_json(&self, store: &Arc<dyn Store>, value: serde_json::Value) -> anyhow::Result<()> {
+        Repository::<E>::new(store).put(&serde_json::from_value(value)?);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::axum_server::state::store::memory::MemoryStore;
+    use serde_derive::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    struct Record {
+        id: String,
+        status: String,
+        #[serde(default)]
+        tags: Vec<String>,
+    }
+
+    impl Entity for Record {
+        const TREE: &'static str = "records";
+
+        fn key(&self) -> String {
+            self.id.to_owned()
+        }
+
+        fn indexes(&self) -> Vec<(&'static str, String)> {
+            let mut indexes = vec![("status", self.status.to_owned())];
+            indexes.extend(self.tags.iter().map(|t| ("tag", t.to_owned())));
+            indexes
+        }
+    }
+
+    fn record(id: &str, status: &str, tags: &[&str]) -> Record {
+        Record {
+            id: id.to_string(),
+            status: status.to_string(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_repository() {
+        let store: Arc<dyn Store> = Arc::new(MemoryStore::default());
+        let repo = Repository::<Record>::new(&store);
+        repo.put(&record("a/1", "new", &["x"]));
+        repo.put(&record("a/2", "new", &["x", "y"]));
+        repo.put(&record("b/1", "done", &[]));
+
+        assert_eq!(repo.get("a/2"), Some(record("a/2", "new", &["x", "y"])));
+        assert_eq!(repo.scan_prefix("a/").count(), 2);
+        assert_eq!(
+            repo.range("a/2".to_string().."b/2".to_string())
+                .map(|r| r.id)
+                .collect::<Vec<String>>(),
+            vec!["a/2", "b/1"]
+        );
+        assert_eq!(repo.keys_by("status", "new"), vec!["a/1", "a/2"]);
+
+        // moving a record out of an index drops the old entry
+        repo.put(&record("a/1", "done", &[]));
+        assert_eq!(repo.keys_by("status", "new"), vec!["a/2"]);
+        assert_eq!(repo.keys_by("tag", "x"), vec!["a/2"]);
+        repo.remove("a/2");
+        assert!(repo.keys_by("tag", "y").is_empty());
+        assert_eq!(repo.find_by("status", "done").len(), 2);
+
+        repo.tree.insert(b"c", br#"{"id":"c","status":"new"}"#);
+        assert!(repo.keys_by("status", "new").is_empty());
+        assert_eq!(repo.rewrite_outdated(true), 1);
+        assert!(repo.keys_by("status", "new").is_empty());
+        assert_eq!(repo.rewrite_outdated(false), 1);
+        assert_eq!(repo.keys_by("status", "new"), vec!["c"]);
+        assert_eq!(repo.rewrite_outdated(false), 0);
+
+        let (old, new) = repo
+            .update("c", |old| {
+                let mut new = old.unwrap().clone();
+                new.status = "done".to_string();
+                Ok(new)
+            })
+            .unwrap();
+        assert_eq!(old.unwrap().status, "new");
+        assert_eq!(new.status, "done");
+        assert!(repo.keys_by("status", "new").is_empty());
+        assert!(repo
+            .update("c", |_| Err(anyhow::anyhow!("refused")))
+            .is_err());
+        assert_eq!(repo.get("c").unwrap().status, "done");
+
+        // a record that can not be read is not taken for a missing one
+        repo.tree.insert(b"d", b"garbage");
+        assert!(repo.update("d", |_| Ok(record("d", "new", &[]))).is_err());
+        assert_eq!(repo.tree.get(b"d").unwrap(), b"garbage");
+    }
+
+    #[test]
+    fn test_snapshot_sees_whole_writes() {
+        let store: Arc<dyn Store> = Arc::new(MemoryStore::default());
+        let repo = Repository::<Record>::new(&store);
+        let writer = {
+            let repo = repo.clone();
+            std::thread::spawn(move || {
+                for i in 0..500 {
+                    let status = ["new", "done"][i % 2];
+                    repo.put(&record("a", status, &[]));
+                    repo.update("b", |_| Ok(record("b", status, &[]))).unwrap();
+                }
+            })
+        };
+        while !writer.is_finished() {
+            let snapshot = store.snapshot();
+            let tree = |name: &str| {
+                snapshot
+                    .iter()
+                    .find(|(n, _)| n == name)
+                    .map(|(_, entries)| entries.clone())
+                    .unwrap_or_default()
+            };
+            let mut expected = tree("records")
+                .iter()
+                .map(|(key, value)| {
+                    index_key(
+                        &decode::<Record>(value).unwrap().status,
+                        &String::from_utf8_lossy(key),
+                    )
+                })
+                .collect::<Vec<Vec<u8>>>();
+            expected.sort();
+            let indexed = tree("records.by_status")
+                .into_iter()
+                .map(|(key, _)| key)
+                .collect::<Vec<Vec<u8>>>();
+            assert_eq!(indexed, expected);
+        }
+        writer.join().unwrap();
+    }
+
+    #[test]
+    fn test_decode_versions() {
+        let value = record("a", "new", &["x"]);
+        let bytes = encode(&value);
+        assert_eq!(bytes[..2], [MARKER, 1]);
+        assert_eq!(decode::<Record>(&bytes).unwrap(), value);
+        // JSON from before the repository layer, missing a defaulted field
+        let legacy = br#"{"id":"a","status":"new"}"#;
+        assert_eq!(decode::<Record>(legacy).unwrap(), record("a", "new", &[]));
+        assert!(decode::<Record>(&[MARKER, 9, 0x90]).is_err());
+    }
+}
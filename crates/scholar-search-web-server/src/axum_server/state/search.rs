@@ -0,0 +1,294 @@
+use crate::axum_server::state::{
+    delivery::{Delivery, DeliveryConfig},
+    repo::Entity,
+    StateMach,
+};
+use crate::semantic_scholar_api::{data::Paper, paper_fetch::BulkRequest};
+use chrono::{DateTime, Utc};
+use convert_case::{Case, Casing};
+use cron::Schedule;
+use serde_derive::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Monday 06:00 UTC, the weekly re-run the saved searches replace.
+pub const DEFAULT_SCHEDULE: &str = "0 6 * * Mon";
+
+/// Accepts the usual five field cron syntax (`min hour dom month dow`) as
+/// well as the six/seven field form with seconds and `@daily` style aliases.
+pub fn parse_schedule(expression: &str) -> anyhow::Result<Schedule> {
+    let expression = expression.trim();
+    let expression = match expression.split_whitespace().count() {
+        5 => format!("0 {}", expression),
+        _ => expression.to_string(),
+    };
+    Schedule::from_str(&expression).map_err(|e| anyhow::anyhow!("invalid schedule: {}", e))
+}
+
+/// A named `BulkRequest` that the scheduler re-runs.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SavedSearch {
+    pub search_id: String,
+    pub name: String,
+    pub request: BulkRequest,
+    pub schedule: String,
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+    pub last_run: Option<DateTime<Utc>>,
+    pub next_run: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub delivery: DeliveryConfig,
+}
+
+impl SavedSearch {
+    pub fn new(name: &str, request: BulkRequest, schedule: &str) -> anyhow::Result<Self> {
+        let mut search = Self {
+            search_id: name
+                .to_case(Case::Kebab)
+                .chars()
+                .filter(|c| c.is_alphanumeric() || *c == '-')
+                .collect(),
+            name: name.trim().to_string(),
+            // a continuation token only makes sense for the page it came from
+            request: BulkRequest {
+                token: None,
+                ..request
+            },
+            schedule: schedule.trim().to_string(),
+            enabled: true,
+            created_at: Utc::now(),
+            last_run: None,
+            next_run: None,
+            delivery: DeliveryConfig::default(),
+        };
+        search.schedule_next(Utc::now())?;
+        Ok(search)
+    }
+
+    pub fn schedule_next(&mut self, after: DateTime<Utc>) -> anyhow::Result<()> {
+        self.next_run = parse_schedule(&self.schedule)?.after(&after).next();
+        Ok(())
+    }
+
+    pub fn is_due(&self, now: DateTime<Utc>) -> bool {
+        self.enabled && self.next_run.is_some_and(|next_run| next_run <= now)
+    }
+}
+
+/// The outcome of one execution of a saved search.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SearchRun {
+    pub search_id: String,
+    pub run_at: DateTime<Utc>,
+    pub total: i32,
+    pub paper_ids: Vec<String>,
+    pub new_paper_ids: Vec<String>,
+    pub error: Option<String>,
+}
+
+impl SearchRun {
+    /// Papers not returned by the previous run are new; the first run has
+    /// nothing to compare against, so everything it sees is new.
+    pub fn new(
+        search_id: &str,
+        total: i32,
+        paper_ids: Vec<String>,
+        previous: Option<&SearchRun>,
+    ) -> Self {
+        let new_paper_ids = paper_ids
+            .iter()
+            .filter(|paper_id| match previous {
+                Some(previous) => !previous.paper_ids.contains(paper_id),
+                None => true,
+            })
+            .cloned()
+            .collect();
+        Self {
+            search_id: search_id.to_string(),
+            run_at: Utc::now(),
+            total,
+            paper_ids,
+            new_paper_ids,
+            error: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct InboxItem {
+    pub search_id: String,
+    pub paper_id: String,
+    pub title: String,
+    pub authors: String,
+    pub year: Option<i32>,
+    pub citation_count: Option<i32>,
+    pub found_at: DateTime<Utc>,
+    pub seen: bool,
+}
+
+impl InboxItem {
+    pub fn new(search_id: &str, paper: &Paper) -> Option<Self> {
+        Some(Self {
+            search_id: search_id.to_string(),
+            paper_id: paper.paper_id.to_owned()?,
+            title: paper.title.to_owned(),
+            authors: paper
+                .authors
+                .iter()
+                .flatten()
+                .map(|a| a.name.to_owned())
+                .collect::<Vec<String>>()
+                .join(", "),
+            year: paper.year,
+            citation_count: paper.citation_count,
+            found_at: Utc::now(),
+            seen: false,
+        })
+    }
+}
+
+impl Entity for SavedSearch {
+    const TREE: &'static str = "searches";
+
+    fn key(&self) -> String {
+        self.search_id.to_owned()
+    }
+}
+
+/// Runs and inbox items are keyed `{search_id}/...` so a prefix scan returns
+/// everything belonging to one search.
+fn prefix(search_id: &str) -> String {
+    format!("{}/", search_id)
+}
+
+impl Entity for SearchRun {
+    const TREE: &'static str = "search_runs";
+
+    /// The fixed width timestamp suffix sorts runs chronologically.
+    fn key(&self) -> String {
+        format!(
+            "{}{}",
+            prefix(&self.search_id),
+            self.run_at.format("%Y%m%dT%H%M%S%.6f")
+        )
+    }
+}
+
+impl Entity for InboxItem {
+    const TREE: &'static str = "search_inbox";
+
+    fn key(&self) -> String {
+        format!("{}{}", prefix(&self.search_id), self.paper_id)
+    }
+
+    fn indexes(&self) -> Vec<(&'static str, String)> {
+        match self.seen {
+            true => vec![],
+            false => vec![("unseen", self.search_id.to_owned())],
+        }
+    }
+}
+
+pub trait SearchState {
+    fn get_saved_search(&self, search_id: &str) -> Option<SavedSearch>;
+    fn set_saved_search(&self, search: &SavedSearch);
+    fn remove_saved_search(&self, search_id: &str);
+    fn list_saved_searches(&self) -> Vec<SavedSearch>;
+    fn add_search_run(&self, run: &SearchRun);
+    fn list_search_runs(&self, search_id: &str) -> Vec<SearchRun>;
+    fn last_search_run(&self, search_id: &str) -> Option<SearchRun>;
+    fn get_inbox_item(&self, search_id: &str, paper_id: &str) -> Option<InboxItem>;
+    fn set_inbox_item(&self, item: &InboxItem);
+    fn list_inbox(&self, search_id: &str) -> Vec<InboxItem>;
+    fn count_unseen(&self, search_id: &str) -> usize;
+}
+
+impl SearchState for StateMach {
+    fn get_saved_search(&self, search_id: &str) -> Option<SavedSearch> {
+        self.repo::<SavedSearch>().get(search_id)
+    }
+
+    fn set_saved_search(&self, search: &SavedSearch) {
+        self.repo::<SavedSearch>().put(search);
+    }
+
+    fn remove_saved_search(&self, search_id: &str) {
+        self.repo::<SavedSearch>().remove(search_id);
+        self.repo::<SearchRun>().remove_prefix(&prefix(search_id));
+        self.repo::<InboxItem>().remove_prefix(&prefix(search_id));
+        self.repo::<Delivery>().remove_prefix(&prefix(search_id));
+    }
+
+    fn list_saved_searches(&self) -> Vec<SavedSearch> {
+        self.repo::<SavedSearch>().iter().collect()
+    }
+
+    fn add_search_run(&self, run: &SearchRun) {
+        self.repo::<SearchRun>().put(run);
+    }
+
+    /// Oldest first.
+    fn list_search_runs(&self, search_id: &str) -> Vec<SearchRun> {
+        self.repo::<SearchRun>()
+            .scan_prefix(&prefix(search_id))
+            .collect()
+    }
+
+    /// The latest run that succeeded, which new results are compared with.
+    fn last_search_run(&self, search_id: &str) -> Option<SearchRun> {
+        self.repo::<SearchRun>()
+            .scan_prefix(&prefix(search_id))
+            .rev()
+            .find(|run| run.error.is_none())
+    }
+
+    fn get_inbox_item(&self, search_id: &str, paper_id: &str) -> Option<InboxItem> {
+        self.repo::<InboxItem>()
+            .get(&format!("{}{}", prefix(search_id), paper_id))
+    }
+
+    fn set_inbox_item(&self, item: &InboxItem) {
+        self.repo::<InboxItem>().put(item);
+    }
+
+    fn list_inbox(&self, search_id: &str) -> Vec<InboxItem> {
+        let mut items = self
+            .repo::<InboxItem>()
+            .scan_prefix(&prefix(search_id))
+            .collect::<Vec<InboxItem>>();
+        items.sort_by_key(|item| std::cmp::Reverse(item.found_at));
+        items
+    }
+
+    fn count_unseen(&self, search_id: &str) -> usize {
+        self.repo::<InboxItem>().keys_by("unseen", search_id).len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_schedule() {
+        let after = DateTime::parse_from_rfc3339("2024-03-06T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let next = parse_schedule(DEFAULT_SCHEDULE)
+            .unwrap()
+            .after(&after)
+            .next()
+            .unwrap();
+        assert_eq!(next.to_rfc3339(), "2024-03-11T06:00:00+00:00");
+        assert!(parse_schedule("@daily").is_ok());
+        assert!(parse_schedule("every monday").is_err());
+    }
+
+    #[test]
+    fn test_search_run_new_papers() {
+        let ids = |ids: &[&str]| ids.iter().map(|i| i.to_string()).collect::<Vec<String>>();
+        let first = SearchRun::new("nas", 2, ids(&["a", "b"]), None);
+        assert_eq!(first.new_paper_ids, ids(&["a", "b"]));
+        let second = SearchRun::new("nas", 3, ids(&["b", "c", "a"]), Some(&first));
+        assert_eq!(second.new_paper_ids, ids(&["c"]));
+    }
+}
@@ -0,0 +1,95 @@
+use crate::axum_server::state::{repo::Entity, StateMach};
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+
+/// Where a paper's PDF can come from, in the order they are tried.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PdfSource {
+    /// the `openAccessPdf` url of the S2 paper detail
+    OpenAccessPdf,
+    ArXiv,
+    PubMedCentral,
+    /// `https://doi.org/<doi>`, asking for a PDF
+    Doi,
+    /// the `citation_pdf_url` meta tag of a landing page reached before
+    CitationPdfUrl,
+}
+
+impl fmt::Display for PdfSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            PdfSource::OpenAccessPdf => "open access pdf",
+            PdfSource::ArXiv => "arXiv",
+            PdfSource::PubMedCentral => "PubMed Central",
+            PdfSource::Doi => "DOI",
+            PdfSource::CitationPdfUrl => "citation_pdf_url",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct SourceAttempt {
+    pub source: PdfSource,
+    pub url: String,
+    /// why the source gave no PDF; `None` for the one that did
+    pub error: Option<String>,
+}
+
+/// The sources tried by the latest download of a paper.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct PdfSourceLog {
+    pub paper_id: String,
+    pub attempts: Vec<SourceAttempt>,
+    pub resolved_at: DateTime<Utc>,
+}
+
+impl PdfSourceLog {
+    pub fn new(paper_id: &str) -> Self {
+        Self {
+            paper_id: paper_id.to_string(),
+            attempts: vec![],
+            resolved_at: Utc::now(),
+        }
+    }
+
+    pub fn succeeded(&self) -> Option<&SourceAttempt> {
+        self.attempts.iter().find(|a| a.error.is_none())
+    }
+
+    /// One line naming each failed source and why it failed.
+    pub fn failures(&self) -> String {
+        if self.attempts.is_empty() {
+            return "no pdf source known".to_string();
+        }
+        self.attempts
+            .iter()
+            .filter_map(|a| Some(format!("{}: {}", a.source, a.error.as_ref()?)))
+            .collect::<Vec<String>>()
+            .join("; ")
+    }
+}
+
+impl Entity for PdfSourceLog {
+    const TREE: &'static str = "pdf_sources";
+
+    fn key(&self) -> String {
+        self.paper_id.to_owned()
+    }
+}
+
+pub trait PdfSourceState {
+    fn get_pdf_sources(&self, paper_id: &str) -> Option<PdfSourceLog>;
+    fn set_pdf_sources(&self, log: &PdfSourceLog);
+}
+
+impl PdfSourceState for StateMach {
+    fn get_pdf_sources(&self, paper_id: &str) -> Option<PdfSourceLog> {
+        self.repo::<PdfSourceLog>().get(paper_id)
+    }
+
+    fn set_pdf_sources(&self, log: &PdfSourceLog) {
+        self.repo::<PdfSourceLog>().put(log);
+    }
+}
@@ -0,0 +1,77 @@
+use super::{Entry, Store, StoreTree, WriteGate};
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Bound;
+use std::sync::{Arc, Mutex};
+
+type Map = BTreeMap<Vec<u8>, Vec<u8>>;
+
+/// Keeps everything in memory; used by tests.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    trees: Mutex<HashMap<String, Arc<MemoryTree>>>,
+    gate: WriteGate,
+}
+
+#[derive(Debug, Default)]
+pub struct MemoryTree {
+    map: Mutex<Map>,
+}
+
+impl Store for MemoryStore {
+    fn open_tree(&self, name: &str) -> Arc<dyn StoreTree> {
+        self.trees
+            .lock()
+            .unwrap()
+            .entry(name.to_string())
+            .or_default()
+            .clone()
+    }
+
+    fn tree_names(&self) -> Vec<String> {
+        self.trees.lock().unwrap().keys().cloned().collect()
+    }
+
+    fn flush(&self) {}
+
+    fn gate(&self) -> &WriteGate {
+        &self.gate
+    }
+}
+
+impl StoreTree for MemoryTree {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.map.lock().unwrap().get(key).cloned()
+    }
+
+    fn insert(&self, key: &[u8], value: &[u8]) -> Option<Vec<u8>> {
+        self.map
+            .lock()
+            .unwrap()
+            .insert(key.to_vec(), value.to_vec())
+    }
+
+    fn remove(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.map.lock().unwrap().remove(key)
+    }
+
+    fn compare_and_swap(&self, key: &[u8], old: Option<&[u8]>, new: Option<&[u8]>) -> bool {
+        let mut map = self.map.lock().unwrap();
+        if map.get(key).map(|v| v.as_slice()) != old {
+            return false;
+        }
+        match new {
+            Some(new) => map.insert(key.to_vec(), new.to_vec()),
+            None => map.remove(key),
+        };
+        true
+    }
+
+    fn range(&self, start: Bound<Vec<u8>>, end: Bound<Vec<u8>>) -> Vec<Entry> {
+        self.map
+            .lock()
+            .unwrap()
+            .range((start, end))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect()
+    }
+}
@@ -0,0 +1,189 @@
+pub mod memory;
+pub mod sled;
+pub mod sqlite;
+
+use std::fmt::Debug;
+use std::ops::Bound;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// A key and its value.
+pub type Entry = (Vec<u8>, Vec<u8>);
+
+/// Name of the tree that holds data written before entities had trees of
+/// their own; it is sled's default tree.
+pub const DEFAULT_TREE: &str = "__sled__default";
+
+/// Where `StateMach` keeps its trees, chosen with `SCHOLAR_SEARCH_STORE`.
+pub const DEFAULT_STORE: &str = "sled:data/state_mach";
+
+/// Lets writes run side by side, but not while a snapshot is read. Clones
+/// share the gate.
+#[derive(Debug, Default, Clone)]
+pub struct WriteGate(Arc<RwLock<()>>);
+
+impl WriteGate {
+    /// Held across one logical write, such as a record and its index
+    /// entries, so no snapshot sees half of it.
+    pub fn write(&self) -> RwLockReadGuard<'_, ()> {
+        self.0.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Held while a snapshot is read; waits for the writes in flight.
+    pub fn hold(&self) -> RwLockWriteGuard<'_, ()> {
+        self.0.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A database of named, byte-ordered key/value trees.
+pub trait Store: Send + Sync + Debug {
+    /// Opens the tree, creating it if missing.
+    fn open_tree(&self, name: &str) -> Arc<dyn StoreTree>;
+    fn tree_names(&self) -> Vec<String>;
+    fn flush(&self);
+    /// The gate repository writes pass through.
+    fn gate(&self) -> &WriteGate;
+
+    /// Every tree's entries as of one moment between repository writes:
+    /// records agree with their index trees. Writes wait while it is read.
+    fn snapshot(&self) -> Vec<(String, Vec<Entry>)> {
+        let _held = self.gate().hold();
+        self.tree_names()
+            .into_iter()
+            .map(|name| {
+                let entries = self.open_tree(&name).iter();
+                (name, entries)
+            })
+            .collect()
+    }
+}
+
+/// One tree of a `Store`. Keys are compared bytewise on every backend so
+/// range and prefix scans agree between them.
+pub trait StoreTree: Send + Sync {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>>;
+    /// Returns the value that was replaced.
+    fn insert(&self, key: &[u8], value: &[u8]) -> Option<Vec<u8>>;
+    fn remove(&self, key: &[u8]) -> Option<Vec<u8>>;
+    /// Writes `new`, or removes the key for `None`, only if the current
+    /// value is `old`; returns whether it did.
+    fn compare_and_swap(&self, key: &[u8], old: Option<&[u8]>, new: Option<&[u8]>) -> bool;
+    /// Entries with keys in the bounds, in key order.
+    fn range(&self, start: Bound<Vec<u8>>, end: Bound<Vec<u8>>) -> Vec<Entry>;
+
+    fn contains_key(&self, key: &[u8]) -> bool {
+        self.get(key).is_some()
+    }
+
+    fn iter(&self) -> Vec<Entry> {
+        self.range(Bound::Unbounded, Bound::Unbounded)
+    }
+
+    fn scan_prefix(&self, prefix: &[u8]) -> Vec<Entry> {
+        self.range(Bound::Included(prefix.to_vec()), prefix_end(prefix))
+    }
+}
+
+/// The first key after every key starting with `prefix`.
+fn prefix_end(prefix: &[u8]) -> Bound<Vec<u8>> {
+    let mut end = prefix.to_vec();
+    while let Some(last) = end.pop() {
+        if last < u8::MAX {
+            end.push(last + 1);
+            return Bound::Excluded(end);
+        }
+    }
+    Bound::Unbounded
+}
+
+/// Opens a store from `backend:path`: `sled:data/state_mach`,
+/// `sqlite:data/state.sqlite3` or `memory:`.
+pub fn open_store(spec: &str) -> anyhow::Result<Arc<dyn Store>> {
+    let (backend, path) = spec.split_once(':').unwrap_or((spec, ""));
+    match backend {
+        "sled" => Ok(Arc::new(sled::SledStore::open(path)?)),
+        "sqlite" => Ok(Arc::new(sqlite::SqliteStore::open(path)?)),
+        "memory" => Ok(Arc::new(memory::MemoryStore::default())),
+        _ => Err(anyhow::anyhow!("unknown store backend: {}", backend)),
+    }
+}
+
+pub fn store_is_empty(store: &dyn Store) -> bool {
+    store
+        .tree_names()
+        .iter()
+        .all(|name| store.open_tree(name).iter().is_empty())
+}
+
+/// Copies every tree of `from` into `to`, returning the number of entries.
+/// Existing entries in `to` with the same keys are overwritten.
+pub fn copy_store(from: &dyn Store, to: &dyn Store) -> usize {
+    let mut copied = 0;
+    for name in from.tree_names() {
+        let source = from.open_tree(&name);
+        let target = to.open_tree(&name);
+        for (key, value) in source.iter() {
+            target.insert(&key, &value);
+            copied += 1;
+        }
+    }
+    to.flush();
+    copied
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn check_store(store: &dyn Store) {
+        let tree = store.open_tree("records");
+        assert_eq!(tree.insert(b"a/1", b"x"), None);
+        assert_eq!(tree.insert(b"a/1", b"y"), Some(b"x".to_vec()));
+        tree.insert(b"a/2", b"z");
+        tree.insert(b"a\xff", b"w");
+        tree.insert(b"b", b"v");
+        assert_eq!(tree.get(b"a/1"), Some(b"y".to_vec()));
+        assert_eq!(
+            tree.scan_prefix(b"a")
+                .into_iter()
+                .map(|(k, _)| k)
+                .collect::<Vec<Vec<u8>>>(),
+            vec![b"a/1".to_vec(), b"a/2".to_vec(), b"a\xff".to_vec()]
+        );
+        assert_eq!(
+            tree.range(
+                Bound::Excluded(b"a/1".to_vec()),
+                Bound::Included(b"b".to_vec())
+            )
+            .len(),
+            3
+        );
+        assert!(!tree.compare_and_swap(b"a/1", Some(b"x"), Some(b"n")));
+        assert!(tree.compare_and_swap(b"a/1", Some(b"y"), Some(b"n")));
+        assert!(tree.compare_and_swap(b"c", None, Some(b"u")));
+        assert!(tree.compare_and_swap(b"c", Some(b"u"), None));
+        assert!(!tree.contains_key(b"c"));
+        assert_eq!(tree.remove(b"b"), Some(b"v".to_vec()));
+        assert_eq!(tree.iter().len(), 3);
+        assert!(store.tree_names().contains(&"records".to_string()));
+        let snapshot = store.snapshot();
+        let (_, records) = snapshot.iter().find(|(name, _)| name == "records").unwrap();
+        assert_eq!(*records, tree.iter());
+    }
+
+    #[test]
+    fn test_backends() {
+        check_store(&memory::MemoryStore::default());
+        check_store(&sqlite::SqliteStore::open(":memory:").unwrap());
+        check_store(&sled::SledStore::temporary());
+    }
+
+    #[test]
+    fn test_copy_store() {
+        let from = memory::MemoryStore::default();
+        from.open_tree("a").insert(b"1", b"x");
+        from.open_tree("b").insert(b"2", b"y");
+        let to = sqlite::SqliteStore::open(":memory:").unwrap();
+        assert_eq!(copy_store(&from, &to), 2);
+        assert_eq!(to.open_tree("b").get(b"2"), Some(b"y".to_vec()));
+    }
+}
@@ -0,0 +1,80 @@
+use super::{Entry, Store, StoreTree, WriteGate};
+use std::ops::Bound;
+use std::sync::Arc;
+
+/// The embedded sled database the server has always used.
+#[derive(Debug, Clone)]
+pub struct SledStore {
+    db: sled::Db,
+    gate: WriteGate,
+}
+
+impl SledStore {
+    pub fn open(path: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            db: sled::open(path)?,
+            gate: WriteGate::default(),
+        })
+    }
+
+    #[cfg(test)]
+    pub fn temporary() -> Self {
+        Self {
+            db: sled::Config::new().temporary(true).open().unwrap(),
+            gate: WriteGate::default(),
+        }
+    }
+}
+
+impl Store for SledStore {
+    fn open_tree(&self, name: &str) -> Arc<dyn StoreTree> {
+        Arc::new(self.db.open_tree(name).unwrap())
+    }
+
+    fn tree_names(&self) -> Vec<String> {
+        self.db
+            .tree_names()
+            .iter()
+            .map(|name| String::from_utf8_lossy(name).to_string())
+            .collect()
+    }
+
+    fn flush(&self) {
+        self.db.flush().unwrap();
+    }
+
+    fn gate(&self) -> &WriteGate {
+        &self.gate
+    }
+}
+
+impl StoreTree for sled::Tree {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        sled::Tree::get(self, key).unwrap().map(|v| v.to_vec())
+    }
+
+    fn insert(&self, key: &[u8], value: &[u8]) -> Option<Vec<u8>> {
+        sled::Tree::insert(self, key, value)
+            .unwrap()
+            .map(|v| v.to_vec())
+    }
+
+    fn remove(&self, key: &[u8]) -> Option<Vec<u8>> {
+        sled::Tree::remove(self, key).unwrap().map(|v| v.to_vec())
+    }
+
+    fn compare_and_swap(&self, key: &[u8], old: Option<&[u8]>, new: Option<&[u8]>) -> bool {
+        sled::Tree::compare_and_swap(self, key, old, new)
+            .unwrap()
+            .is_ok()
+    }
+
+    fn range(&self, start: Bound<Vec<u8>>, end: Bound<Vec<u8>>) -> Vec<Entry> {
+        sled::Tree::range::<Vec<u8>, _>(self, (start, end))
+            .map(|x| {
+                let (k, v) = x.unwrap();
+                (k.to_vec(), v.to_vec())
+            })
+            .collect()
+    }
+}
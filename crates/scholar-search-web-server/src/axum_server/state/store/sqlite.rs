@@ -0,0 +1,182 @@
+use super::{Entry, Store, StoreTree, WriteGate};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::ops::Bound;
+use std::sync::{Arc, Mutex};
+
+/// One table per tree with `key` and `value` blobs, so the data can be
+/// looked at with the `sqlite3` shell. Values written by a `Repository` are
+/// also copied into a `json` column for `json_extract`.
+#[derive(Debug, Clone)]
+pub struct SqliteStore {
+    conn: Arc<Mutex<Connection>>,
+    gate: WriteGate,
+}
+
+#[derive(Debug, Clone)]
+pub struct SqliteTree {
+    conn: Arc<Mutex<Connection>>,
+    /// the quoted table name
+    table: String,
+}
+
+impl SqliteStore {
+    pub fn open(path: &str) -> anyhow::Result<Self> {
+        if let Some(dir) = std::path::Path::new(path)
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+        {
+            std::fs::create_dir_all(dir)?;
+        }
+        let conn = Connection::open(path)?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+            gate: WriteGate::default(),
+        })
+    }
+}
+
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// The value as JSON if it carries the repository's MessagePack header.
+fn json_of(value: &[u8]) -> Option<String> {
+    match value {
+        [0xc1, _, payload @ ..] => rmp_serde::from_slice::<serde_json::Value>(payload)
+            .ok()
+            .map(|json| json.to_string()),
+        _ => None,
+    }
+}
+
+impl Store for SqliteStore {
+    fn open_tree(&self, name: &str) -> Arc<dyn StoreTree> {
+        let table = quote(name);
+        self.conn
+            .lock()
+            .unwrap()
+            .execute_batch(&format!(
+                "CREATE TABLE IF NOT EXISTS {} \
+                 (key BLOB PRIMARY KEY, value BLOB NOT NULL, json TEXT) WITHOUT ROWID",
+                table
+            ))
+            .unwrap();
+        Arc::new(SqliteTree {
+            conn: self.conn.clone(),
+            table,
+        })
+    }
+
+    fn tree_names(&self) -> Vec<String> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT name FROM sqlite_master WHERE type = 'table' ORDER BY name")
+            .unwrap();
+        let names = stmt
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .map(|name| name.unwrap())
+            .collect();
+        names
+    }
+
+    fn flush(&self) {}
+
+    fn gate(&self) -> &WriteGate {
+        &self.gate
+    }
+}
+
+impl SqliteTree {
+    fn get_with(conn: &Connection, table: &str, key: &[u8]) -> Option<Vec<u8>> {
+        conn.query_row(
+            &format!("SELECT value FROM {} WHERE key = ?1", table),
+            params![key],
+            |row| row.get(0),
+        )
+        .optional()
+        .unwrap()
+    }
+
+    fn write_with(conn: &Connection, table: &str, key: &[u8], value: Option<&[u8]>) {
+        match value {
+            Some(value) => conn.execute(
+                &format!(
+                    "INSERT OR REPLACE INTO {} (key, value, json) VALUES (?1, ?2, ?3)",
+                    table
+                ),
+                params![key, value, json_of(value)],
+            ),
+            None => conn.execute(
+                &format!("DELETE FROM {} WHERE key = ?1", table),
+                params![key],
+            ),
+        }
+        .unwrap();
+    }
+}
+
+impl StoreTree for SqliteTree {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        Self::get_with(&self.conn.lock().unwrap(), &self.table, key)
+    }
+
+    fn insert(&self, key: &[u8], value: &[u8]) -> Option<Vec<u8>> {
+        let conn = self.conn.lock().unwrap();
+        let previous = Self::get_with(&conn, &self.table, key);
+        Self::write_with(&conn, &self.table, key, Some(value));
+        previous
+    }
+
+    fn remove(&self, key: &[u8]) -> Option<Vec<u8>> {
+        let conn = self.conn.lock().unwrap();
+        let previous = Self::get_with(&conn, &self.table, key);
+        Self::write_with(&conn, &self.table, key, None);
+        previous
+    }
+
+    fn compare_and_swap(&self, key: &[u8], old: Option<&[u8]>, new: Option<&[u8]>) -> bool {
+        // the connection lock makes the read and the write one step
+        let conn = self.conn.lock().unwrap();
+        if Self::get_with(&conn, &self.table, key).as_deref() != old {
+            return false;
+        }
+        Self::write_with(&conn, &self.table, key, new);
+        true
+    }
+
+    fn range(&self, start: Bound<Vec<u8>>, end: Bound<Vec<u8>>) -> Vec<Entry> {
+        let mut conditions = vec!["1".to_string()];
+        let mut bounds = vec![];
+        for (bound, included, excluded) in [(start, ">=", ">"), (end, "<=", "<")] {
+            match bound {
+                Bound::Included(key) => {
+                    bounds.push(key);
+                    conditions.push(format!("key {} ?{}", included, bounds.len()));
+                }
+                Bound::Excluded(key) => {
+                    bounds.push(key);
+                    conditions.push(format!("key {} ?{}", excluded, bounds.len()));
+                }
+                Bound::Unbounded => {}
+            }
+        }
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT key, value FROM {} WHERE {} ORDER BY key",
+                self.table,
+                conditions.join(" AND ")
+            ))
+            .unwrap();
+        let entries = stmt
+            .query_map(rusqlite::params_from_iter(bounds.iter()), |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap()
+            .map(|entry| entry.unwrap())
+            .collect();
+        entries
+    }
+}
@@ -0,0 +1,190 @@
+use crate::axum_server::state::{repo::Entity, StateMach};
+use crate::citation::growth::CitationSnapshot;
+use chrono::{DateTime, Duration, Utc};
+use serde_derive::{Deserialize, Serialize};
+
+/// A paper whose citation counts are recorded without it being in the
+/// library; bookmarked papers are always tracked.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TrackedPaper {
+    pub paper_id: String,
+    pub title: String,
+    pub added_at: DateTime<Utc>,
+}
+
+/// Fires when a paper gains at least `min_increase` citations within
+/// `window_days`; `collection_id` limits it to one library collection.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CitationAlert {
+    pub alert_id: String,
+    pub collection_id: Option<String>,
+    pub min_increase: i32,
+    pub window_days: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+impl CitationAlert {
+    pub fn new(collection_id: Option<String>, min_increase: i32, window_days: i64) -> Self {
+        Self {
+            alert_id: format!(
+                "{}-plus-{}-in-{}d",
+                collection_id.as_deref().unwrap_or("all"),
+                min_increase,
+                window_days
+            ),
+            collection_id,
+            min_increase,
+            window_days,
+            created_at: Utc::now(),
+        }
+    }
+
+    pub fn window(&self) -> Duration {
+        Duration::days(self.window_days)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CitationAlertEvent {
+    pub alert_id: String,
+    pub paper_id: String,
+    pub title: String,
+    pub delta: i32,
+    pub citation_count: i32,
+    pub triggered_at: DateTime<Utc>,
+}
+
+impl Entity for TrackedPaper {
+    const TREE: &'static str = "tracked_papers";
+
+    fn key(&self) -> String {
+        self.paper_id.to_owned()
+    }
+}
+
+impl Entity for CitationSnapshot {
+    const TREE: &'static str = "citation_snapshots";
+
+    fn key(&self) -> String {
+        format!(
+            "{}/{}",
+            self.paper_id,
+            self.taken_at.format("%Y%m%dT%H%M%S%.6f")
+        )
+    }
+}
+
+impl Entity for CitationAlert {
+    const TREE: &'static str = "citation_alerts";
+
+    fn key(&self) -> String {
+        self.alert_id.to_owned()
+    }
+}
+
+impl Entity for CitationAlertEvent {
+    const TREE: &'static str = "citation_alert_events";
+
+    fn key(&self) -> String {
+        format!(
+            "{}/{}/{}",
+            self.alert_id,
+            self.paper_id,
+            self.triggered_at.format("%Y%m%dT%H%M%S%.6f")
+        )
+    }
+}
+
+pub trait TrackingState {
+    fn get_tracked_paper(&self, paper_id: &str) -> Option<TrackedPaper>;
+    fn set_tracked_paper(&self, paper: &TrackedPaper);
+    fn remove_tracked_paper(&self, paper_id: &str);
+    fn list_tracked_papers(&self) -> Vec<TrackedPaper>;
+    fn add_citation_snapshot(&self, snapshot: &CitationSnapshot);
+    fn list_citation_snapshots(&self, paper_id: &str) -> Vec<CitationSnapshot>;
+    fn list_citation_snapshots_since(
+        &self,
+        paper_id: &str,
+        since: DateTime<Utc>,
+    ) -> Vec<CitationSnapshot>;
+    fn set_citation_alert(&self, alert: &CitationAlert);
+    fn remove_citation_alert(&self, alert_id: &str);
+    fn list_citation_alerts(&self) -> Vec<CitationAlert>;
+    fn last_alert_event(&self, alert_id: &str, paper_id: &str) -> Option<CitationAlertEvent>;
+    fn add_alert_event(&self, event: &CitationAlertEvent);
+    fn list_alert_events(&self) -> Vec<CitationAlertEvent>;
+}
+
+impl TrackingState for StateMach {
+    fn get_tracked_paper(&self, paper_id: &str) -> Option<TrackedPaper> {
+        self.repo::<TrackedPaper>().get(paper_id)
+    }
+
+    fn set_tracked_paper(&self, paper: &TrackedPaper) {
+        self.repo::<TrackedPaper>().put(paper);
+    }
+
+    /// The snapshots are kept, so tracking again continues the series.
+    fn remove_tracked_paper(&self, paper_id: &str) {
+        self.repo::<TrackedPaper>().remove(paper_id);
+    }
+
+    fn list_tracked_papers(&self) -> Vec<TrackedPaper> {
+        self.repo::<TrackedPaper>().iter().collect()
+    }
+
+    fn add_citation_snapshot(&self, snapshot: &CitationSnapshot) {
+        self.repo::<CitationSnapshot>().put(snapshot);
+    }
+
+    /// Oldest first.
+    fn list_citation_snapshots(&self, paper_id: &str) -> Vec<CitationSnapshot> {
+        self.repo::<CitationSnapshot>()
+            .scan_prefix(&format!("{}/", paper_id))
+            .collect()
+    }
+
+    /// A key range scan from the first snapshot taken at or after `since`.
+    fn list_citation_snapshots_since(
+        &self,
+        paper_id: &str,
+        since: DateTime<Utc>,
+    ) -> Vec<CitationSnapshot> {
+        let start = format!("{}/{}", paper_id, since.format("%Y%m%dT%H%M%S%.6f"));
+        // '0' sorts right after '/', so this ends the paper's key space
+        let end = format!("{}0", paper_id);
+        self.repo::<CitationSnapshot>().range(start..end).collect()
+    }
+
+    fn set_citation_alert(&self, alert: &CitationAlert) {
+        self.repo::<CitationAlert>().put(alert);
+    }
+
+    fn remove_citation_alert(&self, alert_id: &str) {
+        self.repo::<CitationAlert>().remove(alert_id);
+    }
+
+    fn list_citation_alerts(&self) -> Vec<CitationAlert> {
+        self.repo::<CitationAlert>().iter().collect()
+    }
+
+    fn last_alert_event(&self, alert_id: &str, paper_id: &str) -> Option<CitationAlertEvent> {
+        self.repo::<CitationAlertEvent>()
+            .scan_prefix(&format!("{}/{}/", alert_id, paper_id))
+            .next_back()
+    }
+
+    fn add_alert_event(&self, event: &CitationAlertEvent) {
+        self.repo::<CitationAlertEvent>().put(event);
+    }
+
+    /// Newest first.
+    fn list_alert_events(&self) -> Vec<CitationAlertEvent> {
+        let mut events = self
+            .repo::<CitationAlertEvent>()
+            .iter()
+            .collect::<Vec<CitationAlertEvent>>();
+        events.sort_by_key(|event| std::cmp::Reverse(event.triggered_at));
+        events
+    }
+}
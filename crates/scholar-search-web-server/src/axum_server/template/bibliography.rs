@@ -0,0 +1,78 @@
+use crate::axum_server::{
+    api::pdf::citations::CitationContext,
+    state::bibliography::{LinkedReference, PaperBibliography},
+};
+use askama::Template;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CitedReferenceRow {
+    /// `[12]`, or empty for author year styles
+    pub label: String,
+    pub text: String,
+    /// the matched S2 paper; empty when there is none
+    pub paper_id: String,
+    pub paper_title: String,
+    pub confidence: String,
+    /// confident enough to be taken as the cited paper
+    pub linked: bool,
+    pub citations: Vec<CitationContext>,
+}
+
+impl From<LinkedReference> for CitedReferenceRow {
+    fn from(x: LinkedReference) -> Self {
+        let linked = x.is_linked();
+        Self {
+            label: x.label.map(|l| format!("[{}]", l)).unwrap_or_default(),
+            text: x.text,
+            paper_id: x
+                .candidate
+                .as_ref()
+                .map(|c| c.paper_id.to_owned())
+                .unwrap_or_default(),
+            paper_title: x
+                .candidate
+                .as_ref()
+                .map(|c| c.title.to_owned())
+                .unwrap_or_default(),
+            confidence: x
+                .candidate
+                .as_ref()
+                .map(|c| format!("{:.0}%", c.confidence * 100.0))
+                .unwrap_or_default(),
+            linked,
+            citations: x.citations,
+        }
+    }
+}
+
+/// Where and how the paper's converted PDF cites each of its references.
+#[derive(Template, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[template(path = "bibliography_panel.html", ext = "html")]
+pub struct BibliographyPanelTemplate {
+    pub paper_id: String,
+    pub reference_count: usize,
+    pub linked: usize,
+    /// the references cited in the body, in list order
+    pub rows: Vec<CitedReferenceRow>,
+}
+
+impl BibliographyPanelTemplate {
+    pub fn new(paper_id: &str, bibliography: Option<PaperBibliography>) -> Self {
+        let (reference_count, linked) = bibliography
+            .as_ref()
+            .map_or((0, 0), |b| (b.references.len(), b.linked()));
+        Self {
+            paper_id: paper_id.to_string(),
+            reference_count,
+            linked,
+            rows: bibliography
+                .map(|b| b.references)
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|r| !r.citations.is_empty())
+                .map(CitedReferenceRow::from)
+                .collect(),
+        }
+    }
+}
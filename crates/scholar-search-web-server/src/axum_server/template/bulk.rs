@@ -0,0 +1,18 @@
+use askama::Template;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CommonPaperRowTemplate {
+    pub paper_id: String,
+    pub title: String,
+    pub year: i32,
+    pub shared_by: usize,
+}
+
+#[derive(Template, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[template(path = "common_papers.html", ext = "html")]
+pub struct CommonPapersTemplate {
+    pub selected_count: usize,
+    pub references: Vec<CommonPaperRowTemplate>,
+    pub citations: Vec<CommonPaperRowTemplate>,
+}
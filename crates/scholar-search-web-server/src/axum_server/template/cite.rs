@@ -0,0 +1,37 @@
+use crate::citation::style::FormattedCitations;
+use askama::Template;
+use serde::{Deserialize, Serialize};
+
+#[derive(Template, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[template(path = "cite_panel.html", ext = "html")]
+pub struct CitePanelTemplate {
+    pub paper_id: String,
+    pub style: String,
+    pub style_title: String,
+    pub styles: Vec<String>,
+    pub citation_text: String,
+    pub citation_html: String,
+    pub reference_text: String,
+    pub reference_html: String,
+}
+
+impl CitePanelTemplate {
+    pub fn new(paper_id: &str, citations: FormattedCitations, styles: Vec<String>) -> Self {
+        let citation = citations.citations.into_iter().next().unwrap_or_default();
+        let reference = citations
+            .bibliography
+            .into_iter()
+            .next()
+            .unwrap_or_default();
+        Self {
+            paper_id: paper_id.to_string(),
+            style: citations.style,
+            style_title: citations.style_title,
+            styles,
+            citation_text: citation.text,
+            citation_html: citation.html,
+            reference_text: reference.text,
+            reference_html: reference.html,
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use crate::axum_server::state::{
+    source::{PdfSourceLog, SourceAttempt},
+    PdfFile, PdfFileStatus, PdfTransition,
+};
+use askama::Template;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FileRowTemplate {
+    pub paper_id: String,
+    pub status: String,
+    /// failed stage and reason, empty unless failed
+    pub failure: String,
+    pub attempts: u32,
+    pub updated_at: String,
+    pub retryable: bool,
+}
+
+impl From<PdfFile> for FileRowTemplate {
+    fn from(x: PdfFile) -> Self {
+        let (failure, attempts) = match &x.status {
+            PdfFileStatus::Failed {
+                stage,
+                reason,
+                attempts,
+            } => (format!("{}: {}", stage, reason), *attempts),
+            _ => (String::new(), 0),
+        };
+        Self {
+            paper_id: x.paper_id,
+            status: x.status.to_string(),
+            retryable: attempts > 0,
+            failure,
+            attempts,
+            updated_at: x.updated_at.format("%Y-%m-%d %H:%M").to_string(),
+        }
+    }
+}
+
+#[derive(Template, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[template(path = "files.html", ext = "html")]
+pub struct FilesPageTemplate {
+    pub status: String,
+    /// file count per status name
+    pub counts: Vec<(String, usize)>,
+    pub rows: Vec<FileRowTemplate>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FileTransitionRow {
+    pub from: String,
+    pub to: String,
+    pub at: String,
+}
+
+#[derive(Template, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[template(path = "file_history.html", ext = "html")]
+pub struct FileHistoryTemplate {
+    pub paper_id: String,
+    pub transitions: Vec<FileTransitionRow>,
+    /// the sources tried by the latest download
+    pub sources: Vec<SourceAttempt>,
+}
+
+impl FileHistoryTemplate {
+    pub fn new(
+        paper_id: &str,
+        transitions: Vec<PdfTransition>,
+        sources: Option<PdfSourceLog>,
+    ) -> Self {
+        Self {
+            paper_id: paper_id.to_string(),
+            sources: sources.map(|log| log.attempts).unwrap_or_default(),
+            transitions: transitions
+                .into_iter()
+                .rev()
+                .map(|t| FileTransitionRow {
+                    from: t.from.to_string(),
+                    to: match &t.to {
+                        PdfFileStatus::Failed { stage, reason, .. } => {
+                            format!("failed ({}: {})", stage, reason)
+                        }
+                        to => to.to_string(),
+                    },
+                    at: t.at.format("%Y-%m-%d %H:%M:%S").to_string(),
+                })
+                .collect(),
+        }
+    }
+}
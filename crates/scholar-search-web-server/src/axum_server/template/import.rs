@@ -0,0 +1,131 @@
+use crate::axum_server::{
+    state::import::{ImportBatch, ImportEntry, ImportEntryStatus},
+    template::library::CollectionTreeItem,
+};
+use crate::citation::resolve::MatchCandidate;
+use askama::Template;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ImportCandidateTemplate {
+    pub paper_id: String,
+    pub title: String,
+    pub authors: String,
+    pub year: String,
+    pub method: String,
+    pub confidence: String,
+}
+
+impl From<MatchCandidate> for ImportCandidateTemplate {
+    fn from(x: MatchCandidate) -> Self {
+        Self {
+            paper_id: x.paper_id,
+            title: x.title,
+            authors: x.authors,
+            year: x.year.map(|y| y.to_string()).unwrap_or_default(),
+            method: x.method.to_string(),
+            confidence: format!("{:.0}%", x.confidence * 100.0),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ImportRowTemplate {
+    pub index: usize,
+    pub source_key: String,
+    pub title: String,
+    pub authors: String,
+    pub year: String,
+    pub status: String,
+    /// whether the reviewer can still pick a candidate or reject the entry
+    pub reviewable: bool,
+    pub selected: String,
+    pub candidates: Vec<ImportCandidateTemplate>,
+}
+
+impl From<ImportEntry> for ImportRowTemplate {
+    fn from(x: ImportEntry) -> Self {
+        Self {
+            index: x.index,
+            source_key: x.entry.source_key,
+            title: x.entry.title,
+            authors: x
+                .entry
+                .authors
+                .iter()
+                .map(|a| a.inverted())
+                .collect::<Vec<String>>()
+                .join("; "),
+            year: x.entry.year.map(|y| y.to_string()).unwrap_or_default(),
+            status: x.status.to_string(),
+            reviewable: !matches!(
+                x.status,
+                ImportEntryStatus::Pending | ImportEntryStatus::Imported
+            ),
+            selected: x.selected.unwrap_or_default(),
+            candidates: x
+                .candidates
+                .into_iter()
+                .map(ImportCandidateTemplate::from)
+                .collect(),
+        }
+    }
+}
+
+#[derive(Template, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[template(path = "import_review.html", ext = "html")]
+pub struct ImportReviewTemplate {
+    pub import_id: String,
+    pub file_name: String,
+    pub collection_id: String,
+    pub resolved: bool,
+    pub pending: usize,
+    pub ready: usize,
+    pub review: usize,
+    pub imported: usize,
+    pub rows: Vec<ImportRowTemplate>,
+}
+
+impl From<ImportBatch> for ImportReviewTemplate {
+    fn from(x: ImportBatch) -> Self {
+        Self {
+            resolved: x.is_resolved(),
+            pending: x.count(ImportEntryStatus::Pending),
+            ready: x.entries.iter().filter(|e| e.is_ready()).count(),
+            review: x.count(ImportEntryStatus::Ambiguous) + x.count(ImportEntryStatus::Unmatched),
+            imported: x.count(ImportEntryStatus::Imported),
+            import_id: x.import_id,
+            file_name: x.file_name,
+            collection_id: x.collection_id.unwrap_or_default(),
+            rows: x.entries.into_iter().map(ImportRowTemplate::from).collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ImportSummaryTemplate {
+    pub import_id: String,
+    pub file_name: String,
+    pub created_at: String,
+    pub entry_count: usize,
+    pub imported: usize,
+}
+
+impl From<ImportBatch> for ImportSummaryTemplate {
+    fn from(x: ImportBatch) -> Self {
+        Self {
+            imported: x.count(ImportEntryStatus::Imported),
+            entry_count: x.entries.len(),
+            created_at: x.created_at.format("%Y-%m-%d %H:%M").to_string(),
+            import_id: x.import_id,
+            file_name: x.file_name,
+        }
+    }
+}
+
+#[derive(Template, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[template(path = "import.html", ext = "html")]
+pub struct ImportPageTemplate {
+    pub collections: Vec<CollectionTreeItem>,
+    pub imports: Vec<ImportSummaryTemplate>,
+}
@@ -0,0 +1,141 @@
+use crate::axum_server::state::library::{Bookmark, Collection, LibraryFilter};
+use askama::Template;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LibraryRowTemplate {
+    pub paper_id: String,
+    pub title: String,
+    pub authors: String,
+    pub year: i32,
+    pub venue: String,
+    pub citation_count: i32,
+    pub tags: Vec<String>,
+    pub collections: Vec<String>,
+    pub date_added: String,
+}
+
+impl From<Bookmark> for LibraryRowTemplate {
+    fn from(x: Bookmark) -> Self {
+        Self {
+            paper_id: x.paper_id,
+            title: x.paper.title,
+            authors: x
+                .paper
+                .authors
+                .unwrap_or_default()
+                .into_iter()
+                .map(|y| y.name)
+                .collect::<Vec<String>>()
+                .join(", "),
+            year: x.paper.year,
+            venue: x.paper.venue.unwrap_or_default(),
+            citation_count: x.paper.citation_count,
+            tags: x.tags,
+            collections: x.collections,
+            date_added: x.date_added.format("%Y-%m-%d").to_string(),
+        }
+    }
+}
+
+/// A collection with its nesting depth, for rendering the folder list.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CollectionTreeItem {
+    pub collection_id: String,
+    pub name: String,
+    pub depth: usize,
+}
+
+impl CollectionTreeItem {
+    pub fn from_collections(collections: Vec<Collection>) -> Vec<CollectionTreeItem> {
+        fn walk(
+            collections: &[Collection],
+            parent_id: Option<&str>,
+            depth: usize,
+            out: &mut Vec<CollectionTreeItem>,
+        ) {
+            let mut children = collections
+                .iter()
+                .filter(|c| c.parent_id.as_deref() == parent_id)
+                .collect::<Vec<&Collection>>();
+            children.sort_by(|a, b| a.name.cmp(&b.name));
+            for c in children {
+                out.push(CollectionTreeItem {
+                    collection_id: c.collection_id.to_owned(),
+                    name: c.name.to_owned(),
+                    depth,
+                });
+                walk(collections, Some(&c.collection_id), depth + 1, out);
+            }
+        }
+        let mut out = vec![];
+        walk(&collections, None, 0, &mut out);
+        out
+    }
+}
+
+#[derive(Template, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[template(path = "library.html", ext = "html")]
+pub struct LibraryPageTemplate {
+    pub filter: LibraryFilter,
+    pub sort: String,
+    pub collections: Vec<CollectionTreeItem>,
+    /// citation styles offered for collection bibliographies
+    pub styles: Vec<String>,
+    pub tags: Vec<String>,
+    pub rows: Vec<LibraryRowTemplate>,
+}
+
+#[derive(Template, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[template(path = "library_rows.html", ext = "html")]
+pub struct LibraryRowsTemplate {
+    pub rows: Vec<LibraryRowTemplate>,
+}
+
+#[derive(Template, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[template(path = "library_collections.html", ext = "html")]
+pub struct LibraryCollectionsTemplate {
+    pub collections: Vec<CollectionTreeItem>,
+    pub styles: Vec<String>,
+}
+
+#[derive(Template, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[template(path = "bookmark_panel.html", ext = "html")]
+pub struct BookmarkPanelTemplate {
+    pub bookmarked: bool,
+    pub paper_id: String,
+    pub title: String,
+    pub tags: String,
+    pub note: String,
+    pub date_added: String,
+    pub selected_collections: Vec<String>,
+    pub collections: Vec<CollectionTreeItem>,
+}
+
+impl BookmarkPanelTemplate {
+    pub fn new(paper_id: &str, bookmark: Option<Bookmark>, collections: Vec<Collection>) -> Self {
+        let collections = CollectionTreeItem::from_collections(collections);
+        match bookmark {
+            Some(b) => Self {
+                bookmarked: true,
+                paper_id: b.paper_id,
+                title: b.paper.title,
+                tags: b.tags.join(", "),
+                note: b.note,
+                date_added: b.date_added.format("%Y-%m-%d %H:%M").to_string(),
+                selected_collections: b.collections,
+                collections,
+            },
+            None => Self {
+                bookmarked: false,
+                paper_id: paper_id.to_string(),
+                title: "".to_string(),
+                tags: "".to_string(),
+                note: "".to_string(),
+                date_added: "".to_string(),
+                selected_collections: vec![],
+                collections,
+            },
+        }
+    }
+}
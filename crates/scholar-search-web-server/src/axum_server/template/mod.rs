@@ -4,4 +4,12 @@
 pub mod table;
 pub mod search_page;
 pub mod page_detail;
+pub mod library;
+pub mod bulk;
+pub mod files;
+pub mod cite;
+pub mod import;
+pub mod search;
+pub mod tracking;
+pub mod bibliography;
 
@@ -0,0 +1,157 @@
+use crate::alerts::email::SmtpConfig;
+use crate::axum_server::state::{
+    delivery::Delivery,
+    search::{InboxItem, SavedSearch, SearchRun},
+};
+use askama::Template;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+fn format_time(time: Option<DateTime<Utc>>) -> String {
+    time.map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_default()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SavedSearchRowTemplate {
+    pub search_id: String,
+    pub name: String,
+    pub query: String,
+    pub publication_date_or_year: String,
+    pub schedule: String,
+    pub enabled: bool,
+    pub last_run: String,
+    pub next_run: String,
+    pub unseen: usize,
+}
+
+impl SavedSearchRowTemplate {
+    pub fn new(search: SavedSearch, unseen: usize) -> Self {
+        Self {
+            search_id: search.search_id,
+            name: search.name,
+            query: search.request.query,
+            publication_date_or_year: search.request.publication_date_or_year,
+            schedule: search.schedule,
+            enabled: search.enabled,
+            last_run: format_time(search.last_run),
+            next_run: format_time(search.next_run),
+            unseen,
+        }
+    }
+}
+
+#[derive(Template, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[template(path = "searches.html", ext = "html")]
+pub struct SavedSearchesPageTemplate {
+    pub default_schedule: String,
+    pub rows: Vec<SavedSearchRowTemplate>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct InboxItemTemplate {
+    pub paper_id: String,
+    pub title: String,
+    pub authors: String,
+    pub year: String,
+    pub citation_count: String,
+    pub found_at: String,
+    pub seen: bool,
+}
+
+impl From<InboxItem> for InboxItemTemplate {
+    fn from(x: InboxItem) -> Self {
+        Self {
+            paper_id: x.paper_id,
+            title: x.title,
+            authors: x.authors,
+            year: x.year.map(|y| y.to_string()).unwrap_or_default(),
+            citation_count: x.citation_count.map(|c| c.to_string()).unwrap_or_default(),
+            found_at: format_time(Some(x.found_at)),
+            seen: x.seen,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DeliveryRowTemplate {
+    pub delivery_id: String,
+    pub channel: String,
+    pub target: String,
+    pub paper_count: usize,
+    pub status: String,
+    pub attempts: usize,
+    pub last_attempt: String,
+    pub last_message: String,
+    pub next_attempt: String,
+}
+
+impl From<Delivery> for DeliveryRowTemplate {
+    fn from(x: Delivery) -> Self {
+        let last = x.attempts.last();
+        Self {
+            channel: x.channel.to_string(),
+            paper_count: x.payload.papers.len(),
+            status: x.status.to_string(),
+            attempts: x.attempts.len(),
+            last_attempt: format_time(last.map(|a| a.at)),
+            last_message: last.map(|a| a.message.to_owned()).unwrap_or_default(),
+            next_attempt: format_time(x.next_attempt),
+            delivery_id: x.delivery_id,
+            target: x.target,
+        }
+    }
+}
+
+/// Only the most recent deliveries are listed under the inbox.
+const DELIVERY_LOG_SIZE: usize = 20;
+
+#[derive(Template, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[template(path = "search_inbox.html", ext = "html")]
+pub struct SavedSearchInboxTemplate {
+    pub search_id: String,
+    pub name: String,
+    pub last_run: String,
+    pub total: i32,
+    pub new_count: usize,
+    /// set when the most recent run failed
+    pub last_error: String,
+    pub unseen: usize,
+    pub items: Vec<InboxItemTemplate>,
+    pub webhook_url: String,
+    pub email_to: String,
+    pub smtp_configured: bool,
+    pub deliveries: Vec<DeliveryRowTemplate>,
+}
+
+impl SavedSearchInboxTemplate {
+    pub fn new(
+        search: SavedSearch,
+        last_run: Option<SearchRun>,
+        last_error: Option<String>,
+        inbox: Vec<InboxItem>,
+        deliveries: Vec<Delivery>,
+    ) -> Self {
+        Self {
+            webhook_url: search.delivery.webhook_url.to_owned().unwrap_or_default(),
+            email_to: search.delivery.email_to.join(", "),
+            smtp_configured: matches!(SmtpConfig::from_env(), Ok(Some(_))),
+            deliveries: deliveries
+                .into_iter()
+                .take(DELIVERY_LOG_SIZE)
+                .map(DeliveryRowTemplate::from)
+                .collect(),
+            search_id: search.search_id,
+            name: search.name,
+            last_run: format_time(last_run.as_ref().map(|run| run.run_at)),
+            total: last_run.as_ref().map(|run| run.total).unwrap_or_default(),
+            new_count: last_run
+                .as_ref()
+                .map(|run| run.new_paper_ids.len())
+                .unwrap_or_default(),
+            last_error: last_error.unwrap_or_default(),
+            unseen: inbox.iter().filter(|item| !item.seen).count(),
+            items: inbox.into_iter().map(InboxItemTemplate::from).collect(),
+        }
+    }
+}
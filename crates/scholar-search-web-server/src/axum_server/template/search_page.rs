@@ -1,4 +1,4 @@
-use crate::axum_server::template::table::TableRowTemplate;
+use crate::axum_server::template::{library::CollectionTreeItem, table::TableRowTemplate};
 use askama::Template;
 use serde::{Deserialize, Serialize};
 #[derive(Template, Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -6,6 +6,8 @@ use serde::{Deserialize, Serialize};
 pub struct SearchPageLayoutTemplate {
     // pub body: String,
     pub total_count: i32,
+    pub next_token: Option<String>,
+    pub collections: Vec<CollectionTreeItem>,
     pub rows: Vec<TableRowTemplate>,
 }
 
@@ -17,6 +19,7 @@ pub struct SearchPageLayoutTemplate {
  <div id="result-count" class="flex" hx-swap-oob="true">
   <h2>total : {{total_count}}</h2>
 </div>
+{% include "search_pagination.html" %}
 "###,
     ext = "html"
 )]
@@ -24,4 +27,5 @@ pub struct SearchResultTemplate {
     pub query: String,
     pub rows: Vec<TableRowTemplate>,
     pub total_count: i32,
+    pub next_token: Option<String>,
 }
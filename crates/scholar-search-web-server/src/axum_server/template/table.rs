@@ -1,3 +1,4 @@
+use crate::citation::{export, ExportFormat};
 use crate::semantic_scholar_api::data::{Author, Paper, PaperDetail, S2FieldsOfStudy};
 use askama::Template;
 use serde::{Deserialize, Serialize};
@@ -94,39 +95,34 @@ pub struct PaperDetailTemplateDetailPrint {
     // pub references: Vec<TableRowTemplate>,
     // pub embedding:  HashMap<String, String>,
     pub tldr: HashMap<String, String>,
+    pub bibtex: String,
 }
 
 impl From<PaperDetail> for PaperDetailTemplateDetailPrint {
     fn from(sorce: PaperDetail) -> Self {
-        
+        let bibtex = export(vec![sorce.clone()], ExportFormat::Bibtex);
         let mut external_ids_map: HashMap<String, String> = HashMap::new();
         if let Some(ext_ids) = sorce.external_ids {
-            if ext_ids.doi.is_some() {
-                external_ids_map.insert("doi".to_string(), ext_ids.doi.unwrap());
+            if let Some(doi) = ext_ids.doi {
+                external_ids_map.insert("doi".to_string(), doi);
             }
-            if ext_ids.dblp.is_some() {
-                external_ids_map.insert("dblp".to_string(), ext_ids.dblp.unwrap());
+            if let Some(dblp) = ext_ids.dblp {
+                external_ids_map.insert("dblp".to_string(), dblp);
             }
-            if ext_ids.ar_xiv.is_some() {
-                external_ids_map.insert("ar_xiv".to_string(), ext_ids.ar_xiv.unwrap());
+            if let Some(ar_xiv) = ext_ids.ar_xiv {
+                external_ids_map.insert("ar_xiv".to_string(), ar_xiv);
             }
-            if ext_ids.corpus_id.is_some() {
-                external_ids_map.insert(
-                    "corpus_id".to_string(),
-                    ext_ids.corpus_id.unwrap().to_string(),
-                );
+            if let Some(corpus_id) = ext_ids.corpus_id {
+                external_ids_map.insert("corpus_id".to_string(), corpus_id.to_string());
             }
-            if ext_ids.pub_med_central.is_some() {
-                external_ids_map.insert(
-                    "pub_med_central".to_string(),
-                    ext_ids.pub_med_central.unwrap(),
-                );
+            if let Some(pub_med_central) = ext_ids.pub_med_central {
+                external_ids_map.insert("pub_med_central".to_string(), pub_med_central);
             }
-            if ext_ids.pub_med.is_some() {
-                external_ids_map.insert("pub_med".to_string(), ext_ids.pub_med.unwrap());
+            if let Some(pub_med) = ext_ids.pub_med {
+                external_ids_map.insert("pub_med".to_string(), pub_med);
             }
-            if ext_ids.mag.is_some() {
-                external_ids_map.insert("mag".to_string(), ext_ids.mag.unwrap());
+            if let Some(mag) = ext_ids.mag {
+                external_ids_map.insert("mag".to_string(), mag);
             }
         }
         let mut tldr_map: HashMap<String, String> = HashMap::new();
@@ -175,6 +171,7 @@ impl From<PaperDetail> for PaperDetailTemplateDetailPrint {
             //     .map(|x| TableRowTemplate::from(x))
             //     .collect::<Vec<TableRowTemplate>>(),
             tldr: tldr_map,
+            bibtex,
         }
     }
 }
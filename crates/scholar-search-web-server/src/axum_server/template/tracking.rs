@@ -0,0 +1,128 @@
+use crate::axum_server::{
+    state::tracking::{CitationAlert, CitationAlertEvent},
+    template::library::CollectionTreeItem,
+};
+use crate::citation::growth::PaperGrowth;
+use askama::Template;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GrowthRowTemplate {
+    pub paper_id: String,
+    pub title: String,
+    pub citation_count: i32,
+    pub influential_citation_count: i32,
+    pub delta: String,
+    pub velocity: String,
+    pub acceleration: String,
+    pub relative_growth: String,
+}
+
+impl From<PaperGrowth> for GrowthRowTemplate {
+    fn from(x: PaperGrowth) -> Self {
+        Self {
+            paper_id: x.paper_id,
+            title: x.title,
+            citation_count: x.citation_count,
+            influential_citation_count: x.influential_citation_count,
+            delta: x.delta.map(|d| format!("{:+}", d)).unwrap_or_default(),
+            velocity: x.velocity.map(|v| format!("{:.2}", v)).unwrap_or_default(),
+            acceleration: x
+                .acceleration
+                .map(|a| format!("{:+.2}", a))
+                .unwrap_or_default(),
+            relative_growth: x
+                .relative_growth
+                .map(|r| format!("{:+.0}%", r * 100.0))
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CitationAlertRowTemplate {
+    pub alert_id: String,
+    pub scope: String,
+    pub min_increase: i32,
+    pub window_days: i64,
+}
+
+impl CitationAlertRowTemplate {
+    pub fn new(alert: CitationAlert, collection_names: &HashMap<String, String>) -> Self {
+        Self {
+            scope: match &alert.collection_id {
+                Some(collection_id) => collection_names
+                    .get(collection_id)
+                    .cloned()
+                    .unwrap_or(collection_id.to_owned()),
+                None => "all tracked papers".to_string(),
+            },
+            alert_id: alert.alert_id,
+            min_increase: alert.min_increase,
+            window_days: alert.window_days,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AlertEventRowTemplate {
+    pub paper_id: String,
+    pub title: String,
+    pub delta: i32,
+    pub citation_count: i32,
+    pub triggered_at: String,
+}
+
+impl From<CitationAlertEvent> for AlertEventRowTemplate {
+    fn from(x: CitationAlertEvent) -> Self {
+        Self {
+            paper_id: x.paper_id,
+            title: x.title,
+            delta: x.delta,
+            citation_count: x.citation_count,
+            triggered_at: x.triggered_at.format("%Y-%m-%d %H:%M").to_string(),
+        }
+    }
+}
+
+#[derive(Template, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[template(path = "growth.html", ext = "html")]
+pub struct GrowthPageTemplate {
+    pub collection_id: String,
+    pub window_days: i64,
+    pub collections: Vec<CollectionTreeItem>,
+    pub rows: Vec<GrowthRowTemplate>,
+    pub risers: Vec<GrowthRowTemplate>,
+    pub alerts: Vec<CitationAlertRowTemplate>,
+    pub events: Vec<AlertEventRowTemplate>,
+}
+
+#[derive(Template, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[template(path = "track_panel.html", ext = "html")]
+pub struct TrackPanelTemplate {
+    pub paper_id: String,
+    pub tracked: bool,
+    /// bookmarked papers are tracked regardless
+    pub bookmarked: bool,
+    pub snapshot_count: usize,
+    pub growth: GrowthRowTemplate,
+}
+
+impl TrackPanelTemplate {
+    pub fn new(
+        paper_id: &str,
+        tracked: bool,
+        bookmarked: bool,
+        growth: PaperGrowth,
+        snapshot_count: usize,
+    ) -> Self {
+        Self {
+            paper_id: paper_id.to_string(),
+            tracked,
+            bookmarked,
+            snapshot_count,
+            growth: GrowthRowTemplate::from(growth),
+        }
+    }
+}
@@ -0,0 +1,362 @@
+use crate::axum_server::{
+    state::{
+        library::LibraryState,
+        tracking::{CitationAlert, CitationAlertEvent, TrackedPaper, TrackingState},
+        StateMach,
+    },
+    template::{
+        library::CollectionTreeItem,
+        tracking::{
+            AlertEventRowTemplate, CitationAlertRowTemplate, GrowthPageTemplate, GrowthRowTemplate,
+            TrackPanelTemplate,
+        },
+    },
+};
+use crate::citation::growth::{fast_risers, CitationSnapshot, PaperGrowth};
+use crate::semantic_scholar_api::{
+    data::PaperDetail,
+    paper_fetch::{fetch_paper_batch, fetch_paper_detail},
+};
+
+use axum::{
+    extract::{Form, Path, RawQuery, State},
+    http::StatusCode,
+    routing::{get, post},
+    Router,
+};
+use chrono::{DateTime, Duration, Utc};
+use query_map::QueryMap;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tracing::{info, warn};
+
+/// Counts are recorded at most once a day per paper.
+const SNAPSHOT_INTERVAL: i64 = 24;
+
+/// How often the tracker checks for papers due a snapshot.
+const TRACKER_TICK: std::time::Duration = std::time::Duration::from_secs(3600);
+
+/// The batch endpoint accepts up to 500 ids per call.
+const BATCH_SIZE: usize = 500;
+
+const DEFAULT_WINDOW_DAYS: i64 = 30;
+
+const FAST_RISERS: usize = 10;
+
+/// A tracked paper with the library collections it belongs to.
+struct TrackedEntry {
+    paper_id: String,
+    title: String,
+    collections: Vec<String>,
+}
+
+/// Bookmarked papers plus the ones tracked on their own.
+fn tracked_entries(state_mach: &StateMach) -> Vec<TrackedEntry> {
+    let mut entries = state_mach
+        .list_bookmarks()
+        .into_iter()
+        .map(|b| TrackedEntry {
+            paper_id: b.paper_id,
+            title: b.paper.title,
+            collections: b.collections,
+        })
+        .collect::<Vec<TrackedEntry>>();
+    for paper in state_mach.list_tracked_papers() {
+        if !entries.iter().any(|e| e.paper_id == paper.paper_id) {
+            entries.push(TrackedEntry {
+                paper_id: paper.paper_id,
+                title: paper.title,
+                collections: vec![],
+            });
+        }
+    }
+    entries
+}
+
+fn snapshot_of(paper: &PaperDetail, taken_at: DateTime<Utc>) -> CitationSnapshot {
+    CitationSnapshot {
+        paper_id: paper.paper_id.to_owned(),
+        taken_at,
+        citation_count: paper.citation_count,
+        influential_citation_count: paper.influential_citation_count,
+    }
+}
+
+/// Records the current counts of every tracked paper whose last snapshot is
+/// older than `SNAPSHOT_INTERVAL`, or of all of them when `force` is set.
+pub async fn take_citation_snapshots(state_mach: &StateMach, force: bool) -> usize {
+    let now = Utc::now();
+    let due = tracked_entries(state_mach)
+        .into_iter()
+        .filter(|entry| {
+            force
+                || state_mach
+                    .list_citation_snapshots(&entry.paper_id)
+                    .last()
+                    .is_none_or(|last| now - last.taken_at >= Duration::hours(SNAPSHOT_INTERVAL))
+        })
+        .map(|entry| entry.paper_id)
+        .collect::<Vec<String>>();
+    let mut taken = 0;
+    for chunk in due.chunks(BATCH_SIZE) {
+        match fetch_paper_batch(chunk.to_vec()).await {
+            Ok(papers) => {
+                for paper in papers.iter().flatten() {
+                    state_mach.add_citation_snapshot(&snapshot_of(paper, now));
+                    taken += 1;
+                }
+            }
+            Err(e) => warn!("citation snapshot batch failed: {}", e),
+        }
+    }
+    if taken > 0 {
+        info!("recorded {} citation snapshots", taken);
+    }
+    taken
+}
+
+/// Records an event for every paper that crossed an alert threshold; a
+/// paper fires the same alert at most once per alert window.
+pub fn check_citation_alerts(state_mach: &StateMach) -> Vec<CitationAlertEvent> {
+    let now = Utc::now();
+    let entries = tracked_entries(state_mach);
+    let mut events = vec![];
+    for alert in state_mach.list_citation_alerts() {
+        for entry in entries.iter().filter(|entry| match &alert.collection_id {
+            Some(collection_id) => entry.collections.contains(collection_id),
+            None => true,
+        }) {
+            let recently_fired = state_mach
+                .last_alert_event(&alert.alert_id, &entry.paper_id)
+                .is_some_and(|event| now - event.triggered_at < alert.window());
+            if recently_fired {
+                continue;
+            }
+            let growth = PaperGrowth::new(
+                &entry.paper_id,
+                &entry.title,
+                &state_mach.list_citation_snapshots(&entry.paper_id),
+                now,
+                alert.window(),
+            );
+            match growth.delta {
+                Some(delta) if delta >= alert.min_increase => {
+                    let event = CitationAlertEvent {
+                        alert_id: alert.alert_id.to_owned(),
+                        paper_id: entry.paper_id.to_owned(),
+                        title: entry.title.to_owned(),
+                        delta,
+                        citation_count: growth.citation_count,
+                        triggered_at: now,
+                    };
+                    info!(
+                        "citation alert {}: {} gained {} citations",
+                        alert.alert_id, entry.paper_id, delta
+                    );
+                    state_mach.add_alert_event(&event);
+                    events.push(event);
+                }
+                _ => {}
+            }
+        }
+    }
+    events
+}
+
+/// Background task that snapshots citation counts and checks the alerts.
+pub fn spawn_citation_tracker(state_mach: StateMach) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(TRACKER_TICK);
+        loop {
+            interval.tick().await;
+            if take_citation_snapshots(&state_mach, false).await > 0 {
+                check_citation_alerts(&state_mach);
+            }
+        }
+    });
+}
+
+fn track_panel(state_mach: &StateMach, paper_id: &str) -> TrackPanelTemplate {
+    let snapshots = state_mach.list_citation_snapshots(paper_id);
+    TrackPanelTemplate::new(
+        paper_id,
+        state_mach.get_tracked_paper(paper_id).is_some(),
+        state_mach.get_bookmark(paper_id).is_some(),
+        PaperGrowth::new(
+            paper_id,
+            "",
+            &snapshots,
+            Utc::now(),
+            Duration::days(DEFAULT_WINDOW_DAYS),
+        ),
+        snapshots.len(),
+    )
+}
+
+pub async fn paper_track(
+    State(state_mach): State<StateMach>,
+    Path(paper_id): Path<String>,
+) -> TrackPanelTemplate {
+    track_panel(&state_mach, &paper_id)
+}
+
+/// Starts tracking a paper, with its current counts as the first snapshot.
+pub async fn paper_track_add(
+    State(state_mach): State<StateMach>,
+    Path(paper_id): Path<String>,
+) -> Result<TrackPanelTemplate, (StatusCode, String)> {
+    if state_mach.get_tracked_paper(&paper_id).is_none() {
+        let paper = fetch_paper_detail(paper_id.to_owned())
+            .await
+            .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))?;
+        state_mach.set_tracked_paper(&TrackedPaper {
+            paper_id: paper.paper_id.to_owned(),
+            title: paper.title.to_owned(),
+            added_at: Utc::now(),
+        });
+        state_mach.add_citation_snapshot(&snapshot_of(&paper, Utc::now()));
+    }
+    Ok(track_panel(&state_mach, &paper_id))
+}
+
+pub async fn paper_track_remove(
+    State(state_mach): State<StateMach>,
+    Path(paper_id): Path<String>,
+) -> TrackPanelTemplate {
+    state_mach.remove_tracked_paper(&paper_id);
+    track_panel(&state_mach, &paper_id)
+}
+
+/// The snapshot series of a paper, limited to the last `days` if given.
+pub async fn api_citation_history(
+    State(state_mach): State<StateMach>,
+    Path(paper_id): Path<String>,
+    RawQuery(raw_query): RawQuery,
+) -> axum::Json<Vec<CitationSnapshot>> {
+    let query = raw_query.unwrap_or_default().parse::<QueryMap>().unwrap();
+    let days = query.first("days").and_then(|d| d.parse::<i64>().ok());
+    axum::Json(match days {
+        Some(days) => {
+            state_mach.list_citation_snapshots_since(&paper_id, Utc::now() - Duration::days(days))
+        }
+        None => state_mach.list_citation_snapshots(&paper_id),
+    })
+}
+
+pub async fn growth_page(
+    State(state_mach): State<StateMach>,
+    RawQuery(raw_query): RawQuery,
+) -> GrowthPageTemplate {
+    let query = raw_query.unwrap_or_default().parse::<QueryMap>().unwrap();
+    let collection_id = query
+        .first("collection")
+        .filter(|c| !c.is_empty())
+        .map(|c| c.to_string());
+    let window_days = query
+        .first("window")
+        .and_then(|w| w.parse::<i64>().ok())
+        .filter(|w| *w > 0)
+        .unwrap_or(DEFAULT_WINDOW_DAYS);
+
+    let now = Utc::now();
+    let mut growth = tracked_entries(&state_mach)
+        .into_iter()
+        .filter(|entry| match &collection_id {
+            Some(collection_id) => entry.collections.contains(collection_id),
+            None => true,
+        })
+        .map(|entry| {
+            PaperGrowth::new(
+                &entry.paper_id,
+                &entry.title,
+                &state_mach.list_citation_snapshots(&entry.paper_id),
+                now,
+                Duration::days(window_days),
+            )
+        })
+        .collect::<Vec<PaperGrowth>>();
+    let risers = fast_risers(&growth, FAST_RISERS);
+    growth.sort_by(|a, b| b.velocity.partial_cmp(&a.velocity).unwrap());
+
+    let collections = CollectionTreeItem::from_collections(state_mach.list_collections());
+    let collection_names = collections
+        .iter()
+        .map(|c| (c.collection_id.to_owned(), c.name.to_owned()))
+        .collect::<HashMap<String, String>>();
+    GrowthPageTemplate {
+        collection_id: collection_id.unwrap_or_default(),
+        window_days,
+        rows: growth.into_iter().map(GrowthRowTemplate::from).collect(),
+        risers: risers.into_iter().map(GrowthRowTemplate::from).collect(),
+        alerts: state_mach
+            .list_citation_alerts()
+            .into_iter()
+            .map(|alert| CitationAlertRowTemplate::new(alert, &collection_names))
+            .collect(),
+        events: state_mach
+            .list_alert_events()
+            .into_iter()
+            .map(AlertEventRowTemplate::from)
+            .collect(),
+        collections,
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct CitationAlertRequest {
+    collection_id: String,
+    min_increase: i32,
+    window_days: i64,
+}
+
+pub async fn citation_alert_create(
+    State(state_mach): State<StateMach>,
+    Form(payload): Form<CitationAlertRequest>,
+) -> Result<GrowthPageTemplate, (StatusCode, String)> {
+    if payload.min_increase < 1 || payload.window_days < 1 {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "threshold and window must be positive".to_string(),
+        ));
+    }
+    let collection_id = Some(payload.collection_id).filter(|c| !c.is_empty());
+    state_mach.set_citation_alert(&CitationAlert::new(
+        collection_id,
+        payload.min_increase,
+        payload.window_days,
+    ));
+    check_citation_alerts(&state_mach);
+    Ok(growth_page(State(state_mach), RawQuery(None)).await)
+}
+
+pub async fn citation_alert_remove(
+    State(state_mach): State<StateMach>,
+    Path(alert_id): Path<String>,
+) -> GrowthPageTemplate {
+    state_mach.remove_citation_alert(&alert_id);
+    growth_page(State(state_mach), RawQuery(None)).await
+}
+
+pub async fn growth_snapshot(State(state_mach): State<StateMach>) -> GrowthPageTemplate {
+    take_citation_snapshots(&state_mach, true).await;
+    check_citation_alerts(&state_mach);
+    growth_page(State(state_mach), RawQuery(None)).await
+}
+
+pub fn tracking_router() -> Router<StateMach> {
+    Router::new()
+        .route("/x/paper/:paper_id/track", get(paper_track))
+        .route("/x/paper/:paper_id/track/add", post(paper_track_add))
+        .route("/x/paper/:paper_id/track/remove", post(paper_track_remove))
+        .route(
+            "/api/paper/:paper_id/citation_history",
+            get(api_citation_history),
+        )
+        .route("/x/growth", get(growth_page))
+        .route("/x/growth/snapshot", post(growth_snapshot))
+        .route("/x/growth/alerts", post(citation_alert_create))
+        .route(
+            "/x/growth/alerts/:alert_id/remove",
+            post(citation_alert_remove),
+        )
+}
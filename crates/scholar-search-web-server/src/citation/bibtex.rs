@@ -0,0 +1,203 @@
+use crate::citation::{CitationItem, CitationType};
+use std::collections::HashMap;
+
+const STOP_WORDS: [&str; 12] = [
+    "a", "an", "the", "on", "of", "in", "for", "and", "to", "with", "towards", "via",
+];
+
+fn ascii_lower(text: &str) -> String {
+    text.chars()
+        .filter_map(|c| match c {
+            'à'..='å' | 'À'..='Å' => Some('a'),
+            'è'..='ë' | 'È'..='Ë' => Some('e'),
+            'ì'..='ï' | 'Ì'..='Ï' => Some('i'),
+            'ò'..='ö' | 'Ò'..='Ö' | 'ø' | 'Ø' => Some('o'),
+            'ù'..='ü' | 'Ù'..='Ü' => Some('u'),
+            'ç' | 'Ç' => Some('c'),
+            'ñ' | 'Ñ' => Some('n'),
+            c if c.is_ascii_alphanumeric() => Some(c.to_ascii_lowercase()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// `{family}{year}{first significant title word}`, e.g. `jin2019autokeras`.
+/// Only depends on the record itself so keys survive re-exports.
+pub fn base_cite_key(item: &CitationItem) -> String {
+    let family = item
+        .authors
+        .first()
+        .map(|a| ascii_lower(a.family.split_whitespace().last().unwrap_or_default()))
+        .filter(|f| !f.is_empty())
+        .unwrap_or("anon".to_string());
+    let year = item.year.map(|y| y.to_string()).unwrap_or_default();
+    let word = item
+        .title
+        .split_whitespace()
+        .map(|w| ascii_lower(w.split(':').next().unwrap_or_default()))
+        .find(|w| !w.is_empty() && !STOP_WORDS.contains(&w.as_str()))
+        .unwrap_or_default();
+    format!("{}{}{}", family, year, word)
+}
+
+/// Assigns keys to the whole batch; clashing keys get `a`, `b`, ... suffixes in
+/// paper id order, so the same selection always yields the same keys.
+pub fn assign_cite_keys(items: &mut [CitationItem]) {
+    let mut groups: HashMap<String, Vec<usize>> = HashMap::new();
+    for (idx, item) in items.iter().enumerate() {
+        groups.entry(base_cite_key(item)).or_default().push(idx);
+    }
+    for (key, mut indexes) in groups {
+        if indexes.len() == 1 {
+            items[indexes[0]].cite_key = key;
+            continue;
+        }
+        indexes.sort_by(|a, b| items[*a].paper_id.cmp(&items[*b].paper_id));
+        for (n, idx) in indexes.into_iter().enumerate() {
+            items[idx].cite_key = format!("{}{}", key, suffix(n));
+        }
+    }
+}
+
+fn suffix(mut n: usize) -> String {
+    let mut out = vec![];
+    loop {
+        out.push((b'a' + (n % 26) as u8) as char);
+        if n < 26 {
+            break;
+        }
+        n = n / 26 - 1;
+    }
+    out.iter().rev().collect()
+}
+
+pub fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' | '%' | '$' | '#' | '_' | '{' | '}' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '~' => out.push_str("\\textasciitilde{}"),
+            '^' => out.push_str("\\textasciicircum{}"),
+            '\\' => out.push_str("\\textbackslash{}"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn entry_type(item: &CitationItem) -> &'static str {
+    match item.citation_type {
+        CitationType::JournalArticle => "article",
+        CitationType::Conference => "inproceedings",
+        CitationType::Book => "book",
+        CitationType::BookSection => "incollection",
+        CitationType::Thesis => "phdthesis",
+        CitationType::Preprint | CitationType::Generic => "misc",
+    }
+}
+
+pub fn write_entry(item: &CitationItem) -> String {
+    let mut fields: Vec<(&str, String)> = vec![];
+    if !item.authors.is_empty() {
+        let authors = item
+            .authors
+            .iter()
+            .map(|a| a.inverted())
+            .collect::<Vec<String>>()
+            .join(" and ");
+        fields.push(("author", escape(&authors)));
+    }
+    // double braces keep the title's capitalisation
+    fields.push(("title", format!("{{{}}}", escape(&item.title))));
+    if let Some(container) = &item.container_title {
+        let name = match item.citation_type {
+            CitationType::JournalArticle => "journal",
+            CitationType::Conference | CitationType::BookSection => "booktitle",
+            _ => "howpublished",
+        };
+        fields.push((name, escape(container)));
+    }
+    if let Some(year) = item.year {
+        fields.push(("year", year.to_string()));
+    }
+    if let Some(volume) = &item.volume {
+        fields.push(("volume", escape(volume)));
+    }
+    if let Some(pages) = &item.pages {
+        fields.push(("pages", pages.replace('-', "--")));
+    }
+    if let Some(issn) = &item.issn {
+        fields.push(("issn", issn.to_owned()));
+    }
+    if let Some(doi) = &item.doi {
+        fields.push(("doi", escape(doi)));
+    }
+    if let Some(arxiv) = &item.arxiv {
+        fields.push(("eprint", arxiv.to_owned()));
+        fields.push(("archiveprefix", "arXiv".to_string()));
+    }
+    if let Some(url) = &item.url {
+        fields.push(("url", url.to_owned()));
+    }
+
+    let body = fields
+        .iter()
+        .map(|(name, value)| format!("  {} = {{{}}}", name, value))
+        .collect::<Vec<String>>()
+        .join(",\n");
+    format!("@{}{{{},\n{}\n}}", entry_type(item), item.cite_key, body)
+}
+
+pub fn write(items: &[CitationItem]) -> String {
+    items
+        .iter()
+        .map(write_entry)
+        .collect::<Vec<String>>()
+        .join("\n\n")
+        + "\n"
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::citation::PersonName;
+
+    fn item(paper_id: &str, family: &str, year: i32, title: &str) -> CitationItem {
+        CitationItem {
+            paper_id: paper_id.to_string(),
+            title: title.to_string(),
+            year: Some(year),
+            authors: vec![PersonName {
+                given: "A.".to_string(),
+                family: family.to_string(),
+            }],
+            ..CitationItem::default()
+        }
+    }
+
+    #[test]
+    fn test_assign_cite_keys() {
+        let mut items = vec![
+            item("b", "Müller", 2019, "The Search for Architectures"),
+            item("a", "Müller", 2019, "Search: a survey"),
+            item("c", "Jin", 2019, "Auto-Keras: An Efficient System"),
+        ];
+        assign_cite_keys(&mut items);
+        let keys = items.iter().map(|i| i.cite_key.as_str()).collect::<Vec<&str>>();
+        assert_eq!(keys, vec!["muller2019searchb", "muller2019searcha", "jin2019autokeras"]);
+    }
+
+    #[test]
+    fn test_write_entry() {
+        let mut entry = item("a", "Jin", 2019, "Auto-Keras & NAS");
+        entry.cite_key = "jin2019autokeras".to_string();
+        entry.pages = Some("1946-1956".to_string());
+        assert_eq!(
+            write_entry(&entry),
+            "@misc{jin2019autokeras,\n  author = {Jin, A.},\n  title = {{Auto-Keras \\& NAS}},\n  year = {2019},\n  pages = {1946--1956}\n}"
+        );
+    }
+}
@@ -0,0 +1,108 @@
+use crate::citation::{CitationItem, CitationType};
+use serde_derive::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct CslName {
+    pub family: String,
+    #[serde(skip_serializing_if = "String::is_empty", default)]
+    pub given: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct CslDate {
+    #[serde(rename = "date-parts")]
+    pub date_parts: Vec<Vec<i32>>,
+}
+
+/// One item of a CSL-JSON bibliography, as read by citeproc processors.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct CslItem {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub type_field: String,
+    pub title: String,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub author: Vec<CslName>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub issued: Option<CslDate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container_title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volume: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page: Option<String>,
+    #[serde(rename = "ISSN", skip_serializing_if = "Option::is_none")]
+    pub issn: Option<String>,
+    #[serde(rename = "DOI", skip_serializing_if = "Option::is_none")]
+    pub doi: Option<String>,
+    #[serde(rename = "URL", skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(rename = "PMID", skip_serializing_if = "Option::is_none")]
+    pub pmid: Option<String>,
+    #[serde(rename = "PMCID", skip_serializing_if = "Option::is_none")]
+    pub pmcid: Option<String>,
+    #[serde(rename = "abstract", skip_serializing_if = "Option::is_none")]
+    pub abstract_field: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub citation_key: Option<String>,
+}
+
+fn date_parts(item: &CitationItem) -> Option<CslDate> {
+    let parts = match &item.publication_date {
+        Some(date) => date
+            .split('-')
+            .filter_map(|p| p.parse::<i32>().ok())
+            .collect::<Vec<i32>>(),
+        None => item.year.into_iter().collect(),
+    };
+    if parts.is_empty() {
+        return None;
+    }
+    Some(CslDate {
+        date_parts: vec![parts],
+    })
+}
+
+impl From<&CitationItem> for CslItem {
+    fn from(item: &CitationItem) -> Self {
+        Self {
+            id: item.paper_id.to_owned(),
+            type_field: match item.citation_type {
+                CitationType::JournalArticle => "article-journal",
+                CitationType::Conference => "paper-conference",
+                CitationType::Book => "book",
+                CitationType::BookSection => "chapter",
+                CitationType::Thesis => "thesis",
+                CitationType::Preprint => "article",
+                CitationType::Generic => "document",
+            }
+            .to_string(),
+            title: item.title.to_owned(),
+            author: item
+                .authors
+                .iter()
+                .map(|a| CslName {
+                    family: a.family.to_owned(),
+                    given: a.given.to_owned(),
+                })
+                .collect(),
+            issued: date_parts(item),
+            container_title: item.container_title.to_owned(),
+            volume: item.volume.to_owned(),
+            page: item.pages.to_owned(),
+            issn: item.issn.to_owned(),
+            doi: item.doi.to_owned(),
+            url: item.url.to_owned(),
+            pmid: item.pmid.to_owned(),
+            pmcid: item.pmcid.to_owned(),
+            abstract_field: item.abstract_field.to_owned(),
+            citation_key: Some(item.cite_key.to_owned()).filter(|k| !k.is_empty()),
+        }
+    }
+}
+
+pub fn write(items: &[CitationItem]) -> String {
+    let csl_items = items.iter().map(CslItem::from).collect::<Vec<CslItem>>();
+    serde_json::to_string_pretty(&csl_items).unwrap()
+}
@@ -0,0 +1,72 @@
+use crate::citation::CitationItem;
+
+const HEADER: [&str; 13] = [
+    "cite_key",
+    "paper_id",
+    "title",
+    "authors",
+    "year",
+    "publication_date",
+    "container_title",
+    "volume",
+    "pages",
+    "doi",
+    "arxiv",
+    "pmid",
+    "url",
+];
+
+/// RFC 4180 quoting: fields with separators, quotes or newlines are wrapped
+/// in quotes and inner quotes are doubled.
+pub fn quote(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+pub fn write(items: &[CitationItem]) -> String {
+    let mut lines = vec![HEADER.join(",")];
+    for item in items {
+        let fields = [
+            item.cite_key.to_owned(),
+            item.paper_id.to_owned(),
+            item.title.to_owned(),
+            item.authors
+                .iter()
+                .map(|a| a.inverted())
+                .collect::<Vec<String>>()
+                .join("; "),
+            item.year.map(|y| y.to_string()).unwrap_or_default(),
+            item.publication_date.to_owned().unwrap_or_default(),
+            item.container_title.to_owned().unwrap_or_default(),
+            item.volume.to_owned().unwrap_or_default(),
+            item.pages.to_owned().unwrap_or_default(),
+            item.doi.to_owned().unwrap_or_default(),
+            item.arxiv.to_owned().unwrap_or_default(),
+            item.pmid.to_owned().unwrap_or_default(),
+            item.url.to_owned().unwrap_or_default(),
+        ];
+        lines.push(
+            fields
+                .iter()
+                .map(|f| quote(f))
+                .collect::<Vec<String>>()
+                .join(","),
+        );
+    }
+    lines.join("\r\n") + "\r\n"
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("plain"), "plain");
+        assert_eq!(quote("Jin, Haifeng"), "\"Jin, Haifeng\"");
+        assert_eq!(quote("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+}
@@ -0,0 +1,94 @@
+use crate::citation::{CitationItem, CitationType};
+
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn ref_type(item: &CitationItem) -> (u32, &'static str) {
+    match item.citation_type {
+        CitationType::JournalArticle => (17, "Journal Article"),
+        CitationType::Conference => (47, "Conference Paper"),
+        CitationType::Book => (6, "Book"),
+        CitationType::BookSection => (5, "Book Section"),
+        CitationType::Thesis => (32, "Thesis"),
+        CitationType::Preprint => (34, "Unpublished Work"),
+        CitationType::Generic => (13, "Generic"),
+    }
+}
+
+fn tag(name: &str, value: &str) -> String {
+    format!("<{name}>{}</{name}>", escape(value), name = name)
+}
+
+pub fn write_record(item: &CitationItem) -> String {
+    let (type_id, type_name) = ref_type(item);
+    let mut record = vec![format!(
+        "<ref-type name=\"{}\">{}</ref-type>",
+        type_name, type_id
+    )];
+    if !item.authors.is_empty() {
+        let authors = item
+            .authors
+            .iter()
+            .map(|a| tag("author", &a.inverted()))
+            .collect::<String>();
+        record.push(format!("<contributors><authors>{}</authors></contributors>", authors));
+    }
+    let mut titles = tag("title", &item.title);
+    if let Some(container) = &item.container_title {
+        titles += &tag("secondary-title", container);
+    }
+    record.push(format!("<titles>{}</titles>", titles));
+    if let Some(container) = &item.container_title {
+        record.push(format!("<periodical>{}</periodical>", tag("full-title", container)));
+    }
+    if let Some(pages) = &item.pages {
+        record.push(tag("pages", pages));
+    }
+    if let Some(volume) = &item.volume {
+        record.push(tag("volume", volume));
+    }
+    if let Some(year) = item.year {
+        let pub_date = item
+            .publication_date
+            .as_ref()
+            .map(|d| format!("<pub-dates>{}</pub-dates>", tag("date", d)))
+            .unwrap_or_default();
+        record.push(format!(
+            "<dates>{}{}</dates>",
+            tag("year", &year.to_string()),
+            pub_date
+        ));
+    }
+    if let Some(issn) = &item.issn {
+        record.push(tag("isbn", issn));
+    }
+    if let Some(doi) = &item.doi {
+        record.push(tag("electronic-resource-num", doi));
+    }
+    if let Some(url) = &item.url {
+        record.push(format!(
+            "<urls><related-urls>{}</related-urls></urls>",
+            tag("url", url)
+        ));
+    }
+    if let Some(abstract_field) = &item.abstract_field {
+        record.push(tag("abstract", abstract_field));
+    }
+    record.push(tag("label", &item.cite_key));
+    format!("<record>{}</record>", record.join(""))
+}
+
+pub fn write(items: &[CitationItem]) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<xml><records>\n{}\n</records></xml>\n",
+        items
+            .iter()
+            .map(write_record)
+            .collect::<Vec<String>>()
+            .join("\n")
+    )
+}
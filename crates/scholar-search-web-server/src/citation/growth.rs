@@ -0,0 +1,196 @@
+use chrono::{DateTime, Duration, Utc};
+use serde_derive::{Deserialize, Serialize};
+
+/// The S2 counts of one paper at one point in time.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CitationSnapshot {
+    pub paper_id: String,
+    pub taken_at: DateTime<Utc>,
+    pub citation_count: i32,
+    pub influential_citation_count: i32,
+}
+
+/// The snapshot a window starting at `start` is measured from: the last one
+/// taken at or before `start`, or the first one inside the window when
+/// tracking began later.
+fn window_base(snapshots: &[CitationSnapshot], start: DateTime<Utc>) -> Option<&CitationSnapshot> {
+    snapshots
+        .iter()
+        .rev()
+        .find(|s| s.taken_at <= start)
+        .or_else(|| snapshots.first())
+}
+
+fn window_end(snapshots: &[CitationSnapshot], end: DateTime<Utc>) -> Option<&CitationSnapshot> {
+    snapshots.iter().rev().find(|s| s.taken_at <= end)
+}
+
+/// Citations gained in the window ending at `end`; `snapshots` must be
+/// sorted oldest first.
+pub fn delta(snapshots: &[CitationSnapshot], end: DateTime<Utc>, window: Duration) -> Option<i32> {
+    let last = window_end(snapshots, end)?;
+    let base = window_base(snapshots, end - window)?;
+    match base.taken_at < last.taken_at {
+        true => Some(last.citation_count - base.citation_count),
+        false => None,
+    }
+}
+
+/// Citations per day over the window ending at `end`, measured between the
+/// snapshots actually available. Needs at least a day of history.
+pub fn velocity(
+    snapshots: &[CitationSnapshot],
+    end: DateTime<Utc>,
+    window: Duration,
+) -> Option<f64> {
+    let last = window_end(snapshots, end)?;
+    let base = window_base(snapshots, end - window)?;
+    let days = (last.taken_at - base.taken_at).num_seconds() as f64 / 86400.0;
+    if days < 1.0 {
+        return None;
+    }
+    Some((last.citation_count - base.citation_count) as f64 / days)
+}
+
+/// Change in velocity between the previous window and the current one, in
+/// citations per day per window.
+pub fn acceleration(
+    snapshots: &[CitationSnapshot],
+    end: DateTime<Utc>,
+    window: Duration,
+) -> Option<f64> {
+    let previous_end = end - window;
+    // without history before the current window there is nothing to compare
+    if snapshots.first()?.taken_at > previous_end - Duration::days(1) {
+        return None;
+    }
+    Some(velocity(snapshots, end, window)? - velocity(snapshots, previous_end, window)?)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct PaperGrowth {
+    pub paper_id: String,
+    pub title: String,
+    pub citation_count: i32,
+    pub influential_citation_count: i32,
+    pub delta: Option<i32>,
+    pub velocity: Option<f64>,
+    pub acceleration: Option<f64>,
+    /// `delta` relative to the count at the start of the window
+    pub relative_growth: Option<f64>,
+}
+
+impl PaperGrowth {
+    pub fn new(
+        paper_id: &str,
+        title: &str,
+        snapshots: &[CitationSnapshot],
+        end: DateTime<Utc>,
+        window: Duration,
+    ) -> Self {
+        let last = window_end(snapshots, end);
+        let delta = delta(snapshots, end, window);
+        Self {
+            paper_id: paper_id.to_string(),
+            title: title.to_string(),
+            citation_count: last.map(|s| s.citation_count).unwrap_or_default(),
+            influential_citation_count: last
+                .map(|s| s.influential_citation_count)
+                .unwrap_or_default(),
+            delta,
+            velocity: velocity(snapshots, end, window),
+            acceleration: acceleration(snapshots, end, window),
+            relative_growth: delta
+                .zip(window_base(snapshots, end - window))
+                .map(|(delta, base)| delta as f64 / base.citation_count.max(1) as f64),
+        }
+    }
+}
+
+/// Papers that gained citations in the window, fastest relative growth
+/// first; a young paper going from 10 to 30 outranks a classic adding 40.
+pub fn fast_risers(growth: &[PaperGrowth], limit: usize) -> Vec<PaperGrowth> {
+    let mut risers = growth
+        .iter()
+        .filter(|g| g.delta.is_some_and(|delta| delta > 0))
+        .cloned()
+        .collect::<Vec<PaperGrowth>>();
+    risers.sort_by(|a, b| {
+        b.relative_growth
+            .partial_cmp(&a.relative_growth)
+            .unwrap()
+            .then(b.delta.cmp(&a.delta))
+    });
+    risers.truncate(limit);
+    risers
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn snapshots(counts: &[(i64, i32)], now: DateTime<Utc>) -> Vec<CitationSnapshot> {
+        counts
+            .iter()
+            .map(|(days_ago, count)| CitationSnapshot {
+                paper_id: "p".to_string(),
+                taken_at: now - Duration::days(*days_ago),
+                citation_count: *count,
+                influential_citation_count: 0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_velocity_and_acceleration() {
+        let now = Utc::now();
+        let window = Duration::days(30);
+        let series = snapshots(&[(60, 100), (30, 130), (15, 160), (0, 220)], now);
+        assert_eq!(delta(&series, now, window), Some(90));
+        assert_eq!(velocity(&series, now, window), Some(3.0));
+        assert_eq!(acceleration(&series, now, window), Some(2.0));
+
+        // a week of history: measured over the week, no acceleration yet
+        let young = snapshots(&[(7, 10), (0, 24)], now);
+        assert_eq!(delta(&young, now, window), Some(14));
+        assert_eq!(velocity(&young, now, window), Some(2.0));
+        assert_eq!(acceleration(&young, now, window), None);
+
+        assert_eq!(delta(&young[..1], now, window), None);
+    }
+
+    #[test]
+    fn test_fast_risers() {
+        let now = Utc::now();
+        let window = Duration::days(30);
+        let classic = PaperGrowth::new(
+            "classic",
+            "",
+            &snapshots(&[(30, 5000), (0, 5040)], now),
+            now,
+            window,
+        );
+        let young = PaperGrowth::new(
+            "young",
+            "",
+            &snapshots(&[(30, 10), (0, 30)], now),
+            now,
+            window,
+        );
+        let flat = PaperGrowth::new(
+            "flat",
+            "",
+            &snapshots(&[(30, 10), (0, 10)], now),
+            now,
+            window,
+        );
+        let risers = fast_risers(&[classic, flat, young], 10);
+        assert_eq!(
+            risers
+                .iter()
+                .map(|g| g.paper_id.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["young", "classic"]
+        );
+    }
+}
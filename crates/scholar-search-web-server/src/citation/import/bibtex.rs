@@ -0,0 +1,358 @@
+use std::collections::BTreeMap;
+use tracing::warn;
+
+/// A raw BibTeX entry; field names are lowercased, values are still LaTeX.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct BibEntry {
+    pub entry_type: String,
+    pub key: String,
+    pub fields: BTreeMap<String, String>,
+}
+
+const MONTHS: [(&str, &str); 12] = [
+    ("jan", "January"),
+    ("feb", "February"),
+    ("mar", "March"),
+    ("apr", "April"),
+    ("may", "May"),
+    ("jun", "June"),
+    ("jul", "July"),
+    ("aug", "August"),
+    ("sep", "September"),
+    ("oct", "October"),
+    ("nov", "November"),
+    ("dec", "December"),
+];
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    macros: BTreeMap<String, String>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek().is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        self.skip_spaces();
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(format!("expected '{}' at offset {}", c, self.pos))
+        }
+    }
+
+    fn identifier(&mut self) -> String {
+        self.skip_spaces();
+        let mut ident = String::new();
+        while let Some(c) = self
+            .peek()
+            .filter(|c| !c.is_whitespace() && !"{}(),=#\"".contains(*c))
+        {
+            ident.push(c);
+            self.pos += 1;
+        }
+        ident
+    }
+
+    /// Text between balanced braces, the opening brace already consumed.
+    fn braced(&mut self) -> Result<String, String> {
+        let mut depth = 1;
+        let mut text = String::new();
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            match c {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(text);
+                    }
+                }
+                _ => {}
+            }
+            text.push(c);
+        }
+        Err("unbalanced braces".to_string())
+    }
+
+    fn quoted(&mut self) -> Result<String, String> {
+        let mut depth = 0;
+        let mut text = String::new();
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            match c {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                '"' if depth == 0 => return Ok(text),
+                _ => {}
+            }
+            text.push(c);
+        }
+        Err("unterminated string".to_string())
+    }
+
+    /// `piece (# piece)*` where a piece is `{...}`, `"..."`, a number or a macro.
+    fn value(&mut self) -> Result<String, String> {
+        let mut value = String::new();
+        loop {
+            self.skip_spaces();
+            match self.peek() {
+                Some('{') => {
+                    self.pos += 1;
+                    value.push_str(&self.braced()?);
+                }
+                Some('"') => {
+                    self.pos += 1;
+                    value.push_str(&self.quoted()?);
+                }
+                Some(_) => {
+                    let ident = self.identifier();
+                    if ident.is_empty() {
+                        return Err(format!("missing value at offset {}", self.pos));
+                    }
+                    if ident.chars().all(|c| c.is_ascii_digit()) {
+                        value.push_str(&ident);
+                    } else {
+                        match self.macros.get(&ident.to_lowercase()) {
+                            Some(expanded) => value.push_str(expanded),
+                            None => value.push_str(&ident),
+                        }
+                    }
+                }
+                None => return Err("unexpected end of input".to_string()),
+            }
+            self.skip_spaces();
+            if self.peek() == Some('#') {
+                self.pos += 1;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn close_char(&mut self) -> Result<char, String> {
+        self.skip_spaces();
+        match self.peek() {
+            Some('{') => {
+                self.pos += 1;
+                Ok('}')
+            }
+            Some('(') => {
+                self.pos += 1;
+                Ok(')')
+            }
+            _ => Err(format!("expected '{{' or '(' at offset {}", self.pos)),
+        }
+    }
+
+    fn assignments(&mut self, close: char) -> Result<BTreeMap<String, String>, String> {
+        let mut fields = BTreeMap::new();
+        loop {
+            self.skip_spaces();
+            match self.peek() {
+                Some(c) if c == close => {
+                    self.pos += 1;
+                    return Ok(fields);
+                }
+                Some(',') => self.pos += 1,
+                Some(_) => {
+                    let name = self.identifier().to_lowercase();
+                    if name.is_empty() {
+                        return Err(format!("expected field name at offset {}", self.pos));
+                    }
+                    self.expect('=')?;
+                    let value = self.value()?;
+                    fields.insert(name, value);
+                }
+                None => return Err("unexpected end of input".to_string()),
+            }
+        }
+    }
+
+    fn entry(&mut self) -> Result<Option<BibEntry>, String> {
+        let entry_type = self.identifier().to_lowercase();
+        match entry_type.as_str() {
+            "comment" => {
+                // everything up to the end of the line or a braced block
+                self.skip_spaces();
+                if self.peek() == Some('{') {
+                    self.pos += 1;
+                    self.braced()?;
+                }
+                Ok(None)
+            }
+            "preamble" => {
+                let close = self.close_char()?;
+                self.value()?;
+                self.expect(close)?;
+                Ok(None)
+            }
+            "string" => {
+                let close = self.close_char()?;
+                for (name, value) in self.assignments(close)? {
+                    self.macros.insert(name, value);
+                }
+                Ok(None)
+            }
+            _ => {
+                let close = self.close_char()?;
+                let key = self.identifier();
+                self.skip_spaces();
+                if self.peek() == Some(',') {
+                    self.pos += 1;
+                }
+                let fields = self.assignments(close)?;
+                Ok(Some(BibEntry {
+                    entry_type,
+                    key,
+                    fields,
+                }))
+            }
+        }
+    }
+
+    fn skip_to_next_entry(&mut self) {
+        while let Some(c) = self.peek() {
+            if c == '@' {
+                return;
+            }
+            self.pos += 1;
+        }
+    }
+}
+
+/// Copies missing fields from the `crossref` parent; a parent's title becomes
+/// the child's `booktitle`, as BibTeX does for proceedings.
+fn resolve_crossrefs(entries: &mut [BibEntry]) {
+    let parents = entries
+        .iter()
+        .map(|e| (e.key.to_lowercase(), e.fields.clone()))
+        .collect::<BTreeMap<String, BTreeMap<String, String>>>();
+    for entry in entries.iter_mut() {
+        let Some(parent) = entry
+            .fields
+            .get("crossref")
+            .and_then(|key| parents.get(&key.to_lowercase()))
+        else {
+            continue;
+        };
+        for (name, value) in parent {
+            let target = if name == "title" {
+                "booktitle"
+            } else {
+                name.as_str()
+            };
+            if !entry.fields.contains_key(target) {
+                entry.fields.insert(target.to_string(), value.to_owned());
+            }
+        }
+    }
+}
+
+/// Parses a `.bib` file, expanding `@string` macros and `crossref`s. Broken
+/// entries are skipped with a warning instead of failing the whole file.
+pub fn parse(input: &str) -> Vec<BibEntry> {
+    let mut parser = Parser {
+        chars: input.chars().collect(),
+        pos: 0,
+        macros: MONTHS
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect(),
+    };
+    let mut entries = vec![];
+    loop {
+        parser.skip_to_next_entry();
+        if parser.peek().is_none() {
+            break;
+        }
+        parser.pos += 1;
+        let start = parser.pos;
+        match parser.entry() {
+            Ok(Some(entry)) => entries.push(entry),
+            Ok(None) => {}
+            Err(e) => {
+                warn!("skipping bibtex entry at offset {}: {}", start, e);
+                parser.pos = start;
+            }
+        }
+    }
+    resolve_crossrefs(&mut entries);
+    entries
+}
+
+/// Splits an author list on top-level ` and `, ignoring braced groups such as
+/// `{Barnes and Noble}`.
+pub fn split_names(value: &str) -> Vec<String> {
+    let mut names = vec![];
+    let mut depth = 0;
+    let mut current = String::new();
+    let words = value.split_whitespace().collect::<Vec<&str>>();
+    for word in words {
+        if depth == 0 && word.eq_ignore_ascii_case("and") {
+            names.push(current.trim().to_string());
+            current.clear();
+            continue;
+        }
+        depth += word.matches('{').count() as i32 - word.matches('}').count() as i32;
+        current.push_str(word);
+        current.push(' ');
+    }
+    names.push(current.trim().to_string());
+    names.into_iter().filter(|n| !n.is_empty()).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_bibtex() {
+        let entries = parse(
+            r#"
+@string{kdd = "Knowledge Discovery " # "and Data Mining"}
+@comment{ignored}
+@inproceedings{jin2019,
+  author = {Haifeng Jin and Qingquan Song and Xia Hu},
+  title = "{Auto-Keras}: An Efficient {N}eural Architecture Search System",
+  crossref = {kdd19},
+  month = aug,
+  pages = {1946--1956}
+}
+@broken{oops, title = }
+@proceedings(kdd19,
+  title = kdd # " 2019",
+  year = 2019,
+)
+"#,
+        );
+        assert_eq!(entries.len(), 2);
+        let entry = &entries[0];
+        assert_eq!(entry.key, "jin2019");
+        assert_eq!(entry.fields["month"], "August");
+        assert_eq!(entry.fields["year"], "2019");
+        assert_eq!(
+            entry.fields["booktitle"],
+            "Knowledge Discovery and Data Mining 2019"
+        );
+        assert_eq!(split_names(&entry.fields["author"]).len(), 3);
+    }
+
+    #[test]
+    fn test_split_names() {
+        assert_eq!(
+            split_names("{Barnes and Noble} and Doe, John"),
+            vec!["{Barnes and Noble}", "Doe, John"]
+        );
+    }
+}
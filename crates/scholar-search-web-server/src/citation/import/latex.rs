@@ -0,0 +1,213 @@
+use unicode_normalization::UnicodeNormalization;
+
+fn combining_mark(accent: char) -> Option<char> {
+    match accent {
+        '`' => Some('\u{0300}'),
+        '\'' => Some('\u{0301}'),
+        '^' => Some('\u{0302}'),
+        '~' => Some('\u{0303}'),
+        '=' => Some('\u{0304}'),
+        'u' => Some('\u{0306}'),
+        '.' => Some('\u{0307}'),
+        '"' => Some('\u{0308}'),
+        'r' => Some('\u{030A}'),
+        'H' => Some('\u{030B}'),
+        'v' => Some('\u{030C}'),
+        'd' => Some('\u{0323}'),
+        'c' => Some('\u{0327}'),
+        'k' => Some('\u{0328}'),
+        'b' => Some('\u{0331}'),
+        _ => None,
+    }
+}
+
+fn named_symbol(name: &str) -> Option<&'static str> {
+    match name {
+        "ss" => Some("ß"),
+        "o" => Some("ø"),
+        "O" => Some("Ø"),
+        "aa" => Some("å"),
+        "AA" => Some("Å"),
+        "ae" => Some("æ"),
+        "AE" => Some("Æ"),
+        "oe" => Some("œ"),
+        "OE" => Some("Œ"),
+        "l" => Some("ł"),
+        "L" => Some("Ł"),
+        "i" => Some("ı"),
+        "j" => Some("ȷ"),
+        "textendash" => Some("–"),
+        "textemdash" => Some("—"),
+        "textquoteright" => Some("’"),
+        "textquoteleft" => Some("‘"),
+        "ldots" | "dots" | "textellipsis" => Some("…"),
+        "S" => Some("§"),
+        "P" => Some("¶"),
+        "copyright" => Some("©"),
+        "&" => Some("&"),
+        "%" => Some("%"),
+        "$" => Some("$"),
+        "#" => Some("#"),
+        "_" => Some("_"),
+        "{" => Some("{"),
+        "}" => Some("}"),
+        " " => Some(" "),
+        "," | ";" | ":" | "!" => Some(" "),
+        _ => None,
+    }
+}
+
+struct Decoder {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Decoder {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek().is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn command_name(&mut self) -> String {
+        let Some(first) = self.peek() else {
+            return "".to_string();
+        };
+        self.pos += 1;
+        if !first.is_ascii_alphabetic() {
+            return first.to_string();
+        }
+        let mut name = first.to_string();
+        while let Some(c) = self.peek().filter(|c| c.is_ascii_alphabetic()) {
+            name.push(c);
+            self.pos += 1;
+        }
+        name
+    }
+
+    /// The argument of an accent: `{x}`, `x`, or a dotless `\i` / `\j`.
+    fn accent_argument(&mut self) -> String {
+        match self.peek() {
+            Some('{') => {
+                self.pos += 1;
+                let mut depth = 1;
+                let start = self.pos;
+                while let Some(c) = self.peek() {
+                    match c {
+                        '{' => depth += 1,
+                        '}' => depth -= 1,
+                        _ => {}
+                    }
+                    if depth == 0 {
+                        break;
+                    }
+                    self.pos += 1;
+                }
+                let inner = self.chars[start..self.pos].iter().collect::<String>();
+                self.pos += 1;
+                decode_raw(&inner)
+            }
+            Some('\\') => {
+                self.pos += 1;
+                match self.command_name().as_str() {
+                    "i" => "i".to_string(),
+                    "j" => "j".to_string(),
+                    other => named_symbol(other).unwrap_or_default().to_string(),
+                }
+            }
+            Some(c) => {
+                self.pos += 1;
+                c.to_string()
+            }
+            None => "".to_string(),
+        }
+    }
+
+    fn decode(&mut self) -> String {
+        let mut out = String::new();
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            match c {
+                '\\' => {
+                    let name = self.command_name();
+                    let accent = name.chars().next().filter(|_| name.chars().count() == 1);
+                    if let Some(mark) = accent.and_then(combining_mark) {
+                        if name.chars().all(|c| c.is_ascii_alphabetic()) {
+                            self.skip_spaces();
+                        }
+                        let argument = self.accent_argument();
+                        let mut chars = argument.chars();
+                        if let Some(base) = chars.next() {
+                            out.push(base);
+                            out.push(mark);
+                            out.extend(chars);
+                        }
+                    } else if let Some(symbol) = named_symbol(&name) {
+                        out.push_str(symbol);
+                        if name.chars().all(|c| c.is_ascii_alphabetic()) {
+                            // a space after a control word only terminates it
+                            if self.peek() == Some(' ') {
+                                self.pos += 1;
+                            }
+                        }
+                    }
+                    // unknown commands such as \emph or \textbf are dropped and
+                    // their braced argument kept
+                }
+                '{' | '}' | '$' => {}
+                '~' => out.push(' '),
+                '-' if self.peek() == Some('-') => {
+                    self.pos += 1;
+                    if self.peek() == Some('-') {
+                        self.pos += 1;
+                        out.push('—');
+                    } else {
+                        out.push('–');
+                    }
+                }
+                c if c.is_whitespace() => {
+                    if !out.ends_with(' ') {
+                        out.push(' ');
+                    }
+                }
+                c => out.push(c),
+            }
+        }
+        out
+    }
+}
+
+fn decode_raw(text: &str) -> String {
+    Decoder {
+        chars: text.chars().collect(),
+        pos: 0,
+    }
+    .decode()
+}
+
+/// Turns a BibTeX field value into plain Unicode (NFC) text: accents such as
+/// `{\"o}` or `\c{c}` are composed, braces and math delimiters dropped.
+pub fn decode(text: &str) -> String {
+    decode_raw(text).trim().nfc().collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_decode_accents() {
+        assert_eq!(decode(r#"Fran{\c{c}}ois Chollet"#), "François Chollet");
+        assert_eq!(decode(r#"L{\'e}on Bottou"#), "Léon Bottou");
+        assert_eq!(decode(r#"G{\"{o}}del and Erd\H{o}s"#), "Gödel and Erdős");
+        assert_eq!(decode(r#"{\O}ystein and \ss{}"#), "Øystein and ß");
+        assert_eq!(
+            decode(r#"Na{\"\i}ve {B}ayes --- pages 1--10"#),
+            "Naïve Bayes — pages 1–10"
+        );
+    }
+}
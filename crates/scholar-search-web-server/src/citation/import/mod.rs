@@ -0,0 +1,248 @@
+pub mod bibtex;
+pub mod latex;
+pub mod reference;
+pub mod ris;
+
+use crate::citation::PersonName;
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// A bibliography entry read from an uploaded file, reduced to the fields
+/// needed to find the paper on Semantic Scholar.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct ImportedEntry {
+    /// BibTeX key, or the position of the record for RIS files
+    pub source_key: String,
+    pub title: String,
+    pub authors: Vec<PersonName>,
+    pub year: Option<i32>,
+    pub container_title: Option<String>,
+    pub doi: Option<String>,
+    pub arxiv: Option<String>,
+    pub pmid: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ImportFormat {
+    Bibtex,
+    Ris,
+}
+
+impl ImportFormat {
+    /// Guesses the format from the file extension, then from the content.
+    pub fn detect(file_name: &str, content: &str) -> Option<Self> {
+        let extension = file_name.rsplit_once('.').map(|(_, e)| e.to_lowercase());
+        match extension.as_deref() {
+            Some("bib") | Some("bibtex") => return Some(ImportFormat::Bibtex),
+            Some("ris") => return Some(ImportFormat::Ris),
+            _ => {}
+        }
+        let content = content.trim_start_matches('\u{feff}').trim_start();
+        if content.starts_with("TY  -") {
+            Some(ImportFormat::Ris)
+        } else if content.contains('@') {
+            Some(ImportFormat::Bibtex)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for ImportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportFormat::Bibtex => write!(f, "bibtex"),
+            ImportFormat::Ris => write!(f, "ris"),
+        }
+    }
+}
+
+impl FromStr for ImportFormat {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bibtex" | "bib" => Ok(ImportFormat::Bibtex),
+            "ris" => Ok(ImportFormat::Ris),
+            _ => Err(anyhow::anyhow!("unknown import format: {}", s)),
+        }
+    }
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+fn first_year(value: &str) -> Option<i32> {
+    let digits = value
+        .chars()
+        .skip_while(|c| !c.is_ascii_digit())
+        .take_while(|c| c.is_ascii_digit())
+        .collect::<String>();
+    digits.get(..4).and_then(|y| y.parse::<i32>().ok())
+}
+
+/// Strips resolver prefixes so `https://doi.org/10.1/x` and `doi:10.1/x`
+/// both become `10.1/x`.
+pub fn normalize_doi(value: &str) -> Option<String> {
+    let value = value.trim();
+    let start = value.find("10.")?;
+    let doi = value[start..].trim_end_matches(['.', ',', ';']).to_string();
+    Some(doi).filter(|d| d.contains('/'))
+}
+
+/// Reads an arXiv id from a bare id, an `arXiv:` prefix or an abs/pdf URL.
+pub fn normalize_arxiv(value: &str) -> Option<String> {
+    let value = value.trim();
+    let id = match value.find("arxiv.org/") {
+        Some(start) => value[start..]
+            .split_once('/')
+            .and_then(|(_, rest)| rest.split_once('/'))
+            .map(|(_, id)| id)
+            .unwrap_or_default(),
+        None => value
+            .strip_prefix("arXiv:")
+            .or_else(|| value.strip_prefix("arxiv:"))
+            .unwrap_or(value),
+    };
+    let id = id.trim_end_matches(".pdf");
+    // versions are not part of the S2 identifier
+    let id = match id.rsplit_once('v') {
+        Some((base, version))
+            if version.chars().all(|c| c.is_ascii_digit()) && !version.is_empty() =>
+        {
+            base
+        }
+        _ => id,
+    };
+    Some(id.to_string())
+        .filter(|id| !id.is_empty() && id.chars().any(|c| c.is_ascii_digit()) && !id.contains(' '))
+}
+
+impl From<bibtex::BibEntry> for ImportedEntry {
+    fn from(entry: bibtex::BibEntry) -> Self {
+        let field = |name: &str| non_empty(entry.fields.get(name).map(|v| latex::decode(v)));
+        let eprint_is_arxiv = field("archiveprefix")
+            .or_else(|| field("eprinttype"))
+            .is_some_and(|p| p.eq_ignore_ascii_case("arxiv"));
+        let arxiv = field("arxiv")
+            .or_else(|| field("eprint").filter(|_| eprint_is_arxiv))
+            .or_else(|| field("url").filter(|u| u.contains("arxiv.org/")))
+            .and_then(|v| normalize_arxiv(&v));
+        Self {
+            title: field("title").unwrap_or_default(),
+            authors: entry
+                .fields
+                .get("author")
+                .or_else(|| entry.fields.get("editor"))
+                .map(|v| bibtex::split_names(v))
+                .unwrap_or_default()
+                .iter()
+                .map(|name| PersonName::parse(&latex::decode(name)))
+                .collect(),
+            year: field("year")
+                .or_else(|| field("date"))
+                .and_then(|y| first_year(&y)),
+            container_title: field("journal")
+                .or_else(|| field("journaltitle"))
+                .or_else(|| field("booktitle")),
+            doi: field("doi").and_then(|v| normalize_doi(&v)),
+            arxiv,
+            pmid: field("pmid"),
+            source_key: entry.key,
+        }
+    }
+}
+
+impl ImportedEntry {
+    fn from_ris(index: usize, record: ris::RisRecord) -> Self {
+        let urls = record.all(&["UR", "L1", "L2"]);
+        Self {
+            source_key: format!("ris-{}", index + 1),
+            title: record.first(&["TI", "T1", "CT", "BT"]).unwrap_or_default(),
+            authors: record
+                .all(&["AU", "A1"])
+                .iter()
+                .map(|name| PersonName::parse(name))
+                .collect(),
+            year: record
+                .first(&["PY", "Y1", "DA"])
+                .and_then(|y| first_year(&y)),
+            container_title: record.first(&["T2", "JO", "JF", "JA"]),
+            doi: record.first(&["DO"]).and_then(|v| normalize_doi(&v)),
+            arxiv: urls
+                .iter()
+                .filter(|u| u.contains("arxiv.org/"))
+                .find_map(|u| normalize_arxiv(u)),
+            // PubMed exports keep the PMID in the accession number
+            pmid: record
+                .first(&["AN"])
+                .filter(|v| v.chars().all(|c| c.is_ascii_digit())),
+        }
+    }
+}
+
+/// Parses an uploaded bibliography; entries without a title or identifier
+/// can not be resolved and are dropped.
+pub fn parse(content: &str, format: ImportFormat) -> Vec<ImportedEntry> {
+    let entries = match format {
+        ImportFormat::Bibtex => bibtex::parse(content)
+            .into_iter()
+            .map(ImportedEntry::from)
+            .collect::<Vec<ImportedEntry>>(),
+        ImportFormat::Ris => ris::parse(content)
+            .into_iter()
+            .enumerate()
+            .map(|(index, record)| ImportedEntry::from_ris(index, record))
+            .collect::<Vec<ImportedEntry>>(),
+    };
+    entries
+        .into_iter()
+        .filter(|e| !e.title.is_empty() || e.doi.is_some() || e.arxiv.is_some() || e.pmid.is_some())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_bibtex_entry() {
+        let entries = parse(
+            r#"@article{godel31,
+  author = {G{\"o}del, Kurt},
+  title = {{\"U}ber formal unentscheidbare S{\"a}tze},
+  journal = {Monatshefte f{\"u}r Mathematik},
+  year = {1931},
+  doi = {https://doi.org/10.1007/BF01700692},
+}
+@misc{jin2018, title = {Auto-Keras}, eprint = {1806.10282v3}, archivePrefix = {arXiv}}
+@misc{empty, note = {nothing to resolve}}"#,
+            ImportFormat::Bibtex,
+        );
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].title, "Über formal unentscheidbare Sätze");
+        assert_eq!(entries[0].authors[0].family, "Gödel");
+        assert_eq!(entries[0].doi.as_deref(), Some("10.1007/BF01700692"));
+        assert_eq!(entries[1].arxiv.as_deref(), Some("1806.10282"));
+    }
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(
+            ImportFormat::detect("refs.RIS", ""),
+            Some(ImportFormat::Ris)
+        );
+        assert_eq!(
+            ImportFormat::detect("upload", "TY  - JOUR\nER  -"),
+            Some(ImportFormat::Ris)
+        );
+        assert_eq!(
+            ImportFormat::detect("upload", "@article{a, title={b}}"),
+            Some(ImportFormat::Bibtex)
+        );
+        assert_eq!(ImportFormat::detect("notes.txt", "hello"), None);
+    }
+}
@@ -0,0 +1,480 @@
+//! Reference strings as printed in a paper's bibliography, in numbered
+//! (`12.`), bracketed (`[12]`, `[Smi19]`) or author year styles.
+//!
+//! Nothing marks up the fields of a printed reference, so they are found by
+//! where the styles put them: the authors come first, then either the year
+//! or the title, and the venue, volume and pages after the title.
+
+use super::{normalize_arxiv, normalize_doi, ImportedEntry};
+use crate::citation::PersonName;
+use regex::Regex;
+use serde_derive::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// The fields read from a printed reference; what could not be told apart
+/// is left out.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct ParsedReference {
+    pub authors: Vec<PersonName>,
+    pub year: Option<i32>,
+    pub title: Option<String>,
+    pub venue: Option<String>,
+    /// `235-256`
+    pub pages: Option<String>,
+    pub doi: Option<String>,
+    pub arxiv: Option<String>,
+}
+
+/// `[12] `, `[Smi19] `, `12. ` at the start of the text or after a space
+fn label() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"(?:^|\s)(?:\[(?<bracket>[^\]\s]{1,12})\]|(?<number>\d{1,3})\.)\s").unwrap()
+    })
+}
+
+/// `. Smith, J.` or `. Peter Auer, N`: a sentence end followed by a family
+/// name and a comma, where an author year entry may start
+fn author_head() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(concat!(
+            r"[.)]\s+(?<head>\p{Lu}[\p{L}'’-]+",
+            r"(?:\s(?:\p{Lu}[\p{L}'’-]+|van|von|de|der|den|la|le|di|da|du))*",
+            r",\s\p{Lu})"
+        ))
+        .unwrap()
+    })
+}
+
+fn year() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\b(?<year>(?:19|20)\d{2})[a-z]?\b").unwrap())
+}
+
+/// `Authors. 2002. Rest` and `Authors (2002). Rest`
+fn year_after_authors() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            r"^(?<authors>.+?)[.,]?\s+\(?(?<year>(?:19|20)\d{2})[a-z]?\)?\s*[.,:]\s+(?<rest>.+)$",
+        )
+        .unwrap()
+    })
+}
+
+/// `Authors, “Title,” Rest`
+fn quoted_title() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            r#"^(?<authors>.*?)[,.:]?\s*[“"](?<title>[^”"]{4,}?)[,.]?[”"]\s*[,.]?\s*(?<rest>.*)$"#,
+        )
+        .unwrap()
+    })
+}
+
+fn doi() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"(?i)(?:https?://(?:dx\.)?doi\.org/|doi:\s*)?10\.\d{4,9}/\S+").unwrap()
+    })
+}
+
+fn arxiv() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(concat!(
+            r"(?i)(?:arxiv:\s*|arxiv\.org/(?:abs|pdf)/)",
+            r"(?<id>\d{4}\.\d{4,5}(?:v\d+)?|[a-z-]+(?:\.[a-z]{2})?/\d{7}(?:v\d+)?)"
+        ))
+        .unwrap()
+    })
+}
+
+fn url() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"(?i)(?:URL\s*)?https?://\S+").unwrap())
+}
+
+/// `pp. 235–256`, `235--256`
+fn pages() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"(?:pp?\.\s*)?\b(?<first>\d{1,5})\s*[-–—]{1,2}\s*(?<last>\d{1,5})\b").unwrap()
+    })
+}
+
+/// Where the venue name ends: at the year, pages, volume or issue.
+fn venue_end() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(concat!(
+            r"(?i)[,;]?\s*(?:\(?(?:19|20)\d{2}\b|pp?\.\s*\d|vol\.|volume\b|no\.\s*\d",
+            r"|\b\d+\s*(?:[(,:]|$))"
+        ))
+        .unwrap()
+    })
+}
+
+/// The entry start positions and labels of labelled entries, when the text
+/// starts with a label. Numbers must count up by one, and `12.` be followed
+/// by a capital, so that numbers and brackets inside an entry, e.g. `[cs.LG]`
+/// or `47, 2. 3 (2002)`, are not taken for labels.
+fn labelled_starts(text: &str) -> Option<Vec<(usize, usize, String)>> {
+    let mut starts: Vec<(usize, usize, String)> = vec![];
+    let mut bracket = false;
+    let mut next_number: Option<u32> = None;
+    for caps in label().captures_iter(text) {
+        let whole = caps.get(0).unwrap();
+        let (is_bracket, label) = match (caps.name("bracket"), caps.name("number")) {
+            (Some(l), _) => (true, l),
+            (_, Some(l)) => (false, l),
+            _ => continue,
+        };
+        let number = label.as_str().parse::<u32>().ok();
+        let capital = text[whole.end()..].starts_with(|c: char| c.is_uppercase());
+        let accept = match starts.first() {
+            _ if !is_bracket && !capital => false,
+            None => whole.start() == 0,
+            Some(_) if is_bracket != bracket => false,
+            // alphabetic labels such as `Smi19` or `ABC+20`
+            Some(_) if next_number.is_none() => {
+                let l = label.as_str();
+                l.starts_with(|c: char| c.is_uppercase())
+                    && l.chars().any(|c| c.is_ascii_digit())
+                    && !l.contains('.')
+            }
+            Some(_) => number == next_number,
+        };
+        if !accept {
+            if starts.is_empty() {
+                return None;
+            }
+            continue;
+        }
+        bracket = is_bracket;
+        next_number = number.map(|n| n + 1);
+        starts.push((whole.start(), whole.end(), label.as_str().to_string()));
+    }
+    Some(starts).filter(|s| !s.is_empty())
+}
+
+/// The start positions of author year entries. An entry may start after a
+/// sentence end that is followed by a family name and a comma, as long as
+/// both the entry before and the one starting there have a year, so that
+/// `Smith, J. Jones, K. (2019)` stays one entry.
+fn author_year_starts(text: &str) -> Vec<usize> {
+    let heads = author_head()
+        .captures_iter(text)
+        .map(|caps| caps.name("head").unwrap().start())
+        .collect::<Vec<usize>>();
+    let mut starts = vec![0];
+    for (i, head) in heads.iter().enumerate() {
+        let end = heads.get(i + 1).copied().unwrap_or(text.len());
+        let previous = *starts.last().unwrap();
+        if year().is_match(&text[previous..*head]) && year().is_match(&text[*head..end]) {
+            starts.push(*head);
+        }
+    }
+    starts
+}
+
+/// Splits the text of a reference list into its entries and their labels.
+/// Line breaks are not trusted, as the layout may have joined or split
+/// entries.
+pub fn split(text: &str) -> Vec<(Option<String>, String)> {
+    let text = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+    if text.is_empty() {
+        return vec![];
+    }
+    if let Some(starts) = labelled_starts(&text) {
+        return starts
+            .iter()
+            .enumerate()
+            .map(|(i, (_, body, label))| {
+                let end = starts.get(i + 1).map_or(text.len(), |(start, _, _)| *start);
+                (Some(label.to_owned()), text[*body..end].trim().to_string())
+            })
+            .collect();
+    }
+    let starts = author_year_starts(&text);
+    starts
+        .iter()
+        .enumerate()
+        .map(|(i, start)| {
+            let end = starts.get(i + 1).copied().unwrap_or(text.len());
+            (None, text[*start..end].trim().to_string())
+        })
+        .collect()
+}
+
+/// Splits at the first sentence end, skipping the periods of initials and
+/// abbreviations such as `J.`, `e.g.` or `U.S.`.
+fn first_sentence(text: &str) -> (&str, &str) {
+    let mut word_start = 0;
+    for (i, c) in text.char_indices() {
+        if c.is_whitespace() {
+            word_start = i + c.len_utf8();
+            continue;
+        }
+        let at_end = text[i + c.len_utf8()..]
+            .chars()
+            .next()
+            .is_none_or(char::is_whitespace);
+        if !at_end || !matches!(c, '.' | '?' | '!') {
+            continue;
+        }
+        let word = &text[word_start..i];
+        let initial = word.chars().count() == 1 && word.starts_with(char::is_uppercase);
+        if c == '.' && (initial || word.contains('.')) {
+            continue;
+        }
+        let keep = if c == '.' { i } else { i + c.len_utf8() };
+        return (&text[..keep], text[i + c.len_utf8()..].trim_start());
+    }
+    (text, "")
+}
+
+/// `J.`, `J. A.`, `J.-P.` or `JA`
+fn is_initials(text: &str) -> bool {
+    !text.is_empty()
+        && text.split_whitespace().all(|word| {
+            word.starts_with(char::is_uppercase)
+                && word.chars().filter(|c| c.is_alphabetic()).count() <= 2
+        })
+}
+
+/// Reads an author list in either `Given Family, Given Family and Given
+/// Family` or `Family, I., Family, I., & Family, I.` form.
+fn authors(text: &str) -> Vec<PersonName> {
+    let text = text
+        .replace(" & ", ", ")
+        .replace(" and ", ", ")
+        .replace(';', ",");
+    let parts = text
+        .split(',')
+        .map(|part| part.trim().trim_end_matches(['.', ':']).trim())
+        .filter(|part| !part.is_empty() && !matches!(*part, "et al" | "others" | "and" | "&"))
+        .collect::<Vec<&str>>();
+    let inverted = parts.len() % 2 == 0
+        && parts
+            .chunks(2)
+            .all(|pair| !is_initials(pair[0]) && is_initials(pair[1]));
+    if inverted {
+        parts
+            .chunks(2)
+            .map(|pair| PersonName {
+                given: pair[1].to_string(),
+                family: pair[0].to_string(),
+            })
+            .collect()
+    } else {
+        parts.iter().map(|name| PersonName::parse(name)).collect()
+    }
+}
+
+fn venue(text: &str) -> Option<String> {
+    let text = text.trim();
+    let text = text
+        .strip_prefix("In: ")
+        .or_else(|| text.strip_prefix("In "))
+        .or_else(|| text.strip_prefix("in "))
+        .unwrap_or(text);
+    let end = venue_end().find(text).map_or(text.len(), |m| m.start());
+    Some(
+        text[..end]
+            .trim_end_matches(['.', ',', ';', ':', ' '])
+            .trim(),
+    )
+    .filter(|v| v.chars().any(char::is_alphabetic))
+    .map(str::to_string)
+}
+
+fn clean_title(title: &str) -> Option<String> {
+    Some(title.trim().trim_matches(['.', ',', '"', '“', '”', ' ']))
+        .filter(|t| !t.is_empty())
+        .map(str::to_string)
+}
+
+/// Reads the fields of one reference.
+pub fn parse(text: &str) -> ParsedReference {
+    let text = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+    let doi_id = doi().find(&text).and_then(|m| normalize_doi(m.as_str()));
+    let arxiv_id = arxiv()
+        .captures(&text)
+        .and_then(|caps| normalize_arxiv(&caps["id"]));
+    // the identifiers and links would be taken for pages and venues
+    let rest = doi().replace_all(&text, "");
+    let rest = arxiv().replace_all(&rest, "");
+    let rest = url().replace_all(&rest, "");
+    let rest = rest.split_whitespace().collect::<Vec<&str>>().join(" ");
+
+    let mut reference = ParsedReference {
+        doi: doi_id,
+        arxiv: arxiv_id,
+        ..ParsedReference::default()
+    };
+    let after_title = if let Some(caps) = quoted_title().captures(&rest) {
+        reference.authors = authors(&caps["authors"]);
+        reference.title = clean_title(&caps["title"]);
+        caps["rest"].to_string()
+    } else if let Some(caps) = year_after_authors()
+        .captures(&rest)
+        .filter(|caps| first_sentence(&caps["authors"]).1.is_empty())
+    {
+        reference.authors = authors(&caps["authors"]);
+        reference.year = caps["year"].parse().ok();
+        let (title, after) = first_sentence(&caps["rest"]);
+        reference.title = clean_title(title);
+        after.to_string()
+    } else if let Some((names, after)) = rest
+        .split_once(": ")
+        .filter(|(names, _)| !names.chars().any(|c| c.is_ascii_digit()))
+    {
+        // `Authors: Title. Venue (2002)`
+        reference.authors = authors(names);
+        let (title, after) = first_sentence(after);
+        reference.title = clean_title(title);
+        after.to_string()
+    } else {
+        let (names, after) = first_sentence(&rest);
+        reference.authors = authors(names);
+        let (title, after) = first_sentence(after);
+        reference.title = clean_title(title);
+        after.to_string()
+    };
+    if reference.year.is_none() {
+        reference.year = year()
+            .captures_iter(&rest)
+            .last()
+            .and_then(|caps| caps["year"].parse().ok());
+    }
+    reference.pages = pages()
+        .captures_iter(&after_title)
+        .filter_map(|caps| {
+            let first = caps["first"].parse::<u32>().ok()?;
+            let last = caps["last"].parse::<u32>().ok()?;
+            let years = year().is_match(&caps["first"]) && year().is_match(&caps["last"]);
+            Some(format!("{}-{}", first, last)).filter(|_| first < last && !years)
+        })
+        .last();
+    reference.venue = venue(&after_title);
+    reference
+}
+
+impl ParsedReference {
+    /// The reference as an import entry, to resolve it like an uploaded one.
+    pub fn entry(&self, source_key: &str) -> ImportedEntry {
+        ImportedEntry {
+            source_key: source_key.to_string(),
+            title: self.title.to_owned().unwrap_or_default(),
+            authors: self.authors.to_owned(),
+            year: self.year,
+            container_title: self.venue.to_owned(),
+            doi: self.doi.to_owned(),
+            arxiv: self.arxiv.to_owned(),
+            pmid: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_split_references() {
+        let numbered = split(
+            "[1] Peter Auer, Nicolo Cesa-Bianchi, and Paul Fischer. 2002. Finite-time\nanalysis of the multiarmed bandit problem. Machine learning 47, 2 (2002), 235–256. [2] Bowen Baker, Otkrist Gupta, Nikhil Naik, and Ramesh Raskar. 2016. Designing\nneural network architectures using reinforcement learning. arXiv preprint arXiv:1611.02167 [cs.LG] (2016).",
+        );
+        assert_eq!(numbered.len(), 2);
+        assert_eq!(numbered[1].0.as_deref(), Some("2"));
+        assert!(numbered[1].1.starts_with("Bowen Baker"));
+        assert!(numbered[1].1.ends_with("[cs.LG] (2016)."));
+
+        let dotted =
+            split("1. A. Smith. Graphs. J. Graph Theory 2. 3 (1999). 2. B. Jones. Trees. 2001.");
+        assert_eq!(
+            dotted.iter().map(|(l, _)| l.as_deref()).collect::<Vec<_>>(),
+            vec![Some("1"), Some("2")]
+        );
+
+        let alpha = split(
+            "[Knu84] D. E. Knuth. Literate programming. 1984. [Lam94] L. Lamport. LaTeX. 1994.",
+        );
+        assert_eq!(
+            alpha[1],
+            (
+                Some("Lam94".to_string()),
+                "L. Lamport. LaTeX. 1994.".to_string()
+            )
+        );
+
+        let author_year = split(
+            "Smith, J. A., & Jones, B. (2019). Deep graphs. Journal of Graphs, 3(2), 1–10. Miller, K. (2020). Shallow trees. Tree Letters, 12, 5–9.",
+        );
+        assert_eq!(author_year.len(), 2);
+        assert!(author_year[1].1.starts_with("Miller, K. (2020)"));
+        assert_eq!(split(" "), vec![]);
+    }
+
+    #[test]
+    fn test_parse_reference() {
+        let acm = parse("Peter Auer, Nicolo Cesa-Bianchi, and Paul Fischer. 2002. Finite-time analysis of the multiarmed bandit problem. Machine learning 47, 2 (2002), 235–256.");
+        assert_eq!(acm.authors.len(), 3);
+        assert_eq!(acm.authors[1].family, "Cesa-Bianchi");
+        assert_eq!(acm.year, Some(2002));
+        assert_eq!(
+            acm.title.as_deref(),
+            Some("Finite-time analysis of the multiarmed bandit problem")
+        );
+        assert_eq!(acm.venue.as_deref(), Some("Machine learning"));
+        assert_eq!(acm.pages.as_deref(), Some("235-256"));
+
+        let apa = parse("Smith, J. A., & Jones, B. (2019). Deep graphs? Journal of Graphs, 3(2), 1–10. https://doi.org/10.1000/jg.2019.3.");
+        assert_eq!(
+            apa.authors,
+            vec![
+                PersonName {
+                    given: "J. A".to_string(),
+                    family: "Smith".to_string()
+                },
+                PersonName {
+                    given: "B".to_string(),
+                    family: "Jones".to_string()
+                },
+            ]
+        );
+        assert_eq!(apa.title.as_deref(), Some("Deep graphs?"));
+        assert_eq!(apa.venue.as_deref(), Some("Journal of Graphs"));
+        assert_eq!(apa.pages.as_deref(), Some("1-10"));
+        assert_eq!(apa.doi.as_deref(), Some("10.1000/jg.2019.3"));
+
+        let ieee = parse("A. Krizhevsky, I. Sutskever, and G. E. Hinton, “ImageNet classification with deep convolutional neural networks,” in Proc. NeurIPS, 2012, pp. 1097–1105.");
+        assert_eq!(ieee.authors[2].family, "Hinton");
+        assert_eq!(
+            ieee.title.as_deref(),
+            Some("ImageNet classification with deep convolutional neural networks")
+        );
+        assert_eq!(ieee.venue.as_deref(), Some("Proc. NeurIPS"));
+        assert_eq!(
+            (ieee.year, ieee.pages.as_deref()),
+            (Some(2012), Some("1097-1105"))
+        );
+
+        let springer =
+            parse("Auer, P., Fischer, P.: Finite-time analysis. Mach. Learn. 47, 235–256 (2002)");
+        assert_eq!(springer.authors[1].family, "Fischer");
+        assert_eq!(springer.title.as_deref(), Some("Finite-time analysis"));
+        assert_eq!(springer.venue.as_deref(), Some("Mach. Learn"));
+        assert_eq!(springer.year, Some(2002));
+
+        let preprint = parse("Bowen Baker and Ramesh Raskar. 2016. Designing neural network architectures. arXiv preprint arXiv:1611.02167v3 (2016).");
+        assert_eq!(preprint.arxiv.as_deref(), Some("1611.02167"));
+        assert_eq!(preprint.venue.as_deref(), Some("arXiv preprint"));
+        let entry = preprint.entry("2");
+        assert_eq!(entry.title, "Designing neural network architectures");
+        assert_eq!(entry.authors[0].family, "Baker");
+    }
+}
@@ -0,0 +1,102 @@
+use std::collections::BTreeMap;
+
+/// A raw RIS record: tag → values in file order.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RisRecord {
+    pub fields: BTreeMap<String, Vec<String>>,
+}
+
+impl RisRecord {
+    pub fn first(&self, tags: &[&str]) -> Option<String> {
+        tags.iter()
+            .filter_map(|tag| self.fields.get(*tag).and_then(|v| v.first()))
+            .map(|v| v.trim().to_string())
+            .find(|v| !v.is_empty())
+    }
+
+    pub fn all(&self, tags: &[&str]) -> Vec<String> {
+        tags.iter()
+            .flat_map(|tag| self.fields.get(*tag).cloned().unwrap_or_default())
+            .collect()
+    }
+}
+
+/// Parses `TAG  - value` lines; records start at `TY` and end at `ER`.
+/// Lines without a tag continue the previous value.
+pub fn parse(input: &str) -> Vec<RisRecord> {
+    let mut records = vec![];
+    let mut current: Option<RisRecord> = None;
+    let mut last_tag = String::new();
+    for line in input.lines() {
+        let line = line.trim_start_matches('\u{feff}').trim_end();
+        let tagged = line.len() >= 5
+            && line.is_char_boundary(2)
+            && line[..2]
+                .chars()
+                .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+            && line[2..].trim_start().starts_with('-');
+        if !tagged {
+            if let Some(record) = current.as_mut() {
+                if let Some(value) = record
+                    .fields
+                    .get_mut(&last_tag)
+                    .and_then(|values| values.last_mut())
+                {
+                    value.push(' ');
+                    value.push_str(line.trim());
+                }
+            }
+            continue;
+        }
+        let tag = line[..2].to_string();
+        let value = line[2..]
+            .trim_start()
+            .trim_start_matches('-')
+            .trim()
+            .to_string();
+        match tag.as_str() {
+            "TY" => {
+                current = Some(RisRecord::default());
+                current
+                    .as_mut()
+                    .unwrap()
+                    .fields
+                    .insert(tag.to_owned(), vec![value]);
+            }
+            "ER" => {
+                if let Some(record) = current.take() {
+                    records.push(record);
+                }
+            }
+            _ => {
+                if let Some(record) = current.as_mut() {
+                    record.fields.entry(tag.to_owned()).or_default().push(value);
+                }
+            }
+        }
+        last_tag = tag;
+    }
+    if let Some(record) = current.take() {
+        records.push(record);
+    }
+    records
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_ris() {
+        let records = parse(
+            "TY  - CPAPER\r\nAU  - Jin, Haifeng\r\nAU  - Song, Qingquan\r\nTI  - Auto-Keras: An Efficient\r\n  Neural Architecture Search System\r\nPY  - 2019/08/04\r\nDO  - 10.1145/3292500.3330648\r\nER  - \r\n\r\nTY  - JOUR\r\nTI  - Second\r\nER  -\r\n",
+        );
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].all(&["AU"]).len(), 2);
+        assert_eq!(
+            records[0].first(&["TI", "T1"]).unwrap(),
+            "Auto-Keras: An Efficient Neural Architecture Search System"
+        );
+        assert_eq!(records[1].first(&["TI"]).unwrap(), "Second");
+    }
+}
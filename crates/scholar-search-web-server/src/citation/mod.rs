@@ -0,0 +1,303 @@
+pub mod bibtex;
+pub mod csl_json;
+pub mod csv;
+pub mod endnote;
+pub mod growth;
+pub mod import;
+pub mod resolve;
+pub mod ris;
+pub mod style;
+
+use crate::semantic_scholar_api::data::PaperDetail;
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct PersonName {
+    pub given: String,
+    pub family: String,
+}
+
+impl PersonName {
+    /// Splits a display name such as "Ludwig van Beethoven" into given and
+    /// family parts, keeping lowercase particles with the family name.
+    pub fn parse(name: &str) -> Self {
+        let name = name.trim();
+        if let Some((family, given)) = name.split_once(',') {
+            return Self {
+                given: given.trim().to_string(),
+                family: family.trim().to_string(),
+            };
+        }
+        let parts = name.split_whitespace().collect::<Vec<&str>>();
+        if parts.len() < 2 {
+            return Self {
+                given: "".to_string(),
+                family: name.to_string(),
+            };
+        }
+        let mut split = parts.len() - 1;
+        while split > 1 && parts[split - 1].chars().all(|c| c.is_lowercase()) {
+            split -= 1;
+        }
+        Self {
+            given: parts[..split].join(" "),
+            family: parts[split..].join(" "),
+        }
+    }
+
+    /// "Family, Given" as used by BibTeX, RIS and EndNote.
+    pub fn inverted(&self) -> String {
+        if self.given.is_empty() {
+            self.family.to_owned()
+        } else {
+            format!("{}, {}", self.family, self.given)
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum CitationType {
+    JournalArticle,
+    Conference,
+    Book,
+    BookSection,
+    Thesis,
+    Preprint,
+    #[default]
+    Generic,
+}
+
+/// Format independent bibliographic record, normalised from the S2 models.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct CitationItem {
+    pub paper_id: String,
+    pub cite_key: String,
+    pub citation_type: CitationType,
+    pub title: String,
+    pub authors: Vec<PersonName>,
+    pub year: Option<i32>,
+    pub publication_date: Option<String>,
+    pub container_title: Option<String>,
+    pub volume: Option<String>,
+    pub pages: Option<String>,
+    pub issn: Option<String>,
+    pub doi: Option<String>,
+    pub arxiv: Option<String>,
+    pub pmid: Option<String>,
+    pub pmcid: Option<String>,
+    pub url: Option<String>,
+    pub abstract_field: Option<String>,
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+impl From<PaperDetail> for CitationItem {
+    fn from(paper: PaperDetail) -> Self {
+        let external_ids = paper.external_ids.unwrap_or_default();
+        let journal = paper.journal.unwrap_or_default();
+        let venue = paper.publication_venue;
+        let publication_types = paper.publication_types.unwrap_or_default();
+
+        let citation_type = if publication_types.iter().any(|t| t == "JournalArticle") {
+            CitationType::JournalArticle
+        } else if publication_types.iter().any(|t| t == "Conference") {
+            CitationType::Conference
+        } else if publication_types.iter().any(|t| t == "Book") {
+            CitationType::Book
+        } else if publication_types.iter().any(|t| t == "BookSection") {
+            CitationType::BookSection
+        } else if publication_types.iter().any(|t| t == "Dataset" || t == "Review") {
+            CitationType::Generic
+        } else if external_ids.ar_xiv.is_some() && external_ids.doi.is_none() {
+            CitationType::Preprint
+        } else {
+            match venue.as_ref().and_then(|v| v.type_field.as_deref()) {
+                Some("journal") => CitationType::JournalArticle,
+                Some("conference") => CitationType::Conference,
+                _ => CitationType::Generic,
+            }
+        };
+
+        // the journal block is the most specific, then the venue record, then
+        // the normalised venue string
+        let container_title = non_empty(journal.name)
+            .or_else(|| venue.as_ref().map(|v| v.name.to_owned()))
+            .or_else(|| non_empty(paper.venue));
+
+        Self {
+            cite_key: "".to_string(),
+            citation_type,
+            title: paper.title.trim().to_string(),
+            authors: paper
+                .authors
+                .unwrap_or_default()
+                .iter()
+                .map(|a| PersonName::parse(&a.name))
+                .collect(),
+            year: Some(paper.year).filter(|y| *y > 0),
+            publication_date: non_empty(paper.publication_date),
+            container_title: non_empty(container_title),
+            volume: non_empty(journal.volume),
+            pages: non_empty(journal.pages).map(|p| p.replace(' ', "")),
+            issn: non_empty(venue.and_then(|v| v.issn)),
+            url: non_empty(
+                external_ids
+                    .doi
+                    .as_ref()
+                    .map(|doi| format!("https://doi.org/{}", doi)),
+            )
+            .or(non_empty(paper.url)),
+            doi: non_empty(external_ids.doi),
+            arxiv: non_empty(external_ids.ar_xiv),
+            pmid: non_empty(external_ids.pub_med),
+            pmcid: non_empty(external_ids.pub_med_central),
+            abstract_field: non_empty(paper.abstract_field),
+            paper_id: paper.paper_id,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum ExportFormat {
+    #[default]
+    Bibtex,
+    Ris,
+    CslJson,
+    EndnoteXml,
+    Csv,
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Bibtex => "application/x-bibtex; charset=utf-8",
+            ExportFormat::Ris => "application/x-research-info-systems; charset=utf-8",
+            ExportFormat::CslJson => "application/vnd.citationstyles.csl+json",
+            ExportFormat::EndnoteXml => "application/xml; charset=utf-8",
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Bibtex => "bib",
+            ExportFormat::Ris => "ris",
+            ExportFormat::CslJson => "json",
+            ExportFormat::EndnoteXml => "xml",
+            ExportFormat::Csv => "csv",
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportFormat::Bibtex => write!(f, "bibtex"),
+            ExportFormat::Ris => write!(f, "ris"),
+            ExportFormat::CslJson => write!(f, "csl-json"),
+            ExportFormat::EndnoteXml => write!(f, "endnote-xml"),
+            ExportFormat::Csv => write!(f, "csv"),
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bibtex" | "bib" => Ok(ExportFormat::Bibtex),
+            "ris" => Ok(ExportFormat::Ris),
+            "csl-json" | "csljson" | "json" => Ok(ExportFormat::CslJson),
+            "endnote-xml" | "endnote" | "xml" => Ok(ExportFormat::EndnoteXml),
+            "csv" => Ok(ExportFormat::Csv),
+            _ => Err(anyhow::anyhow!("unknown export format: {}", s)),
+        }
+    }
+}
+
+/// Renders the papers in the requested format, with BibTeX keys assigned.
+pub fn export(papers: Vec<PaperDetail>, format: ExportFormat) -> String {
+    let mut items = papers
+        .into_iter()
+        .map(CitationItem::from)
+        .collect::<Vec<CitationItem>>();
+    bibtex::assign_cite_keys(&mut items);
+    match format {
+        ExportFormat::Bibtex => bibtex::write(&items),
+        ExportFormat::Ris => ris::write(&items),
+        ExportFormat::CslJson => csl_json::write(&items),
+        ExportFormat::EndnoteXml => endnote::write(&items),
+        ExportFormat::Csv => csv::write(&items),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::semantic_scholar_api::data::{Author, ExternalIds, Journal, PublicationVenue};
+
+    pub fn auto_keras() -> PaperDetail {
+        PaperDetail {
+            paper_id: "c27ad9346f384e828a4cd6dc8e7e724ea54bd1a2".to_string(),
+            title: "Auto-Keras: An Efficient Neural Architecture Search System".to_string(),
+            year: 2019,
+            venue: Some("KDD".to_string()),
+            publication_types: Some(vec!["Book".to_string(), "Conference".to_string()]),
+            publication_venue: Some(PublicationVenue {
+                name: "Knowledge Discovery and Data Mining".to_string(),
+                type_field: Some("conference".to_string()),
+                ..PublicationVenue::default()
+            }),
+            journal: Some(Journal {
+                name: Some("".to_string()),
+                pages: Some("1946 - 1956".to_string()),
+                volume: None,
+            }),
+            external_ids: Some(ExternalIds {
+                doi: Some("10.1145/3292500.3330648".to_string()),
+                ar_xiv: Some("1806.10282".to_string()),
+                ..ExternalIds::default()
+            }),
+            authors: Some(vec![
+                Author {
+                    author_id: None,
+                    name: "Haifeng Jin".to_string(),
+                },
+                Author {
+                    author_id: None,
+                    name: "Qingquan Song".to_string(),
+                },
+                Author {
+                    author_id: None,
+                    name: "Xia Hu".to_string(),
+                },
+            ]),
+            ..PaperDetail::default()
+        }
+    }
+
+    #[test]
+    fn test_citation_item_fills_gaps() {
+        let item = CitationItem::from(auto_keras());
+        assert_eq!(item.citation_type, CitationType::Conference);
+        assert_eq!(
+            item.container_title.as_deref(),
+            Some("Knowledge Discovery and Data Mining")
+        );
+        assert_eq!(item.pages.as_deref(), Some("1946-1956"));
+        assert_eq!(item.url.as_deref(), Some("https://doi.org/10.1145/3292500.3330648"));
+        assert_eq!(item.authors[0].inverted(), "Jin, Haifeng");
+    }
+
+    #[test]
+    fn test_person_name_parse() {
+        let name = PersonName::parse("Ludwig van Beethoven");
+        assert_eq!(name.given, "Ludwig");
+        assert_eq!(name.family, "van Beethoven");
+        assert_eq!(PersonName::parse("Plato").family, "Plato");
+    }
+}
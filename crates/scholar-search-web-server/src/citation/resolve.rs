@@ -0,0 +1,303 @@
+use crate::citation::{import::ImportedEntry, PersonName};
+use crate::semantic_scholar_api::{
+    data::{Paper, PaperDetail},
+    paper_fetch::{fetch_paper_lookup, fetch_paper_match, fetch_paper_search},
+};
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+use tracing::warn;
+use unicode_normalization::UnicodeNormalization;
+
+/// Confidence at or above which a candidate is accepted without review.
+pub const MATCH_THRESHOLD: f64 = 0.9;
+/// Below this a candidate is not worth offering to the reviewer.
+pub const CANDIDATE_THRESHOLD: f64 = 0.5;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum MatchMethod {
+    Doi,
+    Arxiv,
+    Pmid,
+    Title,
+}
+
+impl fmt::Display for MatchMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MatchMethod::Doi => write!(f, "doi"),
+            MatchMethod::Arxiv => write!(f, "arxiv"),
+            MatchMethod::Pmid => write!(f, "pmid"),
+            MatchMethod::Title => write!(f, "title"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MatchCandidate {
+    pub paper_id: String,
+    pub title: String,
+    pub authors: String,
+    pub year: Option<i32>,
+    pub venue: Option<String>,
+    pub method: MatchMethod,
+    pub confidence: f64,
+}
+
+impl MatchCandidate {
+    fn new(entry: &ImportedEntry, paper: Paper, method: MatchMethod) -> Option<Self> {
+        let confidence = match method {
+            // an identifier hit is trusted unless it points at a clearly
+            // different paper, which usually means a typo in the file
+            MatchMethod::Title => score(entry, &paper),
+            _ if entry.title.is_empty() || title_similarity(&entry.title, &paper.title) >= 0.5 => {
+                1.0
+            }
+            _ => score(entry, &paper),
+        };
+        Some(Self {
+            paper_id: paper.paper_id?,
+            title: paper.title,
+            authors: paper
+                .authors
+                .unwrap_or_default()
+                .into_iter()
+                .map(|a| a.name)
+                .collect::<Vec<String>>()
+                .join(", "),
+            year: paper.year,
+            venue: paper.venue.filter(|v| !v.is_empty()),
+            method,
+            confidence,
+        })
+    }
+}
+
+impl From<&PaperDetail> for MatchCandidate {
+    /// A paper already known locally under the entry's DOI.
+    fn from(paper: &PaperDetail) -> Self {
+        Self {
+            paper_id: paper.paper_id.to_owned(),
+            title: paper.title.to_owned(),
+            authors: paper
+                .authors
+                .iter()
+                .flatten()
+                .map(|a| a.name.to_owned())
+                .collect::<Vec<String>>()
+                .join(", "),
+            year: Some(paper.year).filter(|y| *y > 0),
+            venue: paper.venue.to_owned().filter(|v| !v.is_empty()),
+            method: MatchMethod::Doi,
+            confidence: 1.0,
+        }
+    }
+}
+
+/// Lowercase, accent-free alphanumeric words, so that punctuation, LaTeX
+/// leftovers and casing do not count as differences.
+pub fn normalize_title(title: &str) -> String {
+    title
+        .nfd()
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+        .flat_map(|c| c.to_lowercase())
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+pub fn title_similarity(a: &str, b: &str) -> f64 {
+    strsim::normalized_levenshtein(&normalize_title(a), &normalize_title(b))
+}
+
+/// Weighted agreement between an entry and a paper: 70% title, 15% year and
+/// 15% first author family name. Missing values score half.
+pub fn score(entry: &ImportedEntry, paper: &Paper) -> f64 {
+    let title = title_similarity(&entry.title, &paper.title);
+    let year = match (entry.year, paper.year) {
+        (Some(a), Some(b)) if a == b => 1.0,
+        // preprint and proceedings years are often one apart
+        (Some(a), Some(b)) if (a - b).abs() == 1 => 0.5,
+        (Some(_), Some(_)) => 0.0,
+        _ => 0.5,
+    };
+    let first_author = paper
+        .authors
+        .as_ref()
+        .and_then(|authors| authors.first())
+        .map(|a| PersonName::parse(&a.name).family);
+    let author = match (entry.authors.first(), first_author) {
+        (Some(a), Some(b)) if normalize_title(&a.family) == normalize_title(&b) => 1.0,
+        (Some(_), Some(_)) => 0.0,
+        _ => 0.5,
+    };
+    0.7 * title + 0.15 * year + 0.15 * author
+}
+
+/// Looks the entry's identifiers up in order DOI, arXiv, PMID.
+async fn resolve_identifiers(entry: &ImportedEntry) -> Option<MatchCandidate> {
+    let identifiers = [
+        (
+            MatchMethod::Doi,
+            entry.doi.as_ref().map(|id| format!("DOI:{}", id)),
+        ),
+        (
+            MatchMethod::Arxiv,
+            entry.arxiv.as_ref().map(|id| format!("ARXIV:{}", id)),
+        ),
+        (
+            MatchMethod::Pmid,
+            entry.pmid.as_ref().map(|id| format!("PMID:{}", id)),
+        ),
+    ];
+    for (method, identifier) in identifiers {
+        let Some(identifier) = identifier else {
+            continue;
+        };
+        let paper = match fetch_paper_lookup(identifier.to_owned()).await {
+            Ok(paper) => paper,
+            Err(e) => {
+                warn!("fetch_paper_lookup error: {} {:?}", identifier, e);
+                None
+            }
+        };
+        if let Some(candidate) = paper.and_then(|p| MatchCandidate::new(entry, p, method)) {
+            return Some(candidate);
+        }
+    }
+    None
+}
+
+/// Finds candidate S2 papers for an entry, best first. The title search
+/// only runs when none of the identifiers resolved.
+pub async fn resolve(entry: &ImportedEntry) -> Vec<MatchCandidate> {
+    if let Some(candidate) = resolve_identifiers(entry).await {
+        return vec![candidate];
+    }
+    if entry.title.is_empty() {
+        return vec![];
+    }
+    let papers = match fetch_paper_search(normalize_title(&entry.title), 5).await {
+        Ok(papers) => papers,
+        Err(e) => {
+            warn!("fetch_paper_search error: {} {:?}", entry.source_key, e);
+            vec![]
+        }
+    };
+    rank(entry, papers)
+}
+
+/// Finds the S2 paper a printed reference cites. After the identifiers,
+/// S2's title match is tried, and the keyword search only when that match
+/// scores below `MATCH_THRESHOLD`, as parsed titles are often cut short or
+/// run into the venue.
+pub async fn resolve_reference(entry: &ImportedEntry) -> Option<MatchCandidate> {
+    if let Some(candidate) = resolve_identifiers(entry).await {
+        return Some(candidate);
+    }
+    if entry.title.is_empty() {
+        return None;
+    }
+    let matched = match fetch_paper_match(entry.title.to_owned()).await {
+        Ok(paper) => paper.and_then(|p| MatchCandidate::new(entry, p, MatchMethod::Title)),
+        Err(e) => {
+            warn!("fetch_paper_match error: {} {:?}", entry.source_key, e);
+            None
+        }
+    };
+    if matched
+        .as_ref()
+        .is_some_and(|c| c.confidence >= MATCH_THRESHOLD)
+    {
+        return matched;
+    }
+    let papers = match fetch_paper_search(normalize_title(&entry.title), 5).await {
+        Ok(papers) => papers,
+        Err(e) => {
+            warn!("fetch_paper_search error: {} {:?}", entry.source_key, e);
+            vec![]
+        }
+    };
+    best_candidate(matched, rank(entry, papers))
+}
+
+/// The more confident of the title match and the best search result, if it
+/// is worth offering at all.
+fn best_candidate(
+    matched: Option<MatchCandidate>,
+    ranked: Vec<MatchCandidate>,
+) -> Option<MatchCandidate> {
+    matched
+        .into_iter()
+        .chain(ranked)
+        .filter(|c| c.confidence >= CANDIDATE_THRESHOLD)
+        .max_by(|a, b| a.confidence.total_cmp(&b.confidence))
+}
+
+fn rank(entry: &ImportedEntry, papers: Vec<Paper>) -> Vec<MatchCandidate> {
+    let mut candidates = papers
+        .into_iter()
+        .filter_map(|p| MatchCandidate::new(entry, p, MatchMethod::Title))
+        .filter(|c| c.confidence >= CANDIDATE_THRESHOLD)
+        .collect::<Vec<MatchCandidate>>();
+    candidates.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+    candidates
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::semantic_scholar_api::data::Author;
+
+    fn paper(paper_id: &str, title: &str, year: i32, first_author: &str) -> Paper {
+        Paper {
+            paper_id: Some(paper_id.to_string()),
+            title: title.to_string(),
+            year: Some(year),
+            authors: Some(vec![Author {
+                author_id: None,
+                name: first_author.to_string(),
+            }]),
+            ..Paper::default()
+        }
+    }
+
+    #[test]
+    fn test_score() {
+        let entry = ImportedEntry {
+            title: "Auto-Keras: an efficient neural architecture search system".to_string(),
+            authors: vec![PersonName::parse("Jin, Haifeng")],
+            year: Some(2019),
+            ..ImportedEntry::default()
+        };
+        let exact = paper(
+            "a",
+            "Auto-Keras: An Efficient Neural Architecture Search System",
+            2019,
+            "Haifeng Jin",
+        );
+        assert!((score(&entry, &exact) - 1.0).abs() < 1e-9);
+
+        let candidates = rank(
+            &entry,
+            vec![
+                paper(
+                    "b",
+                    "Efficient Neural Architecture Search",
+                    2018,
+                    "Hieu Pham",
+                ),
+                exact,
+                paper("c", "Deep Residual Learning", 2016, "Kaiming He"),
+            ],
+        );
+        assert_eq!(candidates[0].paper_id, "a");
+        assert!(candidates.iter().all(|c| c.paper_id != "c"));
+
+        let unrelated = paper("c", "Deep Residual Learning", 2016, "Kaiming He");
+        let unrelated = MatchCandidate::new(&entry, unrelated, MatchMethod::Title);
+        assert_eq!(best_candidate(unrelated.clone(), vec![]), None);
+        let best = best_candidate(unrelated, candidates).unwrap();
+        assert_eq!(best.paper_id, "a");
+    }
+}
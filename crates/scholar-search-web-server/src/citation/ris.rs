@@ -0,0 +1,70 @@
+use crate::citation::{CitationItem, CitationType};
+
+fn ris_type(item: &CitationItem) -> &'static str {
+    match item.citation_type {
+        CitationType::JournalArticle => "JOUR",
+        CitationType::Conference => "CPAPER",
+        CitationType::Book => "BOOK",
+        CitationType::BookSection => "CHAP",
+        CitationType::Thesis => "THES",
+        CitationType::Preprint => "UNPB",
+        CitationType::Generic => "GEN",
+    }
+}
+
+pub fn write_entry(item: &CitationItem) -> String {
+    let mut lines: Vec<(&str, String)> = vec![("TY", ris_type(item).to_string())];
+    lines.push(("ID", item.cite_key.to_owned()));
+    for author in item.authors.iter() {
+        lines.push(("AU", author.inverted()));
+    }
+    lines.push(("TI", item.title.to_owned()));
+    if let Some(container) = &item.container_title {
+        lines.push(("T2", container.to_owned()));
+    }
+    if let Some(year) = item.year {
+        lines.push(("PY", year.to_string()));
+    }
+    if let Some(date) = &item.publication_date {
+        lines.push(("DA", date.replace('-', "/")));
+    }
+    if let Some(volume) = &item.volume {
+        lines.push(("VL", volume.to_owned()));
+    }
+    if let Some(pages) = &item.pages {
+        match pages.split_once('-') {
+            Some((start, end)) => {
+                lines.push(("SP", start.to_string()));
+                lines.push(("EP", end.to_string()));
+            }
+            None => lines.push(("SP", pages.to_owned())),
+        }
+    }
+    if let Some(issn) = &item.issn {
+        lines.push(("SN", issn.to_owned()));
+    }
+    if let Some(doi) = &item.doi {
+        lines.push(("DO", doi.to_owned()));
+    }
+    if let Some(url) = &item.url {
+        lines.push(("UR", url.to_owned()));
+    }
+    if let Some(abstract_field) = &item.abstract_field {
+        lines.push(("AB", abstract_field.replace('\n', " ")));
+    }
+    lines.push(("ER", "".to_string()));
+    lines
+        .iter()
+        .map(|(tag, value)| format!("{}  - {}", tag, value).trim_end().to_string())
+        .collect::<Vec<String>>()
+        .join("\r\n")
+}
+
+pub fn write(items: &[CitationItem]) -> String {
+    items
+        .iter()
+        .map(write_entry)
+        .collect::<Vec<String>>()
+        .join("\r\n\r\n")
+        + "\r\n"
+}
@@ -0,0 +1,269 @@
+use crate::citation::{bibtex, csl_json::CslItem, CitationItem};
+use crate::semantic_scholar_api::data::PaperDetail;
+use hayagriva::{
+    archive::{locales, ArchivedStyle},
+    citationberg::{json::Item, Display, IndependentStyle, Locale, Style},
+    BibliographyDriver, BibliographyRequest, BufWriteFormat, CitationItem as CiteItem,
+    CitationRequest, ElemChild, ElemChildren, Formatted,
+};
+use serde_derive::{Deserialize, Serialize};
+use std::path::Path;
+
+/// User supplied `.csl` files; a file named like a built-in style replaces it.
+pub const STYLE_DIR: &str = "data/csl";
+
+/// Styles offered in the UI without any `.csl` files installed.
+pub const BUILTIN_STYLES: [&str; 7] = [
+    "apa",
+    "ieee",
+    "chicago-author-date",
+    "modern-language-association",
+    "harvard-cite-them-right",
+    "vancouver",
+    "nature",
+];
+
+pub struct CitationStyle {
+    pub name: String,
+    pub title: String,
+    style: IndependentStyle,
+}
+
+/// A dependent style only renames its parent, which must be a built-in one.
+fn independent(style: Style) -> anyhow::Result<IndependentStyle> {
+    match style {
+        Style::Independent(style) => Ok(style),
+        Style::Dependent(style) => match ArchivedStyle::by_id(&style.parent_link.href) {
+            Some(parent) => independent(parent.get()),
+            None => Err(anyhow::anyhow!(
+                "unknown parent style: {}",
+                style.parent_link.href
+            )),
+        },
+    }
+}
+
+fn is_style_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Loads `data/csl/{name}.csl` if present, otherwise the bundled style.
+pub fn load_style(name: &str) -> anyhow::Result<CitationStyle> {
+    if !is_style_name(name) {
+        return Err(anyhow::anyhow!("invalid style name: {}", name));
+    }
+    let path = Path::new(STYLE_DIR).join(format!("{}.csl", name));
+    let style = if path.is_file() {
+        independent(Style::from_xml(&std::fs::read_to_string(&path)?)?)?
+    } else {
+        match ArchivedStyle::by_name(name) {
+            Some(archived) => independent(archived.get())?,
+            None => return Err(anyhow::anyhow!("unknown citation style: {}", name)),
+        }
+    };
+    Ok(CitationStyle {
+        name: name.to_string(),
+        title: style.info.title.value.to_owned(),
+        style,
+    })
+}
+
+/// Built-in styles followed by the installed `.csl` files.
+pub fn list_styles() -> Vec<String> {
+    let mut styles = BUILTIN_STYLES
+        .iter()
+        .map(|s| s.to_string())
+        .collect::<Vec<String>>();
+    let mut installed = std::fs::read_dir(STYLE_DIR)
+        .map(|dir| {
+            dir.filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|e| e == "csl"))
+                .filter_map(|path| Some(path.file_stem()?.to_string_lossy().to_string()))
+                .filter(|name| is_style_name(name) && !styles.contains(name))
+                .collect::<Vec<String>>()
+        })
+        .unwrap_or_default();
+    installed.sort();
+    styles.extend(installed);
+    styles
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct FormattedReference {
+    pub paper_id: String,
+    pub text: String,
+    pub html: String,
+}
+
+/// Bibliography entries in the style's order and one in-text citation per
+/// paper, plus a combined citation for the whole set.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct FormattedCitations {
+    pub style: String,
+    pub style_title: String,
+    pub bibliography: Vec<FormattedReference>,
+    pub citations: Vec<FormattedReference>,
+    pub combined_citation: FormattedReference,
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Like `ElemChildren::write_buf` with `Html`, which does not escape the
+/// text taken from the paper records.
+fn write_html(children: &ElemChildren, out: &mut String) {
+    for child in children.0.iter() {
+        match child {
+            ElemChild::Text(text) => ElemChild::Text(Formatted {
+                text: escape_html(&text.text),
+                formatting: text.formatting,
+            })
+            .write_buf(out, BufWriteFormat::Html)
+            .unwrap(),
+            ElemChild::Link { text, url } => ElemChild::Link {
+                text: Formatted {
+                    text: escape_html(&text.text),
+                    formatting: text.formatting,
+                },
+                url: escape_html(url),
+            }
+            .write_buf(out, BufWriteFormat::Html)
+            .unwrap(),
+            ElemChild::Markup(markup) => out.push_str(&escape_html(markup)),
+            ElemChild::Elem(elem) => {
+                let open = match elem.display {
+                    Some(Display::Block) => Some("<div>"),
+                    Some(Display::Indent) => Some("<div style=\"padding-left: 4em;\">"),
+                    Some(Display::LeftMargin) => Some("<div style=\"float: left;\">"),
+                    Some(Display::RightInline) => {
+                        Some("<div style=\"float: right; clear: both;\">")
+                    }
+                    None => None,
+                };
+                out.push_str(open.unwrap_or_default());
+                write_html(&elem.children, out);
+                if open.is_some() {
+                    out.push_str("</div>");
+                }
+            }
+            ElemChild::Transparent { .. } => {}
+        }
+    }
+}
+
+fn render(
+    paper_id: &str,
+    first_field: Option<&ElemChild>,
+    content: &ElemChildren,
+) -> FormattedReference {
+    let mut text = String::new();
+    let mut html = String::new();
+    if let Some(first_field) = first_field {
+        first_field
+            .write_buf(&mut text, BufWriteFormat::Plain)
+            .unwrap();
+        text.push(' ');
+        write_html(&ElemChildren(vec![first_field.clone()]), &mut html);
+        html.push(' ');
+    }
+    content.write_buf(&mut text, BufWriteFormat::Plain).unwrap();
+    write_html(content, &mut html);
+    FormattedReference {
+        paper_id: paper_id.to_string(),
+        text: text.trim().to_string(),
+        html: html.trim().to_string(),
+    }
+}
+
+/// Formats the papers with the style, through their CSL-JSON representation.
+pub fn format(papers: Vec<PaperDetail>, style: &CitationStyle) -> FormattedCitations {
+    let mut items = papers
+        .into_iter()
+        .map(CitationItem::from)
+        .collect::<Vec<CitationItem>>();
+    bibtex::assign_cite_keys(&mut items);
+    let entries = items
+        .iter()
+        .map(|item| {
+            serde_json::from_value::<Item>(serde_json::to_value(CslItem::from(item)).unwrap())
+                .unwrap()
+        })
+        .collect::<Vec<Item>>();
+    let locales: Vec<Locale> = locales();
+
+    let mut driver = BibliographyDriver::new();
+    for entry in entries.iter() {
+        driver.citation(CitationRequest::from_items(
+            vec![CiteItem::with_entry(entry)],
+            &style.style,
+            &locales,
+        ));
+    }
+    if !entries.is_empty() {
+        driver.citation(CitationRequest::from_items(
+            entries.iter().map(CiteItem::with_entry).collect(),
+            &style.style,
+            &locales,
+        ));
+    }
+    let rendered = driver.finish(BibliographyRequest::new(&style.style, None, &locales));
+
+    let mut citations = rendered
+        .citations
+        .iter()
+        .zip(items.iter())
+        .map(|(citation, item)| render(&item.paper_id, None, &citation.citation))
+        .collect::<Vec<FormattedReference>>();
+    // the combined citation was requested last, after the per paper ones
+    let combined_citation = match citations.len() > items.len() {
+        true => citations.pop().unwrap(),
+        false => FormattedReference::default(),
+    };
+    FormattedCitations {
+        style: style.name.to_owned(),
+        style_title: style.title.to_owned(),
+        bibliography: rendered
+            .bibliography
+            .map(|bibliography| bibliography.items)
+            .unwrap_or_default()
+            .iter()
+            .map(|item| render(&item.key, item.first_field.as_ref(), &item.content))
+            .collect(),
+        citations,
+        combined_citation,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::citation::test::auto_keras;
+
+    #[test]
+    fn test_format_builtin_styles() {
+        let apa = format(vec![auto_keras()], &load_style("apa").unwrap());
+        assert_eq!(apa.citations[0].text, "(Jin et al., 2019)");
+        assert!(apa.bibliography[0]
+            .text
+            .starts_with("Jin, H., Song, Q., & Hu, X. (2019)."));
+        assert!(apa.bibliography[0].html.contains("&amp;"));
+
+        let ieee = format(vec![auto_keras()], &load_style("ieee").unwrap());
+        assert_eq!(ieee.citations[0].text, "[1]");
+        assert!(ieee.bibliography[0].text.starts_with("[1] H. Jin"));
+    }
+
+    #[test]
+    fn test_load_style_rejects_paths() {
+        assert!(load_style("../secret").is_err());
+        assert!(load_style("no-such-style").is_err());
+    }
+}
@@ -1,6 +1,86 @@
+mod alerts;
 mod axum_server;
+mod citation;
 mod semantic_scholar_api;
-use crate::axum_server::create_router_service;
+use crate::axum_server::{
+    create_router_service,
+    import::{commit_import, create_import, resolve_import},
+    state::{
+        import::{ImportEntryStatus, ImportState},
+        library::LibraryState,
+        backup::{export_json, import_json, restore_backup, write_backup},
+        blob::{BlobStore, PdfBlobState, GC_GRACE},
+        migration::{current_version, migrate, stored_version},
+        store::{copy_store, open_store, store_is_empty},
+        store_spec, StateMach,
+    },
+};
+use std::io::Write;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "scholar-search")]
+struct Opt {
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(StructOpt, Debug)]
+enum Command {
+    /// Run the web server (default)
+    Serve,
+    /// Import a .bib or .ris file into the library; needs the server stopped
+    /// as both use the same state database
+    Import {
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+        /// Collection name to add the imported papers to, created if missing
+        #[structopt(long)]
+        collection: Option<String>,
+    },
+    /// Copy every tree from one store to another, e.g.
+    /// `migrate-store sled:data/state_mach sqlite:data/state.sqlite3`; needs
+    /// the server stopped. Point SCHOLAR_SEARCH_STORE at the new store after.
+    MigrateStore { from: String, to: String },
+    /// Apply pending data migrations, which the server also does on start
+    Migrate {
+        /// Only report what each migration would change
+        #[structopt(long)]
+        dry_run: bool,
+    },
+    /// Write the store named by SCHOLAR_SEARCH_STORE to an archive; needs the
+    /// server stopped, a running server backs up through POST /api/backup
+    Backup {
+        #[structopt(parse(from_os_str))]
+        archive: PathBuf,
+    },
+    /// Verify an archive and load it into the (empty) store named by
+    /// SCHOLAR_SEARCH_STORE
+    Restore {
+        #[structopt(parse(from_os_str))]
+        archive: PathBuf,
+    },
+    /// Write every entity as JSON lines
+    ExportJson {
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+    },
+    /// Load a JSON-lines export, replacing records with the same keys
+    ImportJson {
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+    },
+    /// Delete stored PDFs no paper points at, and leftovers of crashed
+    /// downloads, once they are an hour old
+    BlobGc {
+        /// Only report what would be deleted
+        #[structopt(long)]
+        dry_run: bool,
+    },
+    /// Re-hash every stored PDF and list the corrupt ones
+    BlobVerify,
+}
 
 // axum service
 
@@ -12,13 +92,155 @@ async fn main() {
         // .with_level(true)
         .with_max_level(tracing::Level::DEBUG)
         .init();
+    match Opt::from_args().command.unwrap_or(Command::Serve) {
+        Command::Serve => serve().await,
+        Command::Import { file, collection } => import(file, collection).await,
+        Command::MigrateStore { from, to } => migrate_store(&from, &to),
+        Command::Migrate { dry_run } => run_migrations(dry_run),
+        Command::Backup { archive } => backup(archive),
+        Command::Restore { archive } => restore(archive),
+        Command::ExportJson { file } => export_entities(file),
+        Command::ImportJson { file } => import_entities(file),
+        Command::BlobGc { dry_run } => blob_gc(dry_run),
+        Command::BlobVerify => blob_verify(),
+    }
+}
+
+async fn serve() {
     // let app = Router::new().nest("/", page_service());
+    // refuses to start on data from a newer version
+    let state_mach = or_exit(StateMach::open(&store_spec()));
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3600").await.unwrap();
-    axum::serve(listener, create_router_service().into_make_service())
+    axum::serve(listener, create_router_service(state_mach).into_make_service())
         .await
         .unwrap();
 }
 
+/// Imports confident matches straight away; ambiguous entries stay in the
+/// batch for review on the /x/import page.
+async fn import(file: PathBuf, collection: Option<String>) {
+    let state_mach = or_exit(StateMach::open(&store_spec()));
+    let collection_id = collection.map(|name| {
+        match state_mach.find_collection(&name, None) {
+            Some(collection) => collection.collection_id,
+            None => {
+                let created = state_mach.create_collection(&name, None);
+                or_exit(created.map_err(|e| anyhow::anyhow!("{}", e))).collection_id
+            }
+        }
+    });
+    let content = std::fs::read_to_string(&file).unwrap();
+    let file_name = file.file_name().unwrap().to_string_lossy().to_string();
+    let batch = match create_import(&state_mach, &file_name, &content, collection_id) {
+        Ok(batch) => batch,
+        Err((_, message)) => {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+    };
+    println!("{}: {} entries", file_name, batch.entries.len());
+    resolve_import(&state_mach, &batch.import_id).await;
+    let imported = commit_import(&state_mach, &batch.import_id).await;
+    let batch = state_mach.get_import(&batch.import_id).unwrap();
+    println!("imported:   {}", imported);
+    println!(
+        "to review:  {}",
+        batch.count(ImportEntryStatus::Ambiguous) + batch.count(ImportEntryStatus::Unmatched)
+    );
+    println!("review at /x/import (batch {})", batch.import_id);
+}
+
+fn migrate_store(from: &str, to: &str) {
+    let (source, target) = (or_exit(open_store(from)), or_exit(open_store(to)));
+    if !store_is_empty(target.as_ref()) {
+        eprintln!("{} already holds data, refusing to mix stores", to);
+        std::process::exit(1);
+    }
+    let copied = copy_store(source.as_ref(), target.as_ref());
+    println!("copied {} entries from {} to {}", copied, from, to);
+}
+
+/// Exits with the error, for the offline commands.
+fn or_exit<T>(result: anyhow::Result<T>) -> T {
+    result.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    })
+}
+
+fn run_migrations(dry_run: bool) {
+    let store = or_exit(open_store(&store_spec()));
+    println!(
+        "schema version {}, this binary writes {}",
+        or_exit(stored_version(store.as_ref())),
+        current_version()
+    );
+    let steps = or_exit(migrate(&StateMach::unmigrated(store), dry_run));
+    for step in steps.iter() {
+        println!(
+            "{:>3}  {:<48} {} records",
+            step.version, step.description, step.changed
+        );
+    }
+    if steps.is_empty() {
+        println!("nothing to migrate");
+    }
+}
+
+fn backup(archive: PathBuf) {
+    let store = or_exit(open_store(&store_spec()));
+    let info = or_exit(write_backup(store.as_ref(), &archive));
+    println!("{} entries, sha256 {}", info.entries, info.sha256);
+}
+
+fn restore(archive: PathBuf) {
+    let store = or_exit(open_store(&store_spec()));
+    let entries = or_exit(restore_backup(&archive, store.as_ref()));
+    println!("restored {} entries into {}", entries, store_spec());
+}
+
+fn export_entities(file: PathBuf) {
+    let state_mach = or_exit(StateMach::open(&store_spec()));
+    let out = or_exit(std::fs::File::create(&file).map_err(Into::into));
+    let mut out = std::io::BufWriter::new(out);
+    let lines = or_exit(export_json(state_mach.store(), &mut out));
+    or_exit(out.flush().map_err(Into::into));
+    println!("exported {} records to {}", lines, file.display());
+}
+
+fn import_entities(file: PathBuf) {
+    let state_mach = or_exit(StateMach::open(&store_spec()));
+    let input = or_exit(std::fs::File::open(&file).map_err(Into::into));
+    let lines = or_exit(import_json(state_mach.store(), std::io::BufReader::new(input)));
+    println!("imported {} records from {}", lines, file.display());
+}
+
+fn blob_gc(dry_run: bool) {
+    let state_mach = or_exit(StateMach::open(&store_spec()));
+    let blobs = or_exit(BlobStore::from_env());
+    let report = or_exit(blobs.gc(&state_mach.referenced_blobs(), GC_GRACE, dry_run));
+    println!(
+        "{} {} files, {} bytes; {} blobs kept",
+        if dry_run { "would remove" } else { "removed" },
+        report.removed,
+        report.freed,
+        report.kept
+    );
+}
+
+fn blob_verify() {
+    let state_mach = or_exit(StateMach::open(&store_spec()));
+    let blobs = or_exit(BlobStore::from_env());
+    let corrupt = or_exit(blobs.verify());
+    for sha256 in corrupt.iter() {
+        println!("{}  {}", sha256, state_mach.list_blob_papers(sha256).join(" "));
+    }
+    println!("{} corrupt blobs", corrupt.len());
+    if !corrupt.is_empty() {
+        std::process::exit(1);
+    }
+}
+
 // let result = fetch_papers(BulkRequest {
 //     query: String::from(r#"AI ML NLP"#),
 //     publication_date_or_year: String::from("2019:"),
@@ -18,6 +18,8 @@ pub struct CitingDaum {
     pub contexts: Vec<String>,
     pub intents: Vec<String>,
     pub is_influential: bool,
+    /// `citingPaper` for citations, `citedPaper` for references
+    #[serde(alias = "citedPaper")]
     pub citing_paper: Paper,
 }
 
@@ -56,11 +58,32 @@ impl SemanticScholarApiRequest for CitingRequest {
     ///  journal - Journal name, volume, and pages, if available
     ///  citationStyles
     fn to_url(self) -> String {
-        format!( "https://api.semanticscholar.org/graph/v1/paper/{paper_id}/citations?offset={offset}&limit={limit}&fields=contexts,intents,isInfluential,paperId,corpusId,url,title,venue,publicationVenue,year,authors,externalIds,abstract,referenceCount,citationCount,influentialCitationCount,isOpenAccess,openAccessPdf,fieldsOfStudy,s2FieldsOfStudy,publicationTypes,publicationDate,journal,citationStyles" ,
-            paper_id = self.paper_id,
-            offset = self.offset,
-            limit = self.limit
-        )
+        self.endpoint_url("citations")
+    }
+}
+
+const CITING_FIELDS: &str = "contexts,intents,isInfluential,paperId,corpusId,url,title,venue,publicationVenue,year,authors,externalIds,abstract,referenceCount,citationCount,influentialCitationCount,isOpenAccess,openAccessPdf,fieldsOfStudy,s2FieldsOfStudy,publicationTypes,publicationDate,journal,citationStyles";
+
+impl CitingRequest {
+    /// The papers this paper cites; entries come as `citedPaper`.
+    pub fn references_url(self) -> String {
+        self.endpoint_url("references")
+    }
+
+    /// `/paper/{paper_id}/{endpoint}` with the page and the citing fields.
+    fn endpoint_url(&self, endpoint: &str) -> String {
+        let mut url =
+            reqwest::Url::parse("https://api.semanticscholar.org/graph/v1/paper").unwrap();
+        url.path_segments_mut()
+            .unwrap()
+            // ids such as `DOI:10.1000/182` keep their slash
+            .extend(self.paper_id.split('/'))
+            .push(endpoint);
+        url.query_pairs_mut()
+            .append_pair("offset", &self.offset.to_string())
+            .append_pair("limit", &self.limit.to_string())
+            .append_pair("fields", CITING_FIELDS);
+        url.to_string()
     }
 }
 
@@ -78,17 +101,14 @@ pub async fn fetch_citing(
     request: CitingRequest,
 ) -> Result<CitingResponse, Box<dyn std::error::Error>> {
     let client = reqwest::Client::new();
-    let usl = request.to_url();
-    println!("usl: {:#?}", usl);
     let response = client
-        .get(usl)
+        .get(request.to_url())
         .header(ACCEPT, "application/json")
         .send()
-        .await
-        .unwrap()
+        .await?
+        .error_for_status()?
         .json::<CitingResponse>()
-        .await
-        .unwrap();
+        .await?;
 
     Ok(response)
 }
@@ -97,17 +117,34 @@ pub async fn fetch_references(
     request: CitingRequest,
 ) -> Result<CitingResponse, Box<dyn std::error::Error>> {
     let client = reqwest::Client::new();
-    let usl = request.to_url();
-    println!("usl: {:#?}", usl);
     let response = client
-        .get(usl)
+        .get(request.references_url())
         .header(ACCEPT, "application/json")
         .send()
-        .await
-        .unwrap()
+        .await?
+        .error_for_status()?
         .json::<CitingResponse>()
-        .await
-        .unwrap();
+        .await?;
 
     Ok(response)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_citing_urls() {
+        let request = CitingRequest {
+            paper_id: "DOI:10.1000/182".to_string(),
+            offset: 100,
+            ..CitingRequest::default()
+        };
+        assert!(request.clone().to_url().starts_with(
+            "https://api.semanticscholar.org/graph/v1/paper/DOI:10.1000/182/citations?offset=100&limit=1000&fields="
+        ));
+        assert!(request.references_url().starts_with(
+            "https://api.semanticscholar.org/graph/v1/paper/DOI:10.1000/182/references?"
+        ));
+    }
+}
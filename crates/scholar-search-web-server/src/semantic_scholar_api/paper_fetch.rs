@@ -24,19 +24,28 @@ pub struct BulkRequest {
     // pub publication_types: Option<Vec<String>>,
     pub min_citation_count: i32,
     pub publication_date_or_year: String,
+    /// continuation token returned by the previous page
+    pub token: Option<String>,
 }
 
+const BULK_FIELDS: &str = "paperId,corpusId,url,title,venue,year,authors,externalIds,abstract,referenceCount,citationCount,influentialCitationCount,isOpenAccess,openAccessPdf,fieldsOfStudy,s2FieldsOfStudy,publicationTypes,publicationDate,journal,citationStyles,authors";
+
 impl SemanticScholarApiRequest for BulkRequest {
     fn to_url(self) -> String {
-        format!(
-            "https://api.semanticscholar.org/graph/v1/paper/search/bulk?query={query}&fields=paperId,corpusId,url,title,venue,year,authors,externalIds,abstract,referenceCount,citationCount,influentialCitationCount,isOpenAccess,openAccessPdf,fieldsOfStudy,s2FieldsOfStudy,publicationTypes,publicationDate,journal,citationStyles,authors&sort={sort}&minCitationCount={min_citation_count}&minCitationCount={min_citation_count}&publicationDateOrYear={publication_date_or_year}"
-            ,
-            query = self.query,
-            sort = "citationCount:desc",
-            // publication_types = self.publication_types,
-            min_citation_count = self.min_citation_count,
-            publication_date_or_year = self.publication_date_or_year
-        )
+        let mut url =
+            reqwest::Url::parse("https://api.semanticscholar.org/graph/v1/paper/search/bulk")
+                .unwrap();
+        url.query_pairs_mut()
+            .append_pair("query", &self.query)
+            .append_pair("fields", BULK_FIELDS)
+            .append_pair("sort", "citationCount:desc")
+            // .append_pair("publicationTypes", ...)
+            .append_pair("minCitationCount", &self.min_citation_count.to_string())
+            .append_pair("publicationDateOrYear", &self.publication_date_or_year);
+        if let Some(token) = self.token {
+            url.query_pairs_mut().append_pair("token", &token);
+        }
+        url.to_string()
     }
 }
 impl Default for BulkRequest {
@@ -45,6 +54,7 @@ impl Default for BulkRequest {
             query: String::from(""),
             min_citation_count: 3,
             publication_date_or_year: String::from("2023"),
+            token: None,
         }
     }
 }
@@ -57,11 +67,10 @@ pub async fn fetch_papers(
         .get(request.to_url())
         .header(ACCEPT, "application/json")
         .send()
-        .await
-        .unwrap()
+        .await?
+        .error_for_status()?
         .json::<BulkResponse>()
-        .await
-        .unwrap();
+        .await?;
     // let bulk_response: BulkResponse = response.()?;
     Ok(response)
 }
@@ -75,12 +84,11 @@ pub async fn fetch_paper_detail(
         .get(request_url)
         .header(ACCEPT, "application/json")
         .send()
-        .await
-        .unwrap()
+        .await?
+        .error_for_status()?
         // .text()
         .json::<PaperDetail>()
-        .await
-        .unwrap();
+        .await?;
 
     // println!("response: {:#?}", response);
     // let deserializer: &mut serde_json::Deserializer<serde_json::de::StrRead<'_>> = &mut serde_json::Deserializer::from_str(&response);
@@ -96,3 +104,130 @@ pub async fn fetch_paper_detail(
     // let bulk_response: BulkResponse = response.()?;
     Ok(response)
 }
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchRequest {
+    pub ids: Vec<String>,
+}
+
+/// Fetches up to 500 papers in one call; unknown ids come back as `None`.
+pub async fn fetch_paper_batch(
+    paper_ids: Vec<String>,
+) -> Result<Vec<Option<PaperDetail>>, Box<dyn std::error::Error>> {
+    let client = reqwest::Client::new();
+    let response = client
+        .post("https://api.semanticscholar.org/graph/v1/paper/batch?fields=paperId,corpusId,url,title,venue,publicationVenue,year,authors,externalIds,abstract,referenceCount,citationCount,influentialCitationCount,isOpenAccess,openAccessPdf,fieldsOfStudy,s2FieldsOfStudy,publicationTypes,publicationDate,journal,citationStyles,tldr")
+        .header(ACCEPT, "application/json")
+        .json(&BatchRequest { ids: paper_ids })
+        .send()
+        .await?
+        .json::<Vec<Option<PaperDetail>>>()
+        .await?;
+    Ok(response)
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResponse {
+    pub total: i32,
+    pub offset: Option<i32>,
+    pub data: Option<Vec<Paper>>,
+}
+
+const LOOKUP_FIELDS: &str = "paperId,title,venue,year,authors,externalIds,citationCount";
+
+/// Looks a paper up by any S2 identifier form, e.g. `DOI:10.1145/...`,
+/// `ARXIV:1806.10282` or `PMID:19872477`; `None` when S2 does not know it.
+pub async fn fetch_paper_lookup(
+    identifier: String,
+) -> Result<Option<Paper>, Box<dyn std::error::Error>> {
+    let client = reqwest::Client::new();
+    let request_url = reqwest::Url::parse_with_params(
+        &format!("https://api.semanticscholar.org/graph/v1/paper/{identifier}"),
+        &[("fields", LOOKUP_FIELDS)],
+    )?;
+    let response = client
+        .get(request_url)
+        .header(ACCEPT, "application/json")
+        .send()
+        .await?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    Ok(Some(response.error_for_status()?.json::<Paper>().await?))
+}
+
+/// Relevance ranked keyword search, used to match titles without identifiers.
+pub async fn fetch_paper_search(
+    query: String,
+    limit: i32,
+) -> Result<Vec<Paper>, Box<dyn std::error::Error>> {
+    let client = reqwest::Client::new();
+    let request_url = reqwest::Url::parse_with_params(
+        "https://api.semanticscholar.org/graph/v1/paper/search",
+        &[
+            ("query", query),
+            ("limit", limit.to_string()),
+            ("fields", LOOKUP_FIELDS.to_string()),
+        ],
+    )?;
+    let response = client
+        .get(request_url)
+        .header(ACCEPT, "application/json")
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<SearchResponse>()
+        .await?;
+    Ok(response.data.unwrap_or_default())
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchResponse {
+    pub data: Option<Vec<Paper>>,
+}
+
+/// The paper whose title best matches the query, for titles read from
+/// reference strings; `None` when S2 finds no close enough title.
+pub async fn fetch_paper_match(title: String) -> Result<Option<Paper>, Box<dyn std::error::Error>> {
+    let client = reqwest::Client::new();
+    let request_url = reqwest::Url::parse_with_params(
+        "https://api.semanticscholar.org/graph/v1/paper/search/match",
+        &[("query", title.as_str()), ("fields", LOOKUP_FIELDS)],
+    )?;
+    let response = client
+        .get(request_url)
+        .header(ACCEPT, "application/json")
+        .send()
+        .await?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    let response = response.error_for_status()?.json::<MatchResponse>().await?;
+    Ok(response.data.unwrap_or_default().into_iter().next())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_bulk_request_url() {
+        let url = BulkRequest {
+            query: "graph & neural".to_string(),
+            token: Some("PCOA3+b/x=".to_string()),
+            ..BulkRequest::default()
+        }
+        .to_url();
+        let url = reqwest::Url::parse(&url).unwrap();
+        let pairs = url.query_pairs().collect::<Vec<_>>();
+        assert!(pairs.contains(&("query".into(), "graph & neural".into())));
+        assert!(pairs.contains(&("minCitationCount".into(), "3".into())));
+        assert_eq!(
+            pairs.last().unwrap(),
+            &("token".into(), "PCOA3+b/x=".into())
+        );
+        assert!(!BulkRequest::default().to_url().contains("token="));
+    }
+}